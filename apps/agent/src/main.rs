@@ -29,6 +29,14 @@ struct SettingsJson {
     dialogue_tool_max_candidate_users: Option<usize>,
     #[serde(default)]
     api_timeout_secs: Option<u64>,
+    #[serde(default)]
+    dialogue_stream_mode: Option<String>,
+    #[serde(default)]
+    dialogue_stream_edit_interval_ms: Option<u64>,
+    #[serde(default)]
+    dialogue_context_window: Option<u32>,
+    #[serde(default)]
+    dialogue_context_max_message_tokens: Option<usize>,
 }
 
 fn parse_truthy(value: &str) -> bool {
@@ -113,6 +121,49 @@ fn resolve_dialogue_tool_calling(settings: &SettingsJson) -> DialogueToolCalling
     }
 }
 
+fn resolve_dialogue_streaming(settings: &SettingsJson) -> DialogueStreamingConfig {
+    let defaults = DialogueStreamingConfig::default();
+    let mode = std::env::var("DIALOGUE_STREAM_MODE")
+        .ok()
+        .or_else(|| settings.dialogue_stream_mode.clone())
+        .map(|value| {
+            DialogueStreamMode::parse(&value).unwrap_or_else(|| {
+                warn!(value = %value, "Unknown dialogue stream mode, using edit");
+                defaults.mode
+            })
+        })
+        .unwrap_or(defaults.mode);
+
+    DialogueStreamingConfig {
+        mode,
+        edit_interval_ms: std::env::var("DIALOGUE_STREAM_EDIT_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .or(settings.dialogue_stream_edit_interval_ms)
+            .unwrap_or(defaults.edit_interval_ms)
+            .max(250),
+    }
+}
+
+fn resolve_context_budget(settings: &SettingsJson) -> ContextBudgetConfig {
+    let defaults = ContextBudgetConfig::default();
+    ContextBudgetConfig {
+        context_window: std::env::var("DIALOGUE_CONTEXT_WINDOW")
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .or(settings.dialogue_context_window)
+            .unwrap_or(defaults.context_window)
+            .max(1_024),
+        max_message_tokens: std::env::var("DIALOGUE_CONTEXT_MAX_MESSAGE_TOKENS")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .or(settings.dialogue_context_max_message_tokens)
+            .unwrap_or(defaults.max_message_tokens)
+            .max(32),
+        ..defaults
+    }
+}
+
 fn apply_non_api_settings_to_env(settings: &SettingsJson) {
     if std::env::var("SEMANTIC_MAX_TOKENS").is_err() {
         std::env::set_var(
@@ -149,11 +200,13 @@ fn load_settings_and_apply_env() -> SettingsJson {
 }
 
 use cognitive::{
-    AffectEvaluatorConfig, AffectEvaluatorWorker, DialogueEngineConfig, DialogueEngineWorker,
+    AffectEvaluatorConfig, AffectEvaluatorWorker, ContextBudgetConfig, DialogueEngineConfig,
+    DialogueEngineWorker, DialogueStreamMode, DialogueStreamingConfig,
 };
 use cognitive::dialogue_engine::DialogueToolCallingConfig;
 use cockpit_api::{CockpitApiConfig, CockpitWorker};
-use mcp::{McpConfig, McpTransport, McpWorker};
+use http_platform::{HttpPlatformConfig, HttpPlatformWorker};
+use mcp::{ForgetToolProvider, McpConfig, McpTransport, McpWorker};
 use memory::MemoryWorker;
 use runtime::{Coordinator, Supervisor};
 use state::{
@@ -392,11 +445,33 @@ async fn main() -> Result<()> {
         let _ = store.recompute_derived().await;
     }
 
+    use std::sync::Arc;
+    use sensory::outbound::{OutboundConfig, OutboundQueue, OutboundStore};
     use sensory::relay::PlatformRelayWorker;
-    supervisor.register(PlatformRelayWorker::from_env());
+
+    let outbound_db_path = agent_profile.outbound_db_path.clone();
+    if let Some(parent) = std::path::Path::new(&outbound_db_path).parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let outbound_queue = Arc::new(tokio::sync::Mutex::new(OutboundQueue::open(
+        OutboundStore::open(&outbound_db_path)?,
+        OutboundConfig::default(),
+        sensory::outbound::now_ms(),
+    )?));
+    supervisor.register(
+        PlatformRelayWorker::from_env().with_outbound_queue(Arc::clone(&outbound_queue)),
+    );
     worker_count += 1;
 
-    use std::sync::Arc;
+    let http_platform_config = HttpPlatformConfig::from_env();
+    if http_platform_config.enabled {
+        info!(bind = %http_platform_config.bind_addr, "Registering HTTP platform worker");
+        supervisor.register(HttpPlatformWorker::new(http_platform_config));
+        worker_count += 1;
+    }
+
     use memory::{episodic::EpisodicStore, embedder::MemoryEmbedder, compressor::SemanticCompressor};
 
     let memory_db_path = agent_profile.memory_db_path.clone();
@@ -421,6 +496,18 @@ async fn main() -> Result<()> {
         std::fs::create_dir_all(lancedb_path_obj)?;
     }
 
+    if parse_env_bool("MIGRATIONS_DRY_RUN", false) {
+        for report in [
+            memory::MemoryStore::migration_plan(&memory_db_path)?,
+            EpisodicStore::migration_plan(&lancedb_path, "episodic_memory").await?,
+            memory::graph::CognitiveGraph::migration_plan(&graph_db_path).await?,
+        ] {
+            report.log();
+        }
+        info!("MIGRATIONS_DRY_RUN is set, exiting before any store is migrated");
+        return Ok(());
+    }
+
     info!("Initializing Episodic Memory and Embedding Engine...");
     let episodic = Arc::new(EpisodicStore::open(&lancedb_path, "episodic_memory").await?);
     let embedder_pool_size = std::env::var("EMBEDDER_POOL_SIZE")
@@ -432,18 +519,12 @@ async fn main() -> Result<()> {
     info!(pool_size = embedder.pool_size(), "Embedding pool initialized");
     let compressor_opt = SemanticCompressor::new().ok().map(Arc::new);
 
-    info!("Initializing SurrealDB Cognitive Graph...");
-    let cognitive_graph = memory::graph::CognitiveGraph::new(&graph_db_path).await?;
-    let mcp_config = load_mcp_config();
+    let identity_store = memory::IdentityStore::open(&memory_db_path)?;
 
-    if mcp_config.enabled {
-        info!(
-            max_tool_calls_per_turn = mcp_config.max_tool_calls_per_turn,
-            "Registering MCP worker"
-        );
-        supervisor.register(McpWorker::new(mcp_config.clone(), cognitive_graph.clone()));
-        worker_count += 1;
-    }
+    info!("Initializing SurrealDB Cognitive Graph...");
+    let cognitive_graph = memory::graph::CognitiveGraph::new(&graph_db_path)
+        .await?
+        .with_identity(identity_store.clone());
 
     if compressor_opt.is_none() {
         warn!("SLM Compressor missing configs, episodic memory will not ingest new events.");
@@ -451,17 +532,43 @@ async fn main() -> Result<()> {
 
     let mut memory_worker = MemoryWorker::new(&memory_db_path)
         .with_episodic(Arc::clone(&episodic))
-        .with_embedder(Arc::clone(&embedder));
+        .with_embedder(Arc::clone(&embedder))
+        .with_identity(identity_store.clone())
+        .with_graph(cognitive_graph.clone())
+        .with_ambient(memory::AmbientConfig::from_env());
     
     if let Some(comp) = &compressor_opt {
         memory_worker = memory_worker.with_compressor(Arc::clone(comp));
     }
 
     let short_term_handle = memory_worker.short_term_handle();
+    let mut forgetter = memory::Forgetter::new(&memory_db_path)
+        .with_short_term(Arc::clone(&short_term_handle))
+        .with_episodic(Arc::clone(&episodic))
+        .with_graph(cognitive_graph.clone())
+        .with_identity(identity_store.clone());
+    if let Some(store) = &state_store {
+        forgetter = forgetter.with_state_store(store.clone());
+    }
+    let memory_worker = memory_worker.with_forgetter(forgetter.clone());
     supervisor.register(memory_worker);
     worker_count += 1;
     info!("Registered Memory worker");
 
+    let mcp_config = load_mcp_config();
+
+    if mcp_config.enabled {
+        info!(
+            max_tool_calls_per_turn = mcp_config.max_tool_calls_per_turn,
+            "Registering MCP worker"
+        );
+        supervisor.register(
+            McpWorker::new(mcp_config.clone(), cognitive_graph.clone())
+                .with_tool_provider(Arc::new(ForgetToolProvider::new(forgetter.clone()))),
+        );
+        worker_count += 1;
+    }
+
     let state_store_for_cockpit = state_store.clone();
     let state_store_for_affect = state_store.clone();
     let state_store_for_dialogue = state_store.clone();
@@ -510,6 +617,11 @@ async fn main() -> Result<()> {
     if cockpit_enabled {
         if let Some(store) = state_store_for_cockpit {
             info!(bind = %cockpit_bind, "Registering local cockpit API worker");
+            let archiver = memory::Archiver::new(&memory_db_path)
+                .with_episodic(Arc::clone(&episodic))
+                .with_graph(cognitive_graph.clone())
+                .with_identity(identity_store.clone())
+                .with_state_store(store.clone());
             supervisor.register(
                 CockpitWorker::new(
                     CockpitApiConfig {
@@ -522,7 +634,11 @@ async fn main() -> Result<()> {
                 .with_memory_db_path(memory_db_path.clone())
                 .with_short_term(Arc::clone(&short_term_handle))
                 .with_episodic(Arc::clone(&episodic))
-                .with_graph(cognitive_graph.clone()),
+                .with_graph(cognitive_graph.clone())
+                .with_identity_store(identity_store.clone())
+                .with_forgetter(forgetter.clone())
+                .with_archiver(archiver)
+                .with_outbound(Arc::clone(&outbound_queue)),
             );
             worker_count += 1;
         } else {
@@ -541,6 +657,8 @@ async fn main() -> Result<()> {
         reasoning: config.dialogue_engine.reasoning.clone(),
         tool_calling: dialogue_tool_calling,
         api_timeout_secs: settings.api_timeout_secs,
+        streaming: resolve_dialogue_streaming(&settings),
+        context_budget: resolve_context_budget(&settings),
     };
 
     if dialogue_engine_config.is_valid() {
@@ -555,7 +673,8 @@ async fn main() -> Result<()> {
                     .with_memory(Arc::clone(&short_term_handle))
                     .with_episodic(Arc::clone(&episodic))
                     .with_embedder(Arc::clone(&embedder))
-                    .with_graph(cognitive_graph.clone());
+                    .with_graph(cognitive_graph.clone())
+                    .with_identity(identity_store.clone());
                 if let Some(store) = state_store_for_dialogue {
                     worker = worker.with_state_store(store);
                 }
@@ -604,7 +723,8 @@ async fn main() -> Result<()> {
                 Arc::clone(&short_term_handle),
                 Some(Arc::clone(&episodic)),
                 Some(Arc::clone(&embedder)),
-            );
+            )
+            .with_identity(identity_store.clone());
             if let Some(store) = state_store_for_affect {
                 affect_worker = affect_worker.with_state_store(store);
             }
@@ -918,6 +1038,36 @@ reasoning = "file-reasoning"
         remove_env("DEBUG_MODE");
     }
 
+    #[test]
+    fn resolve_context_budget_uses_env_then_settings_then_defaults() {
+        let _guard = env_guard();
+        remove_env("DIALOGUE_CONTEXT_WINDOW");
+        remove_env("DIALOGUE_CONTEXT_MAX_MESSAGE_TOKENS");
+
+        let defaults = resolve_context_budget(&SettingsJson::default());
+        assert_eq!(
+            defaults.context_window,
+            ContextBudgetConfig::default().context_window
+        );
+
+        let settings = SettingsJson {
+            dialogue_context_window: Some(8_000),
+            dialogue_context_max_message_tokens: Some(300),
+            ..Default::default()
+        };
+        let budget = resolve_context_budget(&settings);
+        assert_eq!(budget.context_window, 8_000);
+        assert_eq!(budget.max_message_tokens, 300);
+
+        set_env("DIALOGUE_CONTEXT_WINDOW", "100");
+        assert_eq!(resolve_context_budget(&settings).context_window, 1_024);
+        set_env("DIALOGUE_CONTEXT_WINDOW", "128000");
+        assert_eq!(resolve_context_budget(&settings).context_window, 128_000);
+
+        remove_env("DIALOGUE_CONTEXT_WINDOW");
+        remove_env("DIALOGUE_CONTEXT_MAX_MESSAGE_TOKENS");
+    }
+
     #[test]
     fn apply_non_api_settings_to_env_only_fills_missing_values() {
         let _guard = env_guard();
@@ -103,6 +103,7 @@ export type ActiveSessionSnapshot = {
   conversation: string;
   platform: string;
   channel_id: string;
+  thread_id: string | null;
   message_count: number;
   started_at: string;
   last_active: string;
@@ -113,6 +114,7 @@ export type MemoryMessage = {
   id: string;
   platform: string;
   channel_id: string;
+  thread_id?: string | null;
   user_id: string;
   username: string;
   content: string;
@@ -3,12 +3,12 @@ use std::time::Instant;
 use anyhow::Result;
 use async_trait::async_trait;
 use kernel::get_agent_profile;
-use kernel::event::Event;
+use kernel::event::{Event, ReplyReference};
 use kernel::prompt_registry::{get_prompt_or, render_prompt_or};
 use kernel::worker::{Worker, WorkerContext, WorkerStatus};
 use memory::graph::{CognitiveGraph, SocialDelta, EmotionDelta};
 use memory::short_term::ShortTermMemory;
-use memory::{episodic::EpisodicStore, embedder::MemoryEmbedder};
+use memory::{episodic::EpisodicStore, embedder::MemoryEmbedder, identity::IdentityStore};
 use memory::types::ConversationKey;
 use state::{EventDeltaRequest, StateStore};
 use reqwest::Client;
@@ -157,6 +157,7 @@ pub struct AffectEvaluatorWorker {
     pub episodic: Option<Arc<EpisodicStore>>,
     pub embedder: Option<Arc<MemoryEmbedder>>,
     pub state_store: Option<StateStore>,
+    pub identity: Option<IdentityStore>,
     affect_limiter: Arc<Semaphore>,
 }
 
@@ -218,6 +219,7 @@ impl AffectEvaluatorWorker {
             episodic,
             embedder,
             state_store: None,
+            identity: None,
             affect_limiter: Arc::new(Semaphore::new(1)),
         }
     }
@@ -227,6 +229,11 @@ impl AffectEvaluatorWorker {
         self
     }
 
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
     fn build_system_prompt(&self) -> String {
         get_prompt_or(
             "affect_evaluator.base_instruction",
@@ -262,6 +269,7 @@ impl Worker for AffectEvaluatorWorker {
         let graph = self.graph.clone();
         let short_term = self.short_term.clone();
         let state_store = self.state_store.clone();
+        let identity = self.identity.clone();
         let affect_limiter = self.affect_limiter.clone();
 
         let mut active_tasks = tokio::task::JoinSet::new();
@@ -281,8 +289,12 @@ impl Worker for AffectEvaluatorWorker {
 	                            };
                             
                             let target_user = user_id.clone();
+                            let identity = identity.clone();
+                            let platform = raw.platform;
+                            let platform_user_id = raw.user_id.clone();
                             let message_id = raw.message_id.clone();
                             let current_msg = raw.content.clone();
+                            let reply_to = raw.reply_to.clone();
                             let c = config.clone();
                             let h = http_client.clone();
                             let sp = system_prompt.clone();
@@ -299,7 +311,14 @@ impl Worker for AffectEvaluatorWorker {
 	                                    Ok(permit) => permit,
 	                                    Err(_) => return,
 	                                };
-	                                Self::evaluate_turn(&h, &c, &sp, &pp, &g, st, &target_user, &message_id, history, &current_msg, e, em).await;
+	                                let person_id = crate::context::resolve_person(
+	                                    identity,
+	                                    platform,
+	                                    &platform_user_id,
+	                                    &target_user,
+	                                )
+	                                .await;
+	                                Self::evaluate_turn(&h, &c, &sp, &pp, &g, st, &target_user, &person_id, &message_id, history, &current_msg, reply_to.as_ref(), e, em).await;
 	                            });
 	                        }
                         Ok(_) => {}
@@ -336,9 +355,11 @@ impl AffectEvaluatorWorker {
         graph: &CognitiveGraph,
         state_store: Option<StateStore>,
         user_id: &str,
+        person_id: &str,
         message_id: &str,
         history: Vec<(String, String, String)>,
         current_msg: &str,
+        reply_to: Option<&ReplyReference>,
         episodic: Option<Arc<EpisodicStore>>,
         embedder: Option<Arc<MemoryEmbedder>>,
     ) {
@@ -353,7 +374,9 @@ impl AffectEvaluatorWorker {
             episodic.as_ref(),
             embedder.as_ref(),
             user_id,
+            person_id,
             current_msg,
+            reply_to,
         ).await;
         latency.shared_context_ms = context_started.elapsed().as_millis();
         latency.embed_ms = cognitive_context.timing.embed_ms;
@@ -507,7 +530,14 @@ impl AffectEvaluatorWorker {
                         delta_tension: social.actual_perception_delta.delta_tension,
                     };
 
-                    if let Err(e) = graph.update_social_graph(&social.target_user, s_delta).await {
+                    // The speaker is already resolved; only other people are
+                    // looked up by the name the model gave.
+                    let target_key = if social.target_user == user_id {
+                        person_id
+                    } else {
+                        social.target_user.as_str()
+                    };
+                    if let Err(e) = graph.update_social_graph(target_key, s_delta).await {
                         error!("Failed to update social graph for {}: {}", social.target_user, e);
                     }
 
@@ -516,7 +546,7 @@ impl AffectEvaluatorWorker {
                     } else {
                         0.0
                     };
-                    if let Err(e) = graph.project_social_tree(&social.target_user, projection_hint).await {
+                    if let Err(e) = graph.project_social_tree(target_key, projection_hint).await {
                         warn!("Failed to project social tree for {}: {}", social.target_user, e);
                     }
 
@@ -533,7 +563,7 @@ impl AffectEvaluatorWorker {
                                 delta_safety: illusion.expected_delta_safety,
                                 delta_tension: illusion.expected_delta_tension,
                             };
-                            if let Err(e) = graph.update_illusion_graph(&social.target_user, i_delta).await {
+                            if let Err(e) = graph.update_illusion_graph(target_key, i_delta).await {
                                 error!("Failed to update illusion graph for {}: {}", social.target_user, e);
                             }
                         }
@@ -2,11 +2,13 @@ use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 
+use kernel::event::ReplyReference;
 use kernel::get_agent_profile;
 use kernel::prompt_registry::render_prompt_or;
 use memory::{
     episodic::EpisodicStore,
     embedder::MemoryEmbedder,
+    identity::IdentityStore,
 };
 use tokio::sync::Mutex;
 
@@ -59,13 +61,38 @@ fn user_chunk_count_cache() -> &'static Mutex<HashMap<String, CachedChunkCount>>
     USER_CHUNK_COUNT_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// The canonical person behind a platform account, or the username when
+/// identities are not tracked. Resolving may record a new person, so the
+/// SQLite work runs on the blocking pool.
+pub(crate) async fn resolve_person(
+    identity: Option<IdentityStore>,
+    platform: kernel::event::Platform,
+    user_id: &str,
+    username: &str,
+) -> String {
+    let Some(identity) = identity else {
+        return username.to_string();
+    };
+    let (user_id, name) = (user_id.to_string(), username.to_string());
+    let resolved = tokio::task::spawn_blocking(move || identity.resolve(platform, &user_id, &name))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+    resolved.unwrap_or_else(|e| {
+        tracing::warn!(error = %e, user = %username, "Failed to resolve identity; using username");
+        username.to_string()
+    })
+}
+
 pub async fn build_shared_cognitive_context(
     message_id: &str,
     history: &[(String, String, String)],
     episodic: Option<&Arc<EpisodicStore>>,
     embedder: Option<&Arc<MemoryEmbedder>>,
     current_username: &str,
+    person_id: &str,
     new_message: &str,
+    reply_to: Option<&ReplyReference>,
 ) -> SharedCognitiveContext {
     let overall_started = Instant::now();
     let slot = {
@@ -93,7 +120,9 @@ pub async fn build_shared_cognitive_context(
         episodic,
         embedder,
         current_username,
+        person_id,
         new_message,
+        reply_to,
     )
     .await;
 
@@ -112,7 +141,9 @@ async fn build_shared_cognitive_context_uncached(
     episodic: Option<&Arc<EpisodicStore>>,
     embedder: Option<&Arc<MemoryEmbedder>>,
     current_username: &str,
+    person_id: &str,
     new_message: &str,
+    reply_to: Option<&ReplyReference>,
 ) -> SharedCognitiveContext {
     let mut timing = SharedContextTiming::default();
 
@@ -165,7 +196,7 @@ async fn build_shared_cognitive_context_uncached(
     }
 
     let chunk_count_started = Instant::now();
-    let lancedb_count = cached_user_chunk_count(episodic, current_username).await;
+    let lancedb_count = cached_user_chunk_count(episodic, person_id).await;
     timing.chunk_count_ms = chunk_count_started.elapsed().as_millis();
 
     let format_started = Instant::now();
@@ -220,6 +251,10 @@ async fn build_shared_cognitive_context_uncached(
             "[context: there were {{history_len}} previous messages. participants: {{participants}}.]",
         ));
     }
+    if let Some(reference) = reply_to {
+        time_and_history_text.push('\n');
+        time_and_history_text.push_str(&reply_context_text(current_username, reference));
+    }
     timing.format_ms = format_started.elapsed().as_millis();
 
     SharedCognitiveContext {
@@ -230,9 +265,66 @@ async fn build_shared_cognitive_context_uncached(
     }
 }
 
+/// The rolling summary of the messages that fell out of the history, as a
+/// system block.
+pub(crate) fn session_summary_text(summary: Option<&str>) -> Option<String> {
+    let summary = summary.map(str::trim).filter(|s| !s.is_empty())?;
+    Some(render_prompt_or(
+        "context.session.summary",
+        &[("summary", summary)],
+        "### SO FAR IN THIS CONVERSATION (summary of earlier messages no longer shown):\n{{summary}}\n",
+    ))
+}
+
+/// What the channel said without tagging the agent, as a system block.
+pub(crate) fn ambient_context_text(ambient: &[(String, String)]) -> Option<String> {
+    if ambient.is_empty() {
+        return None;
+    }
+    let mut text = render_prompt_or(
+        "context.ambient.header",
+        &[],
+        "### RECENT CHANNEL CHATTER (not addressed to you):\n",
+    );
+    for (username, content) in ambient {
+        text.push_str(&render_prompt_or(
+            "context.ambient.item",
+            &[
+                ("username", username.as_str()),
+                ("content", content.as_str()),
+            ],
+            "- {{username}}: {{content}}\n",
+        ));
+    }
+    Some(text)
+}
+
+fn reply_context_text(current_username: &str, reference: &ReplyReference) -> String {
+    if reference.is_agent {
+        render_prompt_or(
+            "context.reply_to.agent",
+            &[
+                ("username", current_username),
+                ("snippet", reference.snippet.as_str()),
+            ],
+            "[context: {{username}} is replying to your earlier message: \"{{snippet}}\"]",
+        )
+    } else {
+        render_prompt_or(
+            "context.reply_to.other",
+            &[
+                ("username", current_username),
+                ("author", reference.author_name.as_str()),
+                ("snippet", reference.snippet.as_str()),
+            ],
+            "[context: {{username}} is replying to {{author}}: \"{{snippet}}\"]",
+        )
+    }
+}
+
 async fn cached_user_chunk_count(
     episodic: Option<&Arc<EpisodicStore>>,
-    current_username: &str,
+    person_id: &str,
 ) -> usize {
     let Some(ep) = episodic else {
         return 0;
@@ -240,21 +332,21 @@ async fn cached_user_chunk_count(
 
     {
         let cache = user_chunk_count_cache().lock().await;
-        if let Some(entry) = cache.get(current_username) {
+        if let Some(entry) = cache.get(person_id) {
             if entry.cached_at.elapsed() < USER_CHUNK_COUNT_TTL {
                 return entry.value;
             }
         }
     }
 
-    let count = ep.count_user_chunks(current_username).await.unwrap_or(0_usize);
+    let count = ep.count_user_chunks(person_id).await.unwrap_or(0_usize);
 
     let mut cache = user_chunk_count_cache().lock().await;
     if cache.len() > MAX_USER_CHUNK_COUNT_CACHE {
         cache.clear();
     }
     cache.insert(
-        current_username.to_string(),
+        person_id.to_string(),
         CachedChunkCount {
             cached_at: Instant::now(),
             value: count,
@@ -0,0 +1,583 @@
+//! Fitting a dialogue prompt into the model's context window.
+//!
+//! The system prompt is assembled from blocks of very different value. When
+//! the blocks, the session history and the new message do not fit next to
+//! the reply budget, the least valuable parts go first: long past messages
+//! are shortened, then the oldest messages are left out, then optional
+//! blocks are cut from the bottom of the priority list.
+
+use std::fmt;
+
+use kernel::prompt_registry::render_prompt_or;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, CoreBPE};
+
+/// Added per chat message for the role and framing tokens.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// A block is dropped rather than cut below this size.
+const MIN_TRUNCATED_BLOCK_TOKENS: usize = 64;
+const TRUNCATION_MARK: &str = " […]";
+
+#[derive(Debug, Clone)]
+pub struct ContextBudgetConfig {
+    /// Tokens the model accepts, prompt and reply together.
+    pub context_window: u32,
+    /// Past messages longer than this are shortened first.
+    pub max_message_tokens: usize,
+    /// History kept before optional blocks are touched.
+    pub min_history_messages: usize,
+}
+
+impl Default for ContextBudgetConfig {
+    fn default() -> Self {
+        Self {
+            context_window: 32_768,
+            max_message_tokens: 512,
+            min_history_messages: 4,
+        }
+    }
+}
+
+/// Token counts for a model: exact with the model's BPE encoding when it
+/// has a public one, approximated from the text otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenEstimator {
+    /// `cl100k_base` or `o200k_base`, for OpenAI models.
+    encoding: Option<Tokenizer>,
+    /// Characters per token for ASCII text, without an encoding.
+    chars_per_token: f32,
+}
+
+impl TokenEstimator {
+    /// Matches on the model name, which may carry a provider prefix such
+    /// as `anthropic/`. Unknown models get a conservative ratio.
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let name = model.rsplit('/').next().unwrap_or(&model);
+        let encoding = get_tokenizer(name)
+            .filter(|encoding| matches!(encoding, Tokenizer::Cl100kBase | Tokenizer::O200kBase));
+        let chars_per_token =
+            if name.starts_with("gpt") || is_openai_reasoning(name) || name.contains("gemini") {
+                4.0
+            } else if name.contains("claude") {
+                3.5
+            } else if ["llama", "mistral", "mixtral", "qwen", "deepseek", "gemma"]
+                .iter()
+                .any(|family| name.contains(family))
+            {
+                3.6
+            } else {
+                3.3
+            };
+        Self {
+            encoding,
+            chars_per_token,
+        }
+    }
+
+    /// Without an encoding, CJK characters count as a token each and other
+    /// non-ASCII characters as half of one.
+    pub fn estimate(&self, text: &str) -> usize {
+        if let Some(bpe) = self.bpe() {
+            return bpe.encode_ordinary(text).len();
+        }
+        text.chars()
+            .map(|c| self.char_tokens(c))
+            .sum::<f32>()
+            .ceil() as usize
+    }
+
+    /// The longest prefix of `text` within `max_tokens`, marked as cut.
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        if self.estimate(text) <= max_tokens {
+            return text.to_string();
+        }
+        let limit = max_tokens.saturating_sub(self.estimate(TRUNCATION_MARK));
+        let prefix = match self.bpe() {
+            Some(bpe) => {
+                let tokens = bpe.encode_ordinary(text);
+                let mut keep = limit.min(tokens.len());
+                loop {
+                    match bpe.decode(tokens[..keep].to_vec()) {
+                        Ok(prefix) => break prefix,
+                        // The cut fell inside a multi-byte character.
+                        Err(_) if keep > 0 => keep -= 1,
+                        Err(_) => break String::new(),
+                    }
+                }
+            }
+            None => {
+                let mut end = 0;
+                let mut tokens = 0f32;
+                for (i, c) in text.char_indices() {
+                    tokens += self.char_tokens(c);
+                    if tokens.ceil() as usize > limit {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                }
+                text[..end].to_string()
+            }
+        };
+        format!("{}{}", prefix.trim_end(), TRUNCATION_MARK)
+    }
+
+    fn bpe(&self) -> Option<&'static CoreBPE> {
+        match self.encoding? {
+            Tokenizer::O200kBase => Some(o200k_base_singleton()),
+            Tokenizer::Cl100kBase => Some(cl100k_base_singleton()),
+            _ => None,
+        }
+    }
+
+    fn char_tokens(&self, c: char) -> f32 {
+        if c.is_ascii() {
+            1.0 / self.chars_per_token
+        } else if is_cjk(c) {
+            1.0
+        } else {
+            0.5
+        }
+    }
+}
+
+fn is_openai_reasoning(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}'
+            | '\u{f900}'..='\u{faff}'
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Persona,
+    ReactionPolicy,
+    Memory,
+    Ambient,
+    SessionSummary,
+    Social,
+    StateLegend,
+    StateSnapshot,
+    TimeAndHistory,
+    HistoryNote,
+}
+
+impl BlockKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockKind::Persona => "persona",
+            BlockKind::ReactionPolicy => "reaction_policy",
+            BlockKind::Memory => "memory",
+            BlockKind::Ambient => "ambient",
+            BlockKind::SessionSummary => "session_summary",
+            BlockKind::Social => "social",
+            BlockKind::StateLegend => "state_legend",
+            BlockKind::StateSnapshot => "state_snapshot",
+            BlockKind::TimeAndHistory => "time_and_history",
+            BlockKind::HistoryNote => "history_note",
+        }
+    }
+
+    /// Optional blocks in the order they are cut, lowest value first.
+    /// The rest are always sent.
+    const CUT_ORDER: [BlockKind; 6] = [
+        BlockKind::StateLegend,
+        BlockKind::StateSnapshot,
+        BlockKind::Ambient,
+        BlockKind::Social,
+        BlockKind::SessionSummary,
+        BlockKind::Memory,
+    ];
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptBlock {
+    pub kind: BlockKind,
+    pub text: String,
+}
+
+impl PromptBlock {
+    pub fn new(kind: BlockKind, text: String) -> Self {
+        Self { kind, text }
+    }
+}
+
+/// Join the non-empty blocks into one system prompt.
+pub fn merge_blocks(blocks: &[PromptBlock]) -> String {
+    blocks
+        .iter()
+        .map(|block| block.text.as_str())
+        .filter(|text| !text.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// How a turn's prompt was fitted, for the debug log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextAllocation {
+    pub budget: usize,
+    pub used: usize,
+    /// Tokens per block as sent.
+    pub blocks: Vec<(&'static str, usize)>,
+    pub truncated_blocks: Vec<&'static str>,
+    pub dropped_blocks: Vec<&'static str>,
+    pub history_tokens: usize,
+    pub history_kept: usize,
+    pub history_truncated: usize,
+    pub history_dropped: usize,
+}
+
+impl ContextAllocation {
+    pub fn over_budget(&self) -> bool {
+        self.used > self.budget
+    }
+}
+
+impl fmt::Display for ContextAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} tokens;", self.used, self.budget)?;
+        for (name, tokens) in &self.blocks {
+            write!(f, " {}={}", name, tokens)?;
+        }
+        write!(
+            f,
+            " history={} ({} messages, {} shortened, {} left out)",
+            self.history_tokens, self.history_kept, self.history_truncated, self.history_dropped
+        )?;
+        if !self.truncated_blocks.is_empty() {
+            write!(f, "; cut {}", self.truncated_blocks.join(","))?;
+        }
+        if !self.dropped_blocks.is_empty() {
+            write!(f, "; dropped {}", self.dropped_blocks.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// The token budget of one model's prompts.
+#[derive(Debug, Clone)]
+pub struct ContextBudget {
+    config: ContextBudgetConfig,
+    estimator: TokenEstimator,
+    reply_tokens: usize,
+}
+
+impl ContextBudget {
+    pub fn new(model: &str, reply_tokens: u32, config: &ContextBudgetConfig) -> Self {
+        Self {
+            config: config.clone(),
+            estimator: TokenEstimator::for_model(model),
+            reply_tokens: reply_tokens as usize,
+        }
+    }
+
+    /// Tokens left for the prompt once the reply is provided for.
+    pub fn input_budget(&self) -> usize {
+        (self.config.context_window as usize).saturating_sub(self.reply_tokens)
+    }
+
+    /// Shrink `blocks` and `history` (`(role, username, content)`, oldest
+    /// first) until they fit next to `user_message`. The new message and
+    /// the required blocks are never cut, so the result can still be over
+    /// budget.
+    pub fn fit(
+        &self,
+        blocks: &mut Vec<PromptBlock>,
+        history: &mut Vec<(String, String, String)>,
+        user_message: &str,
+    ) -> ContextAllocation {
+        let budget = self.input_budget();
+        let estimate = |text: &str| self.estimator.estimate(text) + MESSAGE_OVERHEAD_TOKENS;
+        let fixed = estimate(user_message);
+        let separator = self.estimator.estimate("\n\n");
+        // Room for the note is kept as soon as any message is left out; the
+        // count only gets shorter than this one.
+        let note_tokens = self.estimator.estimate(&history_note(history.len()));
+        let mut block_tokens: Vec<usize> = blocks
+            .iter()
+            .map(|b| match b.text.trim().is_empty() {
+                true => 0,
+                false => self.estimator.estimate(&b.text),
+            })
+            .collect();
+        let mut history_tokens: Vec<usize> = history.iter().map(|(_, _, c)| estimate(c)).collect();
+        // Blocks are merged into one system message, so each one past the
+        // first also costs a separator. A left-out message is counted as 0.
+        let used = |blocks: &[usize], history: &[usize]| {
+            let note = if history.contains(&0) { note_tokens } else { 0 };
+            let parts = blocks.iter().chain([&note]).filter(|t| **t > 0).count();
+            let system = blocks.iter().sum::<usize>()
+                + note
+                + separator * parts.saturating_sub(1)
+                + MESSAGE_OVERHEAD_TOKENS;
+            fixed + system + history.iter().sum::<usize>()
+        };
+
+        let mut allocation = ContextAllocation {
+            budget,
+            ..ContextAllocation::default()
+        };
+
+        // Shorten long past messages, oldest first.
+        for (i, (_, _, content)) in history.iter_mut().enumerate() {
+            if used(&block_tokens, &history_tokens) <= budget {
+                break;
+            }
+            if history_tokens[i] > self.config.max_message_tokens + MESSAGE_OVERHEAD_TOKENS {
+                *content = self
+                    .estimator
+                    .truncate(content, self.config.max_message_tokens);
+                history_tokens[i] = estimate(content);
+                allocation.history_truncated += 1;
+            }
+        }
+
+        // Leave out the oldest messages, down to the minimum.
+        let mut dropped = 0;
+        while used(&block_tokens, &history_tokens) > budget
+            && history.len() - dropped > self.config.min_history_messages
+        {
+            dropped += 1;
+            history_tokens[dropped - 1] = 0;
+        }
+
+        // Cut optional blocks, lowest value first.
+        for kind in BlockKind::CUT_ORDER {
+            let Some(i) = blocks.iter().position(|b| b.kind == kind) else {
+                continue;
+            };
+            let total = used(&block_tokens, &history_tokens);
+            if total <= budget {
+                break;
+            }
+            let keep = block_tokens[i].saturating_sub(total - budget);
+            if keep >= MIN_TRUNCATED_BLOCK_TOKENS {
+                blocks[i].text = self.estimator.truncate(&blocks[i].text, keep);
+                block_tokens[i] = self.estimator.estimate(&blocks[i].text);
+                allocation.truncated_blocks.push(kind.name());
+            } else {
+                blocks[i].text.clear();
+                block_tokens[i] = 0;
+                allocation.dropped_blocks.push(kind.name());
+            }
+        }
+
+        // Then the rest of the history.
+        while used(&block_tokens, &history_tokens) > budget && dropped < history.len() {
+            dropped += 1;
+            history_tokens[dropped - 1] = 0;
+        }
+
+        if dropped > 0 {
+            history.drain(..dropped);
+            history_tokens.drain(..dropped);
+            let note = history_note(dropped);
+            block_tokens.push(self.estimator.estimate(&note));
+            blocks.push(PromptBlock::new(BlockKind::HistoryNote, note));
+        }
+
+        let mut kept = Vec::new();
+        for (block, tokens) in blocks.iter().zip(&block_tokens) {
+            if block.text.trim().is_empty() {
+                continue;
+            }
+            match kept.iter_mut().find(|(name, _)| *name == block.kind.name()) {
+                Some((_, sum)) => *sum += tokens,
+                None => kept.push((block.kind.name(), *tokens)),
+            }
+        }
+        blocks.retain(|block| !block.text.trim().is_empty());
+
+        allocation.used = used(&block_tokens, &history_tokens);
+        allocation.blocks = kept;
+        allocation.history_tokens = history_tokens.iter().sum();
+        allocation.history_kept = history.len();
+        allocation.history_dropped = dropped;
+        allocation
+    }
+}
+
+/// Tells the model that the first `count` messages were left out.
+fn history_note(count: usize) -> String {
+    let count = count.to_string();
+    render_prompt_or(
+        "context.history.omitted",
+        &[("count", count.as_str())],
+        "({{count}} earlier messages of this conversation were left out to fit the context window.)",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(role: &str, content: &str) -> (String, String, String) {
+        (role.to_string(), "alice".to_string(), content.to_string())
+    }
+
+    fn budget(context_window: u32) -> ContextBudget {
+        ContextBudget::new(
+            "openai/gpt-4o",
+            100,
+            &ContextBudgetConfig {
+                context_window,
+                max_message_tokens: 50,
+                min_history_messages: 2,
+            },
+        )
+    }
+
+    fn blocks() -> Vec<PromptBlock> {
+        vec![
+            PromptBlock::new(BlockKind::Persona, "You are Nova.".to_string()),
+            PromptBlock::new(BlockKind::Memory, "remembered fact ".repeat(40)),
+            PromptBlock::new(BlockKind::StateLegend, "legend line ".repeat(60)),
+            PromptBlock::new(BlockKind::TimeAndHistory, "It is noon.".to_string()),
+        ]
+    }
+
+    #[test]
+    fn openai_models_are_counted_with_their_encoding() {
+        let gpt = TokenEstimator::for_model("openai/gpt-4o-mini");
+        assert_eq!(gpt.encoding, Some(Tokenizer::O200kBase));
+        assert_eq!(TokenEstimator::for_model("o3-mini"), gpt);
+        assert_eq!(
+            TokenEstimator::for_model("gpt-4-turbo").encoding,
+            Some(Tokenizer::Cl100kBase)
+        );
+        assert_eq!(gpt.estimate("hello world"), 2);
+
+        let cut = gpt.truncate(&"word ".repeat(100), 20);
+        assert!(cut.ends_with(TRUNCATION_MARK));
+        assert!(cut.starts_with("word word"));
+        assert!(gpt.estimate(&cut) <= 20);
+        assert_eq!(gpt.truncate("short", 20), "short");
+        // A cut never splits a character, however the encoding splits it.
+        let cut = gpt.truncate(&"😀".repeat(50), 10);
+        assert!(cut.trim_end_matches(TRUNCATION_MARK).chars().all(|c| c == '😀'));
+    }
+
+    #[test]
+    fn other_models_fall_back_to_a_ratio_by_family() {
+        let text = "a".repeat(400);
+        let claude = TokenEstimator::for_model("anthropic/claude-3.5-sonnet");
+        let llama = TokenEstimator::for_model("meta-llama/llama-3.1-70b");
+        assert_eq!(claude.encoding, None);
+        assert_eq!(claude.estimate(&text), 115);
+        assert_eq!(llama.estimate(&text), 112);
+        assert!(TokenEstimator::for_model("some-model").estimate(&text) > claude.estimate(&text));
+        assert_eq!(claude.estimate("你好吗"), 3);
+
+        let cut = claude.truncate(&"word ".repeat(100), 20);
+        assert!(cut.ends_with(TRUNCATION_MARK));
+        assert!(claude.estimate(&cut) <= 20);
+    }
+
+    #[test]
+    fn prompt_that_fits_is_untouched() {
+        let mut blocks = blocks();
+        let mut history = vec![turn("user", "hi"), turn("assistant", "hello")];
+        let allocation = budget(8_000).fit(&mut blocks, &mut history, "how are you?");
+
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(history.len(), 2);
+        assert!(!allocation.over_budget());
+        assert_eq!(allocation.budget, 7_900);
+        assert!(allocation.dropped_blocks.is_empty());
+        assert_eq!(allocation.history_dropped, 0);
+    }
+
+    #[test]
+    fn history_is_shortened_and_thinned_before_blocks() {
+        let mut blocks = blocks();
+        let mut history = vec![
+            turn("user", &"long story ".repeat(80)),
+            turn("assistant", "ok"),
+            turn("user", "and then?"),
+            turn("assistant", "then it ended"),
+        ];
+        let before: usize = blocks
+            .iter()
+            .map(|b| budget(0).estimator.estimate(&b.text))
+            .sum();
+        let allocation = budget(100 + before as u32 + 120).fit(&mut blocks, &mut history, "hm");
+
+        assert!(!allocation.over_budget(), "{allocation}");
+        assert_eq!(allocation.history_truncated, 1);
+        assert!(allocation.dropped_blocks.is_empty());
+        assert_eq!(history.len() + allocation.history_dropped, 4);
+        assert!(history.len() >= 2);
+    }
+
+    #[test]
+    fn optional_blocks_go_lowest_value_first() {
+        let mut blocks = blocks();
+        let mut history = vec![turn("user", "hi"), turn("assistant", "hello")];
+        let allocation = budget(300).fit(&mut blocks, &mut history, "what do you remember?");
+
+        assert!(!allocation.over_budget(), "{allocation}");
+        assert_eq!(allocation.truncated_blocks, vec!["state_legend"]);
+        assert!(allocation.dropped_blocks.is_empty());
+        assert_eq!(history.len(), 2);
+
+        let mut blocks = self::blocks();
+        blocks.insert(
+            2,
+            PromptBlock::new(BlockKind::SessionSummary, "earlier they said ".repeat(20)),
+        );
+        let mut history = vec![turn("user", "hi"), turn("assistant", "hello")];
+        let allocation = budget(110).fit(&mut blocks, &mut history, "what do you remember?");
+        assert!(allocation.over_budget());
+        assert_eq!(
+            allocation.dropped_blocks,
+            vec!["state_legend", "session_summary", "memory"]
+        );
+        assert_eq!(allocation.history_dropped, 2);
+        assert!(history.is_empty());
+        assert_eq!(blocks.last().unwrap().kind, BlockKind::HistoryNote);
+        assert!(blocks.iter().any(|b| b.kind == BlockKind::Persona));
+    }
+
+    #[test]
+    fn a_fitted_prompt_fits_as_sent() {
+        for model in ["openai/gpt-4o", "anthropic/claude-3.5-sonnet"] {
+            let estimator = TokenEstimator::for_model(model);
+            let estimate = |text: &str| estimator.estimate(text) + MESSAGE_OVERHEAD_TOKENS;
+            for window in (250..700).step_by(3) {
+                let config = ContextBudgetConfig {
+                    context_window: window,
+                    max_message_tokens: 50,
+                    min_history_messages: 2,
+                };
+                let mut blocks = blocks();
+                let mut history: Vec<_> = (0..8)
+                    .map(|i| turn("user", &format!("message number {i} ").repeat(6)))
+                    .collect();
+                let allocation = ContextBudget::new(model, 100, &config).fit(
+                    &mut blocks,
+                    &mut history,
+                    "and now?",
+                );
+                // The system prompt goes out as one message, separators and
+                // the history note included.
+                let sent = estimate(&merge_blocks(&blocks))
+                    + history.iter().map(|(_, _, c)| estimate(c)).sum::<usize>()
+                    + estimate("and now?");
+                assert!(!allocation.over_budget(), "{model} {window}: {allocation}");
+                assert!(
+                    sent <= allocation.budget,
+                    "{model} {window}: {sent} sent, {allocation}"
+                );
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
@@ -7,7 +7,7 @@ use async_trait::async_trait;
 use futures::StreamExt;
 use kernel::get_agent_profile;
 use kernel::event::{
-    Event, ResponseEvent, ResponseSource,
+    Command, Event, RawEvent, ResponseEvent, ResponseKind, ResponseSource, TypingEvent,
 };
 use kernel::worker::{Worker, WorkerContext, WorkerStatus};
 use memory::short_term::ShortTermMemory;
@@ -19,12 +19,16 @@ use serde_json::{json, Value};
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
+use crate::context_budget::{
+    merge_blocks, BlockKind, ContextAllocation, ContextBudget, ContextBudgetConfig, PromptBlock,
+};
 use crate::dialogue_tools::{DialogueToolRegistry, SOCIAL_GET_DIALOGUE_SUMMARY_TOOL};
 
 use memory::{
     episodic::EpisodicStore, 
     embedder::MemoryEmbedder,
     graph::CognitiveGraph,
+    identity::IdentityStore,
 };
 
 #[derive(Debug, Clone)]
@@ -36,6 +40,8 @@ pub struct DialogueEngineConfig {
     pub reasoning: Option<String>,
     pub tool_calling: DialogueToolCallingConfig,
     pub api_timeout_secs: Option<u64>,
+    pub streaming: DialogueStreamingConfig,
+    pub context_budget: ContextBudgetConfig,
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +69,40 @@ impl DialogueToolCallingConfig {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogueStreamMode {
+    /// One message per completed line of the reply.
+    Lines,
+    /// One message, edited in place as the reply grows.
+    Edit,
+}
+
+impl DialogueStreamMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "lines" | "line" => Some(Self::Lines),
+            "edit" | "edits" => Some(Self::Edit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DialogueStreamingConfig {
+    pub mode: DialogueStreamMode,
+    /// Minimum gap between in-place edits of a streaming reply.
+    pub edit_interval_ms: u64,
+}
+
+impl Default for DialogueStreamingConfig {
+    fn default() -> Self {
+        Self {
+            mode: DialogueStreamMode::Edit,
+            edit_interval_ms: 1_000,
+        }
+    }
+}
+
 impl DialogueEngineConfig {
     pub fn is_valid(&self) -> bool {
         !self.api_base.is_empty()
@@ -223,6 +263,10 @@ const DIALOGUE_TOOL_POLICY_FALLBACK: &str = "### INTERNAL TOOL POLICY
 Use internal read-only social tools only when they are available in this turn. Never mention tools to the user. Query only the provided candidate users and use the smallest number of tool calls needed. If tool use is unnecessary, answer normally.
 ";
 
+const DIALOGUE_REACTION_POLICY_FALLBACK: &str = "### REACTIONS
+If a message only deserves an acknowledgement, react instead of replying: start your answer with [react:EMOJI] using one standard emoji, e.g. [react:👍]. Text after the tag is sent as a normal reply; leave it out to only react.
+";
+
 const DIALOGUE_TOOL_DESCRIPTION: &str = "Read a compact social summary for one allowed conversation participant so the assistant can ground tone and continuity.";
 
 fn default_tool_definitions() -> Vec<ToolDefinition> {
@@ -437,6 +481,38 @@ fn tool_policy_block(candidate_users: &[String]) -> String {
     }
 }
 
+fn history_chat_messages(history: &[(String, String, String)]) -> Vec<ChatMessage> {
+    history
+        .iter()
+        .cloned()
+        .map(|(role, username, content)| ChatMessage {
+            role,
+            name: if username.is_empty() { None } else { Some(username) },
+            content,
+        })
+        .collect()
+}
+
+fn log_context_allocation(user: &str, allocation: &ContextAllocation) {
+    if allocation.over_budget() {
+        warn!(
+            kind = "prompt.budget",
+            user = %user,
+            allocation = %allocation,
+            "Prompt is over the context budget even after trimming"
+        );
+    } else {
+        debug!(
+            kind = "prompt.budget",
+            user = %user,
+            used = allocation.used,
+            budget = allocation.budget,
+            allocation = %allocation,
+            "Context budget allocated"
+        );
+    }
+}
+
 fn memory_hint_from_history(history_len: usize) -> f32 {
     (history_len as f32 / 12.0).min(0.15)
 }
@@ -697,6 +773,7 @@ pub struct DialogueEngineWorker {
     pub episodic: Option<Arc<EpisodicStore>>,
     pub embedder: Option<Arc<MemoryEmbedder>>,
     pub graph: Option<CognitiveGraph>,
+    pub identity: Option<IdentityStore>,
     pub state_store: Option<StateStore>,
     state_prompt: StatePromptConfig,
 }
@@ -719,6 +796,7 @@ impl DialogueEngineWorker {
             episodic: None,
             embedder: None,
             graph: None,
+            identity: None,
             state_store: None,
             state_prompt: StatePromptConfig::from_env_and_file(),
         }
@@ -749,6 +827,11 @@ impl DialogueEngineWorker {
         self
     }
 
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
     pub fn with_system_prompt(mut self, prompt: String) -> Self {
         self.system_prompt = prompt;
         self
@@ -1045,10 +1128,15 @@ impl Worker for DialogueEngineWorker {
         let episodic = self.episodic.clone();
         let embedder = self.embedder.clone();
         let graph = self.graph.clone();
+        let identity = self.identity.clone();
         let state_store = self.state_store.clone();
         let state_prompt = self.state_prompt.clone();
 
         let mut active_tasks = tokio::task::JoinSet::new();
+        // Conversations muted with `/mute`, until `/unmute` or a restart.
+        let mut muted: HashSet<ConversationKey> = HashSet::new();
+        // Set by an operator's `/pause`; silences every conversation.
+        let mut paused = false;
 
         loop {
             tokio::select! {
@@ -1057,6 +1145,14 @@ impl Worker for DialogueEngineWorker {
                 result = broadcast_rx.recv() => {
                     match result {
                         Ok(Event::Raw(raw)) if raw.is_mention => {
+                            if paused || muted.contains(&ConversationKey::from_raw(&raw)) {
+                                debug!(user = %raw.username, channel = %raw.channel_id, paused, "Conversation is muted, not answering");
+                                continue;
+                            }
+                            if too_late_to_answer(&raw, chrono::Utc::now()) {
+                                info!(user = %raw.username, channel = %raw.channel_id, sent_at = %raw.timestamp, "Caught-up mention is too old to answer");
+                                continue;
+                            }
                             info!(
                                 user = %raw.username,
                                 platform = %raw.platform,
@@ -1064,12 +1160,16 @@ impl Worker for DialogueEngineWorker {
                                 "Processing mention — sending to dialogue engine"
                             );
 
-                            let history = if let Some(ref stm) = short_term {
+                            let (history, ambient, session_summary) = if let Some(ref stm) = short_term {
                                 let key = ConversationKey::from_raw(&raw);
                                 let stm_guard = stm.lock().await;
-                                stm_guard.get_history_for_prompt(&key, &raw.message_id)
+                                (
+                                    stm_guard.get_history_for_prompt(&key, &raw.message_id),
+                                    stm_guard.get_ambient_for_prompt(&key),
+                                    stm_guard.get_summary_for_prompt(&key),
+                                )
                             } else {
-                                Vec::new()
+                                (Vec::new(), Vec::new(), None)
                             };
 
                             if !history.is_empty() {
@@ -1087,22 +1187,45 @@ impl Worker for DialogueEngineWorker {
                             let g = graph.clone();
                             let st = state_store.clone();
                             let sp = state_prompt.clone();
-                            let tx = event_tx.clone();
                             let raw_clone = raw.clone();
                             let username = raw_clone.username.clone();
+                            let identity = identity.clone();
+
+                            // The turn's events pass through a forwarder that keeps
+                            // "typing…" up until the first response goes out.
+                            let (tx, turn_rx) = tokio::sync::mpsc::channel(16);
+                            active_tasks.spawn(forward_turn_with_typing(
+                                TypingEvent {
+                                    platform: raw.platform,
+                                    channel_id: raw.channel_id.clone(),
+                                    thread_id: raw.thread_id.clone(),
+                                },
+                                turn_rx,
+                                event_tx.clone(),
+                            ));
 
                             active_tasks.spawn(async move {
+                                let person_id = crate::context::resolve_person(
+                                    identity,
+                                    raw_clone.platform,
+                                    &raw_clone.user_id,
+                                    &raw_clone.username,
+                                )
+                                .await;
                                 let result = Self::call_dialogue_engine(
                                     &http_client,
                                     &cfg,
                                     &sys,
                                     history,
+                                    ambient,
+                                    session_summary,
                                     ep,
                                     emb,
                                     g,
                                     st,
                                     sp,
                                     &username,
+                                    &person_id,
                                     &raw_clone,
                                     &tx,
                                 )
@@ -1117,6 +1240,108 @@ impl Worker for DialogueEngineWorker {
                                 }
                             });
                         }
+                        Ok(Event::Command(command)) => {
+                            let key = ConversationKey::from_command(&command);
+                            let handled = matches!(
+                                command.command,
+                                Command::Mute
+                                    | Command::Unmute
+                                    | Command::Status
+                                    | Command::Memory
+                                    | Command::Pause
+                                    | Command::Resume
+                                    | Command::Say { .. }
+                            );
+                            if !handled {
+                                continue;
+                            }
+                            if command.command.requires_operator() && !command.is_operator {
+                                let reply = command.reply(DIALOGUE_OPERATOR_ONLY_REPLY);
+                                if let Err(e) = event_tx.send(Event::Response(reply)).await {
+                                    warn!(error = %e, "Failed to answer command");
+                                }
+                                continue;
+                            }
+                            let reply = match &command.command {
+                                Command::Mute => {
+                                    muted.insert(key.clone());
+                                    DIALOGUE_MUTE_REPLY.to_string()
+                                }
+                                Command::Unmute => {
+                                    muted.remove(&key);
+                                    DIALOGUE_UNMUTE_REPLY.to_string()
+                                }
+                                Command::Status => {
+                                    let session_messages = match short_term {
+                                        Some(ref stm) => stm.lock().await.session_message_count(&key),
+                                        None => 0,
+                                    };
+                                    status_reply(&config.model, paused, muted.contains(&key), session_messages)
+                                }
+                                Command::Pause => {
+                                    paused = true;
+                                    DIALOGUE_PAUSE_REPLY.to_string()
+                                }
+                                Command::Resume => {
+                                    paused = false;
+                                    DIALOGUE_RESUME_REPLY.to_string()
+                                }
+                                Command::Say { text } => {
+                                    let said = ResponseEvent {
+                                        platform: command.platform,
+                                        channel_id: command.channel_id.clone(),
+                                        thread_id: command.thread_id.clone(),
+                                        reply_to_message_id: None,
+                                        reply_to_user: None,
+                                        is_dm: command.is_dm,
+                                        content: text.clone(),
+                                        source: ResponseSource::Template,
+                                        kind: ResponseKind::Message,
+                                        private_reply: false,
+                                    };
+                                    if let Err(e) = event_tx.send(Event::Response(said)).await {
+                                        warn!(error = %e, "Failed to send operator message");
+                                    }
+                                    DIALOGUE_SAY_REPLY.to_string()
+                                }
+                                // Looking up the graph and diary can be slow; answer off the loop.
+                                Command::Memory => {
+                                    let session_messages = match short_term {
+                                        Some(ref stm) => stm.lock().await.session_message_count(&key),
+                                        None => 0,
+                                    };
+                                    let episodic = episodic.clone();
+                                    let graph = graph.clone();
+                                    let event_tx = event_tx.clone();
+                                    let identity = identity.clone();
+                                    active_tasks.spawn(async move {
+                                        let person_id = crate::context::resolve_person(
+                                            identity,
+                                            command.platform,
+                                            &command.user_id,
+                                            &command.username,
+                                        )
+                                        .await;
+                                        let reply = memory_reply(
+                                            &person_id,
+                                            session_messages,
+                                            episodic.as_deref(),
+                                            graph.as_ref(),
+                                        )
+                                        .await;
+                                        if let Err(e) = event_tx.send(Event::Response(command.reply(reply))).await {
+                                            warn!(error = %e, "Failed to answer memory command");
+                                        }
+                                    });
+                                    continue;
+                                }
+                                _ => continue,
+                            };
+                            info!(conversation = %key, command = ?command.command, "Dialogue engine answered command");
+                            if let Err(e) = event_tx.send(Event::Response(command.reply(reply))).await {
+                                warn!(error = %e, "Failed to answer command");
+                            }
+                        }
                         Ok(_) => {
                         }
                         Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
@@ -1278,12 +1503,15 @@ impl DialogueEngineWorker {
         config: &DialogueEngineConfig,
         system_prompt: &str,
         history: Vec<(String, String, String)>,
+        ambient: Vec<(String, String)>,
+        session_summary: Option<String>,
         episodic: Option<Arc<EpisodicStore>>,
         embedder: Option<Arc<MemoryEmbedder>>,
         graph: Option<CognitiveGraph>,
         state_store: Option<StateStore>,
         state_prompt: StatePromptConfig,
         current_username: &str,
+        person_id: &str,
         raw_event: &kernel::event::RawEvent,
         event_tx: &tokio::sync::mpsc::Sender<Event>,
     ) -> Result<()> {
@@ -1292,19 +1520,42 @@ impl DialogueEngineWorker {
             config.api_base.trim_end_matches('/')
         );
 
-        let mut system_blocks: Vec<String> = vec![system_prompt.to_string()];
+        let mut system_blocks: Vec<PromptBlock> = vec![
+            PromptBlock::new(BlockKind::Persona, system_prompt.to_string()),
+            PromptBlock::new(
+                BlockKind::ReactionPolicy,
+                kernel::prompt_registry::get_prompt_or(
+                    "dialogue_engine.reaction_policy",
+                    DIALOGUE_REACTION_POLICY_FALLBACK,
+                ),
+            ),
+        ];
         let cognitive_context = crate::context::build_shared_cognitive_context(
             &raw_event.message_id,
             &history,
             episodic.as_ref(),
             embedder.as_ref(),
             current_username,
+            person_id,
             &raw_event.content,
+            raw_event.reply_to.as_ref(),
         )
         .await;
 
         if let Some(memory_text) = cognitive_context.memory_text {
-            system_blocks.push(memory_text);
+            system_blocks.push(PromptBlock::new(BlockKind::Memory, memory_text));
+        }
+        if let Some(ambient_text) = crate::context::ambient_context_text(&ambient) {
+            debug!(
+                messages = ambient.len(),
+                "Injecting ambient channel messages into prompt"
+            );
+            system_blocks.push(PromptBlock::new(BlockKind::Ambient, ambient_text));
+        }
+        if let Some(summary_text) = crate::context::session_summary_text(session_summary.as_deref())
+        {
+            debug!("Injecting session summary into prompt");
+            system_blocks.push(PromptBlock::new(BlockKind::SessionSummary, summary_text));
         }
 
         let mut social_mode = "none";
@@ -1321,7 +1572,7 @@ impl DialogueEngineWorker {
                 reason = "explicit_dialogue_social_text",
                 "Dialogue social context injected"
             );
-            system_blocks.push(dialogue_social_text);
+            system_blocks.push(PromptBlock::new(BlockKind::Social, dialogue_social_text));
         } else {
             let decision = Self::social_fetch_decision(raw_event, &history);
             social_fetch_decision = Some(decision);
@@ -1344,7 +1595,7 @@ impl DialogueEngineWorker {
                 if let Some(graph) = graph.as_ref() {
                     if let Some(summary) = crate::social_context::load_dialogue_social_summary(
                         graph,
-                        current_username,
+                        person_id,
                         memory_hint,
                     )
                     .await
@@ -1359,7 +1610,7 @@ impl DialogueEngineWorker {
                             tension = summary.tension_state,
                             "Injected dialogue social summary context"
                         );
-                        system_blocks.push(summary.summary);
+                        system_blocks.push(PromptBlock::new(BlockKind::Social, summary.summary));
                     } else {
                         debug!(
                             kind = "prompt.social",
@@ -1372,8 +1623,6 @@ impl DialogueEngineWorker {
             }
         }
 
-        let social_context_insert_index = system_blocks.len();
-
         if let Some(store) = state_store {
             let rows = store.rows().await;
             let snapshot = format_state_snapshot(&rows, &state_prompt.snapshot);
@@ -1398,7 +1647,7 @@ goal: focus/commitment/clarity/urgency/constraint_pressure/satisfaction/progress
 environment: load/noise/time_pressure higher = more friction; channel_quality higher = better conditions.
 ",
                     );
-                    system_blocks.push(legend_text);
+                    system_blocks.push(PromptBlock::new(BlockKind::StateLegend, legend_text));
                 }
                 let state_text = kernel::prompt_registry::render_prompt_or(
                     "context.state.snapshot",
@@ -1407,21 +1656,24 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
 {{state_lines}}
 ",
                 );
-                system_blocks.push(state_text);
+                system_blocks.push(PromptBlock::new(BlockKind::StateSnapshot, state_text));
             }
         }
 
-        system_blocks.push(cognitive_context.time_and_history_text);
+        system_blocks.push(PromptBlock::new(
+            BlockKind::TimeAndHistory,
+            cognitive_context.time_and_history_text,
+        ));
 
-        let history_messages: Vec<ChatMessage> = history
-            .iter()
-            .cloned()
-            .map(|(role, username, content)| ChatMessage {
-                role,
-                name: if username.is_empty() { None } else { Some(username) },
-                content,
-            })
-            .collect();
+        let budget = ContextBudget::new(
+            &config.model,
+            config.chat_max_tokens,
+            &config.context_budget,
+        );
+        let mut prompt_blocks = system_blocks.clone();
+        let mut prompt_history = history.clone();
+        let allocation = budget.fit(&mut prompt_blocks, &mut prompt_history, &clean_content);
+        log_context_allocation(current_username, &allocation);
 
         let social_gate_open = social_fetch_decision
             .map(|decision| decision.should_fetch)
@@ -1429,18 +1681,11 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
         let mut tool_loop_degraded = false;
         let mut tool_loop_executed_calls = 0usize;
 
-        let mut merged_system_prompt = system_blocks
-            .iter()
-            .filter(|block| !block.trim().is_empty())
-            .cloned()
-            .collect::<Vec<_>>()
-            .join("
-
-");
+        let mut merged_system_prompt = merge_blocks(&prompt_blocks);
 
         let mut bundle = DialoguePromptBundle {
             merged_system_prompt: merged_system_prompt.clone(),
-            messages: history_messages.clone(),
+            messages: history_chat_messages(&prompt_history),
             social_mode,
             social_gate_open,
             memory_hint,
@@ -1516,25 +1761,36 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
                     if let Some(graph) = graph.as_ref() {
                         if let Some(summary) = crate::social_context::load_dialogue_social_summary(
                             graph,
-                            current_username,
+                            person_id,
                             memory_hint,
                         )
                         .await
                         {
                             social_mode = "summary_fallback";
+                            // Social context goes before the state and time blocks.
+                            let insert_at = system_blocks
+                                .iter()
+                                .position(|block| {
+                                    matches!(
+                                        block.kind,
+                                        BlockKind::StateLegend
+                                            | BlockKind::StateSnapshot
+                                            | BlockKind::TimeAndHistory
+                                    )
+                                })
+                                .unwrap_or(system_blocks.len());
                             system_blocks.insert(
-                                social_context_insert_index.min(system_blocks.len()),
-                                summary.summary,
+                                insert_at,
+                                PromptBlock::new(BlockKind::Social, summary.summary),
                             );
-                            merged_system_prompt = system_blocks
-                                .iter()
-                                .filter(|block| !block.trim().is_empty())
-                                .cloned()
-                                .collect::<Vec<_>>()
-                                .join("
-
-");
+                            prompt_blocks = system_blocks.clone();
+                            prompt_history = history.clone();
+                            let allocation =
+                                budget.fit(&mut prompt_blocks, &mut prompt_history, &clean_content);
+                            log_context_allocation(current_username, &allocation);
+                            merged_system_prompt = merge_blocks(&prompt_blocks);
                             bundle.merged_system_prompt = merged_system_prompt.clone();
+                            bundle.messages = history_chat_messages(&prompt_history);
                             final_messages = build_final_messages(
                                 &bundle,
                                 current_username.to_string(),
@@ -1565,7 +1821,7 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
             social_gate_open = social_gate_open,
             tool_loop_degraded = tool_loop_degraded,
             tool_loop_executed_calls = tool_loop_executed_calls,
-            system_block_count = system_blocks.len(),
+            system_block_count = prompt_blocks.len(),
             merged_system_prompt_len = merged_system_prompt.len(),
             "Dialogue social context path finalized"
         );
@@ -1603,6 +1859,25 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
         let mut full_response_buffer = String::new();
         let mut is_first_chunk = true;
 
+        // Edit mode keeps the raw text (blank lines and indentation intact)
+        // and periodically sends it as one message that is edited in place.
+        // Connections that cannot edit get only the final update from the
+        // relay, which is how the selfbot always receives its reply as one
+        // message.
+        let edit_mode = config.streaming.mode == DialogueStreamMode::Edit
+            || raw_event.platform == kernel::event::Platform::DiscordSelfbot;
+        let stream_id = format!(
+            "{}-{}",
+            raw_event.message_id,
+            chrono::Utc::now().timestamp_millis()
+        );
+        let edit_interval = std::time::Duration::from_millis(config.streaming.edit_interval_ms);
+        let mut streamed_text = String::new();
+        // The first visible text is posted at once; later edits are paced.
+        let mut last_edit_at: Option<std::time::Instant> = None;
+        let mut leading_reaction = LeadingReaction::default();
+        let mut reaction: Option<String> = None;
+
         while let Some(chunk_result) = stream.next().await {
             let chunk = chunk_result.context("Failed to read stream chunk")?;
             let text = String::from_utf8_lossy(&chunk);
@@ -1622,18 +1897,47 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
                         if let Some(choices) = json.get("choices").and_then(|c| c.as_array()) {
                             if let Some(choice) = choices.first() {
                                 if let Some(content) = choice.get("delta").and_then(|d| d.get("content")).and_then(|c| c.as_str()) {
-                                    if is_thinking {
-                                        if let Some(end) = content.find("</think>") {
-                                            is_thinking = false;
-                                            output_buffer.push_str(&content[end + 8..]);
+                                    let visible = if is_thinking {
+                                        match content.find("</think>") {
+                                            Some(end) => {
+                                                is_thinking = false;
+                                                &content[end + 8..]
+                                            }
+                                            None => "",
                                         }
                                     } else if let Some(start) = content.find("<think>") {
                                         is_thinking = true;
-                                        output_buffer.push_str(&content[..start]);
+                                        &content[..start]
                                     } else {
-                                        output_buffer.push_str(content);
+                                        content
+                                    };
+
+                                    let (reacted, visible) = leading_reaction.feed(visible);
+                                    if let Some(emoji) = reacted {
+                                        info!(user = %raw_event.username, emoji = %emoji, "Dialogue engine reacted");
+                                        if let Err(e) = event_tx.send(reaction_event(raw_event, &emoji)).await {
+                                            tracing::error!("Failed to send reaction event: {}", e);
+                                        }
+                                        reaction = Some(emoji);
+                                    }
+                                    let visible = visible.as_str();
+
+                                    if edit_mode {
+                                        streamed_text.push_str(visible);
+                                        let text = streamed_text.trim();
+                                        let due = last_edit_at.is_none_or(|at| at.elapsed() >= edit_interval);
+                                        if !text.is_empty() && due {
+                                            last_edit_at = Some(std::time::Instant::now());
+                                            let event = stream_update_event(raw_event, &stream_id, text.to_string(), false);
+                                            if let Err(e) = event_tx.send(event).await {
+                                                tracing::error!("Failed to send stream update event: {}", e);
+                                            }
+                                        }
+                                        continue;
                                     }
 
+                                    output_buffer.push_str(visible);
+
                                     output_buffer = output_buffer.replace("
 
 ", "
@@ -1651,20 +1955,21 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
                                             );
                                             full_response_buffer.push_str(&msg);
                                             full_response_buffer.push('\n');
-                                            if raw_event.platform != kernel::event::Platform::DiscordSelfbot {
-                                                let event = Event::Response(ResponseEvent {
-                                                    platform: raw_event.platform,
-                                                    channel_id: raw_event.channel_id.clone(),
-                                                    reply_to_message_id: if is_first_chunk { Some(raw_event.message_id.clone()) } else { None },
-                                                    reply_to_user: Some(raw_event.username.clone()),
-                                                    is_dm: raw_event.is_dm,
-                                                    content: msg,
-                                                    source: ResponseSource::CloudLLM,
-                                                });
-                                                is_first_chunk = false;
-                                                if let Err(e) = event_tx.send(event).await {
-                                                    tracing::error!("Failed to send stream line event: {}", e);
-                                                }
+                                            let event = Event::Response(ResponseEvent {
+                                                platform: raw_event.platform,
+                                                channel_id: raw_event.channel_id.clone(),
+                                                thread_id: raw_event.thread_id.clone(),
+                                                reply_to_message_id: if is_first_chunk { Some(raw_event.message_id.clone()) } else { None },
+                                                reply_to_user: Some(raw_event.username.clone()),
+                                                is_dm: raw_event.is_dm,
+                                                content: msg,
+                                                source: ResponseSource::CloudLLM,
+                                                kind: ResponseKind::Message,
+                                                private_reply: false,
+                                            });
+                                            is_first_chunk = false;
+                                            if let Err(e) = event_tx.send(event).await {
+                                                tracing::error!("Failed to send stream line event: {}", e);
                                             }
                                         }
                                     }
@@ -1676,6 +1981,14 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
             }
         }
 
+        // A reply shorter than the reaction tag is still held back.
+        let held_back = leading_reaction.finish();
+        if edit_mode {
+            streamed_text.push_str(&held_back);
+        } else {
+            output_buffer.push_str(&held_back);
+        }
+
         let final_msg = output_buffer.trim().to_string();
         if !final_msg.is_empty() {
             info!(
@@ -1684,44 +1997,52 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
                 "Dialogue engine stream emitted final line"
             );
             full_response_buffer.push_str(&final_msg);
-            if raw_event.platform != kernel::event::Platform::DiscordSelfbot {
-                let event = Event::Response(ResponseEvent {
-                    platform: raw_event.platform,
-                    channel_id: raw_event.channel_id.clone(),
-                    reply_to_message_id: if is_first_chunk { Some(raw_event.message_id.clone()) } else { None },
-                    reply_to_user: Some(raw_event.username.clone()),
-                    is_dm: raw_event.is_dm,
-                    content: final_msg,
-                    source: ResponseSource::CloudLLM,
-                });
-                if let Err(e) = event_tx.send(event).await {
-                    tracing::error!("Failed to send stream final line event: {}", e);
-                }
-            }
-        }
-
-        let full_response = full_response_buffer.trim().to_string();
-        if raw_event.platform == kernel::event::Platform::DiscordSelfbot && !full_response.is_empty() {
             let event = Event::Response(ResponseEvent {
                 platform: raw_event.platform,
                 channel_id: raw_event.channel_id.clone(),
-                reply_to_message_id: Some(raw_event.message_id.clone()),
+                thread_id: raw_event.thread_id.clone(),
+                reply_to_message_id: if is_first_chunk { Some(raw_event.message_id.clone()) } else { None },
                 reply_to_user: Some(raw_event.username.clone()),
                 is_dm: raw_event.is_dm,
-                content: full_response.clone(),
+                content: final_msg,
                 source: ResponseSource::CloudLLM,
+                kind: ResponseKind::Message,
+                private_reply: false,
             });
             if let Err(e) = event_tx.send(event).await {
-                tracing::error!("Failed to send selfbot final response event: {}", e);
+                tracing::error!("Failed to send stream final line event: {}", e);
             }
         }
-        if !full_response.is_empty() {
+
+        if edit_mode {
+            full_response_buffer = streamed_text.trim().to_string();
+            if !full_response_buffer.is_empty() {
+                info!(
+                    user = %raw_event.username,
+                    content_len = full_response_buffer.len(),
+                    "Dialogue engine stream finished"
+                );
+                let event = stream_update_event(raw_event, &stream_id, full_response_buffer.clone(), true);
+                if let Err(e) = event_tx.send(event).await {
+                    tracing::error!("Failed to send final stream update event: {}", e);
+                }
+            }
+        }
+
+        let full_response = full_response_buffer.trim().to_string();
+        // Memory keeps the tag so the reaction shows up in later history.
+        let turn_content = match reaction {
+            Some(emoji) => format!("{REACTION_TAG}{emoji}] {full_response}").trim().to_string(),
+            None => full_response,
+        };
+        if !turn_content.is_empty() {
             let event = Event::BotTurnCompletion(kernel::event::BotTurnCompletion {
                 platform: raw_event.platform,
                 channel_id: raw_event.channel_id.clone(),
+                thread_id: raw_event.thread_id.clone(),
                 reply_to_message_id: Some(raw_event.message_id.clone()),
                 reply_to_user: Some(raw_event.username.clone()),
-                content: full_response,
+                content: turn_content,
             });
             let _ = event_tx.send(event).await;
         }
@@ -1731,6 +2052,193 @@ environment: load/noise/time_pressure higher = more friction; channel_quality hi
 
 }
 
+/// Mentions caught up on after platform downtime are only answered while
+/// a reply still reads as part of the conversation. Older ones are kept in
+/// memory but left unanswered.
+const BACKFILL_REPLY_WINDOW_MINUTES: i64 = 30;
+
+fn too_late_to_answer(raw: &RawEvent, now: chrono::DateTime<chrono::Utc>) -> bool {
+    raw.is_backfill
+        && now.signed_duration_since(raw.timestamp)
+            > chrono::Duration::minutes(BACKFILL_REPLY_WINDOW_MINUTES)
+}
+
+/// Telegram drops the indicator after ~5s and Discord after ~10s.
+const TYPING_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(4);
+
+async fn forward_turn_with_typing(
+    typing: TypingEvent,
+    mut turn_rx: tokio::sync::mpsc::Receiver<Event>,
+    event_tx: tokio::sync::mpsc::Sender<Event>,
+) {
+    let mut refresh = tokio::time::interval(TYPING_REFRESH_INTERVAL);
+    let mut typing_active = true;
+
+    loop {
+        tokio::select! {
+            _ = refresh.tick(), if typing_active => {
+                let _ = event_tx.send(Event::Typing(typing.clone())).await;
+            }
+            event = turn_rx.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                if matches!(&event, Event::Response(response) if response.kind.has_text()) {
+                    typing_active = false;
+                }
+                if event_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+const REACTION_TAG: &str = "[react:";
+/// Past this many characters without a closing `]` the text is a normal reply.
+const MAX_REACTION_TAG_CHARS: usize = 48;
+
+/// Splits a leading `[react:EMOJI]` tag off a streamed reply, holding back
+/// text until it is clear whether the reply starts with one.
+#[derive(Default)]
+struct LeadingReaction {
+    pending: String,
+    decided: bool,
+}
+
+impl LeadingReaction {
+    /// Returns the emoji once its tag is complete, and the text that can be shown.
+    fn feed(&mut self, text: &str) -> (Option<String>, String) {
+        if self.decided {
+            return (None, text.to_string());
+        }
+        self.pending.push_str(text);
+
+        let head = self.pending.trim_start();
+        if head.is_empty() || (head.len() < REACTION_TAG.len() && REACTION_TAG.starts_with(head)) {
+            return (None, String::new());
+        }
+        if let Some(tag) = head.strip_prefix(REACTION_TAG) {
+            if let Some(end) = tag.find(']') {
+                let emoji = tag[..end].trim().to_string();
+                let rest = tag[end + 1..].trim_start().to_string();
+                self.decided = true;
+                self.pending.clear();
+                return ((!emoji.is_empty()).then_some(emoji), rest);
+            }
+            if tag.chars().count() <= MAX_REACTION_TAG_CHARS {
+                return (None, String::new());
+            }
+        }
+
+        self.decided = true;
+        (None, std::mem::take(&mut self.pending))
+    }
+
+    /// Whatever is still held back when the stream ends.
+    fn finish(&mut self) -> String {
+        self.decided = true;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+const DIALOGUE_MUTE_REPLY: &str = "Muted. I'll stay quiet here until someone sends /unmute.";
+const DIALOGUE_UNMUTE_REPLY: &str = "Unmuted. Mention me any time.";
+
+const DIALOGUE_OPERATOR_ONLY_REPLY: &str = "Only operators can do that.";
+const DIALOGUE_PAUSE_REPLY: &str = "Paused. I won't answer anyone until /resume.";
+const DIALOGUE_RESUME_REPLY: &str = "Resumed.";
+const DIALOGUE_SAY_REPLY: &str = "Sent.";
+
+fn status_reply(model: &str, paused: bool, muted: bool, session_messages: usize) -> String {
+    let here = if paused {
+        "Paused everywhere."
+    } else if muted {
+        "Muted in this conversation."
+    } else {
+        "Answering mentions in this conversation."
+    };
+    format!("Online, using {model}. {here} {session_messages} message(s) in the current session.")
+}
+
+/// What the agent holds about `person_id`: the current session, diary
+/// entries that mention them and its view of the relationship.
+async fn memory_reply(
+    person_id: &str,
+    session_messages: usize,
+    episodic: Option<&EpisodicStore>,
+    graph: Option<&CognitiveGraph>,
+) -> String {
+    let mut text = format!(
+        "What I remember about you:\n- {session_messages} message(s) in our current conversation"
+    );
+    if let Some(episodic) = episodic {
+        match episodic.count_user_chunks(person_id).await {
+            Ok(entries) => text.push_str(&format!("\n- {entries} diary entries that mention you")),
+            Err(e) => warn!(error = %e, user = %person_id, "Failed to count diary entries"),
+        }
+    }
+    if let Some(graph) = graph {
+        let result = crate::social_context::query_social_context(
+            graph,
+            crate::social_context::SocialQueryIntent::DialogueSummary,
+            person_id,
+            crate::social_context::SocialQueryOptions::for_dialogue(0.0),
+        )
+        .await;
+        match result {
+            crate::social_context::SocialQueryResult::Dialogue {
+                summary: Some(summary),
+                ..
+            } => text.push_str(&format!("\n- How I see us: {}", summary.summary)),
+            _ => text.push_str("\n- We haven't built up a relationship yet"),
+        }
+    }
+    text
+}
+
+fn reaction_event(raw_event: &kernel::event::RawEvent, emoji: &str) -> Event {
+    Event::Response(ResponseEvent {
+        platform: raw_event.platform,
+        channel_id: raw_event.channel_id.clone(),
+        thread_id: raw_event.thread_id.clone(),
+        reply_to_message_id: Some(raw_event.message_id.clone()),
+        reply_to_user: Some(raw_event.username.clone()),
+        is_dm: raw_event.is_dm,
+        content: String::new(),
+        source: ResponseSource::CloudLLM,
+        kind: ResponseKind::React {
+            message_id: raw_event.message_id.clone(),
+            emoji: emoji.to_string(),
+        },
+        private_reply: false,
+    })
+}
+
+fn stream_update_event(
+    raw_event: &kernel::event::RawEvent,
+    stream_id: &str,
+    content: String,
+    is_final: bool,
+) -> Event {
+    Event::Response(ResponseEvent {
+        platform: raw_event.platform,
+        channel_id: raw_event.channel_id.clone(),
+        thread_id: raw_event.thread_id.clone(),
+        reply_to_message_id: Some(raw_event.message_id.clone()),
+        reply_to_user: Some(raw_event.username.clone()),
+        is_dm: raw_event.is_dm,
+        content,
+        source: ResponseSource::CloudLLM,
+        kind: ResponseKind::StreamUpdate {
+            stream_id: stream_id.to_string(),
+            part: 0,
+            is_final,
+        },
+        private_reply: false,
+    })
+}
+
 fn strip_mention_tags(content: &str, _username: &str) -> String {
     let mut result = String::with_capacity(content.len());
     let chars: Vec<char> = content.chars().collect();
@@ -2172,6 +2680,7 @@ mod tests {
         kernel::event::RawEvent {
             platform,
             channel_id: "test-channel".to_string(),
+            thread_id: None,
             message_id: format!("msg-{username}"),
             user_id: username.to_string(),
             username: username.to_string(),
@@ -2180,6 +2689,8 @@ mod tests {
             is_mention: true,
             is_dm: true,
             timestamp: Utc::now(),
+            reply_to: None,
+            is_backfill: false,
         }
     }
 
@@ -2203,12 +2714,15 @@ mod tests {
             &config,
             "system",
             history,
+            Vec::new(),
+            None,
             None,
             None,
             graph,
             None,
             StatePromptConfig::default(),
             &raw_event.username,
+            &raw_event.username,
             &raw_event,
             &event_tx,
         )
@@ -2248,6 +2762,8 @@ mod tests {
                 max_candidate_users: 3,
             },
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
     }
 
@@ -2265,6 +2781,8 @@ mod tests {
                 max_candidate_users: 3,
             },
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
     }
 
@@ -2437,6 +2955,8 @@ mod tests {
                 max_candidate_users: 3,
             },
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
     }
 
@@ -2511,6 +3031,8 @@ mod tests {
             reasoning: None,
             tool_calling: DialogueToolCallingConfig::default(),
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
         .is_valid());
         assert!(!DialogueEngineConfig {
@@ -2521,6 +3043,8 @@ mod tests {
             reasoning: None,
             tool_calling: DialogueToolCallingConfig::default(),
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
         .is_valid());
         assert!(DialogueEngineConfig {
@@ -2531,6 +3055,8 @@ mod tests {
             reasoning: None,
             tool_calling: DialogueToolCallingConfig::default(),
             api_timeout_secs: None,
+            streaming: DialogueStreamingConfig::default(),
+            context_budget: ContextBudgetConfig::default(),
         }
         .is_valid());
     }
@@ -2714,8 +3240,9 @@ mod tests {
         assert_eq!(user.get("role").and_then(|v| v.as_str()), Some("user"));
         assert_eq!(user.get("content").and_then(|v| v.as_str()), Some("xin lỗi vì lúc nãy nhé"));
 
-        assert_eq!(events.len(), 2);
-        match &events[0] {
+        // The streamed reply is posted once right away, then finalised.
+        assert_eq!(events.len(), 3);
+        match &events[1] {
             Event::Response(response) => {
                 assert_eq!(response.content, "tool loop degraded reply");
                 assert_eq!(response.source, ResponseSource::CloudLLM);
@@ -2723,7 +3250,7 @@ mod tests {
             }
             other => panic!("expected response event, got {other:?}"),
         }
-        match &events[1] {
+        match &events[2] {
             Event::BotTurnCompletion(done) => {
                 assert_eq!(done.content, "tool loop degraded reply");
                 assert_eq!(done.reply_to_user.as_deref(), Some("alice"));
@@ -2778,15 +3305,16 @@ mod tests {
         assert_eq!(user.get("role").and_then(|v| v.as_str()), Some("user"));
         assert_eq!(user.get("content").and_then(|v| v.as_str()), Some("xin lỗi vì lúc nãy nhé"));
 
-        assert_eq!(events.len(), 2);
-        match &events[0] {
+        // The streamed reply is posted once right away, then finalised.
+        assert_eq!(events.len(), 3);
+        match &events[1] {
             Event::Response(response) => {
                 assert_eq!(response.content, "reply without graph");
                 assert_eq!(response.source, ResponseSource::CloudLLM);
             }
             other => panic!("expected response event, got {other:?}"),
         }
-        match &events[1] {
+        match &events[2] {
             Event::BotTurnCompletion(done) => {
                 assert_eq!(done.content, "reply without graph");
             }
@@ -2794,6 +3322,118 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn call_dialogue_engine_streams_edits_or_lines_by_mode() {
+        let sse = streaming_sse(&["first line\\n\\nsecond", " line"]);
+
+        let raw = raw_event(kernel::event::Platform::Telegram, "alice", "hi");
+        let (events, _) =
+            call_dialogue_engine_with_server(vec![sse.clone()], disabled_tool_config(), vec![], None, raw)
+                .await
+                .expect("edit mode should stream");
+        assert_eq!(events.len(), 3);
+        // The first text goes out at once instead of after a full interval.
+        match &events[0] {
+            Event::Response(response) => {
+                assert_eq!(response.content, "first line\n\nsecond");
+                assert!(matches!(
+                    response.kind,
+                    ResponseKind::StreamUpdate { part: 0, is_final: false, .. }
+                ));
+            }
+            other => panic!("expected stream update, got {other:?}"),
+        }
+        match &events[1] {
+            Event::Response(response) => {
+                assert_eq!(response.content, "first line\n\nsecond line");
+                assert!(matches!(
+                    response.kind,
+                    ResponseKind::StreamUpdate { part: 0, is_final: true, .. }
+                ));
+            }
+            other => panic!("expected stream update, got {other:?}"),
+        }
+
+        let mut config = disabled_tool_config();
+        config.streaming.mode = DialogueStreamMode::Lines;
+        let raw = raw_event(kernel::event::Platform::Telegram, "alice", "hi");
+        let (events, _) = call_dialogue_engine_with_server(vec![sse], config, vec![], None, raw)
+            .await
+            .expect("lines mode should stream");
+        let lines: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Response(response) => Some(response),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].content, "first line");
+        assert_eq!(lines[0].kind, ResponseKind::Message);
+        assert!(lines[0].reply_to_message_id.is_some());
+        assert_eq!(lines[1].content, "second line");
+        assert!(lines[1].reply_to_message_id.is_none());
+    }
+
+    #[test]
+    fn leading_reaction_tag_is_split_off_streamed_text() {
+        let mut tag = LeadingReaction::default();
+        assert_eq!(tag.feed(" [rea"), (None, String::new()));
+        assert_eq!(tag.feed("ct: 👍 ] sure"), (Some("👍".to_string()), "sure".to_string()));
+        assert_eq!(tag.feed(" thing"), (None, " thing".to_string()));
+
+        let mut plain = LeadingReaction::default();
+        assert_eq!(plain.feed("[re"), (None, String::new()));
+        assert_eq!(plain.feed("d] alert"), (None, "[red] alert".to_string()));
+
+        let mut short = LeadingReaction::default();
+        assert_eq!(short.feed("[r"), (None, String::new()));
+        assert_eq!(short.finish(), "[r");
+    }
+
+    #[tokio::test]
+    async fn call_dialogue_engine_can_answer_with_only_a_reaction() {
+        let raw = raw_event(kernel::event::Platform::Discord, "alice", "thanks!");
+        let sse = streaming_sse(&["[react:", "🙏]"]);
+        let (events, _) =
+            call_dialogue_engine_with_server(vec![sse], disabled_tool_config(), vec![], None, raw)
+                .await
+                .expect("reaction turn should succeed");
+
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            Event::Response(response) => {
+                assert!(response.content.is_empty());
+                assert_eq!(
+                    response.kind,
+                    ResponseKind::React {
+                        message_id: "msg-alice".to_string(),
+                        emoji: "🙏".to_string(),
+                    }
+                );
+            }
+            other => panic!("expected reaction, got {other:?}"),
+        }
+        match &events[1] {
+            Event::BotTurnCompletion(done) => assert_eq!(done.content, "[react:🙏]"),
+            other => panic!("expected bot turn completion, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn old_caught_up_mentions_are_not_answered() {
+        let now = Utc::now();
+        let mut raw = raw_event(kernel::event::Platform::Discord, "alice", "you there?");
+        raw.timestamp = now - chrono::Duration::hours(2);
+        assert!(!too_late_to_answer(&raw, now));
+
+        raw.is_backfill = true;
+        assert!(too_late_to_answer(&raw, now));
+
+        raw.timestamp = now - chrono::Duration::minutes(5);
+        assert!(!too_late_to_answer(&raw, now));
+    }
+
     #[test]
     fn candidate_users_trim_blanks_and_deduplicate() {
         let history = vec![
@@ -1,10 +1,14 @@
 pub mod context;
+pub mod context_budget;
 pub mod social_context;
 pub mod dialogue_engine;
 pub mod affect_evaluator;
 pub mod dialogue_tools;
 
-pub use dialogue_engine::{DialogueEngineConfig, DialogueEngineWorker};
+pub use context_budget::ContextBudgetConfig;
+pub use dialogue_engine::{
+    DialogueEngineConfig, DialogueEngineWorker, DialogueStreamMode, DialogueStreamingConfig,
+};
 pub use affect_evaluator::{AffectEvaluatorConfig, AffectEvaluatorWorker};
 pub use dialogue_tools::{
     DialogueToolRegistry, ToolDescriptor, ToolNamespace, SOCIAL_GET_AFFECT_CONTEXT_TOOL,
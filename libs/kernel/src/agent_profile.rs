@@ -23,6 +23,10 @@ pub struct AgentProfile {
     pub graph_db_path: String,
     #[serde(default)]
     pub episodic_db_path: String,
+    #[serde(default)]
+    pub outbound_db_path: String,
+    #[serde(default)]
+    pub backfill_db_path: String,
     #[serde(default = "default_agent_timezone_label")]
     pub agent_timezone_label: String,
     #[serde(default = "default_agent_timezone_offset_hours")]
@@ -42,6 +46,8 @@ impl Default for AgentProfile {
             memory_db_path: String::new(),
             graph_db_path: String::new(),
             episodic_db_path: String::new(),
+            outbound_db_path: String::new(),
+            backfill_db_path: String::new(),
             agent_timezone_label: default_agent_timezone_label(),
             agent_timezone_offset_hours: default_agent_timezone_offset_hours(),
             user_timezone_label: default_user_timezone_label(),
@@ -84,6 +90,16 @@ impl AgentProfile {
             self.episodic_db_path = format!("{}/lancedb", DEFAULT_DATA_DIR);
         }
 
+        self.outbound_db_path = self.outbound_db_path.trim().to_string();
+        if self.outbound_db_path.is_empty() {
+            self.outbound_db_path = format!("{}/outbound.db", DEFAULT_DATA_DIR);
+        }
+
+        self.backfill_db_path = self.backfill_db_path.trim().to_string();
+        if self.backfill_db_path.is_empty() {
+            self.backfill_db_path = format!("{}/backfill.db", DEFAULT_DATA_DIR);
+        }
+
         self.agent_timezone_label = self.agent_timezone_label.trim().to_string();
         if self.agent_timezone_label.is_empty() {
             self.agent_timezone_label = default_agent_timezone_label();
@@ -114,6 +130,12 @@ impl AgentProfile {
         if let Ok(value) = std::env::var("LANCE_DB_PATH") {
             self.episodic_db_path = value;
         }
+        if let Ok(value) = std::env::var("OUTBOUND_DB_PATH") {
+            self.outbound_db_path = value;
+        }
+        if let Ok(value) = std::env::var("BACKFILL_DB_PATH") {
+            self.backfill_db_path = value;
+        }
         if let Ok(value) = std::env::var("AGENT_TIMEZONE_LABEL") {
             self.agent_timezone_label = value;
         }
@@ -235,6 +257,8 @@ mod tests {
         assert_eq!(profile.memory_db_path, "data/polyverse-agent/memory.db");
         assert_eq!(profile.graph_db_path, "data/polyverse-agent/graph");
         assert_eq!(profile.episodic_db_path, "data/polyverse-agent/lancedb");
+        assert_eq!(profile.outbound_db_path, "data/polyverse-agent/outbound.db");
+        assert_eq!(profile.backfill_db_path, "data/polyverse-agent/backfill.db");
     }
 
     #[test]
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::worker::WorkerStatus;
+
 pub const MAX_IMAGE_ATTACHMENTS_PER_MESSAGE: usize = 4;
 pub const MAX_IMAGE_ATTACHMENT_BYTES: usize = 5 * 1024 * 1024;
 
@@ -31,6 +33,8 @@ pub enum Platform {
     DiscordSelfbot,
     Telegram,
     Cli,
+    Http,
+    Irc,
 }
 
 impl std::fmt::Display for Platform {
@@ -40,6 +44,53 @@ impl std::fmt::Display for Platform {
             Platform::DiscordSelfbot => write!(f, "DiscordSelfbot"),
             Platform::Telegram => write!(f, "Telegram"),
             Platform::Cli => write!(f, "CLI"),
+            Platform::Http => write!(f, "HTTP"),
+            Platform::Irc => write!(f, "IRC"),
+        }
+    }
+}
+
+/// Parses the [`Display`](std::fmt::Display) names stored in the memory
+/// database as well as the serde names, ignoring case.
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "discord" => Ok(Platform::Discord),
+            "discordselfbot" => Ok(Platform::DiscordSelfbot),
+            "telegram" => Ok(Platform::Telegram),
+            "cli" => Ok(Platform::Cli),
+            "http" => Ok(Platform::Http),
+            "irc" => Ok(Platform::Irc),
+            _ => Err(format!("unknown platform: {}", s)),
+        }
+    }
+}
+
+/// Longest excerpt of a replied-to message carried on a [`RawEvent`].
+pub const MAX_REPLY_SNIPPET_CHARS: usize = 300;
+
+/// The earlier message a user replied to or quoted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReplyReference {
+    pub message_id: String,
+    pub author_id: String,
+    pub author_name: String,
+    /// Start of the referenced text, or the quoted part when the platform
+    /// says which part was quoted.
+    pub snippet: String,
+    /// The referenced message was sent by the agent.
+    pub is_agent: bool,
+}
+
+impl ReplyReference {
+    /// Cut `text` down to [`MAX_REPLY_SNIPPET_CHARS`].
+    pub fn snippet_of(text: &str) -> String {
+        let text = text.trim();
+        match text.char_indices().nth(MAX_REPLY_SNIPPET_CHARS) {
+            Some((end, _)) => format!("{}…", &text[..end]),
+            None => text.to_string(),
         }
     }
 }
@@ -48,15 +99,26 @@ impl std::fmt::Display for Platform {
 pub struct RawEvent {
     pub platform: Platform,
     pub channel_id: String,
+    /// Forum topic or thread inside `channel_id`. Discord threads are
+    /// channels of their own and leave this empty.
+    #[serde(default)]
+    pub thread_id: Option<String>,
     pub message_id: String,
     pub user_id: String,
     pub username: String,
     pub content: String,
     #[serde(default)]
     pub attachments: Vec<ImageAttachment>,
+    /// Replies to the agent's messages are also mentions.
     pub is_mention: bool,
     pub is_dm: bool,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub reply_to: Option<ReplyReference>,
+    /// Sent while the platform process was down and caught up on after it
+    /// reconnected. `timestamp` is when it was sent, so an answer may be late.
+    #[serde(default)]
+    pub is_backfill: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -92,21 +154,230 @@ pub enum ResponseSource {
     Template,
 }
 
+/// How a response is delivered on the platform.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseKind {
+    /// A new message.
+    #[default]
+    Message,
+    /// The full text so far of a reply that is still being generated. The
+    /// first update for a `(stream_id, part)` creates a message; later ones
+    /// edit it in place.
+    StreamUpdate {
+        stream_id: String,
+        /// Index of the platform message this text lands in. The relay sets
+        /// it when a reply outgrows one message.
+        #[serde(default)]
+        part: u32,
+        is_final: bool,
+    },
+    /// React to a message with an emoji instead of writing. `content` is
+    /// unused. Platforms without reactions get the emoji as a reply.
+    React { message_id: String, emoji: String },
+    /// Replace the text of a message the agent sent with `content`.
+    Edit { message_id: String },
+    /// Delete a message the agent sent.
+    Delete { message_id: String },
+}
+
+impl ResponseKind {
+    pub fn is_stream_update(&self) -> bool {
+        matches!(self, ResponseKind::StreamUpdate { .. })
+    }
+
+    /// Whether `content` is text to show on the platform.
+    pub fn has_text(&self) -> bool {
+        matches!(
+            self,
+            ResponseKind::Message | ResponseKind::StreamUpdate { .. } | ResponseKind::Edit { .. }
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseEvent {
     pub platform: Platform,
     pub channel_id: String,
+    /// Topic or thread to post in, copied from the triggering `RawEvent`.
+    #[serde(default)]
+    pub thread_id: Option<String>,
     pub reply_to_message_id: Option<String>,
     pub reply_to_user: Option<String>,
     pub is_dm: bool,
     pub content: String,
     pub source: ResponseSource,
+    #[serde(default)]
+    pub kind: ResponseKind,
+    /// Every chunk of the reply stays attached to the message it answers,
+    /// because the platform may only deliver it there (a Discord
+    /// interaction answer visible to the invoking user alone).
+    #[serde(default)]
+    pub private_reply: bool,
+}
+
+/// A user changed the text of a message they sent earlier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEditEvent {
+    pub platform: Platform,
+    pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    pub message_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A message was deleted on the platform. Telegram does not report these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDeleteEvent {
+    pub platform: Platform,
+    pub channel_id: String,
+    pub message_id: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A user added or removed a reaction on a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReactionEvent {
+    pub platform: Platform,
+    pub channel_id: String,
+    pub message_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub emoji: String,
+    /// `false` when the reaction was taken back.
+    pub added: bool,
+    /// The reacted-to message was sent by the agent.
+    pub on_agent_message: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ReactionEvent {
+    /// Rough sentiment of the emoji; unknown emoji are neutral.
+    pub fn sentiment(&self) -> Sentiment {
+        // Variation selectors and skin tones don't change the meaning.
+        let base: String = self
+            .emoji
+            .chars()
+            .filter(|c| !matches!(*c, '\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}'))
+            .collect();
+        match base.as_str() {
+            "👍" | "❤" | "🥰" | "😍" | "😂" | "🤣" | "😊" | "😄" | "😁" | "🔥" | "🎉" | "👏"
+            | "🙏" | "💯" | "🤩" | "😘" | "🤗" | "👌" | "✅" | "💖" | "💕" | "⭐" | "🏆" => {
+                Sentiment::Positive
+            }
+            "👎" | "😡" | "🤬" | "😠" | "💩" | "🤮" | "🖕" | "😒" | "🙄" | "😢" | "😭" | "💔"
+            | "❌" | "🤡" => Sentiment::Negative,
+            _ => Sentiment::Neutral,
+        }
+    }
+}
+
+/// A command given through the platform's command interface, e.g. a
+/// Telegram `/command` or a Discord slash command. Commands never reach the
+/// dialogue engine as text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Command {
+    /// End the conversation's current session so the next message starts
+    /// fresh. What was said is still kept in long-term memory.
+    Forget,
+    /// Erase the user from memory: their messages, diary entries,
+    /// relationships and linked accounts.
+    ForgetMe,
+    /// Show the user what the agent remembers about them.
+    Memory,
+    /// Link the user's accounts on different platforms. Without a code,
+    /// issue one; with a code issued to another account, link to it.
+    Link {
+        #[serde(default)]
+        code: Option<String>,
+    },
+    /// Report whether the agent is running and answering here.
+    Status,
+    /// Stop answering mentions in this conversation.
+    Mute,
+    Unmute,
+    /// Adjust state dimensions, in `/state` syntax (`warmth=0.7 curiosity+=0.1`).
+    State { args: String },
+    /// List the current state dimensions.
+    StateGet,
+    /// Set one state dimension.
+    StateSet { dimension: String, value: f64 },
+    /// Stop answering everywhere, until `Resume`.
+    Pause,
+    Resume,
+    /// Post `text` as the agent in the command's conversation.
+    Say { text: String },
+}
+
+impl Command {
+    pub fn requires_operator(&self) -> bool {
+        matches!(
+            self,
+            Command::State { .. }
+                | Command::StateGet
+                | Command::StateSet { .. }
+                | Command::Pause
+                | Command::Resume
+                | Command::Say { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandEvent {
+    pub platform: Platform,
+    pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// The message that carried the command; answers reply to it.
+    pub message_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub is_dm: bool,
+    /// The platform worker found the user in its operator list.
+    pub is_operator: bool,
+    pub command: Command,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl CommandEvent {
+    /// A plain-text answer to the command.
+    pub fn reply(&self, content: impl Into<String>) -> ResponseEvent {
+        ResponseEvent {
+            platform: self.platform,
+            channel_id: self.channel_id.clone(),
+            thread_id: self.thread_id.clone(),
+            reply_to_message_id: Some(self.message_id.clone()),
+            reply_to_user: Some(self.username.clone()),
+            is_dm: self.is_dm,
+            content: content.into(),
+            source: ResponseSource::Template,
+            kind: ResponseKind::Message,
+            private_reply: true,
+        }
+    }
+}
+
+/// "Typing…" in a channel while a reply is being prepared. Platforms only
+/// show it for a few seconds, so it is re-sent until the reply starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypingEvent {
+    pub platform: Platform,
+    pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BotTurnCompletion {
     pub platform: Platform,
     pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
     pub reply_to_message_id: Option<String>,
     pub reply_to_user: Option<String>,
     pub content: String,
@@ -131,6 +402,9 @@ pub enum SystemEvent {
     WorkerStarted { name: String },
     WorkerStopped { name: String },
     WorkerError { name: String, error: String },
+    /// Health of a component outside the agent process, such as a
+    /// supervised platform runner reporting over the relay.
+    WorkerHealth { name: String, status: WorkerStatus },
     ShutdownRequested,
     HealthCheckRequest,
 }
@@ -140,6 +414,11 @@ pub enum Event {
     Raw(RawEvent),
     Intent(IntentEvent),
     Response(ResponseEvent),
+    Typing(TypingEvent),
+    MessageEdited(MessageEditEvent),
+    MessageDeleted(MessageDeleteEvent),
+    Reaction(ReactionEvent),
+    Command(CommandEvent),
     BotTurnCompletion(BotTurnCompletion),
     Biology(BiologyEvent),
     System(SystemEvent),
@@ -159,11 +438,28 @@ impl Event {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_platform_round_trips_through_display() {
+        for platform in [
+            Platform::Discord,
+            Platform::DiscordSelfbot,
+            Platform::Telegram,
+            Platform::Cli,
+            Platform::Http,
+            Platform::Irc,
+        ] {
+            assert_eq!(platform.to_string().parse::<Platform>(), Ok(platform));
+        }
+        assert_eq!("telegram".parse::<Platform>(), Ok(Platform::Telegram));
+        assert!("myspace".parse::<Platform>().is_err());
+    }
+
     #[test]
     fn test_raw_event_serialization() {
         let event = RawEvent {
             platform: Platform::Discord,
             channel_id: "123".to_string(),
+            thread_id: None,
             message_id: "456".to_string(),
             user_id: "789".to_string(),
             username: "TestUser".to_string(),
@@ -172,6 +468,8 @@ mod tests {
             is_mention: false,
             is_dm: false,
             timestamp: Utc::now(),
+            reply_to: None,
+            is_backfill: false,
         };
 
         let json = serde_json::to_string(&event).unwrap();
@@ -180,11 +478,92 @@ mod tests {
         assert_eq!(deserialized.content, "Hello agent!");
     }
 
+    #[test]
+    fn test_reply_reference_is_optional_on_the_wire() {
+        let json = r#"{"platform":"Telegram","channel_id":"1","message_id":"2","user_id":"3","username":"u","content":"hi","attachments":[],"is_mention":false,"is_dm":false,"timestamp":"2026-01-01T00:00:00Z"}"#;
+        let raw: RawEvent = serde_json::from_str(json).unwrap();
+        assert!(raw.reply_to.is_none());
+
+        let long = "é".repeat(MAX_REPLY_SNIPPET_CHARS + 5);
+        let snippet = ReplyReference::snippet_of(&long);
+        assert_eq!(snippet.chars().count(), MAX_REPLY_SNIPPET_CHARS + 1);
+        assert!(snippet.ends_with('…'));
+        assert_eq!(ReplyReference::snippet_of("  short  "), "short");
+    }
+
+    #[test]
+    fn test_response_kind_wire_format() {
+        let json = r#"{"platform":"Discord","channel_id":"c","reply_to_message_id":null,"reply_to_user":null,"is_dm":false,"content":"","source":"CloudLLM"}"#;
+        let response: ResponseEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(response.kind, ResponseKind::Message);
+
+        let kind = ResponseKind::React {
+            message_id: "m1".to_string(),
+            emoji: "👍".to_string(),
+        };
+        let encoded = serde_json::to_string(&kind).unwrap();
+        assert_eq!(encoded, r#"{"React":{"message_id":"m1","emoji":"👍"}}"#);
+        assert!(!kind.has_text());
+        assert!(ResponseKind::Edit { message_id: "m1".to_string() }.has_text());
+    }
+
+    #[test]
+    fn test_command_wire_format() {
+        let command = Command::State {
+            args: "warmth=0.7".to_string(),
+        };
+        let encoded = serde_json::to_string(&command).unwrap();
+        assert_eq!(encoded, r#"{"name":"state","args":"warmth=0.7"}"#);
+        assert!(command.requires_operator());
+        assert_eq!(
+            serde_json::from_str::<Command>(r#"{"name":"forget"}"#).unwrap(),
+            Command::Forget
+        );
+        assert!(!Command::Forget.requires_operator());
+
+        let command = Command::StateSet {
+            dimension: "warmth".to_string(),
+            value: 0.5,
+        };
+        let encoded = serde_json::to_string(&command).unwrap();
+        assert_eq!(encoded, r#"{"name":"state_set","dimension":"warmth","value":0.5}"#);
+        assert_eq!(
+            serde_json::from_str::<Command>(r#"{"name":"forget_me"}"#).unwrap(),
+            Command::ForgetMe
+        );
+        assert!(Command::Say { text: "hi".to_string() }.requires_operator());
+        assert!(!Command::Memory.requires_operator());
+        assert_eq!(
+            serde_json::from_str::<Command>(r#"{"name":"link"}"#).unwrap(),
+            Command::Link { code: None }
+        );
+    }
+
+    #[test]
+    fn test_reaction_sentiment_ignores_modifiers() {
+        let reaction = |emoji: &str| ReactionEvent {
+            platform: Platform::Discord,
+            channel_id: "c".to_string(),
+            message_id: "m".to_string(),
+            user_id: "u".to_string(),
+            username: "user".to_string(),
+            emoji: emoji.to_string(),
+            added: true,
+            on_agent_message: true,
+            timestamp: Utc::now(),
+        };
+        assert_eq!(reaction("👍🏽").sentiment(), Sentiment::Positive);
+        assert_eq!(reaction("❤️").sentiment(), Sentiment::Positive);
+        assert_eq!(reaction("👎").sentiment(), Sentiment::Negative);
+        assert_eq!(reaction("🤔").sentiment(), Sentiment::Neutral);
+    }
+
     #[test]
     fn test_event_enum_variants() {
         let raw = Event::Raw(RawEvent {
             platform: Platform::Telegram,
             channel_id: "ch1".to_string(),
+            thread_id: None,
             message_id: "m1".to_string(),
             user_id: "u1".to_string(),
             username: "user".to_string(),
@@ -193,6 +572,8 @@ mod tests {
             is_mention: false,
             is_dm: false,
             timestamp: Utc::now(),
+            reply_to: None,
+            is_backfill: false,
         });
 
         assert!(raw.is_raw());
@@ -0,0 +1,260 @@
+//! Recent untagged chatter in group channels.
+//!
+//! Messages that do not mention the agent are not part of any session. In
+//! channels that opted in, the last few are kept here at low importance, so
+//! that when someone finally tags the agent it knows what the channel was
+//! talking about. Other channels are never buffered.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+use kernel::event::Platform;
+use tracing::warn;
+
+use crate::types::{ConversationKey, MemoryMessage};
+
+/// Importance given to ambient messages, below any message addressed to
+/// the agent.
+pub const AMBIENT_IMPORTANCE: f32 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct AmbientConfig {
+    /// Channels that opted in, as `(platform, channel_id)`. Threads inside
+    /// them are included.
+    pub channels: HashSet<(Platform, String)>,
+    /// Messages kept per conversation.
+    pub max_messages: usize,
+    /// Older messages are no longer shown to the agent.
+    pub max_age_secs: i64,
+    /// Keep the buffer in `memory.db` across restarts.
+    pub persist: bool,
+}
+
+impl Default for AmbientConfig {
+    fn default() -> Self {
+        Self {
+            channels: HashSet::new(),
+            max_messages: 10,
+            max_age_secs: 30 * 60,
+            persist: false,
+        }
+    }
+}
+
+impl AmbientConfig {
+    /// `AMBIENT_CHANNELS` lists the opted-in channels as
+    /// `Platform:channel_id`, comma separated. Without it nothing is kept.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(value) = std::env::var("AMBIENT_CHANNELS") {
+            config.channels = parse_channels(&value);
+        }
+        if let Some(max) = std::env::var("AMBIENT_MAX_MESSAGES")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            config.max_messages = max.clamp(1, 100);
+        }
+        if let Some(minutes) = std::env::var("AMBIENT_MAX_AGE_MINUTES")
+            .ok()
+            .and_then(|v| v.trim().parse::<i64>().ok())
+        {
+            config.max_age_secs = minutes.max(1) * 60;
+        }
+        if let Ok(value) = std::env::var("AMBIENT_PERSIST") {
+            config.persist = matches!(
+                value.to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            );
+        }
+        config
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.channels.is_empty()
+    }
+
+    pub fn allows(&self, platform: Platform, channel_id: &str) -> bool {
+        self.channels.contains(&(platform, channel_id.to_string()))
+    }
+}
+
+fn parse_channels(value: &str) -> HashSet<(Platform, String)> {
+    let mut channels = HashSet::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = entry
+            .split_once(':')
+            .and_then(|(platform, channel)| Some((platform.parse().ok()?, channel.trim())))
+            .filter(|(_, channel)| !channel.is_empty());
+        match parsed {
+            Some((platform, channel)) => {
+                channels.insert((platform, channel.to_string()));
+            }
+            None => warn!(entry, "Ignoring malformed AMBIENT_CHANNELS entry"),
+        }
+    }
+    channels
+}
+
+/// The last untagged messages of each opted-in conversation, oldest first.
+#[derive(Default)]
+pub struct AmbientBuffer {
+    config: AmbientConfig,
+    channels: HashMap<ConversationKey, VecDeque<MemoryMessage>>,
+}
+
+impl AmbientBuffer {
+    pub fn new(config: AmbientConfig) -> Self {
+        Self {
+            config,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &AmbientConfig {
+        &self.config
+    }
+
+    /// Keep `msg` if its channel opted in. Returns whether it was kept.
+    pub fn push(&mut self, mut msg: MemoryMessage) -> bool {
+        if !self.config.allows(msg.platform, &msg.channel_id) {
+            return false;
+        }
+        msg.importance = msg.importance.min(AMBIENT_IMPORTANCE);
+        let buffer = self
+            .channels
+            .entry(ConversationKey::from_message(&msg))
+            .or_default();
+        buffer.push_back(msg);
+        while buffer.len() > self.config.max_messages {
+            buffer.pop_front();
+        }
+        true
+    }
+
+    /// Messages restored from the store, oldest first.
+    pub fn load(&mut self, messages: Vec<MemoryMessage>) {
+        for msg in messages {
+            self.push(msg);
+        }
+    }
+
+    /// Ambient messages of `key` that are recent enough to show, oldest
+    /// first.
+    pub fn recent(&self, key: &ConversationKey, now: DateTime<Utc>) -> Vec<&MemoryMessage> {
+        let cutoff = now - Duration::seconds(self.config.max_age_secs);
+        self.channels
+            .get(key)
+            .map(|buffer| buffer.iter().filter(|m| m.timestamp >= cutoff).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn edit_message(&mut self, key: &ConversationKey, message_id: &str, content: &str) -> bool {
+        let Some(msg) = self
+            .channels
+            .get_mut(key)
+            .and_then(|buffer| buffer.iter_mut().find(|m| m.id == message_id))
+        else {
+            return false;
+        };
+        msg.content = content.to_string();
+        true
+    }
+
+    pub fn remove_message(&mut self, key: &ConversationKey, message_id: &str) -> bool {
+        let Some(buffer) = self.channels.get_mut(key) else {
+            return false;
+        };
+        let before = buffer.len();
+        buffer.retain(|m| m.id != message_id);
+        buffer.len() != before
+    }
+
+    pub fn remove_user(&mut self, platform: Platform, user_id: &str) -> usize {
+        let mut removed = 0;
+        for buffer in self.channels.values_mut() {
+            let before = buffer.len();
+            buffer.retain(|m| m.platform != platform || m.user_id != user_id);
+            removed += before - buffer.len();
+        }
+        self.channels.retain(|_, buffer| !buffer.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn make_msg(channel: &str, user: &str, content: &str) -> MemoryMessage {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut msg = testing::message(&id, channel, user, user, content);
+        msg.importance = 0.4;
+        msg
+    }
+
+    fn buffer(max_messages: usize) -> AmbientBuffer {
+        AmbientBuffer::new(AmbientConfig {
+            channels: parse_channels("Discord:ch1, nonsense, Telegram:"),
+            max_messages,
+            ..AmbientConfig::default()
+        })
+    }
+
+    #[test]
+    fn only_opted_in_channels_are_kept() {
+        let mut ambient = buffer(10);
+        assert_eq!(ambient.config().channels.len(), 1);
+        assert!(ambient.push(make_msg("ch1", "Alice", "lunch?")));
+        assert!(!ambient.push(make_msg("ch2", "Bob", "private")));
+
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        let recent = ambient.recent(&key, Utc::now());
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].importance, AMBIENT_IMPORTANCE);
+        let other = ConversationKey::new(Platform::Discord, "ch2".to_string());
+        assert!(ambient.recent(&other, Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn buffer_is_bounded_and_ages_out() {
+        let mut ambient = buffer(3);
+        let mut old = make_msg("ch1", "Alice", "yesterday");
+        old.timestamp = Utc::now() - Duration::hours(2);
+        ambient.push(old);
+        for i in 0..3 {
+            ambient.push(make_msg("ch1", "Bob", &format!("msg {}", i)));
+        }
+
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        let recent: Vec<&str> = ambient
+            .recent(&key, Utc::now())
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(recent, vec!["msg 0", "msg 1", "msg 2"]);
+
+        let mut ambient = buffer(3);
+        let mut old = make_msg("ch1", "Alice", "yesterday");
+        old.timestamp = Utc::now() - Duration::hours(2);
+        ambient.push(old);
+        assert!(ambient.recent(&key, Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn edits_deletions_and_forgetting_apply() {
+        let mut ambient = buffer(10);
+        let msg = make_msg("ch1", "Alice", "helo");
+        let id = msg.id.clone();
+        ambient.push(msg);
+        ambient.push(make_msg("ch1", "Bob", "hi"));
+
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        assert!(ambient.edit_message(&key, &id, "hello"));
+        assert_eq!(ambient.recent(&key, Utc::now())[0].content, "hello");
+        assert!(ambient.remove_message(&key, &id));
+        assert_eq!(ambient.remove_user(Platform::Discord, "Bob"), 1);
+        assert!(ambient.recent(&key, Utc::now()).is_empty());
+    }
+}
@@ -0,0 +1,632 @@
+//! Portable export and import of what the agent remembers.
+//!
+//! An archive is one JSONL file. The first line is the [`ArchiveManifest`];
+//! every following line is one [`ArchiveRecord`]: a persisted message, an
+//! identity row, an episodic entry with its vector, a graph node or edge, a
+//! social tree or a state value. Archives are scoped to the whole agent or to one person,
+//! whose accounts and usernames are resolved through identity first.
+//!
+//! Both directions stream: exports page through the message store and
+//! imports write each kind of record in batches as they are read.
+//! Importing is idempotent: messages, identity rows, episodic entries and
+//! graph records already present are kept as they are. Identity rows are
+//! imported before graph records so person keys resolve on the new store.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use state::{ManualPatchRequest, StateStore};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+use tracing::info;
+
+use crate::episodic::{EpisodicStore, MemoryEvent};
+use crate::forget::{ForgetSubject, Target};
+use crate::graph::{CognitiveGraph, GraphRecord, SocialTreeSnapshot};
+use crate::identity::{IdentityRecord, IdentityStore};
+use crate::store::{MemoryStore, PersonMessages};
+use crate::types::MemoryMessage;
+
+/// Version of the archive layout. Archives written by a newer build are
+/// refused. Version 2 added identity records.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 2;
+
+/// Messages read from the store per page when exporting.
+const MESSAGE_PAGE: usize = 500;
+/// Records written to a store at a time when importing.
+const IMPORT_BATCH: usize = 256;
+
+/// What an archive covers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum ArchiveScope {
+    /// Everything, for moving the agent to another machine.
+    Agent,
+    /// One person, by person id or a username no one else went by: what
+    /// they wrote, the agent's replies to them in those conversations, and
+    /// what the agent keeps about them.
+    Person { person: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveCounts {
+    pub messages: usize,
+    #[serde(default)]
+    pub identity_records: usize,
+    pub episodic_entries: usize,
+    pub graph_records: usize,
+    pub social_trees: usize,
+    pub state_values: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub agent_id: String,
+    #[serde(flatten)]
+    pub scope: ArchiveScope,
+    /// For a person archive, the canonical person it was resolved to.
+    #[serde(default)]
+    pub person_id: Option<String>,
+    pub counts: ArchiveCounts,
+}
+
+/// A state dimension's value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateValueRecord {
+    pub dimension_id: String,
+    pub value: f64,
+}
+
+/// One line of an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ArchiveRecord {
+    Manifest(ArchiveManifest),
+    Message(MemoryMessage),
+    Identity(IdentityRecord),
+    Episodic(MemoryEvent),
+    Graph(GraphRecord),
+    SocialTree(SocialTreeSnapshot),
+    State(StateValueRecord),
+}
+
+/// Reads every store for export and writes archives back into them. Stores
+/// left unset are skipped.
+#[derive(Clone)]
+pub struct Archiver {
+    db_path: String,
+    episodic: Option<Arc<EpisodicStore>>,
+    graph: Option<CognitiveGraph>,
+    identity: Option<IdentityStore>,
+    state: Option<StateStore>,
+}
+
+impl Archiver {
+    /// `db_path` is the memory database, opened for each export or import.
+    pub fn new(db_path: &str) -> Self {
+        Self {
+            db_path: db_path.to_string(),
+            episodic: None,
+            graph: None,
+            identity: None,
+            state: None,
+        }
+    }
+
+    pub fn with_episodic(mut self, episodic: Arc<EpisodicStore>) -> Self {
+        self.episodic = Some(episodic);
+        self
+    }
+
+    pub fn with_graph(mut self, graph: CognitiveGraph) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Write an archive of `scope` to `out`. Messages are streamed from the
+    /// store a page at a time rather than loaded first.
+    pub async fn export<W>(&self, scope: &ArchiveScope, out: &mut W) -> Result<ArchiveManifest>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let target = match scope {
+            ArchiveScope::Agent => None,
+            ArchiveScope::Person { person } => {
+                let identity = self.identity.clone();
+                let subject = ForgetSubject::Person {
+                    person: person.clone(),
+                };
+                Some(
+                    tokio::task::spawn_blocking(move || {
+                        Target::resolve(identity.as_ref(), &subject)
+                    })
+                    .await??,
+                )
+            }
+        };
+        let names = target.as_ref().map(Target::names);
+
+        let identity_records = match (&self.identity, &target) {
+            (Some(identity), None) => {
+                let identity = identity.clone();
+                tokio::task::spawn_blocking(move || identity.export_records(None)).await??
+            }
+            (
+                Some(identity),
+                Some(Target {
+                    person_id: Some(person),
+                    ..
+                }),
+            ) => {
+                let identity = identity.clone();
+                let person = person.clone();
+                tokio::task::spawn_blocking(move || identity.export_records(Some(&person)))
+                    .await??
+            }
+            _ => Vec::new(),
+        };
+        let episodic = match &self.episodic {
+            Some(episodic) => episodic.export(names.as_deref()).await?,
+            None => Vec::new(),
+        };
+        let (graph_records, social_trees) = match &self.graph {
+            Some(graph) => (
+                graph.export_records(names.as_deref()).await?,
+                graph.export_social_trees(names.as_deref()).await?,
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        // State dimensions belong to the agent, not to any one person.
+        let state_values: Vec<StateValueRecord> = match (&self.state, &target) {
+            (Some(state), None) => state
+                .rows()
+                .await
+                .into_iter()
+                .map(|row| StateValueRecord {
+                    dimension_id: row.id,
+                    value: row.value,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        // The reader counts and pages through one snapshot, so the manifest
+        // matches the messages that follow it.
+        let person = target.as_ref().map(|target| PersonMessages {
+            accounts: target.accounts.clone(),
+            usernames: target.usernames.clone(),
+        });
+        let (count_tx, count_rx) = oneshot::channel();
+        let (page_tx, mut page_rx) = mpsc::channel(2);
+        let db_path = self.db_path.clone();
+        let reader = tokio::task::spawn_blocking(move || {
+            MemoryStore::open_read_only(&db_path)?.export_pages(
+                person.as_ref(),
+                MESSAGE_PAGE,
+                |count| {
+                    let _ = count_tx.send(count);
+                    Ok(())
+                },
+                |page| {
+                    page_tx
+                        .blocking_send(page)
+                        .map_err(|_| anyhow!("archive writer stopped"))
+                },
+            )
+        });
+        let Ok(message_count) = count_rx.await else {
+            reader.await??;
+            bail!("message export stopped before counting");
+        };
+
+        let manifest = ArchiveManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            created_at: Utc::now(),
+            agent_id: kernel::get_agent_profile().agent_id.clone(),
+            scope: scope.clone(),
+            person_id: target.and_then(|target| target.person_id),
+            counts: ArchiveCounts {
+                messages: message_count,
+                identity_records: identity_records.len(),
+                episodic_entries: episodic.len(),
+                graph_records: graph_records.len(),
+                social_trees: social_trees.len(),
+                state_values: state_values.len(),
+            },
+        };
+
+        let mut out = BufWriter::new(out);
+        write_record(&mut out, &ArchiveRecord::Manifest(manifest.clone())).await?;
+        while let Some(page) = page_rx.recv().await {
+            for message in page {
+                write_record(&mut out, &ArchiveRecord::Message(message)).await?;
+            }
+        }
+        reader.await??;
+        for record in identity_records {
+            write_record(&mut out, &ArchiveRecord::Identity(record)).await?;
+        }
+        for event in episodic {
+            write_record(&mut out, &ArchiveRecord::Episodic(event)).await?;
+        }
+        for record in graph_records {
+            write_record(&mut out, &ArchiveRecord::Graph(record)).await?;
+        }
+        for tree in social_trees {
+            write_record(&mut out, &ArchiveRecord::SocialTree(tree)).await?;
+        }
+        for value in state_values {
+            write_record(&mut out, &ArchiveRecord::State(value)).await?;
+        }
+        out.flush().await?;
+
+        info!(scope = ?manifest.scope, counts = ?manifest.counts, "Archive exported");
+        Ok(manifest)
+    }
+
+    /// Load an archive into the stores, reading it line by line and writing
+    /// it in batches. Returns how much of it was new. An import that fails
+    /// partway keeps what it wrote; importing the archive again completes it.
+    pub async fn import(&self, input: impl AsyncBufRead + Unpin) -> Result<ArchiveCounts> {
+        let mut lines = input.lines();
+        let first = lines.next_line().await?.context("archive is empty")?;
+        let manifest =
+            match serde_json::from_str(&first).context("archive manifest is malformed")? {
+                ArchiveRecord::Manifest(manifest) => manifest,
+                _ => bail!("archive does not start with a manifest"),
+            };
+        if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+            bail!(
+                "archive format {} is newer than this build supports ({})",
+                manifest.format_version,
+                ARCHIVE_FORMAT_VERSION
+            );
+        }
+
+        let mut imported = ArchiveCounts::default();
+        let mut batch = ImportBatch::default();
+        let mut line_no = 1;
+        while let Some(line) = lines.next_line().await? {
+            line_no += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: ArchiveRecord = serde_json::from_str(&line)
+                .with_context(|| format!("archive line {} is malformed", line_no))?;
+            if let ArchiveRecord::Manifest(_) = record {
+                bail!("archive line {} is a second manifest", line_no);
+            }
+            // Batches hold one kind of record, so stores are written in the
+            // archive's order and identity rows land before graph records.
+            if !batch.takes(&record) {
+                self.apply(std::mem::take(&mut batch), &mut imported)
+                    .await?;
+            }
+            batch.push(record);
+        }
+        self.apply(batch, &mut imported).await?;
+
+        info!(scope = ?manifest.scope, from = %manifest.agent_id, imported = ?imported, "Archive imported");
+        Ok(imported)
+    }
+
+    async fn apply(&self, batch: ImportBatch, imported: &mut ArchiveCounts) -> Result<()> {
+        if !batch.messages.is_empty() {
+            let db_path = self.db_path.clone();
+            let messages = batch.messages;
+            imported.messages += tokio::task::spawn_blocking(move || {
+                MemoryStore::open(&db_path)?.insert_batch(&messages)
+            })
+            .await??;
+        }
+
+        if let (Some(identity), false) = (&self.identity, batch.identity.is_empty()) {
+            let identity = identity.clone();
+            let records = batch.identity;
+            imported.identity_records += tokio::task::spawn_blocking(move || -> Result<usize> {
+                let mut fresh = 0;
+                for record in &records {
+                    if identity.import_record(record)? {
+                        fresh += 1;
+                    }
+                }
+                Ok(fresh)
+            })
+            .await??;
+        }
+
+        if let (Some(store), false) = (&self.episodic, batch.episodic.is_empty()) {
+            let ids: Vec<String> = batch
+                .episodic
+                .iter()
+                .map(|event| event.id.clone())
+                .collect();
+            let existing = store.existing_ids(&ids).await?;
+            let fresh: Vec<MemoryEvent> = batch
+                .episodic
+                .into_iter()
+                .filter(|event| !existing.contains(&event.id))
+                .collect();
+            imported.episodic_entries += fresh.len();
+            store.insert(fresh).await?;
+        }
+
+        if let Some(graph) = &self.graph {
+            for record in &batch.graph {
+                if graph.import_record(record).await? {
+                    imported.graph_records += 1;
+                }
+            }
+            for tree in &batch.social_trees {
+                if graph.import_social_tree(tree).await? {
+                    imported.social_trees += 1;
+                }
+            }
+        }
+
+        if let Some(state) = &self.state {
+            for value in batch.state {
+                let derived = state.schema().dimensions.iter().any(|dim| {
+                    dim.id == value.dimension_id && dim.update_mode.eq_ignore_ascii_case("derived")
+                });
+                if derived {
+                    continue;
+                }
+                let patch = ManualPatchRequest {
+                    dimension_id: value.dimension_id,
+                    value: value.value,
+                    reason: "archive import".to_string(),
+                    actor: Some("import".to_string()),
+                };
+                if state.patch_manual(patch).await.is_ok() {
+                    imported.state_values += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records read from an archive and not yet written, all of one kind.
+#[derive(Default)]
+struct ImportBatch {
+    kind: Option<std::mem::Discriminant<ArchiveRecord>>,
+    len: usize,
+    messages: Vec<MemoryMessage>,
+    identity: Vec<IdentityRecord>,
+    episodic: Vec<MemoryEvent>,
+    graph: Vec<GraphRecord>,
+    social_trees: Vec<SocialTreeSnapshot>,
+    state: Vec<StateValueRecord>,
+}
+
+impl ImportBatch {
+    fn takes(&self, record: &ArchiveRecord) -> bool {
+        self.len < IMPORT_BATCH
+            && self
+                .kind
+                .is_none_or(|kind| kind == std::mem::discriminant(record))
+    }
+
+    fn push(&mut self, record: ArchiveRecord) {
+        self.kind = Some(std::mem::discriminant(&record));
+        self.len += 1;
+        match record {
+            ArchiveRecord::Manifest(_) => {}
+            ArchiveRecord::Message(message) => self.messages.push(message),
+            ArchiveRecord::Identity(record) => self.identity.push(record),
+            ArchiveRecord::Episodic(event) => self.episodic.push(event),
+            ArchiveRecord::Graph(record) => self.graph.push(record),
+            ArchiveRecord::SocialTree(tree) => self.social_trees.push(tree),
+            ArchiveRecord::State(value) => self.state.push(value),
+        }
+    }
+}
+
+async fn write_record<W>(out: &mut W, record: &ArchiveRecord) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    out.write_all(&line).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDb};
+    use kernel::event::Platform;
+
+    fn message(id: &str, user_id: &str, username: &str, content: &str) -> MemoryMessage {
+        let mut msg = testing::message(id, "c1", user_id, username, content);
+        msg.is_mention = true;
+        msg
+    }
+
+    #[tokio::test]
+    async fn person_archives_round_trip_into_a_fresh_store() -> Result<()> {
+        let dir = TempDb::new("source");
+        let db_path = dir.path();
+        let identity = IdentityStore::open(db_path)?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        identity.resolve(Platform::Discord, "2", "bob")?;
+
+        let store = MemoryStore::open(db_path)?;
+        store.insert(&message("m1", "1", "alice", "hello"))?;
+        store.insert(&message("m2", "2", "bob", "hi all"))?;
+        let mut reply = message("m3", "bot", "Agent", "hello alice");
+        reply.is_bot_response = true;
+        reply.reply_to_user = Some("alice".to_string());
+        store.insert(&reply)?;
+
+        let source = Archiver::new(db_path).with_identity(identity);
+        let mut archive = Vec::new();
+        let manifest = source
+            .export(
+                &ArchiveScope::Person {
+                    person: "alice".to_string(),
+                },
+                &mut archive,
+            )
+            .await?;
+        assert_eq!(manifest.person_id.as_deref(), Some("alice"));
+        assert_eq!(manifest.counts.messages, 2);
+        // Alice's person, account and alias, but nothing of Bob's.
+        assert_eq!(manifest.counts.identity_records, 3);
+        assert_eq!(String::from_utf8(archive.clone())?.lines().count(), 6);
+
+        let fresh_dir = TempDb::new("target");
+        let fresh_path = fresh_dir.path();
+        let target = Archiver::new(fresh_path);
+        let imported = target.import(archive.as_slice()).await?;
+        assert_eq!(imported.messages, 2);
+        // A second import finds everything already there.
+        assert_eq!(target.import(archive.as_slice()).await?.messages, 0);
+
+        let restored = MemoryStore::open(fresh_path)?.export_messages()?;
+        let ids: Vec<&str> = restored.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m3"]);
+        assert_eq!(restored[1].reply_to_user.as_deref(), Some("alice"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replies_to_a_shared_name_stay_in_their_conversation() -> Result<()> {
+        let dir = TempDb::new("archive-shared");
+        let db_path = dir.path();
+        let identity = IdentityStore::open(db_path)?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        identity.resolve(Platform::Telegram, "9", "alice")?;
+
+        let store = MemoryStore::open(db_path)?;
+        let on_telegram = |mut msg: MemoryMessage| {
+            msg.platform = Platform::Telegram;
+            msg.channel_id = "t1".to_string();
+            msg
+        };
+        store.insert(&message("m1", "1", "alice", "hi from discord"))?;
+        store.insert(&on_telegram(message(
+            "m2",
+            "9",
+            "alice",
+            "hi from telegram",
+        )))?;
+        for mut reply in [
+            message("m3", "bot", "Agent", "hello discord alice"),
+            on_telegram(message("m4", "bot", "Agent", "hello telegram alice")),
+        ] {
+            reply.is_bot_response = true;
+            reply.reply_to_user = Some("alice".to_string());
+            store.insert(&reply)?;
+        }
+
+        let archiver = Archiver::new(db_path).with_identity(identity);
+        let ids = |archive: Vec<u8>| -> Vec<String> {
+            String::from_utf8(archive)
+                .unwrap()
+                .lines()
+                .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                    ArchiveRecord::Message(msg) => Some(msg.id),
+                    _ => None,
+                })
+                .collect()
+        };
+        for (person, expected) in [("alice", ["m1", "m3"]), ("alice-2", ["m2", "m4"])] {
+            let mut archive = Vec::new();
+            let manifest = archiver
+                .export(
+                    &ArchiveScope::Person {
+                        person: person.to_string(),
+                    },
+                    &mut archive,
+                )
+                .await?;
+            assert_eq!(manifest.counts.messages, 2);
+            assert_eq!(ids(archive), expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn identity_round_trips_between_fresh_stores() -> Result<()> {
+        let dir = TempDb::new("identity-source");
+        let db_path = dir.path();
+        let identity = IdentityStore::open(db_path)?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        identity.resolve(Platform::Discord, "1", "alicia")?;
+        identity.resolve(Platform::Telegram, "7", "ali")?;
+        identity.merge("ali", "alice")?;
+        identity.resolve(Platform::Irc, "bob", "bob")?;
+
+        let mut archive = Vec::new();
+        let manifest = Archiver::new(db_path)
+            .with_identity(identity.clone())
+            .export(&ArchiveScope::Agent, &mut archive)
+            .await?;
+        // Three persons, three accounts, four aliases.
+        assert_eq!(manifest.counts.identity_records, 10);
+
+        let fresh_dir = TempDb::new("identity-target");
+        let fresh_path = fresh_dir.path();
+        let restored = IdentityStore::open(fresh_path)?;
+        let target = Archiver::new(fresh_path).with_identity(restored.clone());
+        assert_eq!(
+            target.import(archive.as_slice()).await?.identity_records,
+            10
+        );
+        assert_eq!(target.import(archive.as_slice()).await?.identity_records, 0);
+
+        assert_eq!(restored.person("alice")?, identity.person("alice")?);
+        assert_eq!(restored.person("bob")?, identity.person("bob")?);
+        assert_eq!(restored.canonical("ali")?, "alice");
+        assert_eq!(
+            restored
+                .person_for_account(Platform::Telegram, "7")?
+                .as_deref(),
+            Some("alice")
+        );
+        // The merge can still be undone on the new store.
+        assert_eq!(restored.split(Platform::Telegram, "7")?, "ali");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn archives_from_newer_builds_are_refused() {
+        let dir = TempDb::new("newer");
+        let db_path = dir.path();
+        let manifest = ArchiveRecord::Manifest(ArchiveManifest {
+            format_version: ARCHIVE_FORMAT_VERSION + 1,
+            created_at: Utc::now(),
+            agent_id: "agent".to_string(),
+            scope: ArchiveScope::Agent,
+            person_id: None,
+            counts: ArchiveCounts::default(),
+        });
+        let archive = serde_json::to_string(&manifest).unwrap();
+
+        let err = Archiver::new(db_path)
+            .import(archive.as_bytes())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("newer"), "{err}");
+        assert!(Archiver::new(db_path).import(&b""[..]).await.is_err());
+    }
+}
@@ -81,11 +81,40 @@ impl SemanticCompressor {
         let system_prompt_with_diary_cmd =
             format!("{}\n\n{}\n\n{}", base_persona, time_block, diary_cmd);
 
+        let fact = self
+            .request_field(&system_prompt_with_diary_cmd, raw_transcript, "diary_entry")
+            .await;
+        Ok(fact.map(|fact| CompressionResult {
+            fact,
+            importance: 7.0,
+        }))
+    }
+
+    /// Fold the chat log of the messages that left the prompt window into
+    /// a session's running summary. The log starts with the previous
+    /// summary when there is one.
+    pub async fn summarise(
+        &self,
+        base_persona: &str,
+        raw_transcript: &str,
+    ) -> Result<Option<String>> {
+        let summary_cmd = get_prompt_or(
+            "memory.compressor.summary_cmd",
+            "--- SUMMARY COMMAND ---\nThe conversation is still going. Summarise what was said so far, keeping names, facts and open questions. Output JSON with one field: \"summary\".",
+        );
+        let system_prompt = format!("{}\n\n{}", base_persona, summary_cmd);
+        Ok(self
+            .request_field(&system_prompt, raw_transcript, "summary")
+            .await)
+    }
+
+    /// Ask for a JSON object and return its non-empty string `field`.
+    async fn request_field(&self, system_prompt: &str, user_content: &str, field: &str) -> Option<String> {
         let payload = serde_json::json!({
             "model": self.model,
             "messages": [
-                { "role": "system", "content": system_prompt_with_diary_cmd },
-                { "role": "user", "content": raw_transcript }
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": user_content }
             ],
             "temperature": 0.7,
             "max_tokens": self.semantic_max_tokens,
@@ -136,7 +165,7 @@ impl SemanticCompressor {
                 .unwrap_or("");
 
             if raw_content.is_empty() {
-                return Ok(None);
+                return None;
             }
 
             let cleaned_content = raw_content
@@ -149,12 +178,8 @@ impl SemanticCompressor {
             let parsed: Result<serde_json::Value, _> = serde_json::from_str(cleaned_content);
             match parsed {
                 Ok(val) => {
-                    let fact = val.get("diary_entry").and_then(|f| f.as_str()).unwrap_or("").to_string();
-                    if !fact.is_empty() {
-                        return Ok(Some(CompressionResult { fact, importance: 7.0 }));
-                    } else {
-                        return Ok(None);
-                    }
+                    let value = val.get(field).and_then(|f| f.as_str()).unwrap_or("").to_string();
+                    return (!value.is_empty()).then_some(value);
                 }
                 Err(e) => {
                     tracing::warn!(error = %e, attempt, "Failed to parse SLM JSON output, retrying...");
@@ -165,6 +190,6 @@ impl SemanticCompressor {
         }
 
         tracing::error!("Semantic Compressor exhausted all retries.");
-        Ok(None)
+        None
     }
 }
@@ -1,13 +1,16 @@
 use anyhow::{Context, Result};
 use arrow::array::{
-    ArrayRef, Float32Array, Int64Array, RecordBatch, StringArray,
+    Array, ArrayRef, FixedSizeListArray, Float32Array, Int64Array, RecordBatch, StringArray,
 };
 use arrow::datatypes::{DataType, Field, Schema};
 use lancedb::{query::{ExecutableQuery, QueryBase}, Table};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use futures::StreamExt;
 
-#[derive(Debug, Clone)]
+use crate::migrations::{migrate_lance, MigrationReport, EPISODIC_MIGRATIONS, EPISODIC_STORE};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryEvent {
     pub id: String,
     pub vector: Vec<f32>,
@@ -44,10 +47,32 @@ impl EpisodicStore {
                 .execute()
                 .await?
         };
+        migrate_lance(&table, EPISODIC_STORE, EPISODIC_MIGRATIONS, false).await?.log();
 
         Ok(Self { table })
     }
 
+    /// What opening the table would migrate, without changing it. A missing
+    /// table would be created at the latest version.
+    pub async fn migration_plan(uri: &str, table_name: &str) -> Result<MigrationReport> {
+        let conn = lancedb::connect(uri).execute().await
+            .context("Failed to connect to LanceDB")?;
+
+        let table_names = conn.table_names().execute().await?;
+        if table_names.contains(&table_name.to_string()) {
+            let table = conn.open_table(table_name).execute().await?;
+            migrate_lance(&table, EPISODIC_STORE, EPISODIC_MIGRATIONS, true).await
+        } else {
+            Ok(MigrationReport {
+                store: EPISODIC_STORE.to_string(),
+                from_version: 0,
+                to_version: EPISODIC_MIGRATIONS.last().map_or(0, |m| m.version),
+                migrations: EPISODIC_MIGRATIONS.iter().map(|m| m.name.to_string()).collect(),
+                applied: false,
+            })
+        }
+    }
+
     fn schema() -> Arc<Schema> {
         Arc::new(Schema::new(vec![
             Field::new("id", DataType::Utf8, false),
@@ -173,8 +198,13 @@ impl EpisodicStore {
         Ok(candidates.into_iter().take(limit).map(|(event, _)| event).collect())
     }
 
-    pub async fn count_user_chunks(&self, username: &str) -> Result<usize> {
-        let filter_expr = format!("metadata LIKE '%\"username\":\"{}\"%'", username);
+    /// Diary entries about a person, by canonical person id. Entries written
+    /// before identities existed only carry the username.
+    pub async fn count_user_chunks(&self, person_id: &str) -> Result<usize> {
+        let filter_expr = format!(
+            "metadata LIKE '%\"person_id\":\"{0}\"%' OR metadata LIKE '%\"username\":\"{0}\"%'",
+            person_id
+        );
         let mut stream = self.table
             .query()
             .only_if(filter_expr)
@@ -191,6 +221,103 @@ impl EpisodicStore {
         Ok(count)
     }
 
+    /// Every entry with its vector, for export. With `names`, only the
+    /// entries about that person, matched like
+    /// [`EpisodicStore::count_user_chunks`].
+    pub async fn export(&self, names: Option<&[String]>) -> Result<Vec<MemoryEvent>> {
+        let mut query = self.table.query();
+        if let Some(names) = names {
+            let Some(filter_expr) = user_filter(names) else {
+                return Ok(Vec::new());
+            };
+            query = query.only_if(filter_expr);
+        }
+        let mut stream = query.execute().await?;
+        let mut events = Vec::new();
+
+        while let Some(batch_res) = stream.next().await {
+            let batch = batch_res?;
+
+            let id_col = batch.column_by_name("id").context("Missing id")?
+                .as_any().downcast_ref::<StringArray>().context("id not a string")?;
+            let vector_col = batch.column_by_name("vector").context("Missing vector")?
+                .as_any().downcast_ref::<FixedSizeListArray>().context("vector not a fixed size list")?;
+            let content_col = batch.column_by_name("content").context("Missing content")?
+                .as_any().downcast_ref::<StringArray>().context("content not a string")?;
+            let timestamp_col = batch.column_by_name("timestamp").context("Missing timestamp")?
+                .as_any().downcast_ref::<Int64Array>().context("timestamp not i64")?;
+            let importance_col = batch.column_by_name("importance").context("Missing importance")?
+                .as_any().downcast_ref::<Float32Array>().context("importance not f32")?;
+            let metadata_col = batch.column_by_name("metadata").context("Missing metadata")?
+                .as_any().downcast_ref::<StringArray>().context("metadata not string")?;
+
+            for i in 0..batch.num_rows() {
+                let vector = vector_col.value(i);
+                let vector = vector
+                    .as_any()
+                    .downcast_ref::<Float32Array>()
+                    .context("vector items not f32")?;
+                events.push(MemoryEvent {
+                    id: id_col.value(i).to_string(),
+                    vector: vector.values().to_vec(),
+                    content: content_col.value(i).to_string(),
+                    timestamp: timestamp_col.value(i),
+                    importance: importance_col.value(i),
+                    metadata: metadata_col.value(i).to_string(),
+                });
+            }
+        }
+
+        events.sort_by_key(|event| event.timestamp);
+        Ok(events)
+    }
+
+    /// Which of `ids` are already stored.
+    pub async fn existing_ids(&self, ids: &[String]) -> Result<Vec<String>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let filter_expr = format!(
+            "id IN ({})",
+            ids.iter()
+                .map(|id| format!("'{}'", id.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let mut stream = self.table
+            .query()
+            .only_if(filter_expr)
+            .select(lancedb::query::Select::columns(&["id"]))
+            .execute()
+            .await?;
+
+        let mut existing = Vec::new();
+        while let Some(batch_res) = stream.next().await {
+            let batch = batch_res?;
+            let id_col = batch.column_by_name("id").context("Missing id")?
+                .as_any().downcast_ref::<StringArray>().context("id not a string")?;
+            existing.extend((0..batch.num_rows()).map(|i| id_col.value(i).to_string()));
+        }
+        Ok(existing)
+    }
+
+    /// Delete the diary entries about a person, matched on any of `names`
+    /// like [`EpisodicStore::count_user_chunks`]. Returns how many went.
+    pub async fn delete_user_chunks(&self, names: &[String]) -> Result<usize> {
+        let Some(filter_expr) = user_filter(names) else {
+            return Ok(0);
+        };
+
+        let matching = self.table.count_rows(Some(filter_expr.clone())).await?;
+        if matching > 0 {
+            self.table
+                .delete(&filter_expr)
+                .await
+                .context("Failed to delete records from LanceDB")?;
+        }
+        Ok(matching)
+    }
+
     pub async fn count(&self) -> Result<usize> {
         let mut stream = self.table.query().execute().await?;
 
@@ -259,6 +386,25 @@ impl EpisodicStore {
     }
 }
 
+/// Filter matching entries about any of `names`, or `None` for no names.
+fn user_filter(names: &[String]) -> Option<String> {
+    if names.is_empty() {
+        return None;
+    }
+    let filter_expr = names
+        .iter()
+        .map(|name| {
+            let name = name.replace('\'', "''");
+            format!(
+                "metadata LIKE '%\"person_id\":\"{0}\"%' OR metadata LIKE '%\"username\":\"{0}\"%'",
+                name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    Some(filter_expr)
+}
+
 fn calculate_final_score(distance: f32, timestamp: i64, importance: f32, now: i64, lambda: f32) -> f32 {
     let similarity = 1.0 / (1.0 + distance);
     let dt_seconds = (now - timestamp).max(0);
@@ -0,0 +1,443 @@
+//! Forgetting a person on request.
+//!
+//! [`Forgetter::forget`] resolves the subject to a canonical person, with
+//! every account and username they used, and then removes or anonymises
+//! them store by store:
+//!
+//! - short-term sessions and the ambient buffer drop their messages
+//! - `messages` rows they wrote are blanked to tombstones, and the agent's
+//!   replies stop naming them; their ambient rows are deleted
+//! - episodic diary entries about them are deleted
+//! - their graph node, relationship edges and social tree are deleted
+//! - state history entries they caused are attributed to nobody
+//! - their identity records are deleted, last, since the other stores are
+//!   found through them
+//!
+//! Every store is attempted even when an earlier one fails. The receipt
+//! records what was done and what failed, and is kept in `memory.db`.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use kernel::event::Platform;
+use serde::{Deserialize, Serialize};
+use state::StateStore;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::episodic::EpisodicStore;
+use crate::graph::CognitiveGraph;
+use crate::identity::IdentityStore;
+use crate::short_term::ShortTermMemory;
+use crate::store::MemoryStore;
+
+/// Who to forget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ForgetSubject {
+    /// A platform account, and the person it belongs to.
+    Account {
+        platform: Platform,
+        user_id: String,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    /// A person id, or a username no one else went by.
+    Person { person: String },
+}
+
+impl std::fmt::Display for ForgetSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForgetSubject::Account { platform, user_id, .. } => write!(f, "{}:{}", platform, user_id),
+            ForgetSubject::Person { person } => write!(f, "{}", person),
+        }
+    }
+}
+
+/// What a forget request did, store by store.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ForgetReceipt {
+    pub id: String,
+    /// The subject as requested, e.g. `Telegram:42` or `alice`.
+    pub subject: String,
+    /// `cockpit`, `mcp` or `user` for the person themselves.
+    pub requested_by: String,
+    pub accounts: usize,
+    pub usernames: usize,
+    pub session_messages: usize,
+    pub stored_messages: usize,
+    pub replies_unlinked: usize,
+    pub episodic_entries: usize,
+    pub graph_records: usize,
+    pub state_history_entries: usize,
+    pub identity_records: usize,
+    /// Stores that could not be cleared, with the reason.
+    pub errors: Vec<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl ForgetReceipt {
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Everything a subject is known by.
+#[derive(Debug, Default)]
+pub(crate) struct Target {
+    pub(crate) person_id: Option<String>,
+    pub(crate) accounts: Vec<(Platform, String)>,
+    pub(crate) usernames: Vec<String>,
+    /// Usernames another person still goes by, which do not single this
+    /// one out.
+    pub(crate) shared_usernames: Vec<String>,
+}
+
+impl Target {
+    /// Resolve `subject` through `identity` to the canonical person, with
+    /// every account and username they used. A person subject is matched
+    /// by person id before any username, and a username several persons
+    /// went by is refused as ambiguous. Without identity records, the
+    /// subject stands for itself.
+    pub(crate) fn resolve(
+        identity: Option<&IdentityStore>,
+        subject: &ForgetSubject,
+    ) -> Result<Self> {
+        let person_id = match (identity, subject) {
+            (Some(identity), ForgetSubject::Account { platform, user_id, .. }) => {
+                identity.person_for_account(*platform, user_id)?
+            }
+            (Some(identity), ForgetSubject::Person { person }) => identity.exact_person(person)?,
+            (None, _) => None,
+        };
+        let person = match (identity, &person_id) {
+            (Some(identity), Some(id)) => identity.person(id)?,
+            _ => None,
+        };
+
+        let mut target = Target::default();
+        if let (Some(identity), Some(person)) = (identity, person) {
+            target.accounts = person
+                .accounts
+                .iter()
+                .filter_map(|a| Some((a.platform.parse().ok()?, a.user_id.clone())))
+                .collect();
+            for alias in person.aliases {
+                if target.usernames.contains(&alias.username) {
+                    continue;
+                }
+                if identity.used_by_others(&alias.username, &person.id)? {
+                    target.shared_usernames.push(alias.username.clone());
+                }
+                target.usernames.push(alias.username);
+            }
+            target.person_id = Some(person.id);
+            return Ok(target);
+        }
+
+        match subject {
+            ForgetSubject::Account { platform, user_id, username } => {
+                target.accounts.push((*platform, user_id.clone()));
+                target.usernames.extend(username.clone());
+            }
+            ForgetSubject::Person { person } => target.usernames.push(person.clone()),
+        }
+        Ok(target)
+    }
+
+    /// Keys the graph and episodic store may file the person under. Shared
+    /// usernames are left out, since they may key someone else's records.
+    pub(crate) fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.person_id.iter().cloned().collect();
+        for username in &self.usernames {
+            if self.shared_usernames.contains(username) {
+                continue;
+            }
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(username)) {
+                names.push(username.clone());
+            }
+        }
+        names
+    }
+}
+
+/// Handles on every store that holds data about people. Stores left unset
+/// are skipped.
+#[derive(Clone)]
+pub struct Forgetter {
+    db_path: String,
+    short_term: Option<Arc<Mutex<ShortTermMemory>>>,
+    episodic: Option<Arc<EpisodicStore>>,
+    graph: Option<CognitiveGraph>,
+    identity: Option<IdentityStore>,
+    state: Option<StateStore>,
+}
+
+impl Forgetter {
+    /// `db_path` is the memory database, opened for each request.
+    pub fn new(db_path: &str) -> Self {
+        Self {
+            db_path: db_path.to_string(),
+            short_term: None,
+            episodic: None,
+            graph: None,
+            identity: None,
+            state: None,
+        }
+    }
+
+    pub fn with_short_term(mut self, short_term: Arc<Mutex<ShortTermMemory>>) -> Self {
+        self.short_term = Some(short_term);
+        self
+    }
+
+    pub fn with_episodic(mut self, episodic: Arc<EpisodicStore>) -> Self {
+        self.episodic = Some(episodic);
+        self
+    }
+
+    pub fn with_graph(mut self, graph: CognitiveGraph) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    pub fn with_state_store(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub async fn forget(&self, subject: &ForgetSubject, requested_by: &str) -> Result<ForgetReceipt> {
+        let target = Target::resolve(self.identity.as_ref(), subject)?;
+        let names = target.names();
+        let mut receipt = ForgetReceipt {
+            id: uuid::Uuid::new_v4().to_string(),
+            subject: subject.to_string(),
+            requested_by: requested_by.to_string(),
+            accounts: target.accounts.len(),
+            usernames: target.usernames.len(),
+            ..ForgetReceipt::default()
+        };
+
+        if let Some(short_term) = &self.short_term {
+            let mut stm = short_term.lock().await;
+            for (platform, user_id) in &target.accounts {
+                receipt.session_messages += stm.remove_user(*platform, user_id);
+            }
+        }
+
+        let db_path = self.db_path.clone();
+        let accounts = target.accounts.clone();
+        let usernames = target.usernames.clone();
+        let stored = tokio::task::spawn_blocking(move || -> Result<(usize, usize)> {
+            let store = MemoryStore::open(&db_path)?;
+            let now = Utc::now();
+            let mut erased = 0;
+            for (platform, user_id) in &accounts {
+                erased += store.erase_user(&platform.to_string(), user_id, now)?;
+            }
+            if accounts.is_empty() {
+                for username in &usernames {
+                    erased += store.erase_username(username, now)?;
+                }
+            }
+            let mut unlinked = 0;
+            for username in &usernames {
+                unlinked += store.unlink_replies(username)?;
+            }
+            Ok((erased, unlinked))
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+        match stored {
+            Ok((erased, unlinked)) => {
+                receipt.stored_messages = erased;
+                receipt.replies_unlinked = unlinked;
+            }
+            Err(e) => receipt.errors.push(format!("messages: {e}")),
+        }
+
+        if let Some(episodic) = &self.episodic {
+            match episodic.delete_user_chunks(&names).await {
+                Ok(count) => receipt.episodic_entries = count,
+                Err(e) => receipt.errors.push(format!("episodic: {e}")),
+            }
+        }
+
+        if let Some(graph) = &self.graph {
+            match graph.forget_person(&names).await {
+                Ok(count) => receipt.graph_records = count,
+                Err(e) => receipt.errors.push(format!("graph: {e}")),
+            }
+        }
+
+        if let Some(state) = &self.state {
+            receipt.state_history_entries = state.forget_actors(&names).await;
+        }
+
+        if let (Some(identity), Some(person_id)) = (&self.identity, &target.person_id) {
+            match identity.forget(person_id) {
+                Ok(count) => receipt.identity_records = count,
+                Err(e) => receipt.errors.push(format!("identity: {e}")),
+            }
+        }
+
+        receipt.completed_at = Utc::now();
+        let db_path = self.db_path.clone();
+        let saved = receipt.clone();
+        let recorded = tokio::task::spawn_blocking(move || {
+            MemoryStore::open(&db_path)?.insert_forget_receipt(&saved)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+        if let Err(e) = recorded {
+            receipt.errors.push(format!("receipt: {e}"));
+        }
+
+        if receipt.is_complete() {
+            info!(receipt = %receipt.id, requested_by, "Subject forgotten");
+        } else {
+            warn!(receipt = %receipt.id, requested_by, errors = ?receipt.errors, "Subject only partly forgotten");
+        }
+        Ok(receipt)
+    }
+
+    /// Past receipts, latest first.
+    pub async fn receipts(&self, limit: usize) -> Result<Vec<ForgetReceipt>> {
+        let db_path = self.db_path.clone();
+        tokio::task::spawn_blocking(move || MemoryStore::open_read_only(&db_path)?.forget_receipts(limit))
+            .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, TempDb};
+    use crate::types::MemoryMessage;
+
+    fn message(id: &str, platform: Platform, user_id: &str, username: &str, content: &str) -> MemoryMessage {
+        let mut msg = testing::message(id, "c1", user_id, username, content);
+        msg.platform = platform;
+        msg.is_mention = true;
+        msg
+    }
+
+    #[tokio::test]
+    async fn forgets_every_linked_account() -> Result<()> {
+        let dir = TempDb::new("forget");
+        let db_path = dir.path();
+
+        let identity = IdentityStore::open(db_path)?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        identity.resolve(Platform::Telegram, "9", "ally")?;
+        identity.resolve(Platform::Irc, "3", "bob")?;
+        identity.merge("ally", "alice")?;
+
+        let store = MemoryStore::open(db_path)?;
+        store.insert(&message("m1", Platform::Discord, "1", "alice", "hi from discord"))?;
+        store.insert(&message("m2", Platform::Telegram, "9", "ally", "hi from telegram"))?;
+        store.insert(&message("m3", Platform::Irc, "3", "bob", "hi from irc"))?;
+
+        let short_term = Arc::new(Mutex::new(ShortTermMemory::new()));
+        short_term
+            .lock()
+            .await
+            .push(message("m4", Platform::Telegram, "9", "ally", "still here"));
+
+        let forgetter = Forgetter::new(db_path)
+            .with_short_term(Arc::clone(&short_term))
+            .with_identity(identity.clone());
+        let subject = ForgetSubject::Account {
+            platform: Platform::Telegram,
+            user_id: "9".to_string(),
+            username: None,
+        };
+        let receipt = forgetter.forget(&subject, "user").await?;
+
+        assert!(receipt.is_complete(), "{:?}", receipt.errors);
+        assert_eq!(receipt.subject, "Telegram:9");
+        assert_eq!(receipt.accounts, 2);
+        assert_eq!(receipt.stored_messages, 2);
+        assert_eq!(receipt.session_messages, 1);
+        assert!(receipt.identity_records > 0);
+        assert_eq!(short_term.lock().await.total_messages(), 0);
+
+        let left = store.get_recent_all(10)?;
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].username, "bob");
+        assert!(identity.person("alice")?.is_none());
+        assert_eq!(forgetter.receipts(10).await?, vec![receipt]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_usernames_do_not_reach_other_persons() -> Result<()> {
+        let identity = IdentityStore::open_in_memory()?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        identity.resolve(Platform::Telegram, "9", "alice")?;
+        identity.resolve(Platform::Telegram, "9", "al")?;
+
+        let person = |name: &str| ForgetSubject::Person {
+            person: name.to_string(),
+        };
+        let alice = Target::resolve(Some(&identity), &person("alice"))?;
+        assert_eq!(alice.person_id.as_deref(), Some("alice"));
+        assert_eq!(alice.accounts, vec![(Platform::Discord, "1".to_string())]);
+        assert_eq!(alice.names(), vec!["alice"]);
+
+        // "alice" is the other person's id, so only their own keys go.
+        let other = Target::resolve(Some(&identity), &person("al"))?;
+        assert_eq!(other.person_id.as_deref(), Some("alice-2"));
+        assert_eq!(other.usernames.len(), 2);
+        assert_eq!(other.names(), vec!["alice-2", "al"]);
+        Ok(())
+    }
+
+    #[test]
+    fn aliases_of_several_persons_are_refused() -> Result<()> {
+        let identity = IdentityStore::open_in_memory()?;
+        identity.resolve(Platform::Discord, "1", "samuel")?;
+        identity.resolve(Platform::Discord, "1", "sam")?;
+        identity.resolve(Platform::Telegram, "2", "samantha")?;
+        identity.resolve(Platform::Telegram, "2", "sam")?;
+
+        let person = |name: &str| ForgetSubject::Person {
+            person: name.to_string(),
+        };
+        let err = Target::resolve(Some(&identity), &person("sam")).unwrap_err();
+        assert!(err.to_string().contains("ambiguous"), "{err}");
+        assert!(err.to_string().contains("samantha, samuel"), "{err}");
+
+        // The person id or the account still picks out either of them.
+        let samuel = Target::resolve(Some(&identity), &person("samuel"))?;
+        assert_eq!(samuel.accounts, vec![(Platform::Discord, "1".to_string())]);
+        let account = ForgetSubject::Account {
+            platform: Platform::Telegram,
+            user_id: "2".to_string(),
+            username: None,
+        };
+        let samantha = Target::resolve(Some(&identity), &account)?;
+        assert_eq!(samantha.person_id.as_deref(), Some("samantha"));
+        assert_eq!(samantha.names(), vec!["samantha"]);
+        Ok(())
+    }
+
+    #[test]
+    fn subjects_deserialize_from_either_shape() {
+        let account: ForgetSubject =
+            serde_json::from_str(r#"{"platform": "Telegram", "user_id": "9"}"#).unwrap();
+        assert_eq!(account.to_string(), "Telegram:9");
+        let person: ForgetSubject = serde_json::from_str(r#"{"person": "alice"}"#).unwrap();
+        assert_eq!(person.to_string(), "alice");
+    }
+}
@@ -6,16 +6,41 @@ use surrealdb::engine::any::connect;
 use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
 
+use crate::identity::IdentityStore;
+use crate::migrations::{migrate_surreal, MigrationReport, GRAPH_MIGRATIONS, GRAPH_STORE};
+
 #[derive(Clone)]
 pub struct CognitiveGraph {
     pub db: Surreal<Any>,
     agent_id: String,
     display_name: String,
     self_node_id: String,
+    identity: Option<IdentityStore>,
 }
 
 impl CognitiveGraph {
     pub async fn new(path: &str) -> Result<Self> {
+        let db = Self::connect(path).await?;
+        migrate_surreal(&db, GRAPH_STORE, GRAPH_MIGRATIONS, false).await?.log();
+
+        let profile = get_agent_profile();
+
+        Ok(Self {
+            db,
+            agent_id: sanitize_component(&profile.agent_id),
+            display_name: profile.display_name.clone(),
+            self_node_id: profile.graph_self_id.clone(),
+            identity: None,
+        })
+    }
+
+    /// What opening the graph at `path` would migrate, without applying it.
+    pub async fn migration_plan(path: &str) -> Result<MigrationReport> {
+        let db = Self::connect(path).await?;
+        migrate_surreal(&db, GRAPH_STORE, GRAPH_MIGRATIONS, true).await
+    }
+
+    async fn connect(path: &str) -> Result<Surreal<Any>> {
         let endpoint = if path == "memory" {
             "mem://".to_string()
         } else {
@@ -27,15 +52,31 @@ impl CognitiveGraph {
             .context("Failed to connect to SurrealDB endpoint")?;
 
         db.use_ns("polyverse").use_db("cognitive").await?;
+        Ok(db)
+    }
 
-        let profile = get_agent_profile();
+    /// Key `person:` nodes by canonical person id. Usernames given to the
+    /// graph are looked up in `identity` first.
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
 
-        Ok(Self {
-            db,
-            agent_id: sanitize_component(&profile.agent_id),
-            display_name: profile.display_name.clone(),
-            self_node_id: profile.graph_self_id.clone(),
-        })
+    /// The canonical person a username or person id refers to. Live person
+    /// ids come back unchanged, so ids the workers already resolved are
+    /// never mapped onto someone who uses that id as a username.
+    pub fn canonical_person(&self, user_id: &str) -> String {
+        match &self.identity {
+            Some(identity) => identity.canonical(user_id).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, user = %user_id, "Identity lookup failed; using raw user id");
+                user_id.to_string()
+            }),
+            None => user_id.to_string(),
+        }
+    }
+
+    fn person_key(&self, user_id: &str) -> String {
+        sanitize_component(&self.canonical_person(user_id))
     }
 
     pub fn self_node_id(&self) -> &str {
@@ -172,6 +213,35 @@ pub struct RelationshipEdge {
     pub tension: Option<f32>,
 }
 
+/// A graph node or edge as it appears in an archive. Record ids are
+/// strings such as `person:alice`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphRecord {
+    pub id: String,
+    /// `in` of an edge.
+    #[serde(default, rename = "in", skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// `out` of an edge.
+    #[serde(default, rename = "out", skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// RFC3339; kept apart so it is restored as a datetime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<String>,
+    /// Every other field.
+    pub fields: serde_json::Value,
+}
+
+/// Tables exported as [`GraphRecord`]s, nodes before edges. Social trees
+/// are exported on their own.
+const EXPORT_TABLES: [&str; 6] = [
+    "person",
+    "entity",
+    "attitudes_towards",
+    "illusion_of",
+    "interacts_with",
+    "feels_about",
+];
+
 #[derive(Debug, Deserialize)]
 struct RawRelationshipEdge {
     source: String,
@@ -278,6 +348,7 @@ fn tension_state(tension: f32) -> &'static str {
 
 impl CognitiveGraph {
     pub async fn project_social_tree(&self, user_id: &str, memory_hint: f32) -> Result<SocialTreeSnapshot> {
+        let user_id = &self.canonical_person(user_id);
         let safe_user_id = sanitize_component(user_id);
         let att_edge_id = format!("{}_{}", self.agent_id, safe_user_id);
         let ill_edge_id = format!("{}_{}", safe_user_id, self.agent_id);
@@ -451,6 +522,7 @@ impl CognitiveGraph {
     }
 
     pub async fn get_social_tree_snapshot(&self, user_id: &str) -> Result<SocialTreeSnapshot> {
+        let user_id = &self.canonical_person(user_id);
         let query = r#"
             SELECT user_id, relationship_core, dynamic_state, self_other_model, derived_summaries, meta
             FROM social_tree_root
@@ -543,7 +615,7 @@ impl CognitiveGraph {
 
     pub async fn update_social_graph(&self, user_id: &str, delta: SocialDelta) -> Result<()> {
         let delta = delta.clamped();
-        let safe_user_id = sanitize_component(user_id);
+        let safe_user_id = self.person_key(user_id);
         let edge_id = format!("{}_{}", self.agent_id, safe_user_id);
 
         let ensure_query = format!(
@@ -593,7 +665,7 @@ impl CognitiveGraph {
 
     pub async fn update_illusion_graph(&self, user_id: &str, delta: SocialDelta) -> Result<()> {
         let delta = delta.clamped();
-        let safe_user_id = sanitize_component(user_id);
+        let safe_user_id = self.person_key(user_id);
         let edge_id = format!("{}_{}", safe_user_id, self.agent_id);
 
         let ensure_query = format!(
@@ -667,8 +739,8 @@ impl CognitiveGraph {
     }
 
     pub async fn update_observed_dynamic(&self, from_user: &str, to_user: &str, tension: f32) -> Result<()> {
-        let from_user = sanitize_component(from_user);
-        let to_user = sanitize_component(to_user);
+        let from_user = self.person_key(from_user);
+        let to_user = self.person_key(to_user);
         let edge_id = format!("{}_{}", from_user, to_user);
         let query = format!(
             r#"
@@ -687,7 +759,260 @@ impl CognitiveGraph {
     }
 
     pub async fn get_social_context(&self, user_id: &str) -> Result<(AttitudesTowards, IllusionOf)> {
-        let safe_user_id = sanitize_component(user_id);
+        self.social_context_for_key(&self.person_key(user_id)).await
+    }
+
+    /// Carry the agent's relationship with person `from` over to `into`
+    /// after their identities were merged. `into` keeps its own edges when
+    /// it has them. The `from` edges stay, so a later split finds them.
+    pub async fn merge_person(&self, from: &str, into: &str) -> Result<()> {
+        let from_key = sanitize_component(from);
+        let into_key = sanitize_component(into);
+        let (attitudes, illusion) = self.social_context_for_key(&from_key).await?;
+
+        // CREATE fails on an existing record, which leaves `into`'s edge
+        // alone; that is the only failure expected here.
+        let query = format!(
+            r#"
+            CREATE attitudes_towards:`{agent}_{into}` CONTENT {{
+                in: {self_node},
+                out: person:`{into}`,
+                affinity: $att_affinity,
+                attachment: $att_attachment,
+                trust: $att_trust,
+                safety: $att_safety,
+                tension: $att_tension,
+                last_updated: time::now()
+            }};
+            CREATE illusion_of:`{into}_{agent}` CONTENT {{
+                in: person:`{into}`,
+                out: {self_node},
+                affinity: $ill_affinity,
+                attachment: $ill_attachment,
+                trust: $ill_trust,
+                safety: $ill_safety,
+                tension: $ill_tension,
+                last_updated: time::now()
+            }};
+        "#,
+            agent = self.agent_id,
+            into = into_key,
+            self_node = self.self_node_id
+        );
+        let mut response = self
+            .db
+            .query(&query)
+            .bind(("att_affinity", attitudes.affinity))
+            .bind(("att_attachment", attitudes.attachment))
+            .bind(("att_trust", attitudes.trust))
+            .bind(("att_safety", attitudes.safety))
+            .bind(("att_tension", attitudes.tension))
+            .bind(("ill_affinity", illusion.affinity))
+            .bind(("ill_attachment", illusion.attachment))
+            .bind(("ill_trust", illusion.trust))
+            .bind(("ill_safety", illusion.safety))
+            .bind(("ill_tension", illusion.tension))
+            .await
+            .context("Failed to carry the relationship over")?;
+        for (_, e) in response.take_errors() {
+            if !e.to_string().contains("already exists") {
+                return Err(anyhow::Error::from(e).context("Failed to carry the relationship over"));
+            }
+        }
+
+        tracing::info!(from = %from_key, into = %into_key, "Relationship carried over to merged person");
+        Ok(())
+    }
+
+    /// Delete everything the graph holds about a person: their node, the
+    /// agent's edges with them, observed dynamics they take part in and
+    /// their social tree. `keys` are the person id and any usernames older
+    /// records may be keyed by. Returns how many records went.
+    pub async fn forget_person(&self, keys: &[String]) -> Result<usize> {
+        let mut removed = 0;
+        for key in keys {
+            let key = sanitize_component(key);
+            let query = format!(
+                r#"
+                DELETE attitudes_towards:`{agent}_{key}` RETURN VALUE <string> $before.id;
+                DELETE illusion_of:`{key}_{agent}` RETURN VALUE <string> $before.id;
+                DELETE interacts_with WHERE in = person:`{key}` OR out = person:`{key}` RETURN VALUE <string> $before.id;
+                DELETE social_tree_root:`{key}` RETURN VALUE <string> $before.id;
+                DELETE person:`{key}` RETURN VALUE <string> $before.id;
+            "#,
+                agent = self.agent_id,
+                key = key
+            );
+            let mut response = self
+                .db
+                .query(&query)
+                .await
+                .context("Failed to DELETE person from graph")?;
+            for idx in 0..5 {
+                let rows: Vec<serde_json::Value> = response.take(idx).unwrap_or_default();
+                removed += rows.len();
+            }
+        }
+
+        tracing::info!(removed, "Person forgotten in graph");
+        Ok(removed)
+    }
+
+    /// Nodes and edges for export. With `keys`, only a person's node and
+    /// the edges that start or end at it.
+    pub async fn export_records(&self, keys: Option<&[String]>) -> Result<Vec<GraphRecord>> {
+        let filter = if keys.is_some() {
+            r#"
+            WHERE record::tb(id) = 'person' AND record::id(id) IN $keys
+                OR in IN $keys.map(|$k| type::record('person', $k))
+                OR out IN $keys.map(|$k| type::record('person', $k))
+            "#
+        } else {
+            ""
+        };
+        // Record ids and datetimes do not convert to JSON, so they are cast.
+        let query = format!(
+            r#"
+            SELECT
+                <string> id AS id,
+                IF in != NONE THEN <string> in END AS in,
+                IF out != NONE THEN <string> out END AS out,
+                IF last_updated != NONE THEN <string> last_updated END AS last_updated,
+                object::remove($this, ['id', 'in', 'out', 'last_updated']) AS fields
+            FROM type::table($table)
+            {filter};
+        "#
+        );
+        let keys: Vec<String> = keys
+            .unwrap_or_default()
+            .iter()
+            .map(|key| sanitize_component(key))
+            .collect();
+
+        let mut records = Vec::new();
+        for table in EXPORT_TABLES {
+            let response = self
+                .db
+                .query(&query)
+                .bind(("table", table))
+                .bind(("keys", keys.clone()))
+                .await;
+            let rows: Result<Vec<serde_json::Value>> = match response {
+                Ok(mut response) => response.take(0).map_err(anyhow::Error::from),
+                Err(e) => Err(e.into()),
+            };
+            match rows {
+                Ok(rows) => {
+                    for row in rows {
+                        records
+                            .push(serde_json::from_value(row).context("Malformed graph record")?);
+                    }
+                }
+                // Tables only exist once something was written to them.
+                Err(e) if e.to_string().contains("does not exist") => continue,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to export graph table {}", table))
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Social trees for export, like [`CognitiveGraph::export_records`].
+    pub async fn export_social_trees(
+        &self,
+        keys: Option<&[String]>,
+    ) -> Result<Vec<SocialTreeSnapshot>> {
+        let mut response = match keys {
+            Some(keys) => {
+                let keys: Vec<String> = keys.iter().map(|key| sanitize_component(key)).collect();
+                self.db
+                    .query(
+                        "SELECT VALUE user_id FROM social_tree_root WHERE record::id(id) IN $keys;",
+                    )
+                    .bind(("keys", keys))
+                    .await?
+            }
+            None => {
+                self.db
+                    .query("SELECT VALUE user_id FROM social_tree_root;")
+                    .await?
+            }
+        };
+        let user_ids: Vec<String> = response.take(0).unwrap_or_default();
+
+        let mut trees = Vec::with_capacity(user_ids.len());
+        for user_id in user_ids {
+            trees.push(self.get_social_tree_snapshot(&user_id).await?);
+        }
+        Ok(trees)
+    }
+
+    /// Restore an exported record. One that already exists is left alone;
+    /// returns whether it was created.
+    pub async fn import_record(&self, record: &GraphRecord) -> Result<bool> {
+        self.create_record(
+            &record.id,
+            record.source.clone(),
+            record.target.clone(),
+            record.last_updated.clone(),
+            record.fields.clone(),
+        )
+        .await
+    }
+
+    /// Restore an exported social tree, like [`CognitiveGraph::import_record`].
+    pub async fn import_social_tree(&self, tree: &SocialTreeSnapshot) -> Result<bool> {
+        let id = format!("social_tree_root:{}", sanitize_component(&tree.user_id));
+        self.create_record(&id, None, None, None, serde_json::to_value(tree)?)
+            .await
+    }
+
+    async fn create_record(
+        &self,
+        id: &str,
+        source: Option<String>,
+        target: Option<String>,
+        last_updated: Option<String>,
+        fields: serde_json::Value,
+    ) -> Result<bool> {
+        let table = id
+            .split_once(':')
+            .map(|(table, _)| table)
+            .unwrap_or_default();
+        if !EXPORT_TABLES.contains(&table) && table != "social_tree_root" {
+            anyhow::bail!("graph record {} is not in an exported table", id);
+        }
+        // Reading a table that was never written fails, so define it first.
+        let query = format!(
+            r#"
+            DEFINE TABLE IF NOT EXISTS {table};
+            IF record::exists(type::record($id)) {{
+                false
+            }} ELSE {{
+                CREATE type::record($id) CONTENT $fields;
+                IF $in != NONE {{ UPDATE type::record($id) SET in = type::record($in), out = type::record($out) }};
+                IF $last_updated != NONE {{ UPDATE type::record($id) SET last_updated = <datetime> $last_updated }};
+                true
+            }};
+        "#
+        );
+        let mut response = self
+            .db
+            .query(&query)
+            .bind(("id", id.to_string()))
+            .bind(("in", source))
+            .bind(("out", target))
+            .bind(("last_updated", last_updated))
+            .bind(("fields", fields))
+            .await
+            .with_context(|| format!("Failed to import graph record {}", id))?;
+        let created: Option<bool> = response.take(1)?;
+        Ok(created.unwrap_or(false))
+    }
+
+    async fn social_context_for_key(&self, safe_user_id: &str) -> Result<(AttitudesTowards, IllusionOf)> {
         let att_edge_id = format!("{}_{}", self.agent_id, safe_user_id);
         let ill_edge_id = format!("{}_{}", safe_user_id, self.agent_id);
 
@@ -1017,4 +1342,119 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_linked_accounts_share_one_person() -> Result<()> {
+        use kernel::event::Platform;
+
+        let identity = IdentityStore::open_in_memory()?;
+        identity.resolve(Platform::Discord, "1", "bob")?;
+        identity.resolve(Platform::Telegram, "9", "bobby")?;
+        let graph = CognitiveGraph::new("memory").await?.with_identity(identity.clone());
+
+        let warm = SocialDelta {
+            delta_affinity: 0.3,
+            ..SocialDelta::default()
+        };
+        graph.update_social_graph("bobby", warm).await?;
+        let (before, _) = graph.get_social_context("bob").await?;
+        assert_eq!(before.affinity, 0.0);
+
+        let code = identity.issue_link_code(Platform::Discord, "1")?;
+        identity.redeem_link_code(&code, Platform::Telegram, "9")?;
+        graph.merge_person("bobby", "bob").await?;
+
+        let (after, _) = graph.get_social_context("bobby").await?;
+        assert!(after.affinity > 0.29);
+        let (same, _) = graph.get_social_context("bob").await?;
+        assert_eq!(same.affinity, after.affinity);
+
+        // Merging again finds the edges already there and keeps them.
+        graph.merge_person("bobby", "bob").await?;
+        let (again, _) = graph.get_social_context("bob").await?;
+        assert_eq!(again.affinity, after.affinity);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_username_keeps_persons_apart() -> Result<()> {
+        use kernel::event::Platform;
+
+        let identity = IdentityStore::open_in_memory()?;
+        identity.resolve(Platform::Discord, "1", "alice")?;
+        let other = identity.resolve(Platform::Telegram, "9", "alice")?;
+        let graph = CognitiveGraph::new("memory").await?.with_identity(identity);
+
+        let warm = SocialDelta {
+            delta_affinity: 0.3,
+            ..SocialDelta::default()
+        };
+        graph.update_social_graph("alice", warm).await?;
+        let (theirs, _) = graph.get_social_context(&other).await?;
+        assert_eq!(theirs.affinity, 0.0);
+        let (hers, _) = graph.get_social_context("alice").await?;
+        assert!(hers.affinity > 0.29);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forget_person_removes_their_records() -> Result<()> {
+        let graph = CognitiveGraph::new("memory").await?;
+        let warm = SocialDelta {
+            delta_affinity: 0.3,
+            ..SocialDelta::default()
+        };
+        graph.update_social_graph("carol", warm.clone()).await?;
+        graph.update_illusion_graph("carol", warm.clone()).await?;
+        graph.update_observed_dynamic("carol", "dave", 0.2).await?;
+        graph.update_social_graph("dave", warm).await?;
+
+        assert!(graph.forget_person(&["carol".to_string()]).await? >= 3);
+        let (attitudes, illusion) = graph.get_social_context("carol").await?;
+        assert_eq!(attitudes.affinity, 0.0);
+        assert_eq!(illusion.affinity, 0.0);
+        let (dave, _) = graph.get_social_context("dave").await?;
+        assert!(dave.affinity > 0.29);
+        assert_eq!(graph.forget_person(&["carol".to_string()]).await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_round_trip() -> Result<()> {
+        let graph = CognitiveGraph::new("memory").await?;
+        let warm = SocialDelta {
+            delta_trust: 0.2,
+            ..SocialDelta::default()
+        };
+        graph.update_social_graph("erin", warm.clone()).await?;
+        graph.update_observed_dynamic("erin", "frank", 0.1).await?;
+        graph.update_social_graph("frank", warm).await?;
+        graph.project_social_tree("erin", 0.5).await?;
+
+        let all = graph.export_records(None).await?;
+        let erin = graph.export_records(Some(&["erin".to_string()])).await?;
+        assert!(erin.len() < all.len());
+        assert!(erin.iter().all(|r| r.id.contains("erin")
+            || r.source.as_deref() == Some("person:erin")
+            || r.target.as_deref() == Some("person:erin")));
+        let trees = graph
+            .export_social_trees(Some(&["erin".to_string()]))
+            .await?;
+        assert_eq!(trees.len(), 1);
+
+        let fresh = CognitiveGraph::new("memory").await?;
+        for record in &erin {
+            assert!(fresh.import_record(record).await?);
+            assert!(!fresh.import_record(record).await?);
+        }
+        assert!(fresh.import_social_tree(&trees[0]).await?);
+        let (attitudes, _) = fresh.get_social_context("erin").await?;
+        assert!(attitudes.trust > 0.19);
+        assert_eq!(fresh.export_records(None).await?, erin);
+        assert_eq!(
+            fresh.get_social_tree_snapshot("erin").await?.user_id,
+            "erin"
+        );
+        Ok(())
+    }
 }
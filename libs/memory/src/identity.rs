@@ -0,0 +1,793 @@
+//! Who is who across platforms.
+//!
+//! Every platform account belongs to one canonical person. A person's id is
+//! the username their first account was seen with (`alice`, or `alice-2`
+//! when taken), so graph nodes keyed by the old raw usernames keep their
+//! history. Usernames an account went by are kept as aliases; a rename adds
+//! an alias instead of a new person.
+//!
+//! Accounts are linked by merging their persons: one account asks for a
+//! one-time code with `/link`, and the other account sends `/link <code>`.
+//! An account that sends too many wrong codes is locked out for a while.
+//! A merge can be undone per account with [`IdentityStore::split`].
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use kernel::event::Platform;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::migrations::{migrate_sqlite, MEMORY_DB, MEMORY_DB_MIGRATIONS};
+use crate::store::{configure_reader_connection, configure_writer_connection};
+
+/// How long a `/link` code can be redeemed.
+pub const LINK_CODE_TTL_MINUTES: i64 = 10;
+/// Wrong codes an account may send before it is locked out.
+pub const LINK_MAX_FAILURES: i64 = 5;
+/// How long an account that sent too many wrong codes is locked out.
+pub const LINK_LOCKOUT_MINUTES: i64 = 60;
+
+const LINK_CODE_LENGTH: usize = 10;
+/// Letters and digits without the easily confused `0`, `1`, `I` and `O`.
+const LINK_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// What redeeming a `/link` code did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkRedemption {
+    /// The accounts now belong to this person.
+    Linked(String),
+    /// The code is unknown or expired, or was issued to the redeeming
+    /// account itself.
+    Rejected,
+    /// The account sent too many wrong codes and may try again after this.
+    LockedOut(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Account {
+    pub platform: String,
+    pub user_id: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alias {
+    pub platform: String,
+    pub user_id: String,
+    pub username: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Person {
+    pub id: String,
+    pub display_name: String,
+    pub accounts: Vec<Account>,
+    /// Newest first.
+    pub aliases: Vec<Alias>,
+}
+
+/// One identity row as carried in an archive. Persons come before the
+/// accounts that point at them, and accounts before their aliases.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "row", rename_all = "snake_case")]
+pub enum IdentityRecord {
+    Person {
+        id: String,
+        display_name: String,
+        created_at: DateTime<Utc>,
+        merged_into: Option<String>,
+    },
+    Account {
+        platform: String,
+        user_id: String,
+        person_id: String,
+        origin_person_id: String,
+        linked_at: DateTime<Utc>,
+    },
+    Alias {
+        platform: String,
+        user_id: String,
+        username: String,
+        first_seen: DateTime<Utc>,
+        last_seen: DateTime<Utc>,
+    },
+}
+
+/// Canonical persons, their platform accounts and alias history, in the
+/// memory database.
+#[derive(Clone)]
+pub struct IdentityStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl IdentityStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let mut conn = Connection::open(path)
+            .with_context(|| format!("Failed to open identity database: {}", path))?;
+        configure_writer_connection(&conn)?;
+        migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS)?;
+
+        info!(path = %path, "Identity store opened");
+        Ok(Self::with_connection(conn))
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        let mut conn = Connection::open_in_memory()
+            .context("Failed to open in-memory identity database")?;
+        configure_reader_connection(&conn)?;
+        migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS)?;
+        Ok(Self::with_connection(conn))
+    }
+
+    fn with_connection(conn: Connection) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The person behind a platform account, creating one the first time
+    /// the account is seen. Records `username` in the alias history.
+    pub fn resolve(&self, platform: Platform, user_id: &str, username: &str) -> Result<String> {
+        let platform = platform.to_string();
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO aliases (platform, user_id, username, first_seen, last_seen)
+             VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT (platform, user_id, username) DO UPDATE SET last_seen = excluded.last_seen",
+            params![platform, user_id, username, now],
+        )?;
+
+        let person = match account_person(&tx, &platform, user_id)? {
+            Some(person) => person,
+            None => {
+                let person = unused_person_id(&tx, username)?;
+                tx.execute(
+                    "INSERT INTO persons (id, display_name, created_at) VALUES (?1, ?2, ?3)",
+                    params![person, username, now],
+                )?;
+                tx.execute(
+                    "INSERT INTO accounts (platform, user_id, person_id, origin_person_id, linked_at)
+                     VALUES (?1, ?2, ?3, ?3, ?4)",
+                    params![platform, user_id, person, now],
+                )?;
+                info!(person = %person, platform = %platform, user_id = %user_id, "New person");
+                person
+            }
+        };
+        tx.commit()?;
+        Ok(person)
+    }
+
+    /// The person behind an account, if it has been seen.
+    pub fn person_for_account(&self, platform: Platform, user_id: &str) -> Result<Option<String>> {
+        account_person(&self.conn(), &platform.to_string(), user_id)
+    }
+
+    /// The person a graph or memory key refers to. `name` may be a person id
+    /// (merged persons lead to the person they were merged into) or a
+    /// username (the most recently seen account with that alias wins). A
+    /// person id always wins over someone else's alias. Unknown names are
+    /// returned as they are.
+    pub fn canonical(&self, name: &str) -> Result<String> {
+        let conn = self.conn();
+        if let Some(person) = live_person(&conn, name)? {
+            return Ok(person);
+        }
+        let by_alias = conn
+            .query_row(
+                "SELECT accounts.person_id FROM aliases
+                 JOIN accounts USING (platform, user_id)
+                 WHERE aliases.username = ?1 COLLATE NOCASE
+                 ORDER BY aliases.last_seen DESC
+                 LIMIT 1",
+                params![name],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(by_alias.unwrap_or_else(|| name.to_string()))
+    }
+
+    /// The person `name` names without guessing, for calls that destroy or
+    /// export data: a person id (following merges), or else an alias of
+    /// exactly one person. An alias several persons went by is an error
+    /// rather than the most recently seen of them. Unknown names give `None`.
+    pub fn exact_person(&self, name: &str) -> Result<Option<String>> {
+        let conn = self.conn();
+        if let Some(person) = live_person(&conn, name)? {
+            return Ok(Some(person));
+        }
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT accounts.person_id FROM aliases
+             JOIN accounts USING (platform, user_id)
+             WHERE aliases.username = ?1 COLLATE NOCASE
+             ORDER BY accounts.person_id",
+        )?;
+        let persons = stmt
+            .query_map(params![name], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if persons.len() > 1 {
+            bail!(
+                "'{}' is ambiguous: it is an alias of {} persons ({}); name the person id or the platform and user id instead",
+                name,
+                persons.len(),
+                persons.join(", ")
+            );
+        }
+        Ok(persons.into_iter().next())
+    }
+
+    /// Whether `username` is the id of another live person, or an alias of
+    /// an account that does not belong to `person`.
+    pub fn used_by_others(&self, username: &str, person: &str) -> Result<bool> {
+        let used: bool = self.conn().query_row(
+            "SELECT EXISTS (
+                 SELECT 1 FROM persons
+                 WHERE id = ?1 COLLATE NOCASE AND COALESCE(merged_into, id) != ?2
+             ) OR EXISTS (
+                 SELECT 1 FROM aliases
+                 JOIN accounts USING (platform, user_id)
+                 WHERE aliases.username = ?1 COLLATE NOCASE AND accounts.person_id != ?2
+             )",
+            params![username, person],
+            |row| row.get(0),
+        )?;
+        Ok(used)
+    }
+
+    /// A person with their accounts and alias history.
+    pub fn person(&self, id: &str) -> Result<Option<Person>> {
+        let conn = self.conn();
+        let Some(id) = live_person(&conn, id)? else {
+            return Ok(None);
+        };
+        let display_name: String = conn.query_row(
+            "SELECT display_name FROM persons WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT platform, user_id, linked_at FROM accounts
+             WHERE person_id = ?1
+             ORDER BY linked_at",
+        )?;
+        let accounts = stmt
+            .query_map(params![id], |row| {
+                Ok(Account {
+                    platform: row.get(0)?,
+                    user_id: row.get(1)?,
+                    linked_at: parse_time(&row.get::<_, String>(2)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT aliases.platform, aliases.user_id, aliases.username,
+                    aliases.first_seen, aliases.last_seen
+             FROM aliases
+             JOIN accounts USING (platform, user_id)
+             WHERE accounts.person_id = ?1
+             ORDER BY aliases.last_seen DESC",
+        )?;
+        let aliases = stmt
+            .query_map(params![id], |row| {
+                Ok(Alias {
+                    platform: row.get(0)?,
+                    user_id: row.get(1)?,
+                    username: row.get(2)?,
+                    first_seen: parse_time(&row.get::<_, String>(3)?),
+                    last_seen: parse_time(&row.get::<_, String>(4)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(Person {
+            id,
+            display_name,
+            accounts,
+            aliases,
+        }))
+    }
+
+    /// Move every account of `from` to `into`. Returns the surviving person.
+    pub fn merge(&self, from: &str, into: &str) -> Result<String> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let into = merge_persons(&tx, from, into)?;
+        tx.commit()?;
+        Ok(into)
+    }
+
+    /// Move an account back to the person it was first seen as, undoing the
+    /// merge that brought it to its current person. Returns that person.
+    pub fn split(&self, platform: Platform, user_id: &str) -> Result<String> {
+        let platform = platform.to_string();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let (current, origin): (String, String) = tx
+            .query_row(
+                "SELECT person_id, origin_person_id FROM accounts
+                 WHERE platform = ?1 AND user_id = ?2",
+                params![platform, user_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .with_context(|| format!("No {} account {}", platform, user_id))?;
+        if current == origin {
+            bail!("{} account {} was not merged into another person", platform, user_id);
+        }
+
+        tx.execute(
+            "UPDATE persons SET merged_into = NULL WHERE id = ?1",
+            params![origin],
+        )?;
+        tx.execute(
+            "UPDATE accounts SET person_id = ?1 WHERE platform = ?2 AND user_id = ?3",
+            params![origin, platform, user_id],
+        )?;
+        tx.commit()?;
+        info!(person = %origin, from = %current, platform = %platform, user_id = %user_id, "Account split off");
+        Ok(origin)
+    }
+
+    /// Delete a person with their accounts, aliases and pending link codes,
+    /// and the persons merged into them. Returns how many rows went.
+    pub fn forget(&self, id: &str) -> Result<usize> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let Some(id) = live_person(&tx, id)? else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for statement in [
+            "DELETE FROM link_codes WHERE (platform, user_id) IN
+                 (SELECT platform, user_id FROM accounts WHERE person_id = ?1)",
+            "DELETE FROM aliases WHERE (platform, user_id) IN
+                 (SELECT platform, user_id FROM accounts WHERE person_id = ?1)",
+            "DELETE FROM accounts WHERE person_id = ?1",
+            "DELETE FROM persons WHERE id IN (
+                 WITH RECURSIVE gone(id) AS (
+                     SELECT ?1
+                     UNION SELECT persons.id FROM persons JOIN gone ON persons.merged_into = gone.id
+                 )
+                 SELECT id FROM gone
+             )",
+        ] {
+            removed += tx.execute(statement, params![id])?;
+        }
+        tx.commit()?;
+        info!(removed, "Person forgotten");
+        Ok(removed)
+    }
+
+    /// Every person, account and alias for an archive, or only those of
+    /// `person` and the persons merged into them.
+    pub fn export_records(&self, person: Option<&str>) -> Result<Vec<IdentityRecord>> {
+        let conn = self.conn();
+        let person = match person {
+            Some(person) => match live_person(&conn, person)? {
+                Some(person) => Some(person),
+                None => return Ok(Vec::new()),
+            },
+            None => None,
+        };
+        let mut records = Vec::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, display_name, created_at, merged_into FROM persons
+             WHERE ?1 IS NULL OR COALESCE(merged_into, id) = ?1
+             ORDER BY created_at, id",
+        )?;
+        let persons = stmt.query_map(params![person], |row| {
+            Ok(IdentityRecord::Person {
+                id: row.get(0)?,
+                display_name: row.get(1)?,
+                created_at: parse_time(&row.get::<_, String>(2)?),
+                merged_into: row.get(3)?,
+            })
+        })?;
+        records.extend(persons.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let mut stmt = conn.prepare(
+            "SELECT platform, user_id, person_id, origin_person_id, linked_at FROM accounts
+             WHERE ?1 IS NULL OR person_id = ?1
+             ORDER BY linked_at, platform, user_id",
+        )?;
+        let accounts = stmt.query_map(params![person], |row| {
+            Ok(IdentityRecord::Account {
+                platform: row.get(0)?,
+                user_id: row.get(1)?,
+                person_id: row.get(2)?,
+                origin_person_id: row.get(3)?,
+                linked_at: parse_time(&row.get::<_, String>(4)?),
+            })
+        })?;
+        records.extend(accounts.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let mut stmt = conn.prepare(
+            "SELECT aliases.platform, aliases.user_id, aliases.username,
+                    aliases.first_seen, aliases.last_seen
+             FROM aliases
+             JOIN accounts USING (platform, user_id)
+             WHERE ?1 IS NULL OR accounts.person_id = ?1
+             ORDER BY aliases.first_seen, aliases.platform, aliases.user_id, aliases.username",
+        )?;
+        let aliases = stmt.query_map(params![person], |row| {
+            Ok(IdentityRecord::Alias {
+                platform: row.get(0)?,
+                user_id: row.get(1)?,
+                username: row.get(2)?,
+                first_seen: parse_time(&row.get::<_, String>(3)?),
+                last_seen: parse_time(&row.get::<_, String>(4)?),
+            })
+        })?;
+        records.extend(aliases.collect::<rusqlite::Result<Vec<_>>>()?);
+        Ok(records)
+    }
+
+    /// Restore an exported identity row. Rows already present are kept as
+    /// they are, so an account seen here before stays with its person.
+    /// Returns whether the row was new.
+    pub fn import_record(&self, record: &IdentityRecord) -> Result<bool> {
+        let conn = self.conn();
+        let inserted = match record {
+            IdentityRecord::Person {
+                id,
+                display_name,
+                created_at,
+                merged_into,
+            } => conn.execute(
+                "INSERT OR IGNORE INTO persons (id, display_name, created_at, merged_into)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![id, display_name, created_at.to_rfc3339(), merged_into],
+            )?,
+            IdentityRecord::Account {
+                platform,
+                user_id,
+                person_id,
+                origin_person_id,
+                linked_at,
+            } => conn.execute(
+                "INSERT OR IGNORE INTO accounts (platform, user_id, person_id, origin_person_id, linked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![platform, user_id, person_id, origin_person_id, linked_at.to_rfc3339()],
+            )?,
+            IdentityRecord::Alias {
+                platform,
+                user_id,
+                username,
+                first_seen,
+                last_seen,
+            } => conn.execute(
+                "INSERT OR IGNORE INTO aliases (platform, user_id, username, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![platform, user_id, username, first_seen.to_rfc3339(), last_seen.to_rfc3339()],
+            )?,
+        };
+        Ok(inserted > 0)
+    }
+
+    /// A one-time code the account's owner can send from another account to
+    /// link the two. Issuing a new code replaces the account's old one.
+    pub fn issue_link_code(&self, platform: Platform, user_id: &str) -> Result<String> {
+        let platform = platform.to_string();
+        let mut random = uuid::Uuid::new_v4().as_u128();
+        let code: String = (0..LINK_CODE_LENGTH)
+            .map(|_| {
+                let index = (random % LINK_CODE_ALPHABET.len() as u128) as usize;
+                random /= LINK_CODE_ALPHABET.len() as u128;
+                LINK_CODE_ALPHABET[index] as char
+            })
+            .collect();
+        let expires_at = Utc::now() + Duration::minutes(LINK_CODE_TTL_MINUTES);
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM link_codes WHERE (platform = ?1 AND user_id = ?2) OR expires_at < ?3",
+            params![platform, user_id, Utc::now().to_rfc3339()],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO link_codes (code, platform, user_id, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![code, platform, user_id, expires_at.to_rfc3339()],
+        )?;
+        tx.commit()?;
+        Ok(code)
+    }
+
+    /// Link the redeeming account to the account that issued `code`: the
+    /// redeemer's person is merged into the issuer's. Codes are matched
+    /// case-insensitively. Every rejected code counts against the redeeming
+    /// account, which is locked out after [`LINK_MAX_FAILURES`] of them.
+    pub fn redeem_link_code(
+        &self,
+        code: &str,
+        platform: Platform,
+        user_id: &str,
+    ) -> Result<LinkRedemption> {
+        let platform = platform.to_string();
+        let code = code.trim().to_ascii_uppercase();
+        let now = Utc::now();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let (failures, locked_until): (i64, Option<String>) = tx
+            .query_row(
+                "SELECT failures, locked_until FROM link_attempts
+                 WHERE platform = ?1 AND user_id = ?2",
+                params![platform, user_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .unwrap_or((0, None));
+        if let Some(until) = locked_until.map(|t| parse_time(&t)).filter(|t| *t > now) {
+            return Ok(LinkRedemption::LockedOut(until));
+        }
+
+        let issuer: Option<(String, String, String)> = tx
+            .query_row(
+                "SELECT platform, user_id, expires_at FROM link_codes WHERE code = ?1",
+                params![code],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let issuer = issuer.filter(|(issuer_platform, issuer_user_id, expires_at)| {
+            parse_time(expires_at) >= now
+                && !(*issuer_platform == platform && issuer_user_id == user_id)
+        });
+        let Some((issuer_platform, issuer_user_id, _)) = issuer else {
+            let failures = failures + 1;
+            let locked_until = (failures >= LINK_MAX_FAILURES)
+                .then(|| now + Duration::minutes(LINK_LOCKOUT_MINUTES));
+            tx.execute(
+                "INSERT OR REPLACE INTO link_attempts (platform, user_id, failures, locked_until)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    platform,
+                    user_id,
+                    if locked_until.is_some() { 0 } else { failures },
+                    locked_until.map(|t| t.to_rfc3339()),
+                ],
+            )?;
+            tx.commit()?;
+            if let Some(until) = locked_until {
+                warn!(platform = %platform, user_id = %user_id, "Too many wrong link codes, account locked out");
+                return Ok(LinkRedemption::LockedOut(until));
+            }
+            return Ok(LinkRedemption::Rejected);
+        };
+        tx.execute("DELETE FROM link_codes WHERE code = ?1", params![code])?;
+        tx.execute(
+            "DELETE FROM link_attempts WHERE platform = ?1 AND user_id = ?2",
+            params![platform, user_id],
+        )?;
+
+        let issuer_person = account_person(&tx, &issuer_platform, &issuer_user_id)?
+            .context("Link code issuer has no person")?;
+        let redeemer_person = account_person(&tx, &platform, user_id)?
+            .context("Redeeming account has no person")?;
+        let person = if issuer_person == redeemer_person {
+            issuer_person
+        } else {
+            merge_persons(&tx, &redeemer_person, &issuer_person)?
+        };
+        tx.commit()?;
+        Ok(LinkRedemption::Linked(person))
+    }
+}
+
+fn account_person(conn: &Connection, platform: &str, user_id: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT person_id FROM accounts WHERE platform = ?1 AND user_id = ?2",
+            params![platform, user_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Follow `merged_into` from a person id to the person that absorbed it.
+fn live_person(conn: &Connection, id: &str) -> Result<Option<String>> {
+    let mut current = id.to_string();
+    // Merges always point at a live person, so one hop is enough; the bound
+    // only guards against hand-edited cycles.
+    for _ in 0..8 {
+        let merged_into: Option<Option<String>> = conn
+            .query_row(
+                "SELECT merged_into FROM persons WHERE id = ?1",
+                params![current],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match merged_into {
+            None => return Ok(None),
+            Some(None) => return Ok(Some(current)),
+            Some(Some(next)) => current = next,
+        }
+    }
+    Ok(Some(current))
+}
+
+fn merge_persons(tx: &Transaction<'_>, from: &str, into: &str) -> Result<String> {
+    let from = live_person(tx, from)?.with_context(|| format!("Unknown person {}", from))?;
+    let into = live_person(tx, into)?.with_context(|| format!("Unknown person {}", into))?;
+    if from == into {
+        bail!("{} and {} are already the same person", from, into);
+    }
+    tx.execute(
+        "UPDATE accounts SET person_id = ?1 WHERE person_id = ?2",
+        params![into, from],
+    )?;
+    tx.execute(
+        "UPDATE persons SET merged_into = ?1 WHERE id = ?2 OR merged_into = ?2",
+        params![into, from],
+    )?;
+    info!(from = %from, into = %into, "Persons merged");
+    Ok(into)
+}
+
+fn unused_person_id(conn: &Connection, username: &str) -> Result<String> {
+    let base = match username.trim() {
+        "" => "user",
+        name => name,
+    };
+    let taken = |id: &str| -> Result<bool> {
+        Ok(conn
+            .query_row("SELECT 1 FROM persons WHERE id = ?1", params![id], |_| Ok(()))
+            .optional()?
+            .is_some())
+    };
+    if !taken(base)? {
+        return Ok(base.to_string());
+    }
+    let mut n = 2;
+    loop {
+        let id = format!("{}-{}", base, n);
+        if !taken(&id)? {
+            return Ok(id);
+        }
+        n += 1;
+    }
+}
+
+fn parse_time(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_keep_the_person() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        let alice = ids.resolve(Platform::Discord, "1", "alice").unwrap();
+        assert_eq!(alice, "alice");
+        assert_eq!(ids.resolve(Platform::Discord, "1", "alicia").unwrap(), "alice");
+
+        let person = ids.person("alice").unwrap().unwrap();
+        assert_eq!(person.accounts.len(), 1);
+        assert_eq!(person.aliases.len(), 2);
+        assert_eq!(ids.canonical("alicia").unwrap(), "alice");
+        assert_eq!(ids.canonical("stranger").unwrap(), "stranger");
+    }
+
+    #[test]
+    fn taken_usernames_get_a_suffix() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        ids.resolve(Platform::Discord, "1", "alice").unwrap();
+        let other = ids.resolve(Platform::Telegram, "9", "alice").unwrap();
+        assert_eq!(other, "alice-2");
+        // The person id wins over the other account's alias.
+        assert_eq!(ids.canonical("alice").unwrap(), "alice");
+        assert_eq!(ids.canonical("alice-2").unwrap(), "alice-2");
+
+        // Among aliases, the account seen most recently under the name wins.
+        ids.resolve(Platform::Irc, "3", "carol").unwrap();
+        ids.resolve(Platform::Irc, "3", "ali").unwrap();
+        ids.resolve(Platform::Telegram, "9", "ali").unwrap();
+        assert_eq!(ids.canonical("ali").unwrap(), "alice-2");
+    }
+
+    #[test]
+    fn link_codes_merge_accounts_and_split_undoes_it() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        ids.resolve(Platform::Discord, "1", "alice").unwrap();
+        ids.resolve(Platform::Telegram, "9", "ally").unwrap();
+
+        let code = ids.issue_link_code(Platform::Discord, "1").unwrap();
+        assert_eq!(code.len(), LINK_CODE_LENGTH);
+        assert_eq!(
+            ids.redeem_link_code(&code, Platform::Discord, "1").unwrap(),
+            LinkRedemption::Rejected
+        );
+        assert_eq!(
+            ids.redeem_link_code(&code.to_lowercase(), Platform::Telegram, "9").unwrap(),
+            LinkRedemption::Linked("alice".to_string())
+        );
+        assert_eq!(
+            ids.redeem_link_code(&code, Platform::Telegram, "9").unwrap(),
+            LinkRedemption::Rejected
+        );
+
+        assert_eq!(ids.person_for_account(Platform::Telegram, "9").unwrap().as_deref(), Some("alice"));
+        assert_eq!(ids.canonical("ally").unwrap(), "alice");
+        assert_eq!(ids.canonical("ally").unwrap(), ids.canonical("alice").unwrap());
+        assert_eq!(ids.person("alice").unwrap().unwrap().accounts.len(), 2);
+
+        assert!(ids.split(Platform::Discord, "1").is_err());
+        assert_eq!(ids.split(Platform::Telegram, "9").unwrap(), "ally");
+        assert_eq!(ids.canonical("ally").unwrap(), "ally");
+        assert_eq!(ids.person("alice").unwrap().unwrap().accounts.len(), 1);
+    }
+
+    #[test]
+    fn wrong_link_codes_lock_the_account_out() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        ids.resolve(Platform::Discord, "1", "alice").unwrap();
+        ids.resolve(Platform::Telegram, "9", "mallory").unwrap();
+        let code = ids.issue_link_code(Platform::Discord, "1").unwrap();
+
+        for _ in 1..LINK_MAX_FAILURES {
+            assert_eq!(
+                ids.redeem_link_code("WRONGCODE2", Platform::Telegram, "9").unwrap(),
+                LinkRedemption::Rejected
+            );
+        }
+        assert!(matches!(
+            ids.redeem_link_code("WRONGCODE2", Platform::Telegram, "9").unwrap(),
+            LinkRedemption::LockedOut(_)
+        ));
+        // Even the right code is refused while locked out.
+        assert!(matches!(
+            ids.redeem_link_code(&code, Platform::Telegram, "9").unwrap(),
+            LinkRedemption::LockedOut(_)
+        ));
+        assert_eq!(ids.person_for_account(Platform::Telegram, "9").unwrap().as_deref(), Some("mallory"));
+    }
+
+    #[test]
+    fn forgetting_takes_merged_persons_along() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        ids.resolve(Platform::Discord, "1", "alice").unwrap();
+        ids.resolve(Platform::Telegram, "9", "ally").unwrap();
+        ids.resolve(Platform::Irc, "3", "bob").unwrap();
+        ids.merge("ally", "alice").unwrap();
+        ids.issue_link_code(Platform::Telegram, "9").unwrap();
+
+        // 2 accounts, 2 aliases, 1 link code, 2 persons.
+        assert_eq!(ids.forget("ally").unwrap(), 7);
+        assert!(ids.person("alice").unwrap().is_none());
+        assert!(ids.person("ally").unwrap().is_none());
+        assert_eq!(ids.person_for_account(Platform::Telegram, "9").unwrap(), None);
+        assert_eq!(ids.person("bob").unwrap().unwrap().accounts.len(), 1);
+        assert_eq!(ids.forget("alice").unwrap(), 0);
+    }
+
+    #[test]
+    fn merged_persons_lead_to_the_survivor() {
+        let ids = IdentityStore::open_in_memory().unwrap();
+        ids.resolve(Platform::Discord, "1", "a").unwrap();
+        ids.resolve(Platform::Telegram, "2", "b").unwrap();
+        ids.resolve(Platform::Irc, "3", "c").unwrap();
+
+        ids.merge("b", "a").unwrap();
+        ids.merge("a", "c").unwrap();
+        assert_eq!(ids.canonical("b").unwrap(), "c");
+        assert_eq!(ids.person("b").unwrap().unwrap().id, "c");
+        assert!(ids.merge("a", "c").is_err());
+    }
+}
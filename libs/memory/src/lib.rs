@@ -1,13 +1,25 @@
+pub mod ambient;
+pub mod archive;
 pub mod types;
 pub mod short_term;
 pub mod store;
 pub mod worker;
 pub mod episodic;
+pub mod forget;
 pub mod embedder;
 pub mod compressor;
 pub mod graph;
+pub mod identity;
+pub mod migrations;
+#[cfg(test)]
+mod testing;
 
+pub use ambient::AmbientConfig;
+pub use archive::{ArchiveScope, Archiver};
+pub use forget::{ForgetReceipt, ForgetSubject, Forgetter};
 pub use graph::SocialTreeSnapshot;
+pub use identity::IdentityStore;
+pub use migrations::MigrationReport;
 pub use short_term::ShortTermMemory;
 pub use store::MemoryStore;
 pub use types::{ConversationKey, MemoryMessage};
@@ -0,0 +1,606 @@
+//! Versioned schemas for the memory stores.
+//!
+//! Each store keeps the number of the last migration applied to it: SQLite
+//! in `PRAGMA user_version`, the LanceDB episodic table in its schema
+//! metadata and the SurrealDB graph in a `schema_meta:graph` record. On open,
+//! migrations newer than that run in order and bump the version. A store
+//! written by a newer build is refused rather than guessed at.
+//!
+//! Version 0 is a store from before versioning. The first migration of each
+//! store must accept one, since older builds created the same tables.
+//!
+//! New schema changes are appended to the store's list with the next version
+//! number; released migrations are never edited.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use lancedb::Table;
+use rusqlite::{Connection, OpenFlags, TransactionBehavior};
+use serde::Serialize;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+use tracing::info;
+
+/// One step in a store's schema history.
+pub struct Migration<A> {
+    pub version: u32,
+    pub name: &'static str,
+    pub apply: A,
+}
+
+pub type SqliteStep = fn(&Connection) -> Result<()>;
+pub type LanceStep = for<'a> fn(&'a Table) -> BoxFuture<'a, Result<()>>;
+/// SurrealQL run as one query.
+pub type SurrealStep = &'static str;
+
+/// Where a store's schema stands, and what opening it would change.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub store: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Names of the migrations between the two, in order.
+    pub migrations: Vec<String>,
+    /// Whether they were applied or only planned.
+    pub applied: bool,
+}
+
+impl MigrationReport {
+    pub fn is_current(&self) -> bool {
+        self.migrations.is_empty()
+    }
+
+    /// Log the report at startup.
+    pub fn log(&self) {
+        if self.is_current() {
+            info!(store = %self.store, version = self.from_version, "Schema is current");
+        } else if self.applied {
+            info!(
+                store = %self.store,
+                from = self.from_version,
+                to = self.to_version,
+                migrations = ?self.migrations,
+                "Schema migrated"
+            );
+        } else {
+            info!(
+                store = %self.store,
+                from = self.from_version,
+                to = self.to_version,
+                migrations = ?self.migrations,
+                "Schema migrations pending (dry run)"
+            );
+        }
+    }
+}
+
+/// The migrations after `current`, checking the list is in order and the
+/// store is not from a newer build.
+fn pending<'m, A>(store: &str, migrations: &'m [Migration<A>], current: u32) -> Result<Vec<&'m Migration<A>>> {
+    let mut previous = 0;
+    for migration in migrations {
+        if migration.version != previous + 1 {
+            bail!(
+                "{} migrations are out of order: {} follows version {}",
+                store,
+                migration.name,
+                previous
+            );
+        }
+        previous = migration.version;
+    }
+    if current > previous {
+        bail!(
+            "{} schema is at version {}, but this build only knows up to {}; \
+             it was written by a newer build",
+            store,
+            current,
+            previous
+        );
+    }
+    Ok(migrations.iter().filter(|m| m.version > current).collect())
+}
+
+fn report<A>(store: &str, from: u32, pending: &[&Migration<A>], applied: bool) -> MigrationReport {
+    MigrationReport {
+        store: store.to_string(),
+        from_version: from,
+        to_version: pending.last().map_or(from, |m| m.version),
+        migrations: pending.iter().map(|m| m.name.to_string()).collect(),
+        applied,
+    }
+}
+
+// --- SQLite memory database -------------------------------------------------
+
+pub const MEMORY_DB: &str = "memory.db";
+
+/// Schema history of the memory database, shared by [`crate::MemoryStore`]
+/// and [`crate::IdentityStore`].
+pub const MEMORY_DB_MIGRATIONS: &[Migration<SqliteStep>] = &[
+    Migration {
+        version: 1,
+        name: "messages table",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS messages (
+                    id TEXT PRIMARY KEY,
+                    platform TEXT NOT NULL,
+                    channel_id TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    username TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    is_mention BOOLEAN DEFAULT 0,
+                    is_bot_response BOOLEAN DEFAULT 0,
+                    reply_to_user TEXT,
+                    importance REAL DEFAULT 0.5,
+                    created_at TEXT NOT NULL,
+                    accessed_at TEXT,
+                    access_count INTEGER DEFAULT 0
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_messages_channel
+                    ON messages(platform, channel_id);
+                CREATE INDEX IF NOT EXISTS idx_messages_time
+                    ON messages(created_at DESC);
+                CREATE INDEX IF NOT EXISTS idx_messages_importance
+                    ON messages(importance DESC);
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 2,
+        name: "message edits, deletions and threads",
+        // Unversioned builds added these columns one by one, so any of them
+        // may already be there.
+        apply: |conn| {
+            add_column_if_missing(conn, "messages", "edited_at", "TEXT")?;
+            add_column_if_missing(conn, "messages", "deleted_at", "TEXT")?;
+            add_column_if_missing(conn, "messages", "thread_id", "TEXT")?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 3,
+        name: "identity tables",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS persons (
+                    id TEXT PRIMARY KEY,
+                    display_name TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    merged_into TEXT
+                );
+
+                CREATE TABLE IF NOT EXISTS accounts (
+                    platform TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    person_id TEXT NOT NULL,
+                    origin_person_id TEXT NOT NULL,
+                    linked_at TEXT NOT NULL,
+                    PRIMARY KEY (platform, user_id)
+                );
+                CREATE INDEX IF NOT EXISTS idx_accounts_person
+                    ON accounts(person_id);
+
+                CREATE TABLE IF NOT EXISTS aliases (
+                    platform TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    username TEXT NOT NULL,
+                    first_seen TEXT NOT NULL,
+                    last_seen TEXT NOT NULL,
+                    PRIMARY KEY (platform, user_id, username)
+                );
+                CREATE INDEX IF NOT EXISTS idx_aliases_username
+                    ON aliases(username COLLATE NOCASE);
+
+                CREATE TABLE IF NOT EXISTS link_codes (
+                    code TEXT PRIMARY KEY,
+                    platform TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    expires_at TEXT NOT NULL
+                );
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 4,
+        name: "message search index",
+        // An external-content FTS5 index keyed by the messages rowid, kept in
+        // step by triggers. Rowids of a table without an INTEGER PRIMARY KEY
+        // can change on VACUUM, so run `INSERT INTO messages_fts(messages_fts)
+        // VALUES ('rebuild')` after one.
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                    content,
+                    content = 'messages',
+                    content_rowid = 'rowid',
+                    tokenize = 'unicode61 remove_diacritics 2'
+                );
+
+                CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                    INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+                END;
+                CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                    INSERT INTO messages_fts(messages_fts, rowid, content)
+                        VALUES ('delete', old.rowid, old.content);
+                END;
+                CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+                    INSERT INTO messages_fts(messages_fts, rowid, content)
+                        VALUES ('delete', old.rowid, old.content);
+                    INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+                END;
+
+                INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 5,
+        name: "forget receipts",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS forget_receipts (
+                    id TEXT PRIMARY KEY,
+                    requested_by TEXT NOT NULL,
+                    completed_at TEXT NOT NULL,
+                    receipt TEXT NOT NULL
+                );
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 6,
+        name: "ambient messages",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS ambient_messages (
+                    id TEXT NOT NULL,
+                    platform TEXT NOT NULL,
+                    channel_id TEXT NOT NULL,
+                    thread_id TEXT,
+                    user_id TEXT NOT NULL,
+                    username TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    importance REAL NOT NULL,
+                    created_at TEXT NOT NULL,
+                    PRIMARY KEY (platform, channel_id, id)
+                );
+                CREATE INDEX IF NOT EXISTS idx_ambient_time
+                    ON ambient_messages(created_at DESC);
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 7,
+        name: "sessions",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS sessions (
+                    id TEXT PRIMARY KEY,
+                    platform TEXT NOT NULL,
+                    channel_id TEXT NOT NULL,
+                    thread_id TEXT,
+                    started_at TEXT NOT NULL,
+                    last_active TEXT NOT NULL,
+                    ended_at TEXT,
+                    ingested_at TEXT
+                );
+                CREATE INDEX IF NOT EXISTS idx_sessions_pending
+                    ON sessions(ingested_at);
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 8,
+        name: "session summaries",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                ALTER TABLE sessions ADD COLUMN summary TEXT;
+                ALTER TABLE sessions ADD COLUMN summary_until TEXT;
+                ",
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 9,
+        name: "link attempts",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS link_attempts (
+                    platform TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    failures INTEGER NOT NULL,
+                    locked_until TEXT,
+                    PRIMARY KEY (platform, user_id)
+                );
+                ",
+            )?;
+            Ok(())
+        },
+    },
+];
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+    }
+    Ok(())
+}
+
+fn sqlite_version(conn: &Connection) -> Result<u32> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(version as u32)
+}
+
+/// Bring a SQLite database up to date. The migrations and the version bump
+/// share one write transaction, so concurrent openers of the same file wait
+/// for each other and a failed migration leaves the database untouched.
+pub fn migrate_sqlite(
+    conn: &mut Connection,
+    store: &str,
+    migrations: &[Migration<SqliteStep>],
+) -> Result<MigrationReport> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let from = sqlite_version(&tx)?;
+    let pending = pending(store, migrations, from)?;
+    for migration in &pending {
+        (migration.apply)(&tx)
+            .with_context(|| format!("{} migration {} ({}) failed", store, migration.version, migration.name))?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        info!(store, version = migration.version, name = migration.name, "Applied schema migration");
+    }
+    tx.commit()?;
+    Ok(report(store, from, &pending, true))
+}
+
+/// What [`migrate_sqlite`] would do to the database at `path`, without
+/// changing it. A missing database would be created at the latest version.
+pub fn plan_sqlite(path: &str, store: &str, migrations: &[Migration<SqliteStep>]) -> Result<MigrationReport> {
+    let from = if std::path::Path::new(path).exists() {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open {} read-only: {}", store, path))?;
+        sqlite_version(&conn)?
+    } else {
+        0
+    };
+    let pending = pending(store, migrations, from)?;
+    Ok(report(store, from, &pending, false))
+}
+
+/// Refuse a database from a newer build; readers never migrate.
+pub fn check_sqlite(conn: &Connection, store: &str, migrations: &[Migration<SqliteStep>]) -> Result<()> {
+    pending(store, migrations, sqlite_version(conn)?)?;
+    Ok(())
+}
+
+// --- LanceDB episodic table -------------------------------------------------
+
+pub const EPISODIC_STORE: &str = "episodic";
+
+/// Schema metadata key holding the episodic table's version.
+pub const LANCE_SCHEMA_VERSION_KEY: &str = "polyverse::schema_version";
+
+pub const EPISODIC_MIGRATIONS: &[Migration<LanceStep>] = &[Migration {
+    version: 1,
+    name: "episodic event columns",
+    apply: require_episodic_columns,
+}];
+
+/// Tables created before versioning have the same columns; make sure.
+fn require_episodic_columns(table: &Table) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let schema = table.schema().await?;
+        for column in ["id", "vector", "content", "timestamp", "importance", "metadata"] {
+            if schema.field_with_name(column).is_err() {
+                bail!("episodic table has no {} column", column);
+            }
+        }
+        Ok(())
+    })
+}
+
+async fn lance_version(table: &Table) -> Result<u32> {
+    let schema = table.schema().await?;
+    Ok(schema
+        .metadata
+        .get(LANCE_SCHEMA_VERSION_KEY)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0))
+}
+
+/// Bring the episodic table up to date, or only report what that would do.
+pub async fn migrate_lance(
+    table: &Table,
+    store: &str,
+    migrations: &[Migration<LanceStep>],
+    dry_run: bool,
+) -> Result<MigrationReport> {
+    let from = lance_version(table).await?;
+    let pending = pending(store, migrations, from)?;
+    if dry_run {
+        return Ok(report(store, from, &pending, false));
+    }
+    for migration in &pending {
+        (migration.apply)(table)
+            .await
+            .with_context(|| format!("{} migration {} ({}) failed", store, migration.version, migration.name))?;
+        set_lance_version(table, migration.version).await?;
+        info!(store, version = migration.version, name = migration.name, "Applied schema migration");
+    }
+    Ok(report(store, from, &pending, true))
+}
+
+async fn set_lance_version(table: &Table, version: u32) -> Result<()> {
+    let native = table
+        .as_native()
+        .context("episodic table is not a local LanceDB table")?;
+    // Replacing the metadata drops keys not passed in, so carry them over.
+    let mut metadata: HashMap<String, String> = table.schema().await?.metadata.clone();
+    metadata.insert(LANCE_SCHEMA_VERSION_KEY.to_string(), version.to_string());
+    native.replace_schema_metadata(metadata).await?;
+    Ok(())
+}
+
+// --- SurrealDB cognitive graph ----------------------------------------------
+
+pub const GRAPH_STORE: &str = "graph";
+
+pub const GRAPH_MIGRATIONS: &[Migration<SurrealStep>] = &[Migration {
+    version: 1,
+    name: "social tree lookup index",
+    apply: "
+        DEFINE TABLE IF NOT EXISTS social_tree_root SCHEMALESS;
+        DEFINE INDEX IF NOT EXISTS social_tree_root_user ON TABLE social_tree_root FIELDS user_id;
+    ",
+}];
+
+async fn surreal_version(db: &Surreal<Any>) -> Result<u32> {
+    let mut response = db
+        .query("SELECT VALUE version FROM schema_meta:graph;")
+        .await?;
+    let version: Vec<i64> = match response.take(0) {
+        Ok(version) => version,
+        // A graph that was never migrated has no `schema_meta` table yet.
+        Err(e) if e.to_string().contains("does not exist") => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(version.first().copied().unwrap_or(0) as u32)
+}
+
+/// Bring the graph up to date, or only report what that would do.
+pub async fn migrate_surreal(
+    db: &Surreal<Any>,
+    store: &str,
+    migrations: &[Migration<SurrealStep>],
+    dry_run: bool,
+) -> Result<MigrationReport> {
+    let from = surreal_version(db).await?;
+    let pending = pending(store, migrations, from)?;
+    if dry_run {
+        return Ok(report(store, from, &pending, false));
+    }
+    for migration in &pending {
+        db.query(migration.apply)
+            .await
+            .and_then(|response| response.check())
+            .with_context(|| format!("{} migration {} ({}) failed", store, migration.version, migration.name))?;
+        db.query("UPSERT schema_meta:graph SET version = $version, updated_at = time::now();")
+            .bind(("version", migration.version as i64))
+            .await?
+            .check()?;
+        info!(store, version = migration.version, name = migration.name, "Applied schema migration");
+    }
+    Ok(report(store, from, &pending, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDb;
+
+    fn latest() -> u32 {
+        MEMORY_DB_MIGRATIONS.last().unwrap().version
+    }
+
+    #[test]
+    fn unversioned_database_is_brought_up_to_date() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // What unversioned builds left behind: the table with one later column.
+        (MEMORY_DB_MIGRATIONS[0].apply)(&conn).unwrap();
+        conn.execute_batch("ALTER TABLE messages ADD COLUMN edited_at TEXT;").unwrap();
+
+        let report = migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS).unwrap();
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.to_version, latest());
+        assert_eq!(report.migrations.len(), MEMORY_DB_MIGRATIONS.len());
+        assert_eq!(sqlite_version(&conn).unwrap(), latest());
+        conn.execute_batch("SELECT thread_id FROM messages; SELECT * FROM persons;").unwrap();
+
+        let again = migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS).unwrap();
+        assert!(again.is_current());
+        assert_eq!(again.from_version, latest());
+    }
+
+    #[test]
+    fn newer_databases_are_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        let err = migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS).unwrap_err();
+        assert!(err.to_string().contains("newer build"));
+        assert!(check_sqlite(&conn, MEMORY_DB, MEMORY_DB_MIGRATIONS).is_err());
+    }
+
+    #[test]
+    fn failed_migrations_roll_back() {
+        let broken: &[Migration<SqliteStep>] = &[
+            Migration {
+                version: 1,
+                name: "ok",
+                apply: |conn| Ok(conn.execute_batch("CREATE TABLE a (x INTEGER);")?),
+            },
+            Migration {
+                version: 2,
+                name: "broken",
+                apply: |conn| Ok(conn.execute_batch("CREATE TABLE a (x INTEGER);")?),
+            },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(migrate_sqlite(&mut conn, "test", broken).is_err());
+        assert_eq!(sqlite_version(&conn).unwrap(), 0);
+        let tables: i64 = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'a'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tables, 0);
+    }
+
+    #[test]
+    fn plans_do_not_touch_the_database() {
+        let dir = TempDb::new("migrations");
+        let path = dir.path();
+
+        let plan = plan_sqlite(path, MEMORY_DB, MEMORY_DB_MIGRATIONS).unwrap();
+        assert_eq!(
+            (plan.from_version, plan.to_version, plan.applied),
+            (0, latest(), false)
+        );
+        assert!(!std::path::Path::new(path).exists());
+
+        let mut conn = Connection::open(path).unwrap();
+        migrate_sqlite(&mut conn, MEMORY_DB, &MEMORY_DB_MIGRATIONS[..1]).unwrap();
+        let plan = plan_sqlite(path, MEMORY_DB, MEMORY_DB_MIGRATIONS).unwrap();
+        assert_eq!(plan.from_version, 1);
+        let rest: Vec<&str> = MEMORY_DB_MIGRATIONS[1..].iter().map(|m| m.name).collect();
+        assert_eq!(plan.migrations, rest);
+        assert_eq!(sqlite_version(&conn).unwrap(), 1);
+    }
+}
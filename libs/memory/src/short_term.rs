@@ -2,26 +2,38 @@ use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
 use kernel::get_agent_profile;
+use kernel::event::Platform;
 use serde::Serialize;
 use tracing::{debug, info};
 
-use crate::types::{ConversationKey, MemoryMessage};
+use crate::ambient::{AmbientBuffer, AmbientConfig};
+use crate::types::{ConversationKey, MemoryMessage, SessionRecord, SessionSummary};
 
 struct Session {
+    id: String,
     messages: Vec<MemoryMessage>,
     last_active: DateTime<Utc>,
     started_at: DateTime<Utc>,
     already_ingested: bool,
+    summary: Option<SessionSummary>,
+    summary_pending: bool,
+    /// Bumped whenever the summary is dropped, so that a refresh started
+    /// before that is not applied.
+    summary_generation: u64,
 }
 
 impl Session {
     fn new() -> Self {
         let now = Utc::now();
         Self {
+            id: uuid::Uuid::new_v4().to_string(),
             messages: Vec::new(),
             last_active: now,
             started_at: now,
             already_ingested: false,
+            summary: None,
+            summary_pending: false,
+            summary_generation: 0,
         }
     }
 
@@ -33,12 +45,58 @@ impl Session {
         let elapsed = now - self.last_active;
         elapsed.num_seconds() > adaptive_secs
     }
+
+    /// Drop the summary when a message sent at `at` was removed and the
+    /// summary, or a refresh of it, may contain that message.
+    fn forget_summary_covering(&mut self, at: DateTime<Utc>) {
+        let covered = self.summary.as_ref().is_some_and(|s| at <= s.until);
+        if covered || self.summary_pending {
+            self.summary = None;
+            self.summary_pending = false;
+            self.summary_generation += 1;
+        }
+    }
+
+    fn ended(self, key: ConversationKey) -> EndedSession {
+        EndedSession {
+            id: self.id,
+            key,
+            messages: self.messages,
+            summary: self.summary,
+        }
+    }
+}
+
+/// A session that expired or was ended and still has to be ingested into
+/// episodic memory.
+#[derive(Debug)]
+pub struct EndedSession {
+    pub id: String,
+    pub key: ConversationKey,
+    pub messages: Vec<MemoryMessage>,
+    /// Covers the messages up to its `until`; those after it are not in it.
+    pub summary: Option<SessionSummary>,
+}
+
+/// Messages that fell out of a session's prompt window and have to be
+/// folded into its rolling summary.
+#[derive(Debug)]
+pub struct SummaryRequest {
+    pub key: ConversationKey,
+    pub session_id: String,
+    pub previous: Option<String>,
+    pub messages: Vec<MemoryMessage>,
+    generation: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct ShortTermConfig {
     pub base_timeout_secs: i64,
     pub max_prompt_messages: usize,
+    /// How many messages older than the latest `max_prompt_messages` have
+    /// to pile up before the session's summary is refreshed. 0 disables
+    /// summaries.
+    pub summary_batch: usize,
 }
 
 impl Default for ShortTermConfig {
@@ -46,6 +104,7 @@ impl Default for ShortTermConfig {
         Self {
             base_timeout_secs: 20 * 60,
             max_prompt_messages: 20,
+            summary_batch: 10,
         }
     }
 }
@@ -53,6 +112,7 @@ impl Default for ShortTermConfig {
 pub struct ShortTermMemory {
     sessions: HashMap<ConversationKey, Session>,
     config: ShortTermConfig,
+    ambient: AmbientBuffer,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -60,6 +120,7 @@ pub struct ActiveSessionSnapshot {
     pub conversation: String,
     pub platform: String,
     pub channel_id: String,
+    pub thread_id: Option<String>,
     pub message_count: usize,
     pub started_at: DateTime<Utc>,
     pub last_active: DateTime<Utc>,
@@ -71,6 +132,7 @@ impl ShortTermMemory {
         Self {
             sessions: HashMap::new(),
             config: ShortTermConfig::default(),
+            ambient: AmbientBuffer::default(),
         }
     }
 
@@ -78,11 +140,27 @@ impl ShortTermMemory {
         Self {
             sessions: HashMap::new(),
             config,
+            ambient: AmbientBuffer::default(),
         }
     }
 
-    pub fn push(&mut self, msg: MemoryMessage) -> Option<Vec<MemoryMessage>> {
-        let key = ConversationKey::new(msg.platform, msg.channel_id.clone());
+    /// Start keeping untagged messages of the channels `config` opts in.
+    pub fn set_ambient(&mut self, config: AmbientConfig) {
+        self.ambient = AmbientBuffer::new(config);
+    }
+
+    /// Keep a message that does not mention the agent, if its channel
+    /// opted in. Returns whether it was kept.
+    pub fn push_ambient(&mut self, msg: MemoryMessage) -> bool {
+        self.ambient.push(msg)
+    }
+
+    pub fn load_ambient(&mut self, messages: Vec<MemoryMessage>) {
+        self.ambient.load(messages);
+    }
+
+    pub fn push(&mut self, msg: MemoryMessage) -> Option<EndedSession> {
+        let key = ConversationKey::from_message(&msg);
         let now = msg.timestamp;
         let mut expired_messages = None;
 
@@ -101,21 +179,85 @@ impl ShortTermMemory {
                         messages = old.messages.len(),
                         "Session expired, starting new session"
                     );
-                    expired_messages = Some(old.messages);
+                    expired_messages = Some(old.ended(key.clone()));
                 }
             }
         }
 
         let session = self.sessions.entry(key).or_insert_with(Session::new);
+        if session.messages.is_empty() {
+            session.started_at = now;
+        }
         session.last_active = now;
         session.messages.push(msg);
 
         expired_messages
     }
 
+    /// Replace the content of a message that was edited on the platform.
+    /// Returns `false` when the message is not in an active session or the
+    /// ambient buffer.
+    pub fn edit_message(&mut self, key: &ConversationKey, message_id: &str, content: &str) -> bool {
+        if self.ambient.edit_message(key, message_id, content) {
+            return true;
+        }
+        let Some(msg) = self
+            .sessions
+            .get_mut(key)
+            .and_then(|s| s.messages.iter_mut().find(|m| m.id == message_id))
+        else {
+            return false;
+        };
+        msg.content = content.to_string();
+        true
+    }
+
+    /// Drop a message that was deleted on the platform so it never reaches
+    /// a prompt or the episodic store.
+    pub fn remove_message(&mut self, key: &ConversationKey, message_id: &str) -> bool {
+        if self.ambient.remove_message(key, message_id) {
+            return true;
+        }
+        let Some(session) = self.sessions.get_mut(key) else {
+            return false;
+        };
+        let Some(index) = session.messages.iter().position(|m| m.id == message_id) else {
+            return false;
+        };
+        let removed = session.messages.remove(index);
+        session.forget_summary_covering(removed.timestamp);
+        true
+    }
+
+    /// Drop everything `user_id` said, in every conversation. Returns how
+    /// many messages were removed.
+    pub fn remove_user(&mut self, platform: Platform, user_id: &str) -> usize {
+        let mut removed = self.ambient.remove_user(platform, user_id);
+        let written_by = |m: &MemoryMessage| {
+            !m.is_bot_response && m.platform == platform && m.user_id == user_id
+        };
+        for session in self.sessions.values_mut() {
+            let Some(earliest) = session
+                .messages
+                .iter()
+                .filter(|m| written_by(m))
+                .map(|m| m.timestamp)
+                .min()
+            else {
+                continue;
+            };
+            let before = session.messages.len();
+            session.messages.retain(|m| !written_by(m));
+            removed += before - session.messages.len();
+            session.forget_summary_covering(earliest);
+        }
+        self.sessions.retain(|_, session| !session.messages.is_empty());
+        removed
+    }
+
     pub fn load_history(&mut self, messages: Vec<MemoryMessage>) {
         for msg in messages {
-            let key = ConversationKey::new(msg.platform, msg.channel_id.clone());
+            let key = ConversationKey::from_message(&msg);
             let session = self.sessions.entry(key).or_insert_with(Session::new);
 
             if session.messages.is_empty() || msg.timestamp > session.last_active {
@@ -139,6 +281,103 @@ impl ShortTermMemory {
         }
     }
 
+    /// Bring back a session that was live at shutdown, replacing whatever
+    /// history was loaded for its conversation. Returns it as ended
+    /// instead when it expired while the agent was down.
+    pub fn resume_session(
+        &mut self,
+        record: SessionRecord,
+        messages: Vec<MemoryMessage>,
+    ) -> Option<EndedSession> {
+        let session = Session {
+            id: record.id,
+            messages,
+            last_active: record.last_active,
+            started_at: record.started_at,
+            already_ingested: false,
+            summary: record.summary,
+            summary_pending: false,
+            summary_generation: 0,
+        };
+        if session.is_expired(Utc::now(), self.config.base_timeout_secs) {
+            return Some(session.ended(record.key));
+        }
+        self.sessions.insert(record.key, session);
+        None
+    }
+
+    /// Where the session of `key` stands, for the `sessions` table. `None`
+    /// for history loaded at boot, which is never ingested.
+    pub fn session_record(&self, key: &ConversationKey) -> Option<SessionRecord> {
+        let session = self.sessions.get(key).filter(|s| !s.already_ingested)?;
+        Some(SessionRecord {
+            id: session.id.clone(),
+            key: key.clone(),
+            started_at: session.started_at,
+            last_active: session.last_active,
+            ended_at: None,
+            ingested_at: None,
+            summary: session.summary.clone(),
+        })
+    }
+
+    /// The messages of `key` that fell out of the prompt window since its
+    /// summary was last refreshed, once there are `summary_batch` of them.
+    /// The session counts as refreshing until [`Self::finish_summary`].
+    pub fn summary_due(&mut self, key: &ConversationKey) -> Option<SummaryRequest> {
+        let batch = self.config.summary_batch;
+        let window = self.config.max_prompt_messages;
+        let session = self.sessions.get_mut(key)?;
+        if batch == 0 || session.summary_pending || session.messages.len() <= window {
+            return None;
+        }
+        let until = session.summary.as_ref().map(|s| s.until);
+        let outside = session.messages.len() - window;
+        let messages: Vec<MemoryMessage> = session.messages[..outside]
+            .iter()
+            .filter(|m| until.is_none_or(|until| m.timestamp > until))
+            .cloned()
+            .collect();
+        if messages.len() < batch {
+            return None;
+        }
+        session.summary_pending = true;
+        Some(SummaryRequest {
+            key: key.clone(),
+            session_id: session.id.clone(),
+            previous: session.summary.as_ref().map(|s| s.text.clone()),
+            messages,
+            generation: session.summary_generation,
+        })
+    }
+
+    /// Apply the outcome of a summary refresh; `None` when it failed.
+    /// Returns the new summary unless the session ended or had messages
+    /// removed in the meantime.
+    pub fn finish_summary(
+        &mut self,
+        request: &SummaryRequest,
+        text: Option<String>,
+    ) -> Option<SessionSummary> {
+        let session = self
+            .sessions
+            .get_mut(&request.key)
+            .filter(|s| s.id == request.session_id && s.summary_generation == request.generation)?;
+        session.summary_pending = false;
+        let summary = SessionSummary {
+            text: text?,
+            until: request.messages.last()?.timestamp,
+        };
+        session.summary = Some(summary.clone());
+        Some(summary)
+    }
+
+    /// The rolling summary of the session of `key`, if it has one.
+    pub fn get_summary_for_prompt(&self, key: &ConversationKey) -> Option<String> {
+        let summary = self.sessions.get(key)?.summary.as_ref()?;
+        Some(summary.text.clone())
+    }
+
     pub fn get_context_for_prompt(&self, key: &ConversationKey) -> Vec<&MemoryMessage> {
         let session = match self.sessions.get(key) {
             Some(s) => s,
@@ -197,6 +436,9 @@ impl ShortTermMemory {
         Some(formatted.join("\n"))
     }
 
+    /// The best-scoring messages of `key` for the prompt, oldest first.
+    /// Messages the rolling summary covers are left to it, so the two never
+    /// overlap.
     pub fn get_history_for_prompt(
         &self,
         key: &ConversationKey,
@@ -213,12 +455,14 @@ impl ShortTermMemory {
 
         let total = session.messages.len();
         let now = Utc::now();
+        let until = session.summary.as_ref().map(|s| s.until);
 
         let mut scored: Vec<(usize, f32)> = session
             .messages
             .iter()
             .enumerate()
             .filter(|(_, msg)| msg.id != exclude_id)
+            .filter(|(_, msg)| until.is_none_or(|until| msg.timestamp > until))
             .map(|(i, msg)| {
                 let score = Self::prompt_score(msg, i, total, now);
                 (i, score)
@@ -253,7 +497,17 @@ impl ShortTermMemory {
             .collect()
     }
 
-    pub fn flush_expired(&mut self) -> Vec<(ConversationKey, Vec<MemoryMessage>)> {
+    /// Recent untagged messages of `key` as `(username, content)`, oldest
+    /// first. Empty unless the channel opted in.
+    pub fn get_ambient_for_prompt(&self, key: &ConversationKey) -> Vec<(String, String)> {
+        self.ambient
+            .recent(key, Utc::now())
+            .into_iter()
+            .map(|msg| (msg.username.clone(), strip_mention_tags(&msg.content)))
+            .collect()
+    }
+
+    pub fn flush_expired(&mut self) -> Vec<EndedSession> {
         let now = Utc::now();
         let timeout = self.config.base_timeout_secs;
 
@@ -280,13 +534,29 @@ impl ShortTermMemory {
                     messages = session.messages.len(),
                     "Flushing expired session"
                 );
-                result.push((key, session.messages));
+                result.push(session.ended(key));
             }
         }
 
         result
     }
 
+    /// Close the session for `key` as if it had expired. Returns its
+    /// messages when they still have to be ingested into episodic memory.
+    pub fn end_session(&mut self, key: &ConversationKey) -> Option<EndedSession> {
+        let session = self.sessions.remove(key)?;
+        info!(
+            conversation = %key,
+            messages = session.messages.len(),
+            "Session ended on request"
+        );
+        (!session.already_ingested).then(|| session.ended(key.clone()))
+    }
+
+    pub fn session_message_count(&self, key: &ConversationKey) -> usize {
+        self.sessions.get(key).map_or(0, |s| s.messages.len())
+    }
+
     pub fn active_session_count(&self) -> usize {
         self.sessions.len()
     }
@@ -313,6 +583,7 @@ impl ShortTermMemory {
                     conversation: key.to_string(),
                     platform: key.platform.to_string(),
                     channel_id: key.channel_id.clone(),
+                    thread_id: key.thread_id.clone(),
                     message_count: session.messages.len(),
                     started_at: session.started_at,
                     last_active: session.last_active,
@@ -389,22 +660,15 @@ fn strip_mention_tags(content: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing;
     use kernel::event::Platform;
 
     fn make_msg(channel: &str, user: &str, content: &str, is_mention: bool) -> MemoryMessage {
-        MemoryMessage {
-            id: uuid::Uuid::new_v4().to_string(),
-            platform: Platform::Discord,
-            channel_id: channel.to_string(),
-            user_id: user.to_string(),
-            username: user.to_string(),
-            content: content.to_string(),
-            is_mention,
-            reply_to_user: None,
-            is_bot_response: false,
-            timestamp: Utc::now(),
-            importance: if is_mention { 0.7 } else { 0.3 },
-        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut msg = testing::message(&id, channel, user, user, content);
+        msg.is_mention = is_mention;
+        msg.importance = if is_mention { 0.7 } else { 0.3 };
+        msg
     }
 
     #[test]
@@ -435,6 +699,15 @@ mod tests {
         mem.push(make_msg("ch2", "Bob", "hey", false));
 
         assert_eq!(mem.active_session_count(), 2);
+
+        let mut in_topic = make_msg("ch1", "Carol", "over here", false);
+        in_topic.thread_id = Some("42".to_string());
+        mem.push(in_topic);
+
+        assert_eq!(mem.active_session_count(), 3);
+        let topic = ConversationKey::new(Platform::Discord, "ch1".to_string())
+            .with_thread(Some("42".to_string()));
+        assert_eq!(mem.get_context_for_prompt(&topic).len(), 1);
     }
 
     #[test]
@@ -470,4 +743,180 @@ mod tests {
         let context = mem.get_context_for_prompt(&key);
         assert_eq!(context.len(), 3);
     }
+
+    #[test]
+    fn test_edit_and_remove_message() {
+        let mut mem = ShortTermMemory::new();
+        let msg = make_msg("ch1", "Alice", "helo", true);
+        let id = msg.id.clone();
+        mem.push(msg);
+        mem.push(make_msg("ch1", "Bob", "hi", false));
+
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        assert!(mem.edit_message(&key, &id, "hello"));
+        assert_eq!(mem.get_context_for_prompt(&key)[0].content, "hello");
+
+        assert!(mem.remove_message(&key, &id));
+        assert!(!mem.remove_message(&key, &id));
+        assert_eq!(mem.total_messages(), 1);
+    }
+
+    #[test]
+    fn test_end_session() {
+        let mut mem = ShortTermMemory::new();
+        mem.push(make_msg("ch1", "Alice", "hello", true));
+        mem.push(make_msg("ch2", "Bob", "hi", true));
+
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        assert_eq!(
+            mem.end_session(&key).map(|ended| ended.messages.len()),
+            Some(1)
+        );
+        assert!(mem.end_session(&key).is_none());
+        assert!(mem.get_context_for_prompt(&key).is_empty());
+        assert_eq!(mem.active_session_count(), 1);
+    }
+
+    #[test]
+    fn test_remove_user() {
+        let mut mem = ShortTermMemory::new();
+        mem.push(make_msg("ch1", "Alice", "hello", true));
+        mem.push(make_msg("ch1", "Bob", "hi", true));
+        mem.push(make_msg("ch2", "Alice", "again", true));
+
+        assert_eq!(mem.remove_user(Platform::Discord, "Alice"), 2);
+        assert_eq!(mem.remove_user(Platform::Telegram, "Bob"), 0);
+        assert_eq!(mem.active_session_count(), 1);
+        assert_eq!(mem.total_messages(), 1);
+    }
+
+    #[test]
+    fn test_resume_session() {
+        let mut mem = ShortTermMemory::new();
+        mem.push(make_msg("ch1", "Alice", "hello", true));
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        let record = mem.session_record(&key).unwrap();
+
+        let mut resumed = ShortTermMemory::new();
+        resumed.load_history(vec![make_msg("ch1", "Alice", "old", true)]);
+        resumed.mark_all_persisted();
+        assert!(resumed.session_record(&key).is_none());
+        let messages = vec![make_msg("ch1", "Alice", "hello", true)];
+        assert!(resumed.resume_session(record.clone(), messages).is_none());
+        assert_eq!(resumed.session_record(&key).unwrap().id, record.id);
+        assert_eq!(
+            resumed.end_session(&key).unwrap().messages[0].content,
+            "hello"
+        );
+
+        let stale = SessionRecord {
+            last_active: Utc::now() - chrono::Duration::hours(3),
+            ..record
+        };
+        let ended = resumed.resume_session(stale, vec![make_msg("ch1", "Alice", "hello", true)]);
+        assert_eq!(ended.map(|e| e.messages.len()), Some(1));
+        assert_eq!(resumed.active_session_count(), 0);
+    }
+
+    #[test]
+    fn test_rolling_summary() {
+        let config = ShortTermConfig {
+            max_prompt_messages: 3,
+            summary_batch: 2,
+            ..Default::default()
+        };
+        let mut mem = ShortTermMemory::with_config(config);
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        let start = Utc::now() - chrono::Duration::minutes(5);
+        let push = |mem: &mut ShortTermMemory, i: i64, user: &str| {
+            let mut msg = make_msg("ch1", user, &format!("msg {}", i), true);
+            msg.timestamp = start + chrono::Duration::seconds(i);
+            mem.push(msg);
+        };
+        for i in 0..4 {
+            push(&mut mem, i, "Alice");
+        }
+        assert!(mem.summary_due(&key).is_none());
+
+        push(&mut mem, 4, "Bob");
+        let request = mem.summary_due(&key).unwrap();
+        assert_eq!(request.messages.len(), 2);
+        assert!(mem.summary_due(&key).is_none());
+        let summary = mem.finish_summary(&request, Some("they said hi".to_string()));
+        assert_eq!(summary.unwrap().until, start + chrono::Duration::seconds(1));
+        assert_eq!(
+            mem.get_summary_for_prompt(&key).as_deref(),
+            Some("they said hi")
+        );
+        assert!(mem.session_record(&key).unwrap().summary.is_some());
+
+        push(&mut mem, 5, "Bob");
+        push(&mut mem, 6, "Bob");
+        let request = mem.summary_due(&key).unwrap();
+        assert_eq!(request.previous.as_deref(), Some("they said hi"));
+        assert_eq!(request.messages[0].content, "msg 2");
+
+        // Alice's words are in the summary and in the refresh under way.
+        mem.remove_user(Platform::Discord, "Alice");
+        assert!(mem.get_summary_for_prompt(&key).is_none());
+        assert!(mem
+            .finish_summary(&request, Some("stale".to_string()))
+            .is_none());
+        assert!(mem.get_summary_for_prompt(&key).is_none());
+        assert!(mem.end_session(&key).unwrap().summary.is_none());
+    }
+
+    #[test]
+    fn test_history_leaves_summarised_messages_out() {
+        let config = ShortTermConfig {
+            max_prompt_messages: 3,
+            summary_batch: 2,
+            ..Default::default()
+        };
+        let mut mem = ShortTermMemory::with_config(config);
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        let start = Utc::now() - chrono::Duration::minutes(5);
+        for i in 0..5 {
+            // The first message is a mention and outscores the middle ones.
+            let mut msg = make_msg("ch1", "Alice", &format!("msg {}", i), i == 0);
+            msg.timestamp = start + chrono::Duration::seconds(i);
+            mem.push(msg);
+        }
+        let contents = |mem: &ShortTermMemory| -> Vec<String> {
+            mem.get_history_for_prompt(&key, "")
+                .into_iter()
+                .map(|(_, _, content)| content)
+                .collect()
+        };
+        assert_eq!(contents(&mem), vec!["msg 0", "msg 3", "msg 4"]);
+
+        let request = mem.summary_due(&key).unwrap();
+        assert_eq!(request.messages.len(), 2);
+        mem.finish_summary(&request, Some("alice said hi".to_string()));
+        assert_eq!(contents(&mem), vec!["msg 2", "msg 3", "msg 4"]);
+    }
+
+    #[test]
+    fn test_ambient_stays_out_of_sessions() {
+        let mut mem = ShortTermMemory::new();
+        let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
+        assert!(!mem.push_ambient(make_msg("ch1", "Alice", "anyone up?", false)));
+
+        mem.set_ambient(AmbientConfig {
+            channels: [(Platform::Discord, "ch1".to_string())]
+                .into_iter()
+                .collect(),
+            ..AmbientConfig::default()
+        });
+        assert!(mem.push_ambient(make_msg("ch1", "Alice", "anyone up?", false)));
+        mem.push(make_msg("ch1", "Bob", "hey agent", true));
+
+        assert_eq!(
+            mem.get_ambient_for_prompt(&key),
+            vec![("Alice".to_string(), "anyone up?".to_string())]
+        );
+        assert_eq!(mem.total_messages(), 1);
+        assert_eq!(mem.remove_user(Platform::Discord, "Alice"), 1);
+        assert!(mem.get_ambient_for_prompt(&key).is_empty());
+    }
 }
@@ -1,10 +1,112 @@
 use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use kernel::event::Platform;
 use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 
-use crate::types::MemoryMessage;
+use crate::forget::ForgetReceipt;
+use crate::migrations::{
+    check_sqlite, migrate_sqlite, plan_sqlite, MigrationReport, MEMORY_DB, MEMORY_DB_MIGRATIONS,
+};
+use crate::types::{ConversationKey, MemoryMessage, SessionRecord, SessionSummary};
+
+const SEARCH_LIMIT_DEFAULT: usize = 20;
+const SEARCH_LIMIT_MAX: usize = 100;
+
+/// Filters for [`MemoryStore::search`]. Only `query` is required.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MessageSearch {
+    pub query: String,
+    /// A platform user id or a username.
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// `true` for the agent's own replies only, `false` for everyone else's.
+    #[serde(default)]
+    pub from_bot: Option<bool>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageHit {
+    pub message: MemoryMessage,
+    /// The matching part of the content, with matched terms in `**`.
+    pub snippet: String,
+}
+
+/// The messages that belong in one person's archive: what their accounts
+/// wrote, and the agent's replies to them in conversations they took part
+/// in. Without accounts, messages are matched by username instead.
+#[derive(Debug, Clone, Default)]
+pub struct PersonMessages {
+    pub accounts: Vec<(Platform, String)>,
+    pub usernames: Vec<String>,
+}
+
+impl PersonMessages {
+    /// A condition on `messages m`, with its parameters in order.
+    fn filter(&self) -> (String, Vec<String>) {
+        let mut values = Vec::new();
+        let own = self.authored("m", &mut values);
+        if self.usernames.is_empty() {
+            return (own, values);
+        }
+        let names = vec!["?"; self.usernames.len()].join(", ");
+        values.extend(self.usernames.iter().cloned());
+        // A reply to a name another person shares only counts where this
+        // person was part of the conversation.
+        let took_part = self.authored("own", &mut values);
+        let replies = format!(
+            "m.is_bot_response = 1
+             AND m.reply_to_user COLLATE NOCASE IN ({names})
+             AND EXISTS (
+                 SELECT 1 FROM messages own
+                 WHERE own.platform = m.platform
+                   AND own.channel_id = m.channel_id
+                   AND own.thread_id IS m.thread_id
+                   AND {took_part}
+             )"
+        );
+        (format!("({own}) OR ({replies})"), values)
+    }
+
+    /// Messages in `table` the person wrote.
+    fn authored(&self, table: &str, values: &mut Vec<String>) -> String {
+        let clauses: Vec<String> = if self.accounts.is_empty() {
+            self.usernames
+                .iter()
+                .map(|username| {
+                    values.push(username.clone());
+                    format!("{table}.username = ? COLLATE NOCASE")
+                })
+                .collect()
+        } else {
+            self.accounts
+                .iter()
+                .map(|(platform, user_id)| {
+                    values.push(platform.to_string());
+                    values.push(user_id.clone());
+                    format!("({table}.platform = ? AND {table}.user_id = ?)")
+                })
+                .collect()
+        };
+        if clauses.is_empty() {
+            return "0".to_string();
+        }
+        format!("{table}.is_bot_response = 0 AND ({})", clauses.join(" OR "))
+    }
+}
 
 pub struct MemoryStore {
     conn: Connection,
@@ -12,27 +114,25 @@ pub struct MemoryStore {
 
 impl MemoryStore {
     pub fn open(path: &str) -> Result<Self> {
-        let conn = Connection::open(path)
+        let mut conn = Connection::open(path)
             .with_context(|| format!("Failed to open memory database: {}", path))?;
         configure_writer_connection(&conn)?;
-
-        let store = Self { conn };
-        store.init_tables()?;
+        migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS)?.log();
 
         info!(path = %path, "Memory store opened");
-        Ok(store)
+        Ok(Self { conn })
     }
 
     pub fn open_in_memory() -> Result<Self> {
-        let conn = Connection::open_in_memory()
+        let mut conn = Connection::open_in_memory()
             .context("Failed to open in-memory database")?;
         configure_reader_connection(&conn)?;
-
-        let store = Self { conn };
-        store.init_tables()?;
-        Ok(store)
+        migrate_sqlite(&mut conn, MEMORY_DB, MEMORY_DB_MIGRATIONS)?;
+        Ok(Self { conn })
     }
 
+    /// Readers never migrate; they only refuse a database written by a
+    /// newer build.
     pub fn open_read_only(path: &str) -> Result<Self> {
         let conn = Connection::open_with_flags(
             path,
@@ -40,46 +140,23 @@ impl MemoryStore {
         )
         .with_context(|| format!("Failed to open memory database read-only: {}", path))?;
         configure_reader_connection(&conn)?;
+        check_sqlite(&conn, MEMORY_DB, MEMORY_DB_MIGRATIONS)?;
 
         Ok(Self { conn })
     }
 
-    fn init_tables(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS messages (
-                id TEXT PRIMARY KEY,
-                platform TEXT NOT NULL,
-                channel_id TEXT NOT NULL,
-                user_id TEXT NOT NULL,
-                username TEXT NOT NULL,
-                content TEXT NOT NULL,
-                is_mention BOOLEAN DEFAULT 0,
-                is_bot_response BOOLEAN DEFAULT 0,
-                reply_to_user TEXT,
-                importance REAL DEFAULT 0.5,
-                created_at TEXT NOT NULL,
-                accessed_at TEXT,
-                access_count INTEGER DEFAULT 0
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_messages_channel
-                ON messages(platform, channel_id);
-            CREATE INDEX IF NOT EXISTS idx_messages_time
-                ON messages(created_at DESC);
-            CREATE INDEX IF NOT EXISTS idx_messages_importance
-                ON messages(importance DESC);
-            ",
-        )?;
-        Ok(())
+    /// What opening the database at `path` would migrate, without changing it.
+    pub fn migration_plan(path: &str) -> Result<MigrationReport> {
+        plan_sqlite(path, MEMORY_DB, MEMORY_DB_MIGRATIONS)
     }
 
     pub fn insert(&self, msg: &MemoryMessage) -> Result<()> {
         self.conn.execute(
             "INSERT OR IGNORE INTO messages
                 (id, platform, channel_id, user_id, username, content,
-                 is_mention, is_bot_response, reply_to_user, importance, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                 is_mention, is_bot_response, reply_to_user, importance, created_at,
+                 thread_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
             params![
                 msg.id,
                 format!("{}", msg.platform),
@@ -92,20 +169,24 @@ impl MemoryStore {
                 msg.reply_to_user,
                 msg.importance,
                 msg.timestamp.to_rfc3339(),
+                msg.thread_id,
             ],
         )?;
         debug!(id = %msg.id, "Message persisted to store");
         Ok(())
     }
 
-    pub fn insert_batch(&self, messages: &[MemoryMessage]) -> Result<()> {
+    /// Insert messages not stored yet. Returns how many were new.
+    pub fn insert_batch(&self, messages: &[MemoryMessage]) -> Result<usize> {
         let tx = self.conn.unchecked_transaction()?;
+        let mut inserted = 0;
         for msg in messages {
-            tx.execute(
+            inserted += tx.execute(
                 "INSERT OR IGNORE INTO messages
                     (id, platform, channel_id, user_id, username, content,
-                     is_mention, is_bot_response, reply_to_user, importance, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                     is_mention, is_bot_response, reply_to_user, importance, created_at,
+                     thread_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
                 params![
                     msg.id,
                     format!("{}", msg.platform),
@@ -118,106 +199,526 @@ impl MemoryStore {
                     msg.reply_to_user,
                     msg.importance,
                     msg.timestamp.to_rfc3339(),
+                    msg.thread_id,
                 ],
             )?;
         }
         tx.commit()?;
         debug!(count = messages.len(), "Batch persisted to store");
+        Ok(inserted)
+    }
+
+    /// Replace the content of a message the user edited. Returns `false`
+    /// when the message was never stored or has been deleted.
+    pub fn update_content(
+        &self,
+        platform: &str,
+        channel_id: &str,
+        id: &str,
+        content: &str,
+        edited_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE messages SET content = ?4, edited_at = ?5
+             WHERE platform = ?1 AND channel_id = ?2 AND id = ?3 AND deleted_at IS NULL",
+            params![platform, channel_id, id, content, edited_at.to_rfc3339()],
+        )?;
+        let changed = changed
+            + self.conn.execute(
+                "UPDATE ambient_messages SET content = ?4
+                 WHERE platform = ?1 AND channel_id = ?2 AND id = ?3",
+                params![platform, channel_id, id, content],
+            )?;
+        debug!(id = %id, changed, "Message edit applied to store");
+        Ok(changed > 0)
+    }
+
+    /// Blank a deleted message and keep the row as a tombstone, so a later
+    /// re-ingest of the same id is still ignored.
+    pub fn tombstone(
+        &self,
+        platform: &str,
+        channel_id: &str,
+        id: &str,
+        deleted_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool> {
+        self.drop_summaries_covering(
+            "m.platform = ?1 AND m.channel_id = ?2 AND m.id = ?3",
+            params![platform, channel_id, id],
+        )?;
+        let changed = self.conn.execute(
+            "UPDATE messages SET content = '', deleted_at = ?4
+             WHERE platform = ?1 AND channel_id = ?2 AND id = ?3 AND deleted_at IS NULL",
+            params![platform, channel_id, id, deleted_at.to_rfc3339()],
+        )?;
+        // Ambient messages leave no tombstone; they are never re-ingested.
+        let changed = changed
+            + self.conn.execute(
+                "DELETE FROM ambient_messages WHERE platform = ?1 AND channel_id = ?2 AND id = ?3",
+                params![platform, channel_id, id],
+            )?;
+        debug!(id = %id, changed, "Message tombstoned in store");
+        Ok(changed > 0)
+    }
+
+    /// Tombstone every message `user_id` sent on `platform`. Returns how
+    /// many rows changed.
+    pub fn tombstone_user(
+        &self,
+        platform: &str,
+        user_id: &str,
+        deleted_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        self.drop_user_summaries(platform, user_id)?;
+        let changed = self.conn.execute(
+            "UPDATE messages SET content = '', deleted_at = ?3
+             WHERE platform = ?1 AND user_id = ?2 AND is_bot_response = 0 AND deleted_at IS NULL",
+            params![platform, user_id, deleted_at.to_rfc3339()],
+        )?;
+        let changed = changed + self.delete_ambient_user(platform, user_id)?;
+        debug!(user = %user_id, changed, "User's messages tombstoned in store");
+        Ok(changed)
+    }
+
+    /// Erase what `user_id` wrote on `platform` for good: content, username
+    /// and user id are blanked. The rows stay as tombstones, so a message
+    /// delivered again is still ignored. Returns how many rows changed.
+    pub fn erase_user(
+        &self,
+        platform: &str,
+        user_id: &str,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        self.drop_user_summaries(platform, user_id)?;
+        let changed = self.conn.execute(
+            "UPDATE messages
+             SET content = '', username = '', user_id = '', reply_to_user = NULL,
+                 deleted_at = COALESCE(deleted_at, ?3)
+             WHERE platform = ?1 AND user_id = ?2 AND is_bot_response = 0",
+            params![platform, user_id, at.to_rfc3339()],
+        )?;
+        let changed = changed + self.delete_ambient_user(platform, user_id)?;
+        debug!(changed, "User's messages erased from store");
+        Ok(changed)
+    }
+
+    /// [`MemoryStore::erase_user`] for someone known only by username.
+    pub fn erase_username(&self, username: &str, at: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        self.drop_summaries_covering(
+            "m.username = ?1 COLLATE NOCASE AND m.username != '' AND m.is_bot_response = 0",
+            params![username],
+        )?;
+        let changed = self.conn.execute(
+            "UPDATE messages
+             SET content = '', username = '', user_id = '', reply_to_user = NULL,
+                 deleted_at = COALESCE(deleted_at, ?2)
+             WHERE username = ?1 COLLATE NOCASE AND username != '' AND is_bot_response = 0",
+            params![username, at.to_rfc3339()],
+        )?;
+        let changed = changed
+            + self.conn.execute(
+                "DELETE FROM ambient_messages WHERE username = ?1 COLLATE NOCASE",
+                params![username],
+            )?;
+        debug!(changed, "Username's messages erased from store");
+        Ok(changed)
+    }
+
+    fn drop_user_summaries(&self, platform: &str, user_id: &str) -> Result<usize> {
+        self.drop_summaries_covering(
+            "m.platform = ?1 AND m.user_id = ?2 AND m.is_bot_response = 0",
+            params![platform, user_id],
+        )
+    }
+
+    /// Drop the summaries of sessions that cover a message matched by
+    /// `filter`, so that what is forgotten does not live on in them.
+    fn drop_summaries_covering(
+        &self,
+        filter: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<usize> {
+        let dropped = self.conn.execute(
+            &format!(
+                "UPDATE sessions SET summary = NULL, summary_until = NULL
+                 WHERE summary IS NOT NULL AND EXISTS (
+                     SELECT 1 FROM messages m
+                     WHERE m.platform = sessions.platform
+                           AND m.channel_id = sessions.channel_id
+                           AND m.thread_id IS sessions.thread_id
+                           AND m.created_at >= sessions.started_at
+                           AND m.created_at <= sessions.summary_until
+                           AND {filter}
+                 )"
+            ),
+            params,
+        )?;
+        if dropped > 0 {
+            debug!(dropped, "Session summaries dropped from store");
+        }
+        Ok(dropped)
+    }
+
+    fn delete_ambient_user(&self, platform: &str, user_id: &str) -> Result<usize> {
+        Ok(self.conn.execute(
+            "DELETE FROM ambient_messages WHERE platform = ?1 AND user_id = ?2",
+            params![platform, user_id],
+        )?)
+    }
+
+    /// Keep an untagged message for its channel's ambient buffer, dropping
+    /// all but the latest `keep` of that conversation.
+    pub fn insert_ambient(&self, msg: &MemoryMessage, keep: usize) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let platform = msg.platform.to_string();
+        tx.execute(
+            "INSERT OR IGNORE INTO ambient_messages
+                (id, platform, channel_id, thread_id, user_id, username, content,
+                 importance, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                msg.id,
+                platform,
+                msg.channel_id,
+                msg.thread_id,
+                msg.user_id,
+                msg.username,
+                msg.content,
+                msg.importance,
+                msg.timestamp.to_rfc3339(),
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM ambient_messages
+             WHERE platform = ?1 AND channel_id = ?2 AND thread_id IS ?3
+                   AND id NOT IN (
+                       SELECT id FROM ambient_messages
+                       WHERE platform = ?1 AND channel_id = ?2 AND thread_id IS ?3
+                       ORDER BY created_at DESC
+                       LIMIT ?4
+                   )",
+            params![platform, msg.channel_id, msg.thread_id, keep as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Ambient messages written since `since`, oldest first.
+    pub fn recent_ambient(&self, since: DateTime<Utc>) -> Result<Vec<MemoryMessage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, platform, channel_id, user_id, username, content,
+                    0, 0, importance, created_at, thread_id
+             FROM ambient_messages
+             WHERE created_at >= ?1
+             ORDER BY created_at ASC",
+        )?;
+        let rows = stmt.query_map(params![since.to_rfc3339()], message_from_row)?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Record a live session, or move its `last_active` on.
+    pub fn upsert_session(&self, record: &SessionRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sessions (id, platform, channel_id, thread_id, started_at, last_active,
+                                   summary, summary_until)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(id) DO UPDATE SET last_active = excluded.last_active",
+            params![
+                record.id,
+                record.key.platform.to_string(),
+                record.key.channel_id,
+                record.key.thread_id,
+                record.started_at.to_rfc3339(),
+                record.last_active.to_rfc3339(),
+                record.summary.as_ref().map(|s| s.text.as_str()),
+                record.summary.as_ref().map(|s| s.until.to_rfc3339()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the rolling summary of a session.
+    pub fn update_session_summary(&self, id: &str, summary: &SessionSummary) -> Result<()> {
+        self.conn.execute(
+            "UPDATE sessions SET summary = ?2, summary_until = ?3 WHERE id = ?1",
+            params![id, summary.text, summary.until.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_session_ended(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE sessions SET ended_at = COALESCE(ended_at, ?2) WHERE id = ?1",
+            params![id, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_session_ingested(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE sessions SET ingested_at = ?2 WHERE id = ?1",
+            params![id, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Sessions not yet ingested, live or ended, oldest first.
+    pub fn unfinished_sessions(&self) -> Result<Vec<SessionRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, platform, channel_id, thread_id, started_at, last_active, ended_at,
+                    ingested_at, summary, summary_until
+             FROM sessions
+             WHERE ingested_at IS NULL
+             ORDER BY started_at ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let platform = row
+                .get::<_, String>(1)?
+                .parse()
+                .unwrap_or(kernel::event::Platform::Cli);
+            let key = ConversationKey::new(platform, row.get(2)?).with_thread(row.get(3)?);
+            Ok(SessionRecord {
+                id: row.get(0)?,
+                key,
+                started_at: parse_time(&row.get::<_, String>(4)?),
+                last_active: parse_time(&row.get::<_, String>(5)?),
+                ended_at: row.get::<_, Option<String>>(6)?.map(|t| parse_time(&t)),
+                ingested_at: row.get::<_, Option<String>>(7)?.map(|t| parse_time(&t)),
+                summary: match (
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                ) {
+                    (Some(text), Some(until)) => Some(SessionSummary {
+                        text,
+                        until: parse_time(&until),
+                    }),
+                    _ => None,
+                },
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// The messages of a session that are still stored, oldest first.
+    pub fn session_messages(&self, record: &SessionRecord) -> Result<Vec<MemoryMessage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, platform, channel_id, user_id, username, content,
+                    is_mention, is_bot_response, importance, created_at, thread_id
+             FROM messages
+             WHERE platform = ?1 AND channel_id = ?2 AND thread_id IS ?3
+                   AND created_at >= ?4 AND created_at <= ?5
+                   AND deleted_at IS NULL
+             ORDER BY created_at ASC, rowid ASC",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                record.key.platform.to_string(),
+                record.key.channel_id,
+                record.key.thread_id,
+                record.started_at.to_rfc3339(),
+                record.last_active.to_rfc3339(),
+            ],
+            message_from_row,
+        )?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Stop the agent's replies to `username` from naming them. Returns how
+    /// many rows changed.
+    pub fn unlink_replies(&self, username: &str) -> Result<usize> {
+        let changed = self.conn.execute(
+            "UPDATE messages SET reply_to_user = NULL WHERE reply_to_user = ?1 COLLATE NOCASE",
+            params![username],
+        )?;
+        Ok(changed)
+    }
+
+    pub fn insert_forget_receipt(&self, receipt: &ForgetReceipt) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO forget_receipts (id, requested_by, completed_at, receipt)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                receipt.id,
+                receipt.requested_by,
+                receipt.completed_at.to_rfc3339(),
+                serde_json::to_string(receipt)?,
+            ],
+        )?;
         Ok(())
     }
 
+    /// Latest receipts first.
+    pub fn forget_receipts(&self, limit: usize) -> Result<Vec<ForgetReceipt>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT receipt FROM forget_receipts ORDER BY completed_at DESC LIMIT ?1",
+        )?;
+        let receipts = stmt
+            .query_map(params![limit as i64], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .filter_map(|raw| serde_json::from_str(&raw).ok())
+            .collect();
+        Ok(receipts)
+    }
+
+    /// Latest messages of one conversation. `thread_id` selects a topic or
+    /// thread; `None` is the channel itself, not every thread in it.
     pub fn get_recent(
         &self,
         platform: &str,
         channel_id: &str,
+        thread_id: Option<&str>,
         limit: usize,
     ) -> Result<Vec<MemoryMessage>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, platform, channel_id, user_id, username, content,
-                    is_mention, is_bot_response, importance, created_at
+                    is_mention, is_bot_response, importance, created_at, thread_id
              FROM messages
-             WHERE platform = ?1 AND channel_id = ?2
+             WHERE platform = ?1 AND channel_id = ?2 AND thread_id IS ?3
+                   AND deleted_at IS NULL
              ORDER BY created_at DESC
-             LIMIT ?3",
+             LIMIT ?4",
         )?;
 
-        let rows = stmt.query_map(params![platform, channel_id, limit as i64], |row| {
-            let platform_str: String = row.get(1)?;
-            let platform = match platform_str.as_str() {
-                "Discord" => kernel::event::Platform::Discord,
-                "DiscordSelfbot" => kernel::event::Platform::DiscordSelfbot,
-                "Telegram" => kernel::event::Platform::Telegram,
-                _ => kernel::event::Platform::Cli,
-            };
-
-            let timestamp_str: String = row.get(9)?;
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now());
-
-            Ok(MemoryMessage {
-                id: row.get(0)?,
-                platform,
-                channel_id: row.get(2)?,
-                user_id: row.get(3)?,
-                username: row.get(4)?,
-                content: row.get(5)?,
-                is_mention: row.get(6)?,
-                is_bot_response: row.get(7)?,
-                reply_to_user: None,
-                importance: row.get(8)?,
-                timestamp,
-            })
-        })?;
+        let rows = stmt.query_map(
+            params![platform, channel_id, thread_id, limit as i64],
+            message_from_row,
+        )?;
 
         let mut messages: Vec<MemoryMessage> = rows.filter_map(|r| r.ok()).collect();
         messages.reverse();
         Ok(messages)
     }
 
+    /// Every message not deleted, oldest first, for export. Unlike the
+    /// history queries this keeps `reply_to_user`.
+    pub fn export_messages(&self) -> Result<Vec<MemoryMessage>> {
+        let mut messages = Vec::new();
+        self.export_pages(
+            None,
+            usize::MAX,
+            |_| Ok(()),
+            |page| {
+                messages.extend(page);
+                Ok(())
+            },
+        )?;
+        Ok(messages)
+    }
+
+    /// Messages for an archive, oldest first and read from one snapshot:
+    /// `on_count` gets how many match, then `on_page` gets them up to
+    /// `page_size` at a time. `None` takes every message.
+    pub fn export_pages(
+        &self,
+        person: Option<&PersonMessages>,
+        page_size: usize,
+        on_count: impl FnOnce(usize) -> Result<()>,
+        mut on_page: impl FnMut(Vec<MemoryMessage>) -> Result<()>,
+    ) -> Result<()> {
+        let (filter, values) = match person {
+            Some(person) => person.filter(),
+            None => ("1".to_string(), Vec::new()),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let count: i64 = tx.query_row(
+            &format!("SELECT COUNT(*) FROM messages m WHERE m.deleted_at IS NULL AND ({filter})"),
+            rusqlite::params_from_iter(&values),
+            |row| row.get(0),
+        )?;
+        on_count(count as usize)?;
+
+        let mut stmt = tx.prepare(&format!(
+            "SELECT m.id, m.platform, m.channel_id, m.user_id, m.username, m.content,
+                    m.is_mention, m.is_bot_response, m.importance, m.created_at, m.thread_id,
+                    m.reply_to_user
+             FROM messages m
+             WHERE m.deleted_at IS NULL AND ({filter})
+             ORDER BY m.created_at ASC, m.rowid ASC"
+        ))?;
+        let mut rows = stmt.query(rusqlite::params_from_iter(&values))?;
+        let mut page = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut msg = message_from_row(row)?;
+            msg.reply_to_user = row.get(11)?;
+            page.push(msg);
+            if page.len() >= page_size {
+                on_page(std::mem::take(&mut page))?;
+            }
+        }
+        if !page.is_empty() {
+            on_page(page)?;
+        }
+        Ok(())
+    }
+
     pub fn get_recent_all(&self, limit: usize) -> Result<Vec<MemoryMessage>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, platform, channel_id, user_id, username, content,
-                    is_mention, is_bot_response, importance, created_at
+                    is_mention, is_bot_response, importance, created_at, thread_id
              FROM messages
+             WHERE deleted_at IS NULL
              ORDER BY created_at DESC
              LIMIT ?1",
         )?;
 
-        let rows = stmt.query_map(params![limit as i64], |row| {
-            let platform_str: String = row.get(1)?;
-            let platform = match platform_str.as_str() {
-                "Discord" => kernel::event::Platform::Discord,
-                "DiscordSelfbot" => kernel::event::Platform::DiscordSelfbot,
-                "Telegram" => kernel::event::Platform::Telegram,
-                _ => kernel::event::Platform::Cli,
-            };
-
-            let timestamp_str: String = row.get(9)?;
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now());
-
-            Ok(MemoryMessage {
-                id: row.get(0)?,
-                platform,
-                channel_id: row.get(2)?,
-                user_id: row.get(3)?,
-                username: row.get(4)?,
-                content: row.get(5)?,
-                is_mention: row.get(6)?,
-                is_bot_response: row.get(7)?,
-                reply_to_user: None,
-                importance: row.get(8)?,
-                timestamp,
-            })
-        })?;
+        let rows = stmt.query_map(params![limit as i64], message_from_row)?;
 
         let mut messages: Vec<MemoryMessage> = rows.filter_map(|r| r.ok()).collect();
         messages.reverse();
         Ok(messages)
     }
 
+    /// Full-text search over stored messages, best matches first. Terms in
+    /// `query` must all appear; a trailing `*` matches a prefix.
+    pub fn search(&self, search: &MessageSearch) -> Result<Vec<MessageHit>> {
+        let Some(query) = fts_query(&search.query) else {
+            bail!("search query is empty");
+        };
+        let limit = search
+            .limit
+            .unwrap_or(SEARCH_LIMIT_DEFAULT)
+            .clamp(1, SEARCH_LIMIT_MAX);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, m.platform, m.channel_id, m.user_id, m.username, m.content,
+                    m.is_mention, m.is_bot_response, m.importance, m.created_at, m.thread_id,
+                    snippet(messages_fts, 0, '**', '**', '…', 16)
+             FROM messages_fts
+             JOIN messages m ON m.rowid = messages_fts.rowid
+             WHERE messages_fts MATCH ?1
+                   AND m.deleted_at IS NULL
+                   AND (?2 IS NULL OR m.user_id = ?2 OR m.username = ?2 COLLATE NOCASE)
+                   AND (?3 IS NULL OR m.platform = ?3 COLLATE NOCASE)
+                   AND (?4 IS NULL OR m.channel_id = ?4)
+                   AND (?5 IS NULL OR m.created_at >= ?5)
+                   AND (?6 IS NULL OR m.created_at < ?6)
+                   AND (?7 IS NULL OR m.is_bot_response = ?7)
+             ORDER BY rank, m.created_at DESC
+             LIMIT ?8",
+        )?;
+
+        let rows = stmt.query_map(
+            params![
+                query,
+                search.user,
+                search.platform,
+                search.channel_id,
+                search.since.map(|t| t.to_rfc3339()),
+                search.until.map(|t| t.to_rfc3339()),
+                search.from_bot,
+                limit as i64,
+            ],
+            |row| {
+                Ok(MessageHit {
+                    message: message_from_row(row)?,
+                    snippet: row.get(11)?,
+                })
+            },
+        )?;
+
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
     pub fn message_count(&self) -> Result<i64> {
         let count: i64 =
             self.conn
@@ -226,7 +727,63 @@ impl MemoryStore {
     }
 }
 
-fn configure_writer_connection(conn: &Connection) -> Result<()> {
+fn parse_time(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+/// Columns: id, platform, channel_id, user_id, username, content,
+/// is_mention, is_bot_response, importance, created_at, thread_id.
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<MemoryMessage> {
+    let platform = row
+        .get::<_, String>(1)?
+        .parse()
+        .unwrap_or(kernel::event::Platform::Cli);
+
+    let timestamp = parse_time(&row.get::<_, String>(9)?);
+
+    Ok(MemoryMessage {
+        id: row.get(0)?,
+        platform,
+        channel_id: row.get(2)?,
+        thread_id: row.get(10)?,
+        user_id: row.get(3)?,
+        username: row.get(4)?,
+        content: row.get(5)?,
+        is_mention: row.get(6)?,
+        is_bot_response: row.get(7)?,
+        reply_to_user: None,
+        importance: row.get(8)?,
+        timestamp,
+    })
+}
+
+/// Turn free text into an FTS5 query: every word quoted, so punctuation
+/// and FTS operators in it are matched literally.
+fn fts_query(raw: &str) -> Option<String> {
+    let terms: Vec<String> = raw
+        .split_whitespace()
+        .filter_map(|word| {
+            let (word, prefix) = match word.strip_suffix('*') {
+                Some(stem) => (stem, true),
+                None => (word, false),
+            };
+            let word = word.replace('"', "");
+            if word.is_empty() {
+                return None;
+            }
+            Some(if prefix {
+                format!("\"{word}\"*")
+            } else {
+                format!("\"{word}\"")
+            })
+        })
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+pub(crate) fn configure_writer_connection(conn: &Connection) -> Result<()> {
     conn.busy_timeout(Duration::from_millis(1500))
         .context("Failed to set SQLite busy timeout")?;
     conn.execute_batch(
@@ -239,7 +796,7 @@ fn configure_writer_connection(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-fn configure_reader_connection(conn: &Connection) -> Result<()> {
+pub(crate) fn configure_reader_connection(conn: &Connection) -> Result<()> {
     conn.busy_timeout(Duration::from_millis(1500))
         .context("Failed to set SQLite busy timeout")?;
     Ok(())
@@ -248,23 +805,12 @@ fn configure_reader_connection(conn: &Connection) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing;
     use crate::types::MemoryMessage;
     use kernel::event::Platform;
 
     fn make_msg(id: &str, channel: &str, content: &str) -> MemoryMessage {
-        MemoryMessage {
-            id: id.to_string(),
-            platform: Platform::Discord,
-            channel_id: channel.to_string(),
-            user_id: "u1".to_string(),
-            username: "TestUser".to_string(),
-            content: content.to_string(),
-            is_mention: false,
-            is_bot_response: false,
-            reply_to_user: None,
-            timestamp: chrono::Utc::now(),
-            importance: 0.5,
-        }
+        testing::message(id, channel, "u1", "TestUser", content)
     }
 
     #[test]
@@ -274,11 +820,226 @@ mod tests {
         let msg = make_msg("m1", "ch1", "hello world");
         store.insert(&msg).unwrap();
 
-        let messages = store.get_recent("Discord", "ch1", 10).unwrap();
+        let messages = store.get_recent("Discord", "ch1", None, 10).unwrap();
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].content, "hello world");
     }
 
+    #[test]
+    fn test_threads_are_queried_separately() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        let mut in_topic = make_msg("m1", "ch1", "in the topic");
+        in_topic.thread_id = Some("7".to_string());
+        store.insert(&in_topic).unwrap();
+        store.insert(&make_msg("m2", "ch1", "in the channel")).unwrap();
+
+        let topic = store.get_recent("Discord", "ch1", Some("7"), 10).unwrap();
+        assert_eq!(topic.len(), 1);
+        assert_eq!(topic[0].thread_id.as_deref(), Some("7"));
+
+        let channel = store.get_recent("Discord", "ch1", None, 10).unwrap();
+        assert_eq!(channel.len(), 1);
+        assert_eq!(channel[0].content, "in the channel");
+    }
+
+    #[test]
+    fn test_tombstone_user() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        store.insert(&make_msg("m1", "ch1", "mine")).unwrap();
+        store.insert(&make_msg("m2", "ch2", "also mine")).unwrap();
+        let mut other = make_msg("m3", "ch1", "someone else");
+        other.user_id = "u2".to_string();
+        store.insert(&other).unwrap();
+
+        let now = chrono::Utc::now();
+        assert_eq!(store.tombstone_user("Discord", "u1", now).unwrap(), 2);
+        assert_eq!(store.tombstone_user("Discord", "u1", now).unwrap(), 0);
+        let left = store.get_recent("Discord", "ch1", None, 10).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].content, "someone else");
+    }
+
+    #[test]
+    fn test_search() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        store.insert(&make_msg("m1", "ch1", "the new Rust release is out")).unwrap();
+        let mut other = make_msg("m2", "ch2", "rusty bikes and Rust code");
+        other.user_id = "u2".to_string();
+        other.username = "Bea".to_string();
+        store.insert(&other).unwrap();
+        let mut reply = make_msg("m3", "ch1", "Rust is fine");
+        reply.is_bot_response = true;
+        store.insert(&reply).unwrap();
+
+        let search = |query: &str| MessageSearch {
+            query: query.to_string(),
+            ..MessageSearch::default()
+        };
+        assert_eq!(store.search(&search("rust")).unwrap().len(), 3);
+        assert_eq!(store.search(&search("rust*")).unwrap().len(), 3);
+        assert_eq!(store.search(&search("rusty")).unwrap().len(), 1);
+
+        let hits = store
+            .search(&MessageSearch {
+                user: Some("bea".to_string()),
+                ..search("rust")
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message.id, "m2");
+        assert!(hits[0].snippet.contains("**Rust**"));
+
+        let people = MessageSearch {
+            channel_id: Some("ch1".to_string()),
+            from_bot: Some(false),
+            ..search("rust")
+        };
+        assert_eq!(store.search(&people).unwrap()[0].message.id, "m1");
+        assert!(store
+            .search(&MessageSearch {
+                since: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+                ..search("rust")
+            })
+            .unwrap()
+            .is_empty());
+
+        // Operators and quotes are taken literally.
+        assert!(store.search(&search("\"rust OR")).unwrap().is_empty());
+        assert!(store.search(&search("  ")).is_err());
+
+        // Edits and deletions reach the index.
+        let now = chrono::Utc::now();
+        store.update_content("Discord", "ch1", "m1", "a new compiler", now).unwrap();
+        store.tombstone("Discord", "ch2", "m2", now).unwrap();
+        let hits = store.search(&search("rust")).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message.id, "m3");
+        assert_eq!(store.search(&search("compiler")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_erase_user() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        store.insert(&make_msg("m1", "ch1", "my secret plans")).unwrap();
+        let mut reply = make_msg("m2", "ch1", "good luck with that");
+        reply.is_bot_response = true;
+        reply.user_id = "bot".to_string();
+        reply.username = "Agent".to_string();
+        reply.reply_to_user = Some("TestUser".to_string());
+        store.insert(&reply).unwrap();
+
+        let now = chrono::Utc::now();
+        assert_eq!(store.erase_user("Discord", "u1", now).unwrap(), 1);
+        assert_eq!(store.erase_username("testuser", now).unwrap(), 0);
+        assert_eq!(store.unlink_replies("testuser").unwrap(), 1);
+
+        let left = store.get_recent_all(10).unwrap();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].is_bot_response);
+        let search = MessageSearch {
+            query: "secret".to_string(),
+            ..MessageSearch::default()
+        };
+        assert!(store.search(&search).unwrap().is_empty());
+        let traces: i64 = store
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM messages WHERE username = 'TestUser' OR user_id = 'u1'
+                    OR reply_to_user IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(traces, 0);
+
+        // The id stays, so a re-delivered copy is ignored.
+        store.insert(&make_msg("m1", "ch1", "my secret plans")).unwrap();
+        assert_eq!(store.get_recent_all(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_ambient_messages() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        let start = chrono::Utc::now() - chrono::Duration::minutes(1);
+        for i in 0..4 {
+            let mut msg = make_msg(&format!("a{}", i), "ch1", &format!("chatter {}", i));
+            msg.timestamp = start + chrono::Duration::seconds(i);
+            store.insert_ambient(&msg, 3).unwrap();
+        }
+        store
+            .insert_ambient(&make_msg("b1", "ch2", "elsewhere"), 3)
+            .unwrap();
+
+        let kept = store.recent_ambient(start).unwrap();
+        let ids: Vec<&str> = kept.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["a1", "a2", "a3", "b1"]);
+        assert!(!kept[0].is_mention);
+        assert!(store.get_recent_all(10).unwrap().is_empty());
+
+        let now = chrono::Utc::now();
+        assert!(store
+            .update_content("Discord", "ch1", "a3", "edited", now)
+            .unwrap());
+        assert!(store.tombstone("Discord", "ch1", "a2", now).unwrap());
+        assert_eq!(store.erase_user("Discord", "u1", now).unwrap(), 3);
+        assert!(store.recent_ambient(start).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_session_tracking() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        let start = chrono::Utc::now() - chrono::Duration::minutes(5);
+        let mut record = SessionRecord {
+            id: "s1".to_string(),
+            key: ConversationKey::new(Platform::Discord, "ch1".to_string()),
+            started_at: start,
+            last_active: start,
+            ended_at: None,
+            ingested_at: None,
+            summary: None,
+        };
+        store.upsert_session(&record).unwrap();
+        for i in 0..3 {
+            let mut msg = make_msg(&format!("m{}", i), "ch1", &format!("msg {}", i));
+            msg.timestamp = start + chrono::Duration::seconds(i);
+            store.insert(&msg).unwrap();
+            record.last_active = msg.timestamp;
+        }
+        let mut later = make_msg("m9", "ch1", "next session");
+        later.timestamp = start + chrono::Duration::minutes(2);
+        store.insert(&later).unwrap();
+        store.upsert_session(&record).unwrap();
+
+        let unfinished = store.unfinished_sessions().unwrap();
+        assert_eq!(unfinished, vec![record.clone()]);
+        let ids: Vec<String> = store
+            .session_messages(&unfinished[0])
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec!["m0", "m1", "m2"]);
+
+        let summary = SessionSummary {
+            text: "they counted to two".to_string(),
+            until: start + chrono::Duration::seconds(1),
+        };
+        store.update_session_summary("s1", &summary).unwrap();
+        store.upsert_session(&record).unwrap();
+        assert_eq!(store.unfinished_sessions().unwrap()[0].summary, Some(summary));
+        // m2 is past the summary, m1 is in it.
+        let now = chrono::Utc::now();
+        store.tombstone("Discord", "ch1", "m2", now).unwrap();
+        assert!(store.unfinished_sessions().unwrap()[0].summary.is_some());
+        store.tombstone("Discord", "ch1", "m1", now).unwrap();
+        assert!(store.unfinished_sessions().unwrap()[0].summary.is_none());
+
+        store.mark_session_ended("s1", now).unwrap();
+        assert!(store.unfinished_sessions().unwrap()[0].ended_at.is_some());
+        store.mark_session_ingested("s1", now).unwrap();
+        assert!(store.unfinished_sessions().unwrap().is_empty());
+    }
+
     #[test]
     fn test_batch_insert() {
         let store = MemoryStore::open_in_memory().unwrap();
@@ -303,4 +1064,24 @@ mod tests {
 
         assert_eq!(store.message_count().unwrap(), 1);
     }
+
+    #[test]
+    fn test_edit_and_tombstone() {
+        let store = MemoryStore::open_in_memory().unwrap();
+        store.insert(&make_msg("m1", "ch1", "helo")).unwrap();
+        store.insert(&make_msg("m2", "ch1", "oops")).unwrap();
+
+        let now = chrono::Utc::now();
+        assert!(store.update_content("Discord", "ch1", "m1", "hello", now).unwrap());
+        assert!(store.tombstone("Discord", "ch1", "m2", now).unwrap());
+        assert!(!store.update_content("Discord", "ch1", "m2", "back", now).unwrap());
+
+        let messages = store.get_recent("Discord", "ch1", None, 10).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "hello");
+
+        // The tombstone keeps a re-delivered message from coming back.
+        store.insert(&make_msg("m2", "ch1", "oops")).unwrap();
+        assert_eq!(store.get_recent("Discord", "ch1", None, 10).unwrap().len(), 1);
+    }
 }
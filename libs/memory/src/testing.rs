@@ -0,0 +1,57 @@
+//! Fixtures shared by the memory crate's unit tests.
+
+use std::path::PathBuf;
+
+use chrono::Utc;
+use kernel::event::Platform;
+
+use crate::types::MemoryMessage;
+
+/// A plain Discord message; tests adjust the remaining fields in place.
+pub(crate) fn message(
+    id: &str,
+    channel_id: &str,
+    user_id: &str,
+    username: &str,
+    content: &str,
+) -> MemoryMessage {
+    MemoryMessage {
+        id: id.to_string(),
+        platform: Platform::Discord,
+        channel_id: channel_id.to_string(),
+        thread_id: None,
+        user_id: user_id.to_string(),
+        username: username.to_string(),
+        content: content.to_string(),
+        is_mention: false,
+        is_bot_response: false,
+        reply_to_user: None,
+        timestamp: Utc::now(),
+        importance: 0.5,
+    }
+}
+
+/// A scratch directory holding `memory.db`, removed again on drop.
+pub(crate) struct TempDb {
+    dir: PathBuf,
+    path: String,
+}
+
+impl TempDb {
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("memory-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memory.db").to_string_lossy().to_string();
+        Self { dir, path }
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use kernel::get_agent_profile;
-use kernel::event::{Platform, RawEvent};
+use kernel::event::{CommandEvent, Platform, RawEvent};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +8,8 @@ pub struct MemoryMessage {
     pub id: String,
     pub platform: Platform,
     pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
     pub user_id: String,
     pub username: String,
     pub content: String,
@@ -22,6 +24,8 @@ pub struct MemoryMessage {
 pub struct ConversationKey {
     pub platform: Platform,
     pub channel_id: String,
+    /// Topics and threads inside one channel are separate conversations.
+    pub thread_id: Option<String>,
 }
 
 impl ConversationKey {
@@ -29,23 +33,62 @@ impl ConversationKey {
         Self {
             platform,
             channel_id,
+            thread_id: None,
         }
     }
 
+    pub fn with_thread(mut self, thread_id: Option<String>) -> Self {
+        self.thread_id = thread_id;
+        self
+    }
+
     pub fn from_raw(raw: &RawEvent) -> Self {
-        Self {
-            platform: raw.platform,
-            channel_id: raw.channel_id.clone(),
-        }
+        Self::new(raw.platform, raw.channel_id.clone()).with_thread(raw.thread_id.clone())
+    }
+
+    pub fn from_command(command: &CommandEvent) -> Self {
+        Self::new(command.platform, command.channel_id.clone())
+            .with_thread(command.thread_id.clone())
+    }
+
+    pub fn from_message(msg: &MemoryMessage) -> Self {
+        Self::new(msg.platform, msg.channel_id.clone()).with_thread(msg.thread_id.clone())
     }
 }
 
 impl std::fmt::Display for ConversationKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.platform, self.channel_id)
+        match &self.thread_id {
+            Some(thread_id) => write!(f, "{}:{}#{}", self.platform, self.channel_id, thread_id),
+            None => write!(f, "{}:{}", self.platform, self.channel_id),
+        }
     }
 }
 
+/// A session's boundaries and ingestion status, as kept in the `sessions`
+/// table so that a restart neither loses nor repeats its ingestion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub id: String,
+    pub key: ConversationKey,
+    pub started_at: DateTime<Utc>,
+    pub last_active: DateTime<Utc>,
+    /// Set once the session expired or was ended.
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Set once it went to episodic memory, or was too short to.
+    pub ingested_at: Option<DateTime<Utc>>,
+    pub summary: Option<SessionSummary>,
+}
+
+/// A rolling summary of the part of a session that no longer fits in the
+/// prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub text: String,
+    /// Timestamp of the last message the summary covers.
+    pub until: DateTime<Utc>,
+}
+
 impl MemoryMessage {
     pub fn from_raw(raw: &RawEvent) -> Self {
         let importance = Self::compute_importance(raw);
@@ -54,6 +97,7 @@ impl MemoryMessage {
             id: raw.message_id.clone(),
             platform: raw.platform,
             channel_id: raw.channel_id.clone(),
+            thread_id: raw.thread_id.clone(),
             user_id: raw.user_id.clone(),
             username: raw.username.clone(),
             content: Self::strip_mention_tags(&raw.content),
@@ -77,6 +121,7 @@ impl MemoryMessage {
             id: uuid::Uuid::new_v4().to_string(),
             platform,
             channel_id,
+            thread_id: None,
             user_id: profile.agent_id.clone(),
             username: profile.display_name.clone(),
             content,
@@ -88,6 +133,11 @@ impl MemoryMessage {
         }
     }
 
+    pub fn with_thread(mut self, thread_id: Option<String>) -> Self {
+        self.thread_id = thread_id;
+        self
+    }
+
     fn compute_importance(raw: &RawEvent) -> f32 {
         let mut score: f32 = 0.3;
 
@@ -107,7 +157,7 @@ impl MemoryMessage {
         score.min(1.0)
     }
 
-    fn strip_mention_tags(content: &str) -> String {
+    pub(crate) fn strip_mention_tags(content: &str) -> String {
         let mut result = String::with_capacity(content.len());
         let chars: Vec<char> = content.chars().collect();
         let mut i = 0;
@@ -137,6 +187,7 @@ mod tests {
         let raw = RawEvent {
             platform: Platform::Discord,
             channel_id: "ch1".to_string(),
+            thread_id: None,
             message_id: "m1".to_string(),
             user_id: "u1".to_string(),
             username: "TestUser".to_string(),
@@ -145,6 +196,8 @@ mod tests {
             is_mention: true,
             is_dm: false,
             timestamp: Utc::now(),
+            reply_to: None,
+            is_backfill: false,
         };
 
         let msg = MemoryMessage::from_raw(&raw);
@@ -158,6 +211,10 @@ mod tests {
     fn test_conversation_key() {
         let key = ConversationKey::new(Platform::Discord, "ch1".to_string());
         assert_eq!(key.to_string(), "Discord:ch1");
+
+        let topic = key.clone().with_thread(Some("7".to_string()));
+        assert_eq!(topic.to_string(), "Discord:ch1#7");
+        assert_ne!(topic, key);
     }
 
     #[test]
@@ -3,113 +3,210 @@ use std::sync::Arc;
 use anyhow::Result;
 use async_trait::async_trait;
 use kernel::get_agent_profile;
-use kernel::event::Event;
+use kernel::event::{Command, Event, Platform, RawEvent};
 use kernel::worker::Worker;
 use kernel::prompt_registry::{get_prompt_or, render_prompt_or};
 use kernel::WorkerContext;
 use tokio::sync::{broadcast, mpsc, Mutex, Semaphore};
 use tracing::{debug, error, info, warn};
 
-use crate::short_term::ShortTermMemory;
+use crate::ambient::{AmbientConfig, AMBIENT_IMPORTANCE};
+use crate::short_term::{EndedSession, ShortTermMemory, SummaryRequest};
 use crate::store::MemoryStore;
-use crate::types::MemoryMessage;
+use crate::types::{ConversationKey, MemoryMessage, SessionRecord, SessionSummary};
 use crate::episodic::{EpisodicStore, MemoryEvent};
 use crate::embedder::MemoryEmbedder;
 use crate::compressor::SemanticCompressor;
+use crate::forget::{ForgetSubject, Forgetter};
+use crate::graph::CognitiveGraph;
+use crate::identity::{IdentityStore, LinkRedemption, LINK_CODE_TTL_MINUTES};
 
 pub struct MemoryWorker {
     pub short_term: Arc<Mutex<ShortTermMemory>>,
     pub episodic: Option<Arc<EpisodicStore>>,
     pub embedder: Option<Arc<MemoryEmbedder>>,
     pub compressor: Option<Arc<SemanticCompressor>>,
+    pub identity: Option<IdentityStore>,
+    pub graph: Option<CognitiveGraph>,
+    pub forgetter: Option<Forgetter>,
+    ambient: AmbientConfig,
     ingest_limiter: Arc<Semaphore>,
     db_path: String,
 }
 
 unsafe impl Sync for MemoryWorker {}
 
-const MEMORY_WRITE_CHANNEL_CAPACITY: usize = 1_024;
-const MEMORY_WRITE_BATCH_SIZE: usize = 64;
-const MEMORY_WRITE_FLUSH_INTERVAL_MS: u64 = 200;
+/// A queued store write. Edits and deletions share the insert queue so they
+/// never overtake the insert of the message they change.
+enum StoreWrite {
+    Insert(MemoryMessage),
+    Edit {
+        key: ConversationKey,
+        id: String,
+        content: String,
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    Tombstone {
+        key: ConversationKey,
+        id: String,
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    TombstoneUser {
+        platform: Platform,
+        user_id: String,
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    Ambient {
+        msg: MemoryMessage,
+        keep: usize,
+    },
+    Session(SessionRecord),
+    SessionEnded {
+        id: String,
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    SessionIngested {
+        id: String,
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    SessionSummary {
+        id: String,
+        summary: SessionSummary,
+    },
+}
 
-impl MemoryWorker {
-    pub fn new(db_path: &str) -> Self {
-        let ingest_permits = std::thread::available_parallelism()
-            .map(|value| value.get().clamp(1, 4))
-            .unwrap_or(2);
-        Self {
-            short_term: Arc::new(Mutex::new(ShortTermMemory::new())),
-            episodic: None,
-            embedder: None,
-            compressor: None,
-            ingest_limiter: Arc::new(Semaphore::new(ingest_permits)),
-            db_path: db_path.to_string(),
+impl StoreWrite {
+    fn apply(&self, store: &MemoryStore) -> Result<()> {
+        match self {
+            StoreWrite::Insert(msg) => store.insert(msg),
+            StoreWrite::Edit { key, id, content, at } => store
+                .update_content(&key.platform.to_string(), &key.channel_id, id, content, *at)
+                .map(|_| ()),
+            StoreWrite::Tombstone { key, id, at } => store
+                .tombstone(&key.platform.to_string(), &key.channel_id, id, *at)
+                .map(|_| ()),
+            StoreWrite::TombstoneUser { platform, user_id, at } => store
+                .tombstone_user(&platform.to_string(), user_id, *at)
+                .map(|_| ()),
+            StoreWrite::Ambient { msg, keep } => store.insert_ambient(msg, *keep),
+            StoreWrite::Session(record) => store.upsert_session(record),
+            StoreWrite::SessionEnded { id, at } => store.mark_session_ended(id, *at),
+            StoreWrite::SessionIngested { id, at } => store.mark_session_ingested(id, *at),
+            StoreWrite::SessionSummary { id, summary } => store.update_session_summary(id, summary),
         }
     }
+}
 
-    pub fn with_episodic(mut self, episodic: Arc<EpisodicStore>) -> Self {
-        self.episodic = Some(episodic);
-        self
+/// The chat log handed to the compressor, after the summary of what came
+/// before it, if any.
+fn chat_log_document<'m>(
+    summary: Option<&str>,
+    messages: impl IntoIterator<Item = &'m MemoryMessage>,
+) -> String {
+    let profile = get_agent_profile();
+    let mut formatted_msgs = Vec::new();
+    for msg in messages {
+        let speaker = if msg.is_bot_response {
+            profile.display_name.as_str()
+        } else {
+            &msg.username
+        };
+        formatted_msgs.push(format!("[{}]: {}", speaker, msg.content));
     }
 
-    pub fn with_embedder(mut self, embedder: Arc<MemoryEmbedder>) -> Self {
-        self.embedder = Some(embedder);
-        self
+    let joined_log = formatted_msgs.join("\n");
+    let chat_log = render_prompt_or(
+        "memory.chatlog.wrapper",
+        &[("chat_log", joined_log.as_str())],
+        "=== CHAT LOG START ===\n{{chat_log}}\n=== CHAT LOG END ===\n",
+    );
+    match summary {
+        Some(summary) => {
+            let earlier = render_prompt_or(
+                "memory.chatlog.summary",
+                &[("summary", summary)],
+                "=== EARLIER IN THIS SESSION (summary) ===\n{{summary}}\n",
+            );
+            format!("{}\n{}", earlier, chat_log)
+        }
+        None => chat_log,
     }
+}
 
-    pub fn with_compressor(mut self, compressor: Arc<SemanticCompressor>) -> Self {
-        self.compressor = Some(compressor);
-        self
-    }
+fn base_persona() -> String {
+    let fallback_persona = format!("You are {}.", get_agent_profile().display_name);
+    get_prompt_or("persona.base", fallback_persona.as_str())
+}
 
-    pub fn short_term_handle(&self) -> Arc<Mutex<ShortTermMemory>> {
-        Arc::clone(&self.short_term)
-    }
+/// What ingesting an ended session needs, cloned into each ingestion task.
+#[derive(Clone)]
+struct SessionIngest {
+    compressor: Arc<SemanticCompressor>,
+    embedder: Arc<MemoryEmbedder>,
+    episodic: Arc<EpisodicStore>,
+    limiter: Arc<Semaphore>,
+    identity: Option<IdentityStore>,
+    writer_tx: mpsc::Sender<StoreWrite>,
+}
 
-    fn ingest_session(
-        messages: Vec<MemoryMessage>,
-        compressor: Arc<SemanticCompressor>,
-        embedder: Arc<MemoryEmbedder>,
-        episodic: Arc<EpisodicStore>,
-        ingest_limiter: Arc<Semaphore>,
-    ) {
+impl SessionIngest {
+    /// Compress an ended session into an episodic event in the background.
+    /// The session's id doubles as the event id, so a session that was
+    /// already ingested before a restart is not ingested twice.
+    fn spawn(&self, ended: EndedSession) {
+        let EndedSession {
+            id: session_id,
+            messages,
+            summary,
+            ..
+        } = ended;
         if messages.len() < 3 {
-            debug!(count = messages.len(), "Session too short, ignoring semantic compression.");
+            debug!(
+                count = messages.len(),
+                "Session too short, ignoring semantic compression."
+            );
+            self.mark_ingested(session_id);
             return;
         }
-        
+
+        let ingest = self.clone();
         tokio::spawn(async move {
-            let permit = match ingest_limiter.acquire_owned().await {
+            let SessionIngest {
+                compressor,
+                embedder,
+                episodic,
+                limiter,
+                identity,
+                ..
+            } = &ingest;
+            let permit = match Arc::clone(limiter).acquire_owned().await {
                 Ok(permit) => permit,
                 Err(err) => {
                     error!(error = %err, "Failed to acquire ingestion permit");
                     return;
                 }
             };
-            let session_id = uuid::Uuid::new_v4().to_string();
-            let profile = get_agent_profile();
-            let fallback_persona = format!("You are {}.", profile.display_name);
-
-            let base_persona = get_prompt_or("persona.base", fallback_persona.as_str());
-
-            let mut formatted_msgs = Vec::new();
-            for msg in &messages {
-                let speaker = if msg.is_bot_response {
-                    profile.display_name.as_str()
-                } else {
-                    &msg.username
-                };
-                formatted_msgs.push(format!("[{}]: {}", speaker, msg.content));
+            match episodic
+                .existing_ids(std::slice::from_ref(&session_id))
+                .await
+            {
+                Ok(existing) if !existing.is_empty() => {
+                    debug!(session_id = %session_id, "Session already in EpisodicStore");
+                    ingest.mark_ingested(session_id);
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "Failed to check EpisodicStore for session"),
             }
-
-            let joined_log = formatted_msgs.join("\n");
-            let chat_log_doc = render_prompt_or(
-                "memory.chatlog.wrapper",
-                &[("chat_log", joined_log.as_str())],
-                "=== CHAT LOG START ===\n{{chat_log}}\n=== CHAT LOG END ===\n",
+            // Messages the summary covers go in through it.
+            let chat_log_doc = chat_log_document(
+                summary.as_ref().map(|s| s.text.as_str()),
+                messages
+                    .iter()
+                    .filter(|m| summary.as_ref().is_none_or(|s| m.timestamp > s.until)),
             );
 
-            match compressor.compress(&base_persona, &chat_log_doc).await {
+            match compressor.compress(&base_persona(), &chat_log_doc).await {
                 Ok(Some(compression)) => {
                     info!(
                         session_id = %session_id,
@@ -121,9 +218,19 @@ impl MemoryWorker {
                     match embedder.embed_single(compression.fact.clone()).await {
                         Ok(vector) => {
                             let timestamp = messages.last().unwrap().timestamp.timestamp();
-                            let target_username = messages.iter().find(|m| !m.is_bot_response).map(|m| m.username.clone()).unwrap_or_else(|| "unknown".to_string());
+                            let target = messages.iter().find(|m| !m.is_bot_response);
+                            let target_username = target.map(|m| m.username.clone()).unwrap_or_else(|| "unknown".to_string());
+                            let person_id = match (identity, target) {
+                                (Some(identity), Some(m)) => identity
+                                    .person_for_account(m.platform, &m.user_id)
+                                    .ok()
+                                    .flatten(),
+                                _ => None,
+                            }
+                            .unwrap_or_else(|| target_username.clone());
                             let metadata = serde_json::json!({
                                 "username": target_username,
+                                "person_id": person_id,
                                 "message_count": messages.len(),
                                 "first_message_timestamp": messages.first().unwrap().timestamp.timestamp(),
                             }).to_string();
@@ -141,6 +248,7 @@ impl MemoryWorker {
                                 error!(error = %e, "Failed to insert event into EpisodicStore");
                             } else {
                                 info!(session_id = %session_id, "Memory event successfully ingested into EpisodicStore");
+                                ingest.mark_ingested(session_id);
                             }
                         }
                         Err(e) => {
@@ -148,20 +256,259 @@ impl MemoryWorker {
                         }
                     }
                 }
-                Ok(None) => debug!("Semantic compression deemed session trivial; no event to ingest."),
+                Ok(None) => {
+                    debug!("Semantic compression deemed session trivial; no event to ingest.");
+                    ingest.mark_ingested(session_id);
+                }
                 Err(e) => error!(error = %e, "Semantic compression API failed"),
             }
             drop(permit);
         });
     }
 
+    /// Fold the messages of `request` into their session's summary in the
+    /// background.
+    fn spawn_summary(&self, short_term: Arc<Mutex<ShortTermMemory>>, request: SummaryRequest) {
+        let ingest = self.clone();
+        tokio::spawn(async move {
+            let permit = match Arc::clone(&ingest.limiter).acquire_owned().await {
+                Ok(permit) => permit,
+                Err(err) => {
+                    error!(error = %err, "Failed to acquire summary permit");
+                    short_term.lock().await.finish_summary(&request, None);
+                    return;
+                }
+            };
+            let chat_log_doc = chat_log_document(request.previous.as_deref(), &request.messages);
+            let text = match ingest
+                .compressor
+                .summarise(&base_persona(), &chat_log_doc)
+                .await
+            {
+                Ok(text) => text,
+                Err(e) => {
+                    error!(error = %e, "Session summary API failed");
+                    None
+                }
+            };
+            drop(permit);
+
+            let summary = short_term.lock().await.finish_summary(&request, text);
+            match summary {
+                Some(summary) => {
+                    info!(
+                        conversation = %request.key,
+                        messages = request.messages.len(),
+                        "Session summary refreshed"
+                    );
+                    let write = StoreWrite::SessionSummary {
+                        id: request.session_id,
+                        summary,
+                    };
+                    if ingest.writer_tx.try_send(write).is_err() {
+                        warn!(
+                            "Memory write queue unavailable, session summary kept in memory only"
+                        );
+                    }
+                }
+                None => debug!(conversation = %request.key, "Session summary not refreshed"),
+            }
+        });
+    }
+
+    /// A failed ingestion leaves the session pending, to be retried at the
+    /// next start.
+    fn mark_ingested(&self, id: String) {
+        let write = StoreWrite::SessionIngested {
+            id,
+            at: chrono::Utc::now(),
+        };
+        if self.writer_tx.try_send(write).is_err() {
+            warn!("Memory write queue unavailable, session stays pending ingestion");
+        }
+    }
+}
+
+const MEMORY_WRITE_CHANNEL_CAPACITY: usize = 1_024;
+const MEMORY_WRITE_BATCH_SIZE: usize = 64;
+const MEMORY_WRITE_FLUSH_INTERVAL_MS: u64 = 200;
+
+const FORGET_REPLY: &str = "Okay, starting a fresh conversation.";
+const FORGET_ME_REPLY: &str =
+    "Done. I've deleted your messages from my conversation memory.";
+const FORGET_ME_ERASED_REPLY: &str = "Done. I've erased what I remembered about you.";
+const FORGET_ME_PARTIAL_REPLY: &str =
+    "I've erased most of what I remembered about you, but some of it couldn't be removed yet.";
+const LINK_UNAVAILABLE_REPLY: &str = "Account linking isn't set up here.";
+const LINK_PRIVATE_REPLY: &str =
+    "Send /link to me in a direct message so your code stays private.";
+const LINK_BAD_CODE_REPLY: &str =
+    "That code is unknown or has expired. Run /link on your other account for a new one.";
+const LINK_LOCKED_REPLY: &str =
+    "Too many wrong codes. Wait a while before trying /link again.";
+const LINK_DONE_REPLY: &str = "Linked. I'll know you as the same person on both accounts.";
+const LINK_FAILED_REPLY: &str = "Sorry, I couldn't link your accounts right now.";
+
+impl MemoryWorker {
+    pub fn new(db_path: &str) -> Self {
+        let ingest_permits = std::thread::available_parallelism()
+            .map(|value| value.get().clamp(1, 4))
+            .unwrap_or(2);
+        Self {
+            short_term: Arc::new(Mutex::new(ShortTermMemory::new())),
+            episodic: None,
+            embedder: None,
+            compressor: None,
+            identity: None,
+            graph: None,
+            forgetter: None,
+            ambient: AmbientConfig::default(),
+            ingest_limiter: Arc::new(Semaphore::new(ingest_permits)),
+            db_path: db_path.to_string(),
+        }
+    }
+
+    pub fn with_episodic(mut self, episodic: Arc<EpisodicStore>) -> Self {
+        self.episodic = Some(episodic);
+        self
+    }
+
+    pub fn with_embedder(mut self, embedder: Arc<MemoryEmbedder>) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    pub fn with_compressor(mut self, compressor: Arc<SemanticCompressor>) -> Self {
+        self.compressor = Some(compressor);
+        self
+    }
+
+    /// Record who sent each message and answer `/link`.
+    pub fn with_identity(mut self, identity: IdentityStore) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Graph to carry relationships over when `/link` merges two persons.
+    pub fn with_graph(mut self, graph: CognitiveGraph) -> Self {
+        self.graph = Some(graph);
+        self
+    }
+
+    /// Make `/forget-me` erase the sender from every store, not only
+    /// from conversation memory.
+    pub fn with_forgetter(mut self, forgetter: Forgetter) -> Self {
+        self.forgetter = Some(forgetter);
+        self
+    }
+
+    /// Keep recent untagged messages of the channels `config` opts in, for
+    /// the dialogue engine to read.
+    pub fn with_ambient(mut self, config: AmbientConfig) -> Self {
+        self.ambient = config;
+        self
+    }
+
+    pub fn short_term_handle(&self) -> Arc<Mutex<ShortTermMemory>> {
+        Arc::clone(&self.short_term)
+    }
+
+    /// Record that a session ended, then ingest it when a compressor is
+    /// configured. Until then it stays pending in the `sessions` table.
+    async fn finish_session(
+        writer_tx: &mpsc::Sender<StoreWrite>,
+        writer_store: &Arc<std::sync::Mutex<MemoryStore>>,
+        ingest: Option<&SessionIngest>,
+        ended: EndedSession,
+    ) {
+        let write = StoreWrite::SessionEnded {
+            id: ended.id.clone(),
+            at: chrono::Utc::now(),
+        };
+        Self::persist_message(writer_tx, writer_store, write, "session_end").await;
+        if let Some(ingest) = ingest {
+            ingest.spawn(ended);
+        }
+    }
+
+    /// `/link` without a code issues one; with a code, merges the sender's
+    /// person into the one that asked for it.
+    async fn link_accounts(
+        identity: &IdentityStore,
+        graph: Option<&CognitiveGraph>,
+        command: &kernel::event::CommandEvent,
+        code: Option<&str>,
+    ) -> String {
+        let person = match identity.resolve(command.platform, &command.user_id, &command.username) {
+            Ok(person) => person,
+            Err(e) => {
+                error!(error = %e, user = %command.username, "Failed to resolve identity for link");
+                return LINK_FAILED_REPLY.to_string();
+            }
+        };
+
+        let Some(code) = code else {
+            // Discord answers slash commands privately; elsewhere only DMs are.
+            if !command.is_dm && command.platform != Platform::Discord {
+                return LINK_PRIVATE_REPLY.to_string();
+            }
+            return match identity.issue_link_code(command.platform, &command.user_id) {
+                Ok(code) => format!(
+                    "Your link code is {code}. Within {LINK_CODE_TTL_MINUTES} minutes, send /link {code} to me from your other account."
+                ),
+                Err(e) => {
+                    error!(error = %e, user = %command.username, "Failed to issue link code");
+                    LINK_FAILED_REPLY.to_string()
+                }
+            };
+        };
+
+        match identity.redeem_link_code(code, command.platform, &command.user_id) {
+            Ok(LinkRedemption::Linked(linked)) => {
+                info!(person = %linked, from = %person, platform = %command.platform, "Accounts linked");
+                if linked != person {
+                    if let Some(graph) = graph {
+                        if let Err(e) = graph.merge_person(&person, &linked).await {
+                            warn!(error = %e, from = %person, into = %linked, "Failed to carry relationship over");
+                        }
+                    }
+                }
+                LINK_DONE_REPLY.to_string()
+            }
+            Ok(LinkRedemption::Rejected) => LINK_BAD_CODE_REPLY.to_string(),
+            Ok(LinkRedemption::LockedOut(until)) => {
+                info!(platform = %command.platform, user = %command.username, until = %until, "Link attempt refused during lockout");
+                LINK_LOCKED_REPLY.to_string()
+            }
+            Err(e) => {
+                error!(error = %e, user = %command.username, "Failed to redeem link code");
+                LINK_FAILED_REPLY.to_string()
+            }
+        }
+    }
+
+    /// Record who wrote a message the agent keeps: mentions, DMs and
+    /// messages in channels that opted in to ambient memory. Chatter the
+    /// agent drops leaves no identity behind.
+    async fn record_identity(identity: Option<&IdentityStore>, raw: &RawEvent) {
+        let Some(identity) = identity.cloned() else {
+            return;
+        };
+        let (platform, user_id, username) = (raw.platform, raw.user_id.clone(), raw.username.clone());
+        match tokio::task::spawn_blocking(move || identity.resolve(platform, &user_id, &username)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!(error = %e, user = %raw.username, "Failed to record identity"),
+            Err(e) => warn!(error = %e, user = %raw.username, "Identity task failed"),
+        }
+    }
+
     async fn persist_message(
-        writer_tx: &mpsc::Sender<MemoryMessage>,
+        writer_tx: &mpsc::Sender<StoreWrite>,
         writer_store: &Arc<std::sync::Mutex<MemoryStore>>,
-        msg: MemoryMessage,
+        write: StoreWrite,
         context: &'static str,
     ) {
-        match writer_tx.try_send(msg.clone()) {
+        match writer_tx.try_send(write) {
             Ok(()) => {}
             Err(mpsc::error::TrySendError::Full(pending)) => {
                 if let Err(err) = writer_tx.send(pending).await {
@@ -178,7 +525,7 @@ impl MemoryWorker {
 
     async fn flush_persist_batch(
         store: Arc<std::sync::Mutex<MemoryStore>>,
-        batch: Vec<MemoryMessage>,
+        batch: Vec<StoreWrite>,
         context: &'static str,
     ) {
         if batch.is_empty() {
@@ -190,7 +537,22 @@ impl MemoryWorker {
             let store = store
                 .lock()
                 .map_err(|_| anyhow::anyhow!("memory store mutex poisoned"))?;
-            store.insert_batch(&batch)
+            let mut inserts = Vec::new();
+            for write in batch {
+                match write {
+                    StoreWrite::Insert(msg) => inserts.push(msg),
+                    update => {
+                        if !inserts.is_empty() {
+                            store.insert_batch(&std::mem::take(&mut inserts))?;
+                        }
+                        update.apply(&store)?;
+                    }
+                }
+            }
+            if inserts.is_empty() {
+                return Ok(());
+            }
+            store.insert_batch(&inserts).map(|_| ())
         })
         .await
         {
@@ -206,7 +568,7 @@ impl MemoryWorker {
 
     async fn run_persist_writer(
         store: Arc<std::sync::Mutex<MemoryStore>>,
-        mut writer_rx: mpsc::Receiver<MemoryMessage>,
+        mut writer_rx: mpsc::Receiver<StoreWrite>,
         mut shutdown_rx: broadcast::Receiver<()>,
     ) {
         let mut buffer = Vec::with_capacity(MEMORY_WRITE_BATCH_SIZE);
@@ -291,6 +653,59 @@ impl Worker for MemoryWorker {
             stm.mark_all_persisted();
             info!("Loaded recent history into short-term memory");
         }
+        // Sessions that were live at shutdown pick up where they left off;
+        // those that ended without being ingested are ingested below.
+        let mut pending_ingest = Vec::new();
+        let mut resumed = 0;
+        match store.unfinished_sessions() {
+            Ok(records) => {
+                let mut stm = self.short_term.lock().await;
+                for record in records {
+                    let messages = match store.session_messages(&record) {
+                        Ok(messages) => messages,
+                        Err(e) => {
+                            warn!(error = %e, session_id = %record.id, "Failed to load session messages");
+                            continue;
+                        }
+                    };
+                    if record.ended_at.is_some() {
+                        pending_ingest.push(EndedSession {
+                            id: record.id,
+                            key: record.key,
+                            messages,
+                            summary: record.summary,
+                        });
+                    } else if let Some(ended) = stm.resume_session(record, messages) {
+                        pending_ingest.push(ended);
+                    } else {
+                        resumed += 1;
+                    }
+                }
+                info!(
+                    resumed,
+                    pending_ingest = pending_ingest.len(),
+                    "Restored unfinished sessions"
+                );
+            }
+            Err(e) => warn!(error = %e, "Failed to load unfinished sessions"),
+        }
+        if self.ambient.is_enabled() {
+            let since = chrono::Utc::now() - chrono::Duration::seconds(self.ambient.max_age_secs);
+            let restored = if self.ambient.persist {
+                store.recent_ambient(since).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            info!(
+                channels = self.ambient.channels.len(),
+                persist = self.ambient.persist,
+                restored = restored.len(),
+                "Ambient channel buffer enabled"
+            );
+            let mut stm = self.short_term.lock().await;
+            stm.set_ambient(self.ambient.clone());
+            stm.load_ambient(restored);
+        }
         let writer_store = Arc::new(std::sync::Mutex::new(store));
         let (writer_tx, writer_rx) = mpsc::channel(MEMORY_WRITE_CHANNEL_CAPACITY);
         let writer_handle = tokio::spawn(Self::run_persist_writer(
@@ -301,8 +716,21 @@ impl Worker for MemoryWorker {
 
         let episodic = Arc::clone(self.episodic.as_ref().expect("EpisodicStore not initialized"));
         let embedder = Arc::clone(self.embedder.as_ref().expect("MemoryEmbedder not initialized"));
-        let compressor = self.compressor.clone();
-        let ingest_limiter = Arc::clone(&self.ingest_limiter);
+        let identity = self.identity.clone();
+        let ingest = self.compressor.clone().map(|compressor| SessionIngest {
+            compressor,
+            embedder,
+            episodic,
+            limiter: Arc::clone(&self.ingest_limiter),
+            identity: identity.clone(),
+            writer_tx: writer_tx.clone(),
+        });
+        for ended in pending_ingest {
+            Self::finish_session(&writer_tx, &writer_store, ingest.as_ref(), ended).await;
+        }
+        let graph = self.graph.clone();
+        let forgetter = self.forgetter.clone();
+        let ambient_keep = self.ambient.persist.then_some(self.ambient.max_messages);
 
         let mut broadcast_rx = ctx.subscribe_events();
         let mut shutdown_rx = ctx.subscribe_shutdown();
@@ -318,14 +746,29 @@ impl Worker for MemoryWorker {
                     match event {
                         Ok(Event::Raw(raw)) => {
                             if !raw.is_mention {
-                                debug!(
-                                    user = %raw.username,
-                                    channel = %raw.channel_id,
-                                    "Skipping non-mention message (not stored in memory)"
-                                );
+                                let mut msg = MemoryMessage::from_raw(&raw);
+                                msg.importance = msg.importance.min(AMBIENT_IMPORTANCE);
+                                let kept = {
+                                    let mut stm = short_term.lock().await;
+                                    stm.push_ambient(msg.clone())
+                                };
+                                if !kept {
+                                    debug!(
+                                        user = %raw.username,
+                                        channel = %raw.channel_id,
+                                        "Skipping non-mention message (channel has no ambient opt-in)"
+                                    );
+                                    continue;
+                                }
+                                Self::record_identity(identity.as_ref(), &raw).await;
+                                if let Some(keep) = ambient_keep {
+                                    let write = StoreWrite::Ambient { msg, keep };
+                                    Self::persist_message(&writer_tx, &writer_store, write, "ambient_message").await;
+                                }
                                 continue;
                             }
 
+                            Self::record_identity(identity.as_ref(), &raw).await;
                             let msg = MemoryMessage::from_raw(&raw);
                             debug!(
                                 user = %msg.username,
@@ -334,23 +777,24 @@ impl Worker for MemoryWorker {
                                 "Recording mention/DM to memory"
                             );
 
-                            let expired = {
+                            let key = ConversationKey::from_message(&msg);
+                            let (expired, record, summary_due) = {
                                 let mut stm = short_term.lock().await;
-                                stm.push(msg.clone())
+                                let expired = stm.push(msg.clone());
+                                let due = ingest.as_ref().and_then(|_| stm.summary_due(&key));
+                                (expired, stm.session_record(&key), due)
                             };
+                            if let (Some(ingest), Some(request)) = (&ingest, summary_due) {
+                                ingest.spawn_summary(Arc::clone(&short_term), request);
+                            }
 
-                            Self::persist_message(&writer_tx, &writer_store, msg.clone(), "raw_message").await;
+                            Self::persist_message(&writer_tx, &writer_store, StoreWrite::Insert(msg.clone()), "raw_message").await;
+                            if let Some(record) = record {
+                                Self::persist_message(&writer_tx, &writer_store, StoreWrite::Session(record), "session").await;
+                            }
 
-                            if let Some(expired_msgs) = expired {
-                                if let Some(comp) = &compressor {
-                                    Self::ingest_session(
-                                        expired_msgs,
-                                        Arc::clone(comp),
-                                        Arc::clone(&embedder),
-                                        Arc::clone(&episodic),
-                                        Arc::clone(&ingest_limiter),
-                                    );
-                                }
+                            if let Some(ended) = expired {
+                                Self::finish_session(&writer_tx, &writer_store, ingest.as_ref(), ended).await;
                             }
                         }
                         Ok(Event::BotTurnCompletion(complete)) => {
@@ -360,17 +804,148 @@ impl Worker for MemoryWorker {
                                 complete.content.clone(),
                                 complete.reply_to_message_id.clone(),
                                 complete.reply_to_user.clone(),
-                            );
+                            )
+                            .with_thread(complete.thread_id.clone());
                             debug!(
                                 channel = %msg.channel_id,
                                 reply_to = ?msg.reply_to_user,
                                 "Recording full bot turn to memory"
                             );
 
-                            let mut stm = short_term.lock().await;
-                            stm.push(msg.clone());
+                            let key = ConversationKey::from_message(&msg);
+                            let (expired, record, summary_due) = {
+                                let mut stm = short_term.lock().await;
+                                let expired = stm.push(msg.clone());
+                                let due = ingest.as_ref().and_then(|_| stm.summary_due(&key));
+                                (expired, stm.session_record(&key), due)
+                            };
+                            if let (Some(ingest), Some(request)) = (&ingest, summary_due) {
+                                ingest.spawn_summary(Arc::clone(&short_term), request);
+                            }
+
+                            Self::persist_message(&writer_tx, &writer_store, StoreWrite::Insert(msg.clone()), "bot_turn").await;
+                            if let Some(record) = record {
+                                Self::persist_message(&writer_tx, &writer_store, StoreWrite::Session(record), "session").await;
+                            }
+                            if let Some(ended) = expired {
+                                Self::finish_session(&writer_tx, &writer_store, ingest.as_ref(), ended).await;
+                            }
+                        }
+                        Ok(Event::MessageEdited(edit)) => {
+                            let key = ConversationKey::new(edit.platform, edit.channel_id.clone())
+                                .with_thread(edit.thread_id.clone());
+                            let content = MemoryMessage::strip_mention_tags(&edit.content);
+                            let in_session = {
+                                let mut stm = short_term.lock().await;
+                                stm.edit_message(&key, &edit.message_id, &content)
+                            };
+                            debug!(
+                                conversation = %key,
+                                message = %edit.message_id,
+                                in_session,
+                                "Applying message edit to memory"
+                            );
+
+                            let write = StoreWrite::Edit {
+                                key,
+                                id: edit.message_id,
+                                content,
+                                at: edit.timestamp,
+                            };
+                            Self::persist_message(&writer_tx, &writer_store, write, "message_edit").await;
+                        }
+                        Ok(Event::Command(command)) if command.command == Command::Forget => {
+                            let key = ConversationKey::from_command(&command);
+                            let ended = {
+                                let mut stm = short_term.lock().await;
+                                stm.end_session(&key)
+                            };
+                            info!(conversation = %key, user = %command.username, "Forgetting conversation on request");
+
+                            // The session still goes to episodic memory, like an expired one.
+                            if let Some(ended) = ended {
+                                Self::finish_session(&writer_tx, &writer_store, ingest.as_ref(), ended).await;
+                            }
+                            let reply = command.reply(FORGET_REPLY);
+                            if let Err(e) = ctx.event_tx.send(Event::Response(reply)).await {
+                                warn!(error = %e, "Failed to answer forget command");
+                            }
+                        }
+                        Ok(Event::Command(command)) if command.command == Command::ForgetMe => {
+                            let removed = {
+                                let mut stm = short_term.lock().await;
+                                stm.remove_user(command.platform, &command.user_id)
+                            };
+                            info!(user = %command.username, platform = %command.platform, in_session = removed, "Forgetting user's messages on request");
+
+                            let write = StoreWrite::TombstoneUser {
+                                platform: command.platform,
+                                user_id: command.user_id.clone(),
+                                at: command.timestamp,
+                            };
+                            Self::persist_message(&writer_tx, &writer_store, write, "forget_me").await;
+
+                            let Some(forgetter) = forgetter.clone() else {
+                                let reply = command.reply(FORGET_ME_REPLY);
+                                if let Err(e) = ctx.event_tx.send(Event::Response(reply)).await {
+                                    warn!(error = %e, "Failed to answer forget-me command");
+                                }
+                                continue;
+                            };
+                            // Erasing touches every store, so it runs beside the event loop.
+                            let event_tx = ctx.event_tx.clone();
+                            tokio::spawn(async move {
+                                let subject = ForgetSubject::Account {
+                                    platform: command.platform,
+                                    user_id: command.user_id.clone(),
+                                    username: Some(command.username.clone()),
+                                };
+                                let text = match forgetter.forget(&subject, "user").await {
+                                    Ok(receipt) if receipt.is_complete() => FORGET_ME_ERASED_REPLY,
+                                    Ok(_) => FORGET_ME_PARTIAL_REPLY,
+                                    Err(e) => {
+                                        error!(error = %e, user = %command.username, "Failed to forget user");
+                                        FORGET_ME_PARTIAL_REPLY
+                                    }
+                                };
+                                if let Err(e) = event_tx.send(Event::Response(command.reply(text))).await {
+                                    warn!(error = %e, "Failed to answer forget-me command");
+                                }
+                            });
+                        }
+                        Ok(Event::Command(command)) if matches!(command.command, Command::Link { .. }) => {
+                            let Command::Link { code } = &command.command else {
+                                continue;
+                            };
+                            let text = match &identity {
+                                Some(identity) => {
+                                    Self::link_accounts(identity, graph.as_ref(), &command, code.as_deref()).await
+                                }
+                                None => LINK_UNAVAILABLE_REPLY.to_string(),
+                            };
+                            if let Err(e) = ctx.event_tx.send(Event::Response(command.reply(text))).await {
+                                warn!(error = %e, "Failed to answer link command");
+                            }
+                        }
+                        Ok(Event::MessageDeleted(deleted)) => {
+                            let key = ConversationKey::new(deleted.platform, deleted.channel_id.clone());
+                            let in_session = {
+                                let mut stm = short_term.lock().await;
+                                stm.remove_message(&key, &deleted.message_id)
+                            };
+                            debug!(
+                                conversation = %key,
+                                message = %deleted.message_id,
+                                in_session,
+                                "Tombstoning deleted message in memory"
+                            );
 
-                            Self::persist_message(&writer_tx, &writer_store, msg.clone(), "bot_turn").await;
+                            let write = StoreWrite::Tombstone {
+                                key,
+                                id: deleted.message_id,
+                                at: deleted.timestamp,
+                            };
+                            Self::persist_message(&writer_tx, &writer_store, write, "message_delete").await;
                         }
                         Ok(_) => {}
                         Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
@@ -388,21 +963,13 @@ impl Worker for MemoryWorker {
                         stm.flush_expired()
                     };
 
-                    for (key, messages) in expired {
+                    for ended in expired {
                         info!(
-                            conversation = %key,
-                            messages = messages.len(),
+                            conversation = %ended.key,
+                            messages = ended.messages.len(),
                                 "Session expired, flushed to store"
                             );
-                        if let Some(comp) = &compressor {
-                            Self::ingest_session(
-                                messages,
-                                Arc::clone(comp),
-                                Arc::clone(&embedder),
-                                Arc::clone(&episodic),
-                                Arc::clone(&ingest_limiter),
-                            );
-                        }
+                        Self::finish_session(&writer_tx, &writer_store, ingest.as_ref(), ended).await;
                     }
                 }
                 _ = shutdown_rx.recv() => {
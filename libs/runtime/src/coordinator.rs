@@ -138,7 +138,22 @@ impl Coordinator {
                 }
             }
 
-            Event::BotTurnCompletion(_) => {
+            Event::Typing(_) | Event::BotTurnCompletion(_) => {
+                let _ = self.broadcast_tx.send(event);
+            }
+
+            Event::MessageEdited(_) | Event::MessageDeleted(_) | Event::Reaction(_) => {
+                debug!(event = ?event, "Broadcasting platform message update");
+                let _ = self.broadcast_tx.send(event);
+            }
+
+            Event::Command(command) => {
+                info!(
+                    platform = %command.platform,
+                    user = %command.username,
+                    command = ?command.command,
+                    "Coordinator received command"
+                );
                 let _ = self.broadcast_tx.send(event);
             }
 
@@ -102,6 +102,7 @@ mod tests {
         let event = Event::Raw(RawEvent {
             platform: Platform::Discord,
             channel_id: "ch1".to_string(),
+            thread_id: None,
             message_id: "m1".to_string(),
             user_id: "u1".to_string(),
             username: "user".to_string(),
@@ -110,6 +111,8 @@ mod tests {
             is_mention: false,
             is_dm: false,
             timestamp: chrono::Utc::now(),
+            reply_to: None,
+            is_backfill: false,
         });
 
         bus.broadcast_tx.send(event).unwrap();
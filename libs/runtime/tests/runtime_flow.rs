@@ -61,6 +61,7 @@ fn raw_event(content: &str) -> Event {
     Event::Raw(RawEvent {
         platform: Platform::Cli,
         channel_id: "cli".to_string(),
+        thread_id: None,
         message_id: "m1".to_string(),
         user_id: "u1".to_string(),
         username: "tester".to_string(),
@@ -69,6 +70,8 @@ fn raw_event(content: &str) -> Event {
         is_mention: false,
         is_dm: true,
         timestamp: Utc::now(),
+        reply_to: None,
+        is_backfill: false,
     })
 }
 
@@ -0,0 +1,31 @@
+#![no_main]
+
+//! Feeds arbitrary bytes through the relay frame reader and decoder, as
+//! both ends of the connection would see them.
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use sensory::relay::protocol::{decode_frame, read_frame};
+use sensory::relay::{AgentMessage, PlatformMessage};
+
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("fuzz runtime")
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    runtime().block_on(async {
+        let mut reader = data;
+        while let Ok(Some(frame)) = read_frame(&mut reader, MAX_FRAME_BYTES).await {
+            let _ = decode_frame::<PlatformMessage>(&frame, MAX_FRAME_BYTES);
+            let _ = decode_frame::<AgentMessage>(&frame, MAX_FRAME_BYTES);
+        }
+    });
+});
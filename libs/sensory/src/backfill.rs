@@ -0,0 +1,277 @@
+//! Catching up on messages sent while a platform process was down.
+//!
+//! Workers record the newest message id they ingested in each channel as a
+//! high-water mark in [`HighWaterMarks`]. After reconnecting they look for
+//! messages past that mark, keep those allowed by [`BackfillConfig`], and
+//! ingest them with `RawEvent::is_backfill` set. Message ids must grow
+//! over time within a channel, as Discord snowflakes and Telegram message
+//! ids do.
+
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use kernel::event::Platform;
+use rusqlite::{params, Connection, OptionalExtension};
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    pub enabled: bool,
+    /// Messages older than this are not caught up on.
+    pub max_age: Duration,
+    /// Most messages caught up on per channel; the newest are kept.
+    pub max_messages: usize,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_age: Duration::from_secs(6 * 60 * 60),
+            max_messages: 50,
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+}
+
+impl BackfillConfig {
+    /// `BACKFILL_ENABLED`, `BACKFILL_MAX_AGE_SECS` and `BACKFILL_MAX_MESSAGES`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: env_parse("BACKFILL_ENABLED").unwrap_or(defaults.enabled),
+            max_age: env_parse("BACKFILL_MAX_AGE_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_age),
+            max_messages: env_parse("BACKFILL_MAX_MESSAGES").unwrap_or(defaults.max_messages),
+        }
+    }
+
+    /// Whether a message sent at `sent_at` is recent enough to catch up on.
+    pub fn admits(&self, sent_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let age = now.signed_duration_since(sent_at);
+        self.enabled && age.to_std().map_or(true, |age| age <= self.max_age)
+    }
+
+    /// The messages of one channel worth catching up on: newer than `mark`,
+    /// within the age limit, at most `max_messages` of them, oldest first.
+    pub fn select<T>(
+        &self,
+        mut messages: Vec<T>,
+        mark: u64,
+        now: DateTime<Utc>,
+        key: impl Fn(&T) -> (u64, DateTime<Utc>),
+    ) -> Vec<T> {
+        messages.retain(|message| {
+            let (id, sent_at) = key(message);
+            id > mark && self.admits(sent_at, now)
+        });
+        messages.sort_by_key(|message| key(message).0);
+        let skip = messages.len().saturating_sub(self.max_messages);
+        messages.split_off(skip)
+    }
+}
+
+/// The newest ingested message id per channel of one platform, persisted
+/// in SQLite so it survives the platform process.
+#[derive(Clone)]
+pub struct HighWaterMarks {
+    platform: Platform,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl HighWaterMarks {
+    pub fn open(path: &str, platform: Platform) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create backfill directory: {}", parent.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open backfill database: {}", path))?;
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+
+        let marks = Self::with_connection(conn, platform)?;
+        info!(path = %path, platform = %platform, "Backfill high-water marks opened");
+        Ok(marks)
+    }
+
+    pub fn open_in_memory(platform: Platform) -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .context("Failed to open in-memory backfill database")?;
+        Self::with_connection(conn, platform)
+    }
+
+    fn with_connection(conn: Connection, platform: Platform) -> Result<Self> {
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS high_water_marks (
+                platform TEXT NOT NULL,
+                channel_id TEXT NOT NULL,
+                message_id INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (platform, channel_id)
+            );
+            ",
+        )?;
+        Ok(Self {
+            platform,
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move the channel's mark up to `message_id`. Older ids leave it alone.
+    pub fn record(&self, channel_id: &str, message_id: u64) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO high_water_marks (platform, channel_id, message_id, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (platform, channel_id) DO UPDATE SET
+                message_id = MAX(message_id, excluded.message_id),
+                updated_at = excluded.updated_at",
+            params![
+                self.platform.to_string(),
+                channel_id,
+                message_id as i64,
+                Utc::now().timestamp_millis(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, channel_id: &str) -> Result<Option<u64>> {
+        let mark = self
+            .conn()
+            .query_row(
+                "SELECT message_id FROM high_water_marks WHERE platform = ?1 AND channel_id = ?2",
+                params![self.platform.to_string(), channel_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        Ok(mark.map(|id| id as u64))
+    }
+
+    /// Every channel with a mark, as `(channel_id, message_id)`.
+    pub fn all(&self) -> Result<Vec<(String, u64)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT channel_id, message_id FROM high_water_marks
+             WHERE platform = ?1
+             ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_map(params![self.platform.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        let mut marks = Vec::new();
+        for row in rows {
+            marks.push(row?);
+        }
+        Ok(marks)
+    }
+}
+
+/// Ids of recently ingested messages. A message fetched while catching up
+/// may also arrive live; only its first sighting is ingested.
+#[derive(Clone, Default)]
+pub struct RecentMessages(Arc<Mutex<RecentIds>>);
+
+#[derive(Default)]
+struct RecentIds {
+    ids: HashSet<(String, u64)>,
+    order: VecDeque<(String, u64)>,
+}
+
+impl RecentMessages {
+    const CAPACITY: usize = 4096;
+
+    /// Remember the message; `false` when it was already seen.
+    pub fn first_sighting(&self, channel_id: &str, message_id: u64) -> bool {
+        let key = (channel_id.to_string(), message_id);
+        let mut recent = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !recent.ids.insert(key.clone()) {
+            return false;
+        }
+        recent.order.push_back(key);
+        while recent.order.len() > Self::CAPACITY {
+            if let Some(oldest) = recent.order.pop_front() {
+                recent.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_only_move_forward() {
+        let marks = HighWaterMarks::open_in_memory(Platform::Discord).unwrap();
+        assert_eq!(marks.get("c1").unwrap(), None);
+
+        marks.record("c1", 10).unwrap();
+        marks.record("c1", 7).unwrap();
+        marks.record("c2", 3).unwrap();
+        assert_eq!(marks.get("c1").unwrap(), Some(10));
+
+        let mut all = marks.all().unwrap();
+        all.sort();
+        assert_eq!(all, vec![("c1".to_string(), 10), ("c2".to_string(), 3)]);
+
+        let other = HighWaterMarks {
+            platform: Platform::Telegram,
+            conn: Arc::clone(&marks.conn),
+        };
+        assert_eq!(other.get("c1").unwrap(), None);
+    }
+
+    #[test]
+    fn select_keeps_the_newest_recent_messages_in_order() {
+        let now = Utc::now();
+        let config = BackfillConfig {
+            enabled: true,
+            max_age: Duration::from_secs(3600),
+            max_messages: 2,
+        };
+        let minutes_ago = |m: i64| now - chrono::Duration::minutes(m);
+        let messages = vec![
+            (14, minutes_ago(1)),
+            (11, minutes_ago(300)),
+            (12, minutes_ago(30)),
+            (13, minutes_ago(20)),
+            (9, minutes_ago(2)),
+        ];
+
+        let picked = config.select(messages, 10, now, |m| *m);
+        let ids: Vec<u64> = picked.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![13, 14]);
+    }
+
+    #[test]
+    fn disabled_backfill_admits_nothing() {
+        let config = BackfillConfig {
+            enabled: false,
+            ..BackfillConfig::default()
+        };
+        assert!(!config.admits(Utc::now(), Utc::now()));
+    }
+
+    #[test]
+    fn recent_messages_report_repeats() {
+        let recent = RecentMessages::default();
+        assert!(recent.first_sighting("c1", 1));
+        assert!(!recent.first_sighting("c1", 1));
+        assert!(recent.first_sighting("c2", 1));
+    }
+}
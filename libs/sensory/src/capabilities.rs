@@ -0,0 +1,76 @@
+//! What a platform connection can render and send, declared in the relay hello.
+
+use kernel::event::Platform;
+use serde::{Deserialize, Serialize};
+
+/// Markup dialect the platform renders responses in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkupDialect {
+    DiscordMarkdown,
+    TelegramMarkdownV2,
+    TelegramHtml,
+    Plain,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformCapabilities {
+    /// Longest message the platform accepts, in characters.
+    pub max_message_chars: usize,
+    pub markup: MarkupDialect,
+    /// The client can edit and delete messages it sent, so streamed replies
+    /// are delivered as progressive edits. Otherwise only the final text is
+    /// sent, and `Edit`/`Delete` actions are dropped.
+    #[serde(default)]
+    pub supports_edits: bool,
+    /// The client can react to messages. Otherwise reactions are sent as a
+    /// reply containing the emoji.
+    #[serde(default)]
+    pub reactions: bool,
+    /// The client can show a typing indicator (`AgentMessage::Typing`).
+    #[serde(default)]
+    pub typing: bool,
+}
+
+impl PlatformCapabilities {
+    /// What each platform supports when its connection does not say otherwise.
+    pub fn defaults_for(platform: Platform) -> Self {
+        match platform {
+            Platform::Discord | Platform::DiscordSelfbot => Self {
+                max_message_chars: 2000,
+                markup: MarkupDialect::DiscordMarkdown,
+                supports_edits: platform == Platform::Discord,
+                reactions: platform == Platform::Discord,
+                typing: platform == Platform::Discord,
+            },
+            Platform::Telegram => Self {
+                max_message_chars: 4096,
+                markup: MarkupDialect::TelegramHtml,
+                supports_edits: true,
+                reactions: true,
+                typing: true,
+            },
+            Platform::Cli => Self {
+                max_message_chars: 16_000,
+                markup: MarkupDialect::Plain,
+                supports_edits: false,
+                reactions: false,
+                typing: false,
+            },
+            Platform::Irc => Self {
+                max_message_chars: 1_500,
+                markup: MarkupDialect::Plain,
+                supports_edits: false,
+                reactions: false,
+                typing: false,
+            },
+            Platform::Http => Self {
+                max_message_chars: 16_000,
+                markup: MarkupDialect::Plain,
+                supports_edits: true,
+                reactions: true,
+                typing: true,
+            },
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Text commands (`/status`, `/state warmth=0.7`) typed on platforms without
+//! a structured command interface.
+
+use kernel::event::Command;
+
+/// Commands offered to users, as `(name, description)`, for help text and
+/// platform command menus.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("forget", "Start a fresh conversation"),
+    ("forget_me", "Erase everything I remember about you"),
+    ("status", "Show whether I'm online and answering here"),
+    ("mute", "Stop answering in this chat"),
+    ("unmute", "Answer in this chat again"),
+    ("link", "Link your accounts on other platforms, e.g. /link 123456"),
+    ("state", "Operators: adjust my state, e.g. /state warmth=0.7"),
+    ("help", "List commands"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextCommand {
+    /// A command for the agent.
+    Agent(Command),
+    /// `/help` or `/start`; answered by the platform worker itself.
+    Help,
+    Unknown(String),
+}
+
+/// Parse a message starting with `/`. `/name@bot` addressed to a different
+/// bot than `bot_username` is not ours and yields `None`.
+pub fn parse_text_command(text: &str, bot_username: Option<&str>) -> Option<TextCommand> {
+    let rest = text.trim_start().strip_prefix('/')?;
+    let (head, args) = match rest.split_once(char::is_whitespace) {
+        Some((head, args)) => (head, args.trim()),
+        None => (rest, ""),
+    };
+    let name = match head.split_once('@') {
+        Some((name, target)) => {
+            if !bot_username.is_some_and(|bot| bot.eq_ignore_ascii_case(target)) {
+                return None;
+            }
+            name
+        }
+        None => head,
+    };
+    if name.is_empty() {
+        return None;
+    }
+
+    let command = match name.to_ascii_lowercase().as_str() {
+        "forget" => TextCommand::Agent(Command::Forget),
+        "forget_me" | "forget-me" => TextCommand::Agent(Command::ForgetMe),
+        "status" => TextCommand::Agent(Command::Status),
+        "mute" => TextCommand::Agent(Command::Mute),
+        "unmute" => TextCommand::Agent(Command::Unmute),
+        "link" => TextCommand::Agent(Command::Link {
+            code: Some(args.to_string()).filter(|code| !code.is_empty()),
+        }),
+        "state" => TextCommand::Agent(Command::State {
+            args: args.to_string(),
+        }),
+        "help" | "start" => TextCommand::Help,
+        other => TextCommand::Unknown(other.to_string()),
+    };
+    Some(command)
+}
+
+pub fn help_text() -> String {
+    let mut text = String::from("Commands:");
+    for (name, description) in COMMANDS {
+        text.push_str(&format!("\n/{name} - {description}"));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_addressed_to_us() {
+        assert_eq!(
+            parse_text_command("/status", Some("agent_bot")),
+            Some(TextCommand::Agent(Command::Status))
+        );
+        assert_eq!(
+            parse_text_command("/Mute@Agent_Bot", Some("agent_bot")),
+            Some(TextCommand::Agent(Command::Mute))
+        );
+        assert_eq!(
+            parse_text_command("/state  warmth=0.7 curiosity+=0.1 ", None),
+            Some(TextCommand::Agent(Command::State {
+                args: "warmth=0.7 curiosity+=0.1".to_string()
+            }))
+        );
+        assert_eq!(
+            parse_text_command("/link 042917", None),
+            Some(TextCommand::Agent(Command::Link {
+                code: Some("042917".to_string())
+            }))
+        );
+        assert_eq!(
+            parse_text_command("/link", None),
+            Some(TextCommand::Agent(Command::Link { code: None }))
+        );
+        assert_eq!(
+            parse_text_command("/forget-me", None),
+            Some(TextCommand::Agent(Command::ForgetMe))
+        );
+        assert_eq!(
+            parse_text_command("/forget_me@agent_bot", Some("agent_bot")),
+            Some(TextCommand::Agent(Command::ForgetMe))
+        );
+        assert_eq!(parse_text_command("/start", None), Some(TextCommand::Help));
+        assert_eq!(
+            parse_text_command("/dance", None),
+            Some(TextCommand::Unknown("dance".to_string()))
+        );
+    }
+
+    #[test]
+    fn ignores_plain_text_and_other_bots() {
+        assert_eq!(parse_text_command("hello /status", None), None);
+        assert_eq!(parse_text_command("/status@other_bot", Some("agent_bot")), None);
+        assert_eq!(parse_text_command("/", None), None);
+    }
+}
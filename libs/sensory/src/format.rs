@@ -0,0 +1,676 @@
+//! Outbound response formatting.
+//!
+//! The model writes CommonMark-ish markdown. Before a response leaves the
+//! agent it is split to fit the platform's message limit (on paragraph,
+//! sentence and word boundaries, never inside a code block that fits) and
+//! each chunk is rendered into the platform's markup dialect.
+
+use crate::capabilities::MarkupDialect;
+
+const FENCE: &str = "```";
+
+/// Split `text` into chunks of at most `max_chars` characters.
+///
+/// Fenced code blocks stay whole when they fit in one message; larger ones
+/// are cut on line boundaries and each piece is re-fenced so it still renders
+/// as code.
+pub fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    if char_len(text) <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let pieces = markdown_blocks(text).into_iter().flat_map(|block| {
+        if block.starts_with(FENCE) {
+            split_code_block(&block, max_chars)
+        } else {
+            split_lines(&block, max_chars)
+        }
+    });
+    pack(pieces, "\n\n", max_chars)
+}
+
+/// Render markdown into the given platform dialect.
+pub fn render(markdown: &str, dialect: MarkupDialect) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut code: Option<(String, Vec<&str>)> = None;
+    let mut in_quote = false;
+
+    for line in markdown.lines() {
+        if let Some((lang, lines)) = code.as_mut() {
+            if line.trim_start().starts_with(FENCE) {
+                out.push(render_code_block(lang, lines, dialect));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        let quote = line
+            .strip_prefix('>')
+            .map(|rest| rest.strip_prefix(' ').unwrap_or(rest));
+        if in_quote && quote.is_none() && dialect == MarkupDialect::TelegramHtml {
+            if let Some(last) = out.last_mut() {
+                last.push_str("</blockquote>");
+            }
+        }
+
+        if let Some(lang) = line.trim_start().strip_prefix(FENCE) {
+            in_quote = false;
+            code = Some((lang.trim().to_string(), Vec::new()));
+            continue;
+        }
+
+        if let Some(text) = quote {
+            let body = render_inline(text, dialect);
+            out.push(match dialect {
+                MarkupDialect::DiscordMarkdown => line.to_string(),
+                MarkupDialect::TelegramHtml if in_quote => body,
+                MarkupDialect::TelegramHtml => format!("<blockquote>{body}"),
+                MarkupDialect::TelegramMarkdownV2 | MarkupDialect::Plain => format!(">{body}"),
+            });
+            in_quote = true;
+            continue;
+        }
+        in_quote = false;
+
+        out.push(render_line(line, dialect));
+    }
+
+    if in_quote && dialect == MarkupDialect::TelegramHtml {
+        if let Some(last) = out.last_mut() {
+            last.push_str("</blockquote>");
+        }
+    }
+    // An unterminated fence still renders as code.
+    if let Some((lang, lines)) = code {
+        out.push(render_code_block(&lang, &lines, dialect));
+    }
+
+    out.join("\n")
+}
+
+/// Strip Telegram HTML from [`render`] back to plain text, for resending a
+/// chunk the platform refused to parse. Link targets follow their text, as
+/// they do for [`MarkupDialect::Plain`].
+pub fn html_to_plain(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut link: Option<(usize, &str)> = None;
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let Some(len) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..len];
+        if let Some(url) = tag
+            .strip_prefix("a href=\"")
+            .and_then(|tag| tag.strip_suffix('"'))
+        {
+            link = Some((out.len(), url));
+        } else if tag == "/a" {
+            if let Some((start, url)) = link.take() {
+                if unescape_html(&out[start..]) != unescape_html(url) {
+                    out.push_str(&format!(" ({url})"));
+                }
+            }
+        }
+        rest = &rest[len + 1..];
+    }
+    out.push_str(rest);
+    unescape_html(&out)
+}
+
+fn char_len(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Paragraphs (separated by blank lines) and whole fenced code blocks.
+fn markdown_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_code = false;
+
+    for line in text.lines() {
+        let is_fence = line.trim_start().starts_with(FENCE);
+        if in_code {
+            current.push(line);
+            if is_fence {
+                blocks.push(current.join("\n"));
+                current.clear();
+                in_code = false;
+            }
+        } else if is_fence {
+            if !current.is_empty() {
+                blocks.push(current.join("\n"));
+                current.clear();
+            }
+            current.push(line.trim_start());
+            in_code = true;
+        } else if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current.join("\n"));
+    }
+    blocks
+}
+
+fn split_code_block(block: &str, max_chars: usize) -> Vec<String> {
+    if char_len(block) <= max_chars {
+        return vec![block.to_string()];
+    }
+
+    let mut lines: Vec<&str> = block.lines().collect();
+    let open = lines.remove(0);
+    if lines
+        .last()
+        .is_some_and(|l| l.trim_start().starts_with(FENCE))
+    {
+        lines.pop();
+    }
+
+    // Opening fence, newline, body, newline, closing fence.
+    let overhead = char_len(open) + 1 + 1 + FENCE.len();
+    if overhead >= max_chars {
+        return split_lines(&lines.join("\n"), max_chars);
+    }
+    let budget = max_chars - overhead;
+
+    let body = lines.into_iter().flat_map(|line| hard_split(line, budget));
+    pack(body, "\n", budget)
+        .into_iter()
+        .map(|chunk| format!("{open}\n{chunk}\n{FENCE}"))
+        .collect()
+}
+
+fn split_lines(block: &str, max_chars: usize) -> Vec<String> {
+    if char_len(block) <= max_chars {
+        return vec![block.to_string()];
+    }
+    let pieces = block
+        .lines()
+        .flat_map(|line| split_sentences(line, max_chars));
+    pack(pieces, "\n", max_chars)
+}
+
+fn split_sentences(line: &str, max_chars: usize) -> Vec<String> {
+    if char_len(line) <= max_chars {
+        return vec![line.to_string()];
+    }
+    let pieces = sentences(line)
+        .into_iter()
+        .flat_map(|sentence| split_words(sentence, max_chars));
+    pack(pieces, " ", max_chars)
+}
+
+fn split_words(sentence: &str, max_chars: usize) -> Vec<String> {
+    if char_len(sentence) <= max_chars {
+        return vec![sentence.to_string()];
+    }
+    let pieces = sentence
+        .split_whitespace()
+        .flat_map(|word| hard_split(word, max_chars));
+    pack(pieces, " ", max_chars)
+}
+
+fn hard_split(s: &str, max_chars: usize) -> Vec<String> {
+    if char_len(s) <= max_chars {
+        return vec![s.to_string()];
+    }
+    let chars: Vec<char> = s.chars().collect();
+    chars
+        .chunks(max_chars)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Sentences ending in `.`, `!` or `?` followed by whitespace, trimmed.
+fn sentences(line: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut prev_terminal = false;
+    for (i, c) in line.char_indices() {
+        if prev_terminal && c.is_whitespace() {
+            let sentence = line[start..i].trim();
+            if !sentence.is_empty() {
+                out.push(sentence);
+            }
+            start = i;
+        }
+        prev_terminal = matches!(c, '.' | '!' | '?');
+    }
+    let rest = line[start..].trim();
+    if !rest.is_empty() {
+        out.push(rest);
+    }
+    out
+}
+
+/// Greedily join pieces (each already within the limit) into chunks.
+fn pack(pieces: impl IntoIterator<Item = String>, sep: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    let sep_len = char_len(sep);
+
+    for piece in pieces {
+        let piece_len = char_len(&piece);
+        if current.is_empty() {
+            current = piece;
+            current_len = piece_len;
+        } else if current_len + sep_len + piece_len <= max_chars {
+            current.push_str(sep);
+            current.push_str(&piece);
+            current_len += sep_len + piece_len;
+        } else {
+            chunks.push(std::mem::replace(&mut current, piece));
+            current_len = piece_len;
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn render_code_block(lang: &str, lines: &[&str], dialect: MarkupDialect) -> String {
+    let code = lines.join("\n");
+    match dialect {
+        MarkupDialect::DiscordMarkdown => format!("{FENCE}{lang}\n{code}\n{FENCE}"),
+        MarkupDialect::TelegramHtml if lang.is_empty() => {
+            format!("<pre>{}</pre>", escape_html(&code))
+        }
+        MarkupDialect::TelegramHtml => format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>",
+            escape_html(lang),
+            escape_html(&code)
+        ),
+        MarkupDialect::TelegramMarkdownV2 => {
+            format!("{FENCE}{lang}\n{}\n{FENCE}", escape_v2_code(&code))
+        }
+        MarkupDialect::Plain => code,
+    }
+}
+
+fn render_line(line: &str, dialect: MarkupDialect) -> String {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+        let text = trimmed[hashes..].trim();
+        return match dialect {
+            // Discord renders up to `###`; deeper headings show the hashes.
+            MarkupDialect::DiscordMarkdown if hashes <= 3 => line.to_string(),
+            MarkupDialect::DiscordMarkdown => format!("**{text}**"),
+            MarkupDialect::TelegramHtml => format!("<b>{}</b>", render_inline(text, dialect)),
+            MarkupDialect::TelegramMarkdownV2 => format!("*{}*", render_inline(text, dialect)),
+            MarkupDialect::Plain => render_inline(text, dialect),
+        };
+    }
+
+    if dialect == MarkupDialect::DiscordMarkdown {
+        return line.to_string();
+    }
+
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = trimmed.strip_prefix(bullet) {
+            return format!("{indent}• {}", render_inline(text, dialect));
+        }
+    }
+
+    format!("{indent}{}", render_inline(trimmed, dialect))
+}
+
+#[derive(Debug, PartialEq)]
+enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Strike(Vec<Inline>),
+    Code(String),
+    Link { text: Vec<Inline>, url: String },
+}
+
+fn render_inline(text: &str, dialect: MarkupDialect) -> String {
+    if dialect == MarkupDialect::DiscordMarkdown {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    for node in parse_inline(&chars) {
+        emit_inline(&node, dialect, &mut out);
+    }
+    out
+}
+
+fn parse_inline(chars: &[char]) -> Vec<Inline> {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let parsed = match c {
+            '\\' if chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()) => {
+                text.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            '`' => find(chars, i + 1, &['`']).map(|end| {
+                let code = chars[i + 1..end].iter().collect();
+                (Inline::Code(code), end + 1)
+            }),
+            '*' | '_' if chars.get(i + 1) == Some(&c) => find_closing(chars, i + 2, &[c, c])
+                .map(|end| (Inline::Bold(parse_inline(&chars[i + 2..end])), end + 2)),
+            '~' if chars.get(i + 1) == Some(&'~') => find_closing(chars, i + 2, &['~', '~'])
+                .map(|end| (Inline::Strike(parse_inline(&chars[i + 2..end])), end + 2)),
+            '*' => find_closing(chars, i + 1, &['*'])
+                .map(|end| (Inline::Italic(parse_inline(&chars[i + 1..end])), end + 1)),
+            // `_` only opens at a word boundary so snake_case stays literal.
+            '_' if i == 0 || !chars[i - 1].is_alphanumeric() => find_closing(chars, i + 1, &['_'])
+                .filter(|end| chars.get(end + 1).is_none_or(|n| !n.is_alphanumeric()))
+                .map(|end| (Inline::Italic(parse_inline(&chars[i + 1..end])), end + 1)),
+            '[' => parse_link(chars, i),
+            _ => None,
+        };
+
+        match parsed {
+            Some((node, next)) => {
+                if !text.is_empty() {
+                    nodes.push(Inline::Text(std::mem::take(&mut text)));
+                }
+                nodes.push(node);
+                i = next;
+            }
+            None => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(Inline::Text(text));
+    }
+    nodes
+}
+
+fn parse_link(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    let close = find(chars, start + 1, &[']'])?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = find(chars, close + 2, &[')'])?;
+    let url: String = chars[close + 2..end].iter().collect();
+    if close == start + 1 || url.trim().is_empty() {
+        return None;
+    }
+    Some((
+        Inline::Link {
+            text: parse_inline(&chars[start + 1..close]),
+            url,
+        },
+        end + 1,
+    ))
+}
+
+fn find(chars: &[char], from: usize, delim: &[char]) -> Option<usize> {
+    (from..chars.len().saturating_sub(delim.len() - 1)).find(|&j| chars[j..].starts_with(delim))
+}
+
+/// Like [`find`], but the delimited span must be non-empty and not start or
+/// end with whitespace (`2 * 3 * 4` is not emphasis).
+fn find_closing(chars: &[char], from: usize, delim: &[char]) -> Option<usize> {
+    if chars.get(from).is_none_or(|c| c.is_whitespace()) {
+        return None;
+    }
+    let end = find(chars, from, delim)?;
+    (end > from && !chars[end - 1].is_whitespace()).then_some(end)
+}
+
+fn emit_inline(node: &Inline, dialect: MarkupDialect, out: &mut String) {
+    let wrap = |open: &str, children: &[Inline], close: &str, out: &mut String| {
+        out.push_str(open);
+        for child in children {
+            emit_inline(child, dialect, out);
+        }
+        out.push_str(close);
+    };
+
+    match (node, dialect) {
+        (Inline::Text(text), MarkupDialect::TelegramHtml) => out.push_str(&escape_html(text)),
+        (Inline::Text(text), MarkupDialect::TelegramMarkdownV2) => out.push_str(&escape_v2(text)),
+        (Inline::Text(text), _) => out.push_str(text),
+
+        (Inline::Code(code), MarkupDialect::TelegramHtml) => {
+            out.push_str(&format!("<code>{}</code>", escape_html(code)))
+        }
+        (Inline::Code(code), MarkupDialect::TelegramMarkdownV2) => {
+            out.push_str(&format!("`{}`", escape_v2_code(code)))
+        }
+        (Inline::Code(code), _) => out.push_str(code),
+
+        (Inline::Bold(children), MarkupDialect::TelegramHtml) => wrap("<b>", children, "</b>", out),
+        (Inline::Bold(children), MarkupDialect::TelegramMarkdownV2) => {
+            wrap("*", children, "*", out)
+        }
+        (Inline::Italic(children), MarkupDialect::TelegramHtml) => {
+            wrap("<i>", children, "</i>", out)
+        }
+        (Inline::Italic(children), MarkupDialect::TelegramMarkdownV2) => {
+            wrap("_", children, "_", out)
+        }
+        (Inline::Strike(children), MarkupDialect::TelegramHtml) => {
+            wrap("<s>", children, "</s>", out)
+        }
+        (Inline::Strike(children), MarkupDialect::TelegramMarkdownV2) => {
+            wrap("~", children, "~", out)
+        }
+        (Inline::Bold(children) | Inline::Italic(children) | Inline::Strike(children), _) => {
+            wrap("", children, "", out)
+        }
+
+        (Inline::Link { text, url }, MarkupDialect::TelegramHtml) => wrap(
+            &format!("<a href=\"{}\">", escape_html(url).replace('"', "&quot;")),
+            text,
+            "</a>",
+            out,
+        ),
+        (Inline::Link { text, url }, MarkupDialect::TelegramMarkdownV2) => {
+            let url = url.replace('\\', "\\\\").replace(')', "\\)");
+            wrap("[", text, &format!("]({url})"), out)
+        }
+        (Inline::Link { text, url }, _) => {
+            let start = out.len();
+            wrap("", text, "", out);
+            if out[start..] != *url {
+                out.push_str(&format!(" ({url})"));
+            }
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+fn escape_v2(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn escape_v2_code(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('`', "\\`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_fits(chunks: &[String], max: usize) {
+        for chunk in chunks {
+            assert!(char_len(chunk) <= max, "chunk over {max}: {chunk:?}");
+        }
+    }
+
+    #[test]
+    fn short_message_is_not_split() {
+        assert_eq!(split_message("hello there", 2000), vec!["hello there"]);
+    }
+
+    #[test]
+    fn splits_on_paragraphs_then_sentences() {
+        let text = "First paragraph is here.\n\nSecond one. It has two sentences.";
+        let chunks = split_message(text, 30);
+        assert_fits(&chunks, 30);
+        assert_eq!(
+            chunks,
+            vec![
+                "First paragraph is here.",
+                "Second one.",
+                "It has two sentences."
+            ]
+        );
+    }
+
+    #[test]
+    fn long_words_are_hard_split() {
+        let word = "a".repeat(25);
+        let chunks = split_message(&word, 10);
+        assert_fits(&chunks, 10);
+        assert_eq!(chunks.concat(), word);
+    }
+
+    #[test]
+    fn code_block_that_fits_stays_whole() {
+        let text = format!(
+            "{}\n\n```rust\nfn main() {{}}\n```\n\n{}",
+            "x".repeat(20),
+            "y".repeat(20)
+        );
+        let chunks = split_message(&text, 35);
+        assert_fits(&chunks, 35);
+        assert!(chunks.contains(&"```rust\nfn main() {}\n```".to_string()));
+    }
+
+    #[test]
+    fn oversized_code_block_is_refenced() {
+        let body: Vec<String> = (0..20).map(|i| format!("let v{i} = {i};")).collect();
+        let text = format!("```rust\n{}\n```", body.join("\n"));
+        let chunks = split_message(&text, 80);
+        assert!(chunks.len() > 1);
+        assert_fits(&chunks, 80);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("```rust\n"));
+            assert!(chunk.ends_with("\n```"));
+        }
+    }
+
+    #[test]
+    fn multibyte_text_is_measured_in_chars() {
+        let text = "é".repeat(15);
+        let chunks = split_message(&text, 10);
+        assert_eq!(chunks.len(), 2);
+        assert_fits(&chunks, 10);
+    }
+
+    #[test]
+    fn renders_telegram_html() {
+        let md = "## Title\n**bold** and *it* with `a<b>` & [link](https://x.y/?a=1&b=2)\n- item";
+        assert_eq!(
+            render(md, MarkupDialect::TelegramHtml),
+            "<b>Title</b>\n<b>bold</b> and <i>it</i> with <code>a&lt;b&gt;</code> &amp; \
+             <a href=\"https://x.y/?a=1&amp;b=2\">link</a>\n• item"
+        );
+    }
+
+    #[test]
+    fn snake_case_and_arithmetic_stay_literal() {
+        assert_eq!(
+            render(
+                "call some_func_name with 2 * 3 * 4",
+                MarkupDialect::TelegramHtml
+            ),
+            "call some_func_name with 2 * 3 * 4"
+        );
+    }
+
+    #[test]
+    fn renders_telegram_code_blocks_and_quotes() {
+        let md = "> quoted\n> more\nafter\n```py\nif a < b:\n```";
+        assert_eq!(
+            render(md, MarkupDialect::TelegramHtml),
+            "<blockquote>quoted\nmore</blockquote>\nafter\n\
+             <pre><code class=\"language-py\">if a &lt; b:</code></pre>"
+        );
+    }
+
+    #[test]
+    fn escapes_markdown_v2() {
+        assert_eq!(
+            render(
+                "**Done!** v1.2 (beta) `x_y`",
+                MarkupDialect::TelegramMarkdownV2
+            ),
+            "*Done\\!* v1\\.2 \\(beta\\) `x_y`"
+        );
+    }
+
+    #[test]
+    fn plain_strips_markup() {
+        assert_eq!(
+            render(
+                "# Hi\n**bold** ~~old~~ [docs](https://d.example)",
+                MarkupDialect::Plain
+            ),
+            "Hi\nbold old docs (https://d.example)"
+        );
+    }
+
+    #[test]
+    fn telegram_html_strips_back_to_plain_text() {
+        let html = render(
+            "# Hi\n**bold** a < b & [docs](https://d.example?a=1&b=2) [https://x.example](https://x.example)",
+            MarkupDialect::TelegramHtml,
+        );
+        assert_eq!(
+            html_to_plain(&html),
+            "Hi\nbold a < b & docs (https://d.example?a=1&b=2) https://x.example"
+        );
+        // A stray `<` is kept as text.
+        assert_eq!(html_to_plain("<b>ok</b> <i"), "ok <i");
+    }
+
+    #[test]
+    fn discord_passes_through_except_deep_headings() {
+        let md = "## Ok\n#### Deep\n**bold** _it_";
+        assert_eq!(
+            render(md, MarkupDialect::DiscordMarkdown),
+            "## Ok\n**Deep**\n**bold** _it_"
+        );
+    }
+}
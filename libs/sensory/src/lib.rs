@@ -1,7 +1,14 @@
+pub mod backfill;
 pub mod buffer;
+pub mod capabilities;
+pub mod commands;
+pub mod format;
+pub mod outbound;
 pub mod platform;
 pub mod relay;
 
+pub use backfill::{BackfillConfig, HighWaterMarks, RecentMessages};
 pub use buffer::SensoryBuffer;
+pub use capabilities::{MarkupDialect, PlatformCapabilities};
 pub use platform::PlatformAdapter;
-pub use relay::{PlatformRelayWorker, RelayClient};
+pub use relay::{PlatformRelayWorker, RelayClient, RelaySender};
@@ -0,0 +1,21 @@
+//! Durable outbound delivery between the dialogue engine and the relay.
+//!
+//! Responses are persisted before they are handed to a platform process and
+//! removed only once that process reports them sent. Each channel is a FIFO
+//! with a single delivery in flight; retries use exponential backoff or the
+//! platform's own retry-after hint.
+
+pub mod queue;
+pub mod rate_limit;
+pub mod store;
+
+pub use queue::{OutboundConfig, OutboundQueue, OutboundStats};
+pub use rate_limit::{PlatformRateLimits, RateLimit};
+pub use store::{OutboundStore, QueuedResponse};
+
+pub fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,585 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::Result;
+use kernel::event::{Platform, ResponseEvent, ResponseKind};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use super::rate_limit::{PlatformRateLimits, TokenBucket};
+use super::store::{OutboundStore, QueuedResponse};
+use crate::relay::protocol::DeliveryOutcome;
+
+#[derive(Debug, Clone)]
+pub struct OutboundConfig {
+    /// Failed deliveries are dead-lettered after this many attempts.
+    pub max_attempts: u32,
+    pub base_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// An unacknowledged delivery is retried after this long.
+    pub ack_timeout_ms: u64,
+    /// Entries older than this are dropped instead of sent; the conversation has moved on.
+    pub max_age_ms: i64,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 6,
+            base_backoff_ms: 1_000,
+            max_backoff_ms: 60_000,
+            ack_timeout_ms: 30_000,
+            max_age_ms: 15 * 60 * 1000,
+        }
+    }
+}
+
+type ChannelKey = (Platform, String);
+
+struct InFlight {
+    key: ChannelKey,
+    deadline_ms: i64,
+}
+
+/// Queue depth and delivery counters for the cockpit.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OutboundStats {
+    pub pending_by_platform: BTreeMap<String, usize>,
+    pub pending_channels: usize,
+    pub in_flight: usize,
+    pub oldest_pending_age_ms: Option<i64>,
+    pub delivered_total: u64,
+    pub retried_total: u64,
+    pub dead_lettered_total: u64,
+    /// Bus events the relay fell too far behind to see. Any of them may have
+    /// been a response that never reached the queue.
+    pub missed_total: u64,
+    /// Dead-lettered rows kept in the store, including ones from earlier runs.
+    pub dead_letter_rows: usize,
+}
+
+/// Per-channel FIFO of responses waiting for delivery.
+///
+/// Only the head of each channel is ever in flight, so multi-line replies
+/// arrive in order even when an earlier line has to be retried.
+pub struct OutboundQueue {
+    store: OutboundStore,
+    config: OutboundConfig,
+    channels: HashMap<ChannelKey, VecDeque<QueuedResponse>>,
+    in_flight: HashMap<u64, InFlight>,
+    channel_buckets: HashMap<ChannelKey, TokenBucket>,
+    platform_buckets: HashMap<Platform, TokenBucket>,
+    delivered_total: u64,
+    retried_total: u64,
+    dead_lettered_total: u64,
+    missed_total: u64,
+}
+
+impl OutboundQueue {
+    /// Restore undelivered entries from the store.
+    pub fn open(store: OutboundStore, config: OutboundConfig, now_ms: i64) -> Result<Self> {
+        let mut queue = Self {
+            store,
+            config,
+            channels: HashMap::new(),
+            in_flight: HashMap::new(),
+            channel_buckets: HashMap::new(),
+            platform_buckets: HashMap::new(),
+            delivered_total: 0,
+            retried_total: 0,
+            dead_lettered_total: 0,
+            missed_total: 0,
+        };
+
+        let pending = queue.store.load_pending()?;
+        let mut restored = 0usize;
+        for entry in pending {
+            if now_ms - entry.created_at > queue.config.max_age_ms {
+                queue.store.dead_letter(entry.id, "expired before restart")?;
+                queue.dead_lettered_total += 1;
+                continue;
+            }
+            queue
+                .channels
+                .entry(channel_key(&entry.event))
+                .or_default()
+                .push_back(entry);
+            restored += 1;
+        }
+        if restored > 0 {
+            debug!(restored, "Outbound queue restored pending responses");
+        }
+
+        Ok(queue)
+    }
+
+    pub fn enqueue(&mut self, event: &ResponseEvent, now_ms: i64) -> Result<u64> {
+        if let Some(id) = self.coalesce_stream_update(event)? {
+            return Ok(id);
+        }
+
+        let entry = self.store.insert(event, now_ms)?;
+        let id = entry.id;
+        self.channels
+            .entry(channel_key(event))
+            .or_default()
+            .push_back(entry);
+        Ok(id)
+    }
+
+    /// Take every channel head that may be sent now and mark it in flight.
+    pub fn take_ready(
+        &mut self,
+        now_ms: i64,
+        is_connected: impl Fn(Platform) -> bool,
+    ) -> Vec<QueuedResponse> {
+        let mut ready = Vec::new();
+
+        for (key, entries) in &self.channels {
+            let Some(head) = entries.front() else {
+                continue;
+            };
+            if self.in_flight.contains_key(&head.id)
+                || head.next_attempt_at > now_ms
+                || !is_connected(key.0)
+            {
+                continue;
+            }
+
+            let limits = PlatformRateLimits::for_platform(key.0);
+            let channel_ok = match limits.per_channel {
+                Some(limit) => self
+                    .channel_buckets
+                    .entry(key.clone())
+                    .or_insert_with(|| TokenBucket::new(limit, now_ms))
+                    .has_token(now_ms),
+                None => true,
+            };
+            let platform_ok = match limits.global {
+                Some(limit) => self
+                    .platform_buckets
+                    .entry(key.0)
+                    .or_insert_with(|| TokenBucket::new(limit, now_ms))
+                    .has_token(now_ms),
+                None => true,
+            };
+            if !channel_ok || !platform_ok {
+                continue;
+            }
+
+            if let Some(bucket) = self.channel_buckets.get_mut(key) {
+                bucket.take(now_ms);
+            }
+            if let Some(bucket) = self.platform_buckets.get_mut(&key.0) {
+                bucket.take(now_ms);
+            }
+            ready.push(head.clone());
+        }
+
+        for entry in &ready {
+            self.in_flight.insert(
+                entry.id,
+                InFlight {
+                    key: channel_key(&entry.event),
+                    deadline_ms: now_ms + self.config.ack_timeout_ms as i64,
+                },
+            );
+        }
+        ready
+    }
+
+    /// Apply a delivery report from the platform process.
+    pub fn complete(&mut self, id: u64, outcome: DeliveryOutcome, now_ms: i64) -> Result<()> {
+        let Some(in_flight) = self.in_flight.remove(&id) else {
+            debug!(delivery_id = id, "Outbound queue: report for unknown delivery");
+            return Ok(());
+        };
+        let key = in_flight.key;
+
+        match outcome {
+            DeliveryOutcome::Sent => {
+                self.store.remove(id)?;
+                self.pop_head(&key, id);
+                self.delivered_total += 1;
+            }
+            DeliveryOutcome::RetryAfter { retry_after_ms } => {
+                self.retried_total += 1;
+                let next = now_ms + retry_after_ms as i64;
+                if let Some(head) = self.head_mut(&key, id) {
+                    head.next_attempt_at = next;
+                    let attempts = head.attempts;
+                    self.store.reschedule(id, attempts, next)?;
+                }
+                debug!(delivery_id = id, retry_after_ms, "Outbound queue: platform rate limited");
+            }
+            DeliveryOutcome::Failed { error, retryable } => {
+                self.fail(&key, id, &error, retryable, now_ms)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Put an in-flight entry back without counting an attempt, e.g. when the
+    /// connection went away before the frame was written.
+    pub fn release(&mut self, id: u64) {
+        self.in_flight.remove(&id);
+    }
+
+    /// Retry deliveries whose acknowledgement never arrived.
+    pub fn expire_in_flight(&mut self, now_ms: i64) -> Result<()> {
+        let expired: Vec<u64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, f)| f.deadline_ms <= now_ms)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in expired {
+            if let Some(in_flight) = self.in_flight.remove(&id) {
+                self.fail(&in_flight.key, id, "delivery ack timed out", true, now_ms)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count bus events lost before they could be queued.
+    pub fn record_missed(&mut self, count: u64) {
+        self.missed_total += count;
+    }
+
+    pub fn stats(&self, now_ms: i64) -> OutboundStats {
+        let mut stats = OutboundStats {
+            in_flight: self.in_flight.len(),
+            delivered_total: self.delivered_total,
+            retried_total: self.retried_total,
+            dead_lettered_total: self.dead_lettered_total,
+            missed_total: self.missed_total,
+            dead_letter_rows: self.store.dead_letter_count().unwrap_or_default(),
+            ..OutboundStats::default()
+        };
+
+        for ((platform, _), entries) in &self.channels {
+            if entries.is_empty() {
+                continue;
+            }
+            stats.pending_channels += 1;
+            *stats
+                .pending_by_platform
+                .entry(platform.to_string())
+                .or_default() += entries.len();
+            if let Some(head) = entries.front() {
+                let age = now_ms - head.created_at;
+                stats.oldest_pending_age_ms =
+                    Some(stats.oldest_pending_age_ms.map_or(age, |a| a.max(age)));
+            }
+        }
+        stats
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.channels.values().map(VecDeque::len).sum()
+    }
+
+    fn fail(
+        &mut self,
+        key: &ChannelKey,
+        id: u64,
+        error: &str,
+        retryable: bool,
+        now_ms: i64,
+    ) -> Result<()> {
+        let config = self.config.clone();
+        let Some(head) = self.head_mut(key, id) else {
+            return Ok(());
+        };
+        head.attempts += 1;
+        let attempts = head.attempts;
+
+        if !retryable || attempts >= config.max_attempts {
+            warn!(
+                delivery_id = id,
+                platform = %key.0,
+                channel = %key.1,
+                attempts,
+                error = %error,
+                "Outbound queue: giving up on response"
+            );
+            self.store.dead_letter(id, error)?;
+            self.pop_head(key, id);
+            self.dead_lettered_total += 1;
+            return Ok(());
+        }
+
+        let backoff = backoff_ms(&config, attempts);
+        head.next_attempt_at = now_ms + backoff as i64;
+        let next = head.next_attempt_at;
+        self.store.reschedule(id, attempts, next)?;
+        self.retried_total += 1;
+        debug!(
+            delivery_id = id,
+            attempts,
+            backoff_ms = backoff,
+            error = %error,
+            "Outbound queue: scheduling retry"
+        );
+        Ok(())
+    }
+
+    /// A stream update replaces the latest queued update for the same message
+    /// if that one has not been handed out yet, so a slow channel skips
+    /// straight to the newest text instead of replaying every edit.
+    fn coalesce_stream_update(&mut self, event: &ResponseEvent) -> Result<Option<u64>> {
+        let ResponseKind::StreamUpdate {
+            stream_id, part, ..
+        } = &event.kind
+        else {
+            return Ok(None);
+        };
+        let Some(entries) = self.channels.get_mut(&channel_key(event)) else {
+            return Ok(None);
+        };
+        let latest = entries.iter_mut().rev().find(|entry| {
+            matches!(
+                &entry.event.kind,
+                ResponseKind::StreamUpdate { stream_id: s, part: p, .. }
+                    if s == stream_id && p == part
+            )
+        });
+        let Some(entry) = latest else {
+            return Ok(None);
+        };
+        if self.in_flight.contains_key(&entry.id) {
+            return Ok(None);
+        }
+
+        entry.event = event.clone();
+        self.store.replace_payload(entry.id, &entry.event)?;
+        Ok(Some(entry.id))
+    }
+
+    fn head_mut(&mut self, key: &ChannelKey, id: u64) -> Option<&mut QueuedResponse> {
+        self.channels
+            .get_mut(key)
+            .and_then(|entries| entries.front_mut())
+            .filter(|head| head.id == id)
+    }
+
+    fn pop_head(&mut self, key: &ChannelKey, id: u64) {
+        if let Some(entries) = self.channels.get_mut(key) {
+            if entries.front().is_some_and(|head| head.id == id) {
+                entries.pop_front();
+            }
+            if entries.is_empty() {
+                self.channels.remove(key);
+            }
+        }
+    }
+}
+
+fn channel_key(event: &ResponseEvent) -> ChannelKey {
+    (event.platform, event.channel_id.clone())
+}
+
+fn backoff_ms(config: &OutboundConfig, attempts: u32) -> u64 {
+    let exp = attempts.saturating_sub(1).min(16);
+    config
+        .base_backoff_ms
+        .saturating_mul(1u64 << exp)
+        .min(config.max_backoff_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::event::ResponseSource;
+
+    fn response(platform: Platform, channel: &str, content: &str) -> ResponseEvent {
+        ResponseEvent {
+            platform,
+            channel_id: channel.to_string(),
+            thread_id: None,
+            reply_to_message_id: None,
+            reply_to_user: None,
+            is_dm: false,
+            content: content.to_string(),
+            source: ResponseSource::CloudLLM,
+            kind: ResponseKind::Message,
+            private_reply: false,
+        }
+    }
+
+    fn stream_update(channel: &str, content: &str, is_final: bool) -> ResponseEvent {
+        ResponseEvent {
+            kind: ResponseKind::StreamUpdate {
+                stream_id: "s1".to_string(),
+                part: 0,
+                is_final,
+            },
+            ..response(Platform::Discord, channel, content)
+        }
+    }
+
+    fn queue() -> OutboundQueue {
+        OutboundQueue::open(
+            OutboundStore::open_in_memory().unwrap(),
+            OutboundConfig::default(),
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_channel_head_is_in_flight() {
+        let mut q = queue();
+        q.enqueue(&response(Platform::Cli, "a", "one"), 0).unwrap();
+        q.enqueue(&response(Platform::Cli, "a", "two"), 0).unwrap();
+        q.enqueue(&response(Platform::Cli, "b", "other"), 0).unwrap();
+
+        let ready = q.take_ready(0, |_| true);
+        assert_eq!(ready.len(), 2);
+        assert!(q.take_ready(0, |_| true).is_empty());
+
+        let first = ready.iter().find(|r| r.event.channel_id == "a").unwrap();
+        assert_eq!(first.event.content, "one");
+        q.complete(first.id, DeliveryOutcome::Sent, 0).unwrap();
+
+        let next = q.take_ready(0, |_| true);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].event.content, "two");
+    }
+
+    #[test]
+    fn retry_after_holds_the_channel_in_order() {
+        let mut q = queue();
+        q.enqueue(&response(Platform::Cli, "a", "one"), 0).unwrap();
+        q.enqueue(&response(Platform::Cli, "a", "two"), 0).unwrap();
+
+        let head = q.take_ready(0, |_| true).remove(0);
+        q.complete(head.id, DeliveryOutcome::RetryAfter { retry_after_ms: 2_000 }, 0)
+            .unwrap();
+
+        assert!(q.take_ready(1_000, |_| true).is_empty());
+        let again = q.take_ready(2_000, |_| true);
+        assert_eq!(again[0].event.content, "one");
+    }
+
+    #[test]
+    fn failures_back_off_then_dead_letter() {
+        let mut q = OutboundQueue::open(
+            OutboundStore::open_in_memory().unwrap(),
+            OutboundConfig {
+                max_attempts: 2,
+                ..OutboundConfig::default()
+            },
+            0,
+        )
+        .unwrap();
+        q.enqueue(&response(Platform::Cli, "a", "one"), 0).unwrap();
+
+        let failed = DeliveryOutcome::Failed {
+            error: "boom".to_string(),
+            retryable: true,
+        };
+        let head = q.take_ready(0, |_| true).remove(0);
+        q.complete(head.id, failed.clone(), 0).unwrap();
+        assert!(q.take_ready(500, |_| true).is_empty());
+
+        let head = q.take_ready(1_000, |_| true).remove(0);
+        q.complete(head.id, failed, 1_000).unwrap();
+        assert_eq!(q.pending_len(), 0);
+        assert_eq!(q.stats(1_000).dead_lettered_total, 1);
+    }
+
+    #[test]
+    fn per_channel_rate_limit_applies() {
+        let mut q = queue();
+        for i in 0..3 {
+            q.enqueue(&response(Platform::DiscordSelfbot, "a", &i.to_string()), 0)
+                .unwrap();
+        }
+
+        for _ in 0..2 {
+            let head = q.take_ready(0, |_| true).remove(0);
+            q.complete(head.id, DeliveryOutcome::Sent, 0).unwrap();
+        }
+        assert!(q.take_ready(0, |_| true).is_empty());
+        assert_eq!(q.take_ready(2_000, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn disconnected_platform_is_skipped() {
+        let mut q = queue();
+        q.enqueue(&response(Platform::Telegram, "a", "one"), 0).unwrap();
+        assert!(q.take_ready(0, |p| p != Platform::Telegram).is_empty());
+        assert_eq!(q.stats(5).pending_by_platform.get("Telegram"), Some(&1));
+    }
+
+    #[test]
+    fn unacked_delivery_is_retried() {
+        let mut q = queue();
+        q.enqueue(&response(Platform::Cli, "a", "one"), 0).unwrap();
+        let head = q.take_ready(0, |_| true).remove(0);
+
+        q.expire_in_flight(30_000).unwrap();
+        assert!(q.take_ready(30_000, |_| true).is_empty());
+        let retried = q.take_ready(31_000, |_| true);
+        assert_eq!(retried[0].id, head.id);
+        assert_eq!(retried[0].attempts, 1);
+    }
+
+    #[test]
+    fn stream_updates_coalesce_until_sent() {
+        let mut q = queue();
+        let first = q.enqueue(&stream_update("a", "Hel", false), 0).unwrap();
+        let head = q.take_ready(0, |_| true).remove(0);
+        assert_eq!(head.id, first);
+
+        // The in-flight update is left alone; later ones collapse into one entry.
+        let second = q.enqueue(&stream_update("a", "Hello", false), 0).unwrap();
+        let third = q.enqueue(&stream_update("a", "Hello there", true), 0).unwrap();
+        assert_ne!(second, first);
+        assert_eq!(second, third);
+        assert_eq!(q.pending_len(), 2);
+
+        q.complete(first, DeliveryOutcome::Sent, 0).unwrap();
+        let next = q.take_ready(1_000, |_| true).remove(0);
+        assert_eq!(next.event.content, "Hello there");
+        assert!(matches!(
+            next.event.kind,
+            ResponseKind::StreamUpdate { is_final: true, .. }
+        ));
+    }
+
+    #[test]
+    fn pending_entries_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!(
+            "outbound-queue-test-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("outbound.db");
+        let path = path.to_str().unwrap();
+
+        {
+            let mut q =
+                OutboundQueue::open(OutboundStore::open(path).unwrap(), OutboundConfig::default(), 0)
+                    .unwrap();
+            q.enqueue(&response(Platform::Discord, "a", "one"), 0).unwrap();
+            q.enqueue(&response(Platform::Discord, "a", "two"), 0).unwrap();
+            let head = q.take_ready(0, |_| true).remove(0);
+            q.complete(head.id, DeliveryOutcome::Sent, 0).unwrap();
+        }
+
+        let mut q =
+            OutboundQueue::open(OutboundStore::open(path).unwrap(), OutboundConfig::default(), 10)
+                .unwrap();
+        let ready = q.take_ready(10, |_| true);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].event.content, "two");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
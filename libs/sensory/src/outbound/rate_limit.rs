@@ -0,0 +1,88 @@
+use kernel::event::Platform;
+
+/// Token-bucket parameters. `None` in [`PlatformRateLimits`] means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// Send limits for one platform, applied per channel and across the whole connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlatformRateLimits {
+    pub per_channel: Option<RateLimit>,
+    pub global: Option<RateLimit>,
+}
+
+impl PlatformRateLimits {
+    /// Defaults that stay under each platform's documented limits.
+    pub fn for_platform(platform: Platform) -> Self {
+        match platform {
+            // 5 messages / 5s per channel, 50 requests/s per bot.
+            Platform::Discord => Self {
+                per_channel: Some(RateLimit::new(5, 1.0)),
+                global: Some(RateLimit::new(50, 50.0)),
+            },
+            // User accounts get flagged well before the bot limits.
+            Platform::DiscordSelfbot => Self {
+                per_channel: Some(RateLimit::new(2, 0.5)),
+                global: Some(RateLimit::new(5, 1.0)),
+            },
+            // ~1 message/s per chat, 30 messages/s per bot.
+            Platform::Telegram => Self {
+                per_channel: Some(RateLimit::new(3, 1.0)),
+                global: Some(RateLimit::new(30, 30.0)),
+            },
+            // Networks kill clients that flood; the IRC client also paces each line.
+            Platform::Irc => Self {
+                per_channel: Some(RateLimit::new(2, 0.5)),
+                global: Some(RateLimit::new(4, 1.0)),
+            },
+            // Local and HTTP clients pace themselves; a webhook 429 comes back as retry-after.
+            Platform::Cli | Platform::Http => Self {
+                per_channel: None,
+                global: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill_ms: i64,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit, now_ms: i64) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            last_refill_ms: now_ms,
+        }
+    }
+
+    fn refill(&mut self, now_ms: i64) {
+        let elapsed_ms = (now_ms - self.last_refill_ms).max(0) as f64;
+        self.tokens = (self.tokens + elapsed_ms / 1000.0 * self.limit.per_second)
+            .min(f64::from(self.limit.burst));
+        self.last_refill_ms = now_ms;
+    }
+
+    pub(crate) fn has_token(&mut self, now_ms: i64) -> bool {
+        self.refill(now_ms);
+        self.tokens >= 1.0
+    }
+
+    pub(crate) fn take(&mut self, now_ms: i64) {
+        self.refill(now_ms);
+        self.tokens = (self.tokens - 1.0).max(0.0);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use kernel::event::ResponseEvent;
+use rusqlite::{params, Connection};
+use tracing::info;
+
+/// A response waiting to be delivered, as persisted in the outbound queue.
+#[derive(Debug, Clone)]
+pub struct QueuedResponse {
+    pub id: u64,
+    pub event: ResponseEvent,
+    pub attempts: u32,
+    /// Unix millis before which the entry must not be dispatched again.
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+}
+
+/// SQLite persistence for the outbound queue so undelivered responses
+/// survive an agent restart.
+pub struct OutboundStore {
+    conn: Connection,
+}
+
+impl OutboundStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open outbound queue database: {}", path))?;
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
+
+        let store = Self { conn };
+        store.init_tables()?;
+
+        info!(path = %path, "Outbound queue store opened");
+        Ok(store)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .context("Failed to open in-memory outbound queue database")?;
+
+        let store = Self { conn };
+        store.init_tables()?;
+        Ok(store)
+    }
+
+    fn init_tables(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS outbound_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                platform TEXT NOT NULL,
+                channel_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                dead_letter_reason TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_outbound_pending
+                ON outbound_queue(dead_letter_reason, id);
+            ",
+        )?;
+        Ok(())
+    }
+
+    pub fn insert(&self, event: &ResponseEvent, now_ms: i64) -> Result<QueuedResponse> {
+        self.conn.execute(
+            "INSERT INTO outbound_queue
+                (platform, channel_id, payload, attempts, next_attempt_at, created_at)
+             VALUES (?1, ?2, ?3, 0, ?4, ?4)",
+            params![
+                format!("{}", event.platform),
+                event.channel_id,
+                serde_json::to_string(event)?,
+                now_ms,
+            ],
+        )?;
+
+        Ok(QueuedResponse {
+            id: self.conn.last_insert_rowid() as u64,
+            event: event.clone(),
+            attempts: 0,
+            next_attempt_at: now_ms,
+            created_at: now_ms,
+        })
+    }
+
+    /// All live entries in insertion order.
+    pub fn load_pending(&self) -> Result<Vec<QueuedResponse>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, payload, attempts, next_attempt_at, created_at
+             FROM outbound_queue
+             WHERE dead_letter_reason IS NULL
+             ORDER BY id ASC",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })?;
+
+        let mut pending = Vec::new();
+        for row in rows {
+            let (id, payload, attempts, next_attempt_at, created_at) = row?;
+            let event: ResponseEvent = serde_json::from_str(&payload)
+                .with_context(|| format!("Corrupt outbound queue payload for id {}", id))?;
+            pending.push(QueuedResponse {
+                id: id as u64,
+                event,
+                attempts,
+                next_attempt_at,
+                created_at,
+            });
+        }
+        Ok(pending)
+    }
+
+    /// Swap in a newer payload for an entry that has not been sent yet.
+    pub fn replace_payload(&self, id: u64, event: &ResponseEvent) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbound_queue SET payload = ?2 WHERE id = ?1",
+            params![id as i64, serde_json::to_string(event)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: u64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM outbound_queue WHERE id = ?1", params![id as i64])?;
+        Ok(())
+    }
+
+    pub fn reschedule(&self, id: u64, attempts: u32, next_attempt_at: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbound_queue SET attempts = ?2, next_attempt_at = ?3 WHERE id = ?1",
+            params![id as i64, attempts, next_attempt_at],
+        )?;
+        Ok(())
+    }
+
+    /// Keep the row for inspection but stop delivering it.
+    pub fn dead_letter(&self, id: u64, reason: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE outbound_queue SET dead_letter_reason = ?2 WHERE id = ?1",
+            params![id as i64, reason],
+        )?;
+        Ok(())
+    }
+
+    pub fn dead_letter_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM outbound_queue WHERE dead_letter_reason IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+}
@@ -18,4 +18,13 @@ pub trait PlatformAdapter: kernel::Worker {
         message_id: &str,
         content: &str,
     ) -> Result<()>;
+
+    async fn edit_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        content: &str,
+    ) -> Result<()>;
+
+    async fn delete_message(&self, channel_id: &str, message_id: &str) -> Result<()>;
 }
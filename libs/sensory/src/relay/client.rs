@@ -2,33 +2,187 @@
 //!
 //! Usage:
 //! ```ignore
-//! let mut client = RelayClient::connect("/tmp/polyverse-agent-relay.sock").await?;
-//! client.ingest(raw_event).await?;
+//! let mut client = RelayClient::connect_platform(Platform::Discord).await?;
+//! let sender = client.sender();
+//! sender.ingest(raw_event).await?;
 //!
 //! // In a separate task:
-//! while let Some(response) = client.recv_response().await? {
-//!     // send response back to the platform
+//! while let Some(inbound) = client.recv().await {
+//!     match inbound {
+//!         RelayInbound::Response(response) => {
+//!             let outcome = send_to_platform(&response.event).await;
+//!             sender.report_delivery(response.delivery_id, outcome).await?;
+//!         }
+//!         RelayInbound::Typing { channel_id, .. } => show_typing(&channel_id).await,
+//!     }
 //! }
 //! ```
 
+use std::time::{Duration, Instant};
+
 use anyhow::{Context, Result};
-use kernel::event::{RawEvent, ResponseEvent};
+use kernel::event::{
+    CommandEvent, MessageDeleteEvent, MessageEditEvent, Platform, RawEvent, ReactionEvent,
+    ResponseEvent,
+};
+use kernel::worker::WorkerStatus;
 use tokio::net::UnixStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, warn};
 
-use super::protocol::{recv_message, send_message, AgentMessage, PlatformMessage};
+use crate::capabilities::PlatformCapabilities;
+
+use super::protocol::{
+    decode_frame, read_frame, send_message, AgentMessage, DeliveryOutcome, ErrorBudget,
+    PlatformMessage, RelayProtocolConfig,
+};
+
+/// A response handed to the platform process by the agent's outbound queue.
+#[derive(Debug, Clone)]
+pub struct RelayResponse {
+    /// Must be passed back through [`RelaySender::report_delivery`].
+    pub delivery_id: Option<u64>,
+    pub event: ResponseEvent,
+}
+
+/// Something the agent wants the platform process to do.
+#[derive(Debug, Clone)]
+pub enum RelayInbound {
+    Response(RelayResponse),
+    Typing {
+        channel_id: String,
+        thread_id: Option<String>,
+    },
+}
+
+/// Cloneable handle for sending frames to the agent over a [`RelayClient`] connection.
+#[derive(Clone)]
+pub struct RelaySender {
+    outbound_tx: mpsc::Sender<PlatformMessage>,
+    /// Counts pongs from the agent; closed when the reader task stops.
+    pongs: watch::Receiver<u64>,
+}
+
+impl RelaySender {
+    /// Send a RawEvent to the agent.
+    pub async fn ingest(&self, event: RawEvent) -> Result<()> {
+        self.send(PlatformMessage::Ingest { event }).await
+    }
+
+    /// Tell the agent a user edited one of their messages.
+    pub async fn message_edited(&self, event: MessageEditEvent) -> Result<()> {
+        self.send(PlatformMessage::MessageEdited { event }).await
+    }
+
+    /// Tell the agent a message was deleted.
+    pub async fn message_deleted(&self, event: MessageDeleteEvent) -> Result<()> {
+        self.send(PlatformMessage::MessageDeleted { event }).await
+    }
+
+    /// Tell the agent a reaction was added or removed.
+    pub async fn reaction(&self, event: ReactionEvent) -> Result<()> {
+        self.send(PlatformMessage::Reaction { event }).await
+    }
+
+    /// Pass a parsed command to the agent.
+    pub async fn command(&self, event: CommandEvent) -> Result<()> {
+        self.send(PlatformMessage::Command { event }).await
+    }
+
+    /// Tell the agent what happened to a response. A `None` id is a no-op.
+    pub async fn report_delivery(
+        &self,
+        delivery_id: Option<u64>,
+        outcome: DeliveryOutcome,
+    ) -> Result<()> {
+        let Some(delivery_id) = delivery_id else {
+            return Ok(());
+        };
+        self.send(PlatformMessage::Delivery {
+            delivery_id,
+            outcome,
+        })
+        .await
+    }
+
+    /// Report the health of a process this client looks after.
+    pub async fn status(&self, name: impl Into<String>, status: WorkerStatus) -> Result<()> {
+        self.send(PlatformMessage::Status {
+            name: name.into(),
+            status,
+        })
+        .await
+    }
+
+    /// Ping the agent and wait for its pong. Returns the round-trip time, or
+    /// an error when the connection is closed or no pong arrives in `timeout`.
+    pub async fn heartbeat(&self, timeout: Duration) -> Result<Duration> {
+        let mut pongs = self.pongs.clone();
+        pongs.borrow_and_update();
+        let started = Instant::now();
+        self.send(PlatformMessage::Ping).await?;
+        tokio::time::timeout(timeout, pongs.changed())
+            .await
+            .context("Relay client: no pong from agent")?
+            .context("Relay client: connection closed")?;
+        Ok(started.elapsed())
+    }
+
+    async fn send(&self, msg: PlatformMessage) -> Result<()> {
+        self.outbound_tx
+            .send(msg)
+            .await
+            .context("Relay client: outbound channel closed")?;
+        Ok(())
+    }
+}
 
 pub struct RelayClient {
-    /// Sender for outbound events (ingest).
-    ingest_tx: mpsc::Sender<RawEvent>,
-    /// Receiver for inbound responses from the agent.
-    response_rx: mpsc::Receiver<ResponseEvent>,
+    /// Sender for outbound frames (ingest, delivery reports).
+    sender: RelaySender,
+    /// Receiver for responses and typing indicators from the agent.
+    inbound_rx: mpsc::Receiver<RelayInbound>,
 }
 
 impl RelayClient {
     /// Connect to the agent relay socket and start background I/O tasks.
     pub async fn connect(socket_path: &str) -> Result<Self> {
+        Self::connect_with_config(socket_path, None, None, RelayProtocolConfig::from_env()).await
+    }
+
+    /// Connect using the env-configured or default socket path.
+    pub async fn connect_default() -> Result<Self> {
+        Self::connect(&super::protocol::resolve_socket_path()).await
+    }
+
+    /// Connect using the default socket path and register for `platform`'s
+    /// responses immediately rather than on the first ingest.
+    pub async fn connect_platform(platform: Platform) -> Result<Self> {
+        Self::connect_platform_with(platform, PlatformCapabilities::defaults_for(platform)).await
+    }
+
+    /// Like [`RelayClient::connect_platform`], declaring non-default message
+    /// limits or markup.
+    pub async fn connect_platform_with(
+        platform: Platform,
+        capabilities: PlatformCapabilities,
+    ) -> Result<Self> {
+        Self::connect_with_config(
+            &super::protocol::resolve_socket_path(),
+            Some(platform),
+            Some(capabilities),
+            RelayProtocolConfig::from_env(),
+        )
+        .await
+    }
+
+    /// Connect with explicit frame limits instead of the env-configured ones.
+    pub async fn connect_with_config(
+        socket_path: &str,
+        platform: Option<Platform>,
+        capabilities: Option<PlatformCapabilities>,
+        protocol: RelayProtocolConfig,
+    ) -> Result<Self> {
         let stream = UnixStream::connect(socket_path)
             .await
             .with_context(|| format!("Failed to connect to relay socket: {}", socket_path))?;
@@ -37,14 +191,26 @@ impl RelayClient {
         let mut reader = tokio::io::BufReader::new(read_half);
         let mut writer = write_half;
 
-        let (ingest_tx, mut ingest_rx) = mpsc::channel::<RawEvent>(64);
-        let (response_tx, response_rx) = mpsc::channel::<ResponseEvent>(64);
+        let (outbound_tx, mut outbound_rx) = mpsc::channel::<PlatformMessage>(64);
+        let (inbound_tx, inbound_rx) = mpsc::channel::<RelayInbound>(64);
+        let (pong_tx, pong_rx) = watch::channel(0u64);
 
-        // Writer task: drain ingest_rx and send frames to agent.
+        // Writer task: announce ourselves, then drain outbound_rx and send frames to agent.
         tokio::spawn(async move {
-            while let Some(event) = ingest_rx.recv().await {
-                let msg = PlatformMessage::Ingest { event };
-                if let Err(e) = send_message(&mut writer, &msg).await {
+            let compress = protocol.compress_min_bytes;
+            let hello = PlatformMessage::Hello {
+                platform,
+                compression: compress.is_some(),
+                delivery_acks: true,
+                capabilities,
+            };
+            if let Err(e) = send_message(&mut writer, &hello, None).await {
+                debug!(error = %e, "Relay client: write error");
+                return;
+            }
+
+            while let Some(msg) = outbound_rx.recv().await {
+                if let Err(e) = send_message(&mut writer, &msg, compress).await {
                     debug!(error = %e, "Relay client: write error");
                     break;
                 }
@@ -53,54 +219,75 @@ impl RelayClient {
 
         // Reader task: receive frames from agent and dispatch.
         tokio::spawn(async move {
+            let mut budget = ErrorBudget::new(protocol.error_budget);
             loop {
-                match recv_message::<_, AgentMessage>(&mut reader).await {
+                let frame = match read_frame(&mut reader, protocol.max_frame_bytes).await {
+                    Ok(Some(frame)) => frame,
                     Ok(None) => {
                         debug!("Relay client: connection closed by agent");
                         break;
                     }
-                    Ok(Some(AgentMessage::Response { event })) => {
-                        if response_tx.send(event).await.is_err() {
+                    Err(e) => {
+                        warn!(error = %e, "Relay client: read error");
+                        break;
+                    }
+                };
+
+                match decode_frame::<AgentMessage>(&frame, protocol.max_frame_bytes) {
+                    Ok(AgentMessage::Response { event, delivery_id }) => {
+                        let response = RelayResponse { delivery_id, event };
+                        if inbound_tx.send(RelayInbound::Response(response)).await.is_err() {
                             break;
                         }
                     }
-                    Ok(Some(AgentMessage::Ack)) => {
+                    Ok(AgentMessage::Typing { channel_id, thread_id }) => {
+                        // Stale indicators are worthless; drop rather than wait.
+                        let _ = inbound_tx.try_send(RelayInbound::Typing { channel_id, thread_id });
+                    }
+                    Ok(AgentMessage::Ack) => {
                         debug!("Relay client: ack received");
                     }
-                    Ok(Some(AgentMessage::Pong)) => {
+                    Ok(AgentMessage::Pong) => {
                         debug!("Relay client: pong received");
+                        pong_tx.send_modify(|count| *count += 1);
                     }
                     Err(e) => {
-                        warn!(error = %e, "Relay client: read error");
-                        break;
+                        if !budget.spend() {
+                            warn!(error = %e, "Relay client: error budget exhausted");
+                            break;
+                        }
+                        warn!(
+                            error = %e,
+                            remaining_budget = budget.remaining(),
+                            "Relay client: skipping undecodable frame"
+                        );
                     }
                 }
             }
         });
 
         Ok(Self {
-            ingest_tx,
-            response_rx,
+            sender: RelaySender {
+                outbound_tx,
+                pongs: pong_rx,
+            },
+            inbound_rx,
         })
     }
 
-    /// Connect using the env-configured or default socket path.
-    pub async fn connect_default() -> Result<Self> {
-        Self::connect(&super::protocol::resolve_socket_path()).await
+    /// Handle for ingesting events and reporting deliveries from other tasks.
+    pub fn sender(&self) -> RelaySender {
+        self.sender.clone()
     }
 
     /// Send a RawEvent to the agent.
     pub async fn ingest(&self, event: RawEvent) -> Result<()> {
-        self.ingest_tx
-            .send(event)
-            .await
-            .context("Relay client: ingest channel closed")?;
-        Ok(())
+        self.sender.ingest(event).await
     }
 
-    /// Wait for the next response from the agent.
+    /// Wait for the next response or typing indicator from the agent.
     /// Returns `None` when the connection is closed.
-    pub async fn recv_response(&mut self) -> Option<ResponseEvent> {
-        self.response_rx.recv().await
+    pub async fn recv(&mut self) -> Option<RelayInbound> {
+        self.inbound_rx.recv().await
     }
 }
@@ -1,7 +1,12 @@
 pub mod client;
 pub mod protocol;
 pub mod server;
+pub mod streams;
 
-pub use client::RelayClient;
-pub use protocol::{resolve_socket_path, AgentMessage, PlatformMessage, DEFAULT_RELAY_SOCKET};
+pub use client::{RelayClient, RelayInbound, RelayResponse, RelaySender};
+pub use protocol::{
+    resolve_socket_path, AgentMessage, DeliveryOutcome, ErrorBudget, PlatformMessage,
+    RelayProtocolConfig, DEFAULT_RELAY_SOCKET,
+};
 pub use server::PlatformRelayWorker;
+pub use streams::StreamedMessages;
@@ -1,30 +1,97 @@
 //! Wire protocol for the UDS platform relay.
 //!
 //! Each message is a length-prefixed JSON frame:
-//!   [u32 LE length][JSON bytes]
+//!   [u32 LE header][payload bytes]
 //!
-//! Platform process → agent: `PlatformMessage::Ingest`
+//! The low 31 bits of the header carry the payload length. The high bit
+//! marks a zstd-compressed payload; peers that never send
+//! `PlatformMessage::Hello { compression: true }` only ever see plain
+//! frames, so hand-rolled clients (the Node selfbot) keep working.
+//!
+//! Platform process → agent: `PlatformMessage::Ingest`, `PlatformMessage::Delivery`,
+//! commands, edits, deletions and reactions of existing messages, and
+//! `PlatformMessage::Status` health reports from platform runners
 //! Agent → platform process: `AgentMessage::Response`
 
-use kernel::event::{RawEvent, ResponseEvent};
+use anyhow::{bail, Context};
+use kernel::event::{
+    CommandEvent, MessageDeleteEvent, MessageEditEvent, Platform, RawEvent, ReactionEvent,
+    ResponseEvent,
+};
+use kernel::worker::WorkerStatus;
 use serde::{Deserialize, Serialize};
 
+use crate::capabilities::PlatformCapabilities;
+
 /// Messages sent from a platform process to the agent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum PlatformMessage {
+    /// Optional handshake. Announcing the platform registers the connection
+    /// for responses before its first ingest.
+    Hello {
+        #[serde(default)]
+        platform: Option<Platform>,
+        #[serde(default)]
+        compression: bool,
+        /// The client reports a `Delivery` outcome for every response carrying a `delivery_id`.
+        #[serde(default)]
+        delivery_acks: bool,
+        /// Message limits and markup; platform defaults apply when omitted.
+        #[serde(default)]
+        capabilities: Option<PlatformCapabilities>,
+    },
     /// A new inbound message from a user on the platform.
     Ingest { event: RawEvent },
+    /// A user edited a message they sent.
+    MessageEdited { event: MessageEditEvent },
+    /// A message was deleted on the platform.
+    MessageDeleted { event: MessageDeleteEvent },
+    /// A user added or removed a reaction.
+    Reaction { event: ReactionEvent },
+    /// A user gave a command through the platform's command interface.
+    Command { event: CommandEvent },
+    /// Result of sending a queued response to the platform.
+    Delivery {
+        delivery_id: u64,
+        outcome: DeliveryOutcome,
+    },
+    /// Health of a process the client looks after, shown in the cockpit
+    /// under `name`.
+    Status { name: String, status: WorkerStatus },
     /// Keepalive ping.
     Ping,
 }
 
+/// What happened when the platform process tried to send a response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeliveryOutcome {
+    Sent,
+    /// The platform asked us to slow down (Discord 429, Telegram flood-wait).
+    RetryAfter { retry_after_ms: u64 },
+    /// Sending failed; `retryable` is false for errors a retry cannot fix.
+    Failed { error: String, retryable: bool },
+}
+
 /// Messages sent from the agent back to a platform process.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AgentMessage {
     /// A response to send back to the user on the platform.
-    Response { event: ResponseEvent },
+    Response {
+        event: ResponseEvent,
+        /// Set when the agent expects a `PlatformMessage::Delivery` report.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        delivery_id: Option<u64>,
+    },
+    /// Show a typing indicator in the channel. Only sent to clients that
+    /// declare the `typing` capability.
+    Typing {
+        channel_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thread_id: Option<String>,
+    },
     /// Keepalive pong.
     Pong,
     /// Acknowledge receipt of an ingest message.
@@ -37,13 +104,110 @@ pub const DEFAULT_RELAY_SOCKET: &str = "/tmp/polyverse-agent-relay.sock";
 /// Env var to override the socket path.
 pub const RELAY_SOCKET_ENV: &str = "PLATFORM_RELAY_SOCKET";
 
+/// Env var capping the size of a single frame, both on the wire and after decompression.
+pub const RELAY_MAX_FRAME_BYTES_ENV: &str = "PLATFORM_RELAY_MAX_FRAME_BYTES";
+
+/// Env var enabling zstd compression for frames at least this many bytes long.
+pub const RELAY_COMPRESS_MIN_BYTES_ENV: &str = "PLATFORM_RELAY_COMPRESS_MIN_BYTES";
+
+/// Env var setting how many undecodable frames a connection may send before it is dropped.
+pub const RELAY_ERROR_BUDGET_ENV: &str = "PLATFORM_RELAY_ERROR_BUDGET";
+
+/// Large enough for a message carrying the maximum number of base64 image attachments.
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 32 * 1024 * 1024;
+
+pub const DEFAULT_ERROR_BUDGET: u32 = 16;
+
+const COMPRESSED_FLAG: u32 = 1 << 31;
+const LENGTH_MASK: u32 = COMPRESSED_FLAG - 1;
+const ZSTD_LEVEL: i32 = 3;
+
 pub fn resolve_socket_path() -> String {
     std::env::var(RELAY_SOCKET_ENV).unwrap_or_else(|_| DEFAULT_RELAY_SOCKET.to_string())
 }
 
+/// Per-connection limits shared by the relay server and client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayProtocolConfig {
+    pub max_frame_bytes: usize,
+    /// `None` disables outbound compression.
+    pub compress_min_bytes: Option<usize>,
+    pub error_budget: u32,
+}
+
+impl Default for RelayProtocolConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            compress_min_bytes: None,
+            error_budget: DEFAULT_ERROR_BUDGET,
+        }
+    }
+}
+
+impl RelayProtocolConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let max_frame_bytes = parse_env::<usize>(RELAY_MAX_FRAME_BYTES_ENV)
+            .filter(|v| *v > 0)
+            .map(|v| v.min(LENGTH_MASK as usize))
+            .unwrap_or(defaults.max_frame_bytes);
+        let compress_min_bytes =
+            parse_env::<usize>(RELAY_COMPRESS_MIN_BYTES_ENV).filter(|v| *v > 0);
+        let error_budget =
+            parse_env::<u32>(RELAY_ERROR_BUDGET_ENV).unwrap_or(defaults.error_budget);
+
+        Self {
+            max_frame_bytes,
+            compress_min_bytes,
+            error_budget,
+        }
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.trim().parse().ok())
+}
+
+/// Counts skippable decode failures on one connection.
+#[derive(Debug, Clone)]
+pub struct ErrorBudget {
+    remaining: u32,
+}
+
+impl ErrorBudget {
+    pub fn new(limit: u32) -> Self {
+        Self { remaining: limit }
+    }
+
+    /// Record one bad frame. Returns `false` once the budget is exhausted.
+    pub fn spend(&mut self) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+/// A frame as read off the wire, before decompression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub compressed: bool,
+    pub payload: Vec<u8>,
+}
+
 /// Read a length-prefixed frame from an async reader.
 /// Returns `None` on clean EOF.
-pub async fn read_frame<R>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>>
+///
+/// Errors here leave the stream out of sync and must close the connection.
+/// A header announcing more than `max_frame_bytes` is rejected before any
+/// payload buffer is allocated.
+pub async fn read_frame<R>(reader: &mut R, max_frame_bytes: usize) -> anyhow::Result<Option<Frame>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
 {
@@ -53,49 +217,274 @@ where
         Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
         Err(e) => return Err(e.into()),
     }
-    let len = u32::from_le_bytes(len_buf) as usize;
+    let header = u32::from_le_bytes(len_buf);
+    let compressed = header & COMPRESSED_FLAG != 0;
+    let len = (header & LENGTH_MASK) as usize;
+    if len > max_frame_bytes {
+        bail!("relay frame of {len} bytes exceeds limit of {max_frame_bytes} bytes");
+    }
     if len == 0 {
-        return Ok(Some(vec![]));
+        return Ok(Some(Frame {
+            compressed,
+            payload: vec![],
+        }));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(Frame {
+        compressed,
+        payload,
+    }))
+}
+
+/// Decompress (if needed) and deserialize a frame.
+///
+/// Errors here are skippable: the stream is still aligned on the next frame.
+pub fn decode_frame<T>(frame: &Frame, max_frame_bytes: usize) -> anyhow::Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    if !frame.compressed {
+        return Ok(serde_json::from_slice(&frame.payload)?);
+    }
+    let bytes = decompress_bounded(&frame.payload, max_frame_bytes)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+fn decompress_bounded(payload: &[u8], max_bytes: usize) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let decoder =
+        zstd::stream::read::Decoder::new(payload).context("invalid zstd relay frame")?;
+    let mut out = Vec::new();
+    decoder
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut out)
+        .context("invalid zstd relay frame")?;
+    if out.len() > max_bytes {
+        bail!("decompressed relay frame exceeds limit of {max_bytes} bytes");
     }
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf).await?;
-    Ok(Some(buf))
+    Ok(out)
 }
 
 /// Write a length-prefixed frame to an async writer.
-pub async fn write_frame<W>(writer: &mut W, data: &[u8]) -> anyhow::Result<()>
+///
+/// Payloads of at least `compress_min_bytes` are zstd-compressed when that
+/// actually makes them smaller.
+pub async fn write_frame<W>(
+    writer: &mut W,
+    data: &[u8],
+    compress_min_bytes: Option<usize>,
+) -> anyhow::Result<()>
 where
     W: tokio::io::AsyncWriteExt + Unpin,
 {
-    let len = data.len() as u32;
-    writer.write_all(&len.to_le_bytes()).await?;
-    writer.write_all(data).await?;
+    let compressed = compress_min_bytes
+        .filter(|min| data.len() >= *min)
+        .and_then(|_| zstd::bulk::compress(data, ZSTD_LEVEL).ok())
+        .filter(|packed| packed.len() < data.len());
+
+    let (payload, flag) = match compressed.as_deref() {
+        Some(packed) => (packed, COMPRESSED_FLAG),
+        None => (data, 0),
+    };
+    if payload.len() > LENGTH_MASK as usize {
+        bail!("relay frame of {} bytes is too large to encode", payload.len());
+    }
+
+    let header = payload.len() as u32 | flag;
+    writer.write_all(&header.to_le_bytes()).await?;
+    writer.write_all(payload).await?;
     writer.flush().await?;
     Ok(())
 }
 
 /// Serialize a message and write it as a frame.
-pub async fn send_message<W, T>(writer: &mut W, msg: &T) -> anyhow::Result<()>
+pub async fn send_message<W, T>(
+    writer: &mut W,
+    msg: &T,
+    compress_min_bytes: Option<usize>,
+) -> anyhow::Result<()>
 where
     W: tokio::io::AsyncWriteExt + Unpin,
     T: Serialize,
 {
     let bytes = serde_json::to_vec(msg)?;
-    write_frame(writer, &bytes).await
+    write_frame(writer, &bytes, compress_min_bytes).await
 }
 
-/// Read a frame and deserialize it.
+/// Read a frame and deserialize it, treating any decode failure as fatal.
 /// Returns `None` on clean EOF.
-pub async fn recv_message<R, T>(reader: &mut R) -> anyhow::Result<Option<T>>
+pub async fn recv_message<R, T>(reader: &mut R, max_frame_bytes: usize) -> anyhow::Result<Option<T>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
     T: for<'de> Deserialize<'de>,
 {
-    match read_frame(reader).await? {
+    match read_frame(reader, max_frame_bytes).await? {
         None => Ok(None),
-        Some(bytes) => {
-            let msg = serde_json::from_slice(&bytes)?;
-            Ok(Some(msg))
+        Some(frame) => Ok(Some(decode_frame(&frame, max_frame_bytes)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn encode(msg: &AgentMessage, compress_min_bytes: Option<usize>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        send_message(&mut buf, msg, compress_min_bytes).await.unwrap();
+        buf
+    }
+
+    fn response(content: String) -> AgentMessage {
+        AgentMessage::Response {
+            delivery_id: None,
+            event: ResponseEvent {
+                platform: kernel::event::Platform::Discord,
+                channel_id: "c1".to_string(),
+                thread_id: None,
+                reply_to_message_id: None,
+                reply_to_user: None,
+                is_dm: false,
+                content,
+                source: kernel::event::ResponseSource::CloudLLM,
+                kind: kernel::event::ResponseKind::Message,
+                private_reply: false,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn plain_frame_round_trips() {
+        let bytes = encode(&AgentMessage::Pong, None).await;
+        assert_eq!(&bytes[4..], br#"{"type":"pong"}"#);
+
+        let mut reader = bytes.as_slice();
+        let msg: Option<AgentMessage> = recv_message(&mut reader, 1024).await.unwrap();
+        assert!(matches!(msg, Some(AgentMessage::Pong)));
+        let eof: Option<AgentMessage> = recv_message(&mut reader, 1024).await.unwrap();
+        assert!(eof.is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_header_is_rejected_without_reading_payload() {
+        let header = LENGTH_MASK.to_le_bytes();
+        let mut reader = header.as_slice();
+        let err = read_frame(&mut reader, 1024).await.unwrap_err();
+        assert!(err.to_string().contains("exceeds limit"));
+    }
+
+    #[tokio::test]
+    async fn large_frames_are_compressed_and_decoded() {
+        let msg = response("a".repeat(64 * 1024));
+        let bytes = encode(&msg, Some(1024)).await;
+        let header = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        assert_ne!(header & COMPRESSED_FLAG, 0);
+        assert!(bytes.len() < 64 * 1024);
+
+        let mut reader = bytes.as_slice();
+        let decoded: AgentMessage = recv_message(&mut reader, 128 * 1024)
+            .await
+            .unwrap()
+            .unwrap();
+        match decoded {
+            AgentMessage::Response { event, .. } => assert_eq!(event.content.len(), 64 * 1024),
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn decompression_is_capped_by_frame_limit() {
+        let bytes = encode(&response("a".repeat(64 * 1024)), Some(1024)).await;
+        let mut reader = bytes.as_slice();
+        let frame = read_frame(&mut reader, 4096).await.unwrap().unwrap();
+        let err = decode_frame::<AgentMessage>(&frame, 4096).unwrap_err();
+        assert!(err.to_string().contains("exceeds limit"));
+    }
+
+    #[tokio::test]
+    async fn bad_json_frame_leaves_stream_aligned() {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, b"{not json", None).await.unwrap();
+        bytes.extend(encode(&AgentMessage::Ack, None).await);
+
+        let mut reader = bytes.as_slice();
+        let bad = read_frame(&mut reader, 1024).await.unwrap().unwrap();
+        assert!(decode_frame::<AgentMessage>(&bad, 1024).is_err());
+        let next = read_frame(&mut reader, 1024).await.unwrap().unwrap();
+        assert!(matches!(
+            decode_frame::<AgentMessage>(&next, 1024).unwrap(),
+            AgentMessage::Ack
+        ));
+    }
+
+    #[test]
+    fn error_budget_runs_out() {
+        let mut budget = ErrorBudget::new(2);
+        assert!(budget.spend());
+        assert!(budget.spend());
+        assert!(!budget.spend());
+        assert_eq!(budget.remaining(), 0);
+    }
+
+    #[test]
+    fn hello_defaults_to_legacy_client() {
+        let msg: PlatformMessage = serde_json::from_str(r#"{"type":"hello"}"#).unwrap();
+        assert!(matches!(
+            msg,
+            PlatformMessage::Hello {
+                platform: None,
+                compression: false,
+                delivery_acks: false,
+                capabilities: None,
+            }
+        ));
+    }
+
+    #[test]
+    fn delivery_report_wire_format() {
+        let msg: PlatformMessage = serde_json::from_str(
+            r#"{"type":"delivery","delivery_id":7,"outcome":{"status":"retry_after","retry_after_ms":1500}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            msg,
+            PlatformMessage::Delivery {
+                delivery_id: 7,
+                outcome: DeliveryOutcome::RetryAfter { retry_after_ms: 1500 },
+            }
+        ));
+    }
+
+    #[test]
+    fn status_wire_format() {
+        let msg: PlatformMessage = serde_json::from_str(
+            r#"{"type":"status","name":"discord_selfbot","status":{"Degraded":{"reason":"restarting"}}}"#,
+        )
+        .unwrap();
+        match msg {
+            PlatformMessage::Status { name, status } => {
+                assert_eq!(name, "discord_selfbot");
+                assert_eq!(
+                    status,
+                    WorkerStatus::Degraded {
+                        reason: "restarting".to_string()
+                    }
+                );
+            }
+            other => panic!("unexpected message: {other:?}"),
         }
     }
+
+    #[test]
+    fn typing_wire_format() {
+        let msg = AgentMessage::Typing {
+            channel_id: "c1".to_string(),
+            thread_id: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"type":"typing","channel_id":"c1"}"#
+        );
+    }
 }
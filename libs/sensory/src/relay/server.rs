@@ -1,24 +1,58 @@
 //! UDS relay server — runs inside the agent process.
 //!
-//! Accepts connections from platform processes, forwards inbound
-//! `PlatformMessage::Ingest` events onto the agent EventBus, and
-//! sends back `AgentMessage::Response` frames for any `Event::Response`
-//! that matches the platform served by that connection.
+//! Accepts connections from platform processes and forwards inbound
+//! `PlatformMessage::Ingest` events onto the agent EventBus. Every
+//! `Event::Response` goes through the outbound queue, which hands each
+//! channel's next response to the connection serving that platform and
+//! waits for its `PlatformMessage::Delivery` report before sending more.
+//! Responses are split to the connection's message limit when queued and
+//! rendered into its markup dialect when sent. Streamed replies arrive as
+//! `ResponseKind::StreamUpdate`s; connections that cannot edit messages only
+//! receive the final one. Reactions fall back to an emoji reply where the
+//! platform has none; edits and deletions are dropped. `PlatformMessage::Status`
+//! reports become `SystemEvent::WorkerHealth` events; a runner that
+//! disconnects without reporting `Stopped` is marked degraded.
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use kernel::event::{Event, Platform};
+use kernel::event::{Event, Platform, ResponseEvent, ResponseKind, SystemEvent, TypingEvent};
 use kernel::worker::{Worker, WorkerContext, WorkerStatus};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 
-use super::protocol::{recv_message, send_message, AgentMessage, PlatformMessage};
+use super::protocol::{
+    decode_frame, read_frame, send_message, AgentMessage, DeliveryOutcome, ErrorBudget,
+    PlatformMessage, RelayProtocolConfig,
+};
+use crate::capabilities::PlatformCapabilities;
+use crate::format;
+use crate::outbound::{now_ms, OutboundConfig, OutboundQueue, OutboundStore};
+
+const DISPATCH_INTERVAL_MS: u64 = 100;
+const CONNECTION_OUTBOX_CAPACITY: usize = 64;
+
+/// Connections currently able to deliver responses, per platform.
+/// The most recently registered connection wins.
+type ConnectionRegistry = Arc<RwLock<HashMap<Platform, Vec<ConnectionHandle>>>>;
+
+#[derive(Clone)]
+struct ConnectionHandle {
+    id: u64,
+    outbox: mpsc::Sender<AgentMessage>,
+    delivery_acks: bool,
+    capabilities: PlatformCapabilities,
+}
 
 pub struct PlatformRelayWorker {
     socket_path: String,
+    protocol: RelayProtocolConfig,
+    outbound: Option<Arc<Mutex<OutboundQueue>>>,
     status: WorkerStatus,
 }
 
@@ -26,12 +60,27 @@ impl PlatformRelayWorker {
     pub fn new(socket_path: impl Into<String>) -> Self {
         Self {
             socket_path: socket_path.into(),
+            protocol: RelayProtocolConfig::default(),
+            outbound: None,
             status: WorkerStatus::NotStarted,
         }
     }
 
     pub fn from_env() -> Self {
         Self::new(super::protocol::resolve_socket_path())
+            .with_protocol_config(RelayProtocolConfig::from_env())
+    }
+
+    pub fn with_protocol_config(mut self, protocol: RelayProtocolConfig) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Use a (typically disk-backed) outbound queue. Without one the worker
+    /// falls back to an in-memory queue that does not survive restarts.
+    pub fn with_outbound_queue(mut self, queue: Arc<Mutex<OutboundQueue>>) -> Self {
+        self.outbound = Some(queue);
+        self
     }
 }
 
@@ -46,23 +95,61 @@ impl Worker for PlatformRelayWorker {
         let _ = std::fs::remove_file(&self.socket_path);
 
         let listener = UnixListener::bind(&self.socket_path)?;
-        info!(socket = %self.socket_path, "Platform relay UDS server listening");
+        info!(
+            socket = %self.socket_path,
+            max_frame_bytes = self.protocol.max_frame_bytes,
+            compress_min_bytes = ?self.protocol.compress_min_bytes,
+            error_budget = self.protocol.error_budget,
+            "Platform relay UDS server listening"
+        );
+
+        let queue = match &self.outbound {
+            Some(queue) => Arc::clone(queue),
+            None => Arc::new(Mutex::new(OutboundQueue::open(
+                OutboundStore::open_in_memory()?,
+                OutboundConfig::default(),
+                now_ms(),
+            )?)),
+        };
 
         self.status = WorkerStatus::Healthy;
 
         let event_tx = ctx.event_tx.clone();
-        let broadcast_tx = ctx.broadcast_rx.clone();
         let mut shutdown_rx = ctx.subscribe_shutdown();
+        let protocol = self.protocol;
+        let registry: ConnectionRegistry = Arc::new(RwLock::new(HashMap::new()));
+        let (report_tx, report_rx) = mpsc::channel::<(u64, DeliveryOutcome)>(256);
+        let next_connection_id = AtomicU64::new(1);
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel::<Event>();
+
+        tokio::spawn(forward_outbound(
+            ctx.subscribe_events(),
+            outbound_tx,
+            Arc::clone(&queue),
+            ctx.subscribe_shutdown(),
+        ));
+        tokio::spawn(run_dispatcher(
+            Arc::clone(&queue),
+            Arc::clone(&registry),
+            outbound_rx,
+            report_rx,
+            ctx.subscribe_shutdown(),
+        ));
 
         loop {
             tokio::select! {
                 accept = listener.accept() => {
                     match accept {
                         Ok((stream, _)) => {
-                            let tx = event_tx.clone();
-                            let bcast = broadcast_tx.clone();
+                            let connection = ConnectionContext {
+                                id: next_connection_id.fetch_add(1, Ordering::Relaxed),
+                                event_tx: event_tx.clone(),
+                                registry: Arc::clone(&registry),
+                                report_tx: report_tx.clone(),
+                                protocol,
+                            };
                             tokio::spawn(async move {
-                                if let Err(e) = handle_connection(stream, tx, bcast).await {
+                                if let Err(e) = handle_connection(stream, connection).await {
                                     debug!(error = %e, "Platform relay connection closed");
                                 }
                             });
@@ -94,68 +181,405 @@ impl Worker for PlatformRelayWorker {
     }
 }
 
-async fn handle_connection(
-    stream: UnixStream,
-    event_tx: mpsc::Sender<Event>,
-    broadcast_tx: broadcast::Sender<Event>,
-) -> Result<()> {
-    let (read_half, write_half) = stream.into_split();
-    let mut reader = tokio::io::BufReader::new(read_half);
-    let write_half = Arc::new(RwLock::new(write_half));
-
-    // Detect which platform this connection serves from the first message,
-    // then subscribe to responses for that platform only.
-    let mut detected_platform: Option<Platform> = None;
-
-    // Spawn response forwarder — waits until platform is detected.
-    let (platform_tx, mut platform_rx) = mpsc::channel::<Platform>(1);
-    let write_clone = Arc::clone(&write_half);
-    let mut bcast_rx = broadcast_tx.subscribe();
-
-    tokio::spawn(async move {
-        // Wait for the platform to be detected from the ingest loop.
-        let platform = match platform_rx.recv().await {
-            Some(p) => p,
-            None => return,
-        };
+/// Move responses and typing events off the bus into the dispatcher's own
+/// channel. This task does nothing else, so it keeps up with the bus while
+/// the dispatcher waits on the queue or on slow connections; when it still
+/// falls behind, the loss is logged and counted in the outbound stats.
+async fn forward_outbound(
+    mut bcast_rx: broadcast::Receiver<Event>,
+    outbound_tx: mpsc::UnboundedSender<Event>,
+    queue: Arc<Mutex<OutboundQueue>>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) {
+    loop {
+        tokio::select! {
+            event = bcast_rx.recv() => {
+                match event {
+                    Ok(event @ (Event::Response(_) | Event::Typing(_))) => {
+                        if outbound_tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(
+                            missed = n,
+                            "Platform relay fell behind the event bus; responses among the missed events are lost"
+                        );
+                        queue.lock().await.record_missed(n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            _ = shutdown_rx.recv() => break,
+        }
+    }
+}
 
-        loop {
-            match bcast_rx.recv().await {
-                Ok(Event::Response(resp)) if resp.platform == platform => {
-                    let msg = AgentMessage::Response { event: resp };
-                    let mut w = write_clone.write().await;
-                    if let Err(e) = send_message(&mut *w, &msg).await {
-                        debug!(error = %e, "Platform relay: failed to forward response");
-                        break;
+async fn run_dispatcher(
+    queue: Arc<Mutex<OutboundQueue>>,
+    registry: ConnectionRegistry,
+    mut outbound_rx: mpsc::UnboundedReceiver<Event>,
+    mut report_rx: mpsc::Receiver<(u64, DeliveryOutcome)>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) {
+    let mut tick = tokio::time::interval(Duration::from_millis(DISPATCH_INTERVAL_MS));
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            event = outbound_rx.recv() => {
+                match event {
+                    Some(Event::Typing(typing)) => {
+                        send_typing(&registry, typing).await;
+                    }
+                    Some(Event::Response(resp)) => {
+                        let capabilities = capabilities_for(&registry, resp.platform).await;
+                        let mut chunks = if resp.kind.has_text() {
+                            format::split_message(&resp.content, capabilities.max_message_chars)
+                        } else {
+                            vec![resp.content.clone()]
+                        };
+                        if chunks.len() > 1 {
+                            debug!(
+                                platform = %resp.platform,
+                                chunks = chunks.len(),
+                                max_chars = capabilities.max_message_chars,
+                                "Platform relay: splitting long response"
+                            );
+                            // An edit rewrites one message; the overflow has nowhere to go.
+                            if matches!(resp.kind, ResponseKind::Edit { .. }) {
+                                warn!(
+                                    platform = %resp.platform,
+                                    channel = %resp.channel_id,
+                                    "Platform relay: truncating edit to one message"
+                                );
+                                chunks.truncate(1);
+                            }
+                        }
+
+                        let mut q = queue.lock().await;
+                        for chunk in response_chunks(&resp, chunks) {
+                            if let Err(e) = q.enqueue(&chunk, now_ms()) {
+                                error!(
+                                    error = %e,
+                                    platform = %resp.platform,
+                                    channel = %resp.channel_id,
+                                    "Platform relay: failed to queue response"
+                                );
+                                break;
+                            }
+                        }
+                        dispatch_ready(&mut q, &registry).await;
                     }
+                    Some(_) => {}
+                    None => break,
                 }
-                Ok(_) => {}
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!(missed = n, "Platform relay broadcast receiver lagged");
+            }
+            Some((delivery_id, outcome)) = report_rx.recv() => {
+                let mut q = queue.lock().await;
+                if let Err(e) = q.complete(delivery_id, outcome, now_ms()) {
+                    error!(error = %e, delivery_id, "Platform relay: failed to record delivery");
+                }
+                dispatch_ready(&mut q, &registry).await;
+            }
+            _ = tick.tick() => {
+                let mut q = queue.lock().await;
+                if let Err(e) = q.expire_in_flight(now_ms()) {
+                    error!(error = %e, "Platform relay: failed to expire deliveries");
+                }
+                dispatch_ready(&mut q, &registry).await;
+            }
+            _ = shutdown_rx.recv() => break,
+        }
+    }
+}
+
+/// One response per chunk of `resp`. Only the first chunk of a reply
+/// quotes the message it answers; the rest follow it in order. Private
+/// replies keep the reference on every chunk.
+fn response_chunks(resp: &ResponseEvent, chunks: Vec<String>) -> Vec<ResponseEvent> {
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, content)| {
+            let mut chunk = resp.clone();
+            chunk.content = content;
+            if i > 0 && !resp.private_reply {
+                chunk.reply_to_message_id = None;
+            }
+            // Each chunk of a streamed reply is its own editable message.
+            if let ResponseKind::StreamUpdate { part, .. } = &mut chunk.kind {
+                *part = i as u32;
+            }
+            chunk
+        })
+        .collect()
+}
+
+async fn dispatch_ready(queue: &mut OutboundQueue, registry: &ConnectionRegistry) {
+    let connections: HashMap<Platform, ConnectionHandle> = registry
+        .read()
+        .await
+        .iter()
+        .filter_map(|(platform, handles)| handles.last().map(|h| (*platform, h.clone())))
+        .collect();
+
+    let now = now_ms();
+    for entry in queue.take_ready(now, |platform| connections.contains_key(&platform)) {
+        let Some(conn) = connections.get(&entry.event.platform) else {
+            queue.release(entry.id);
+            continue;
+        };
+
+        let mut event = entry.event;
+        if let ResponseKind::StreamUpdate { is_final, .. } = event.kind {
+            if !conn.capabilities.supports_edits {
+                // Without edits only the finished text is worth sending.
+                if !is_final {
+                    skip_delivery(queue, entry.id, now);
+                    continue;
                 }
-                Err(broadcast::error::RecvError::Closed) => break,
+                event.kind = ResponseKind::Message;
+            }
+        }
+        match &event.kind {
+            ResponseKind::React { message_id, emoji } if !conn.capabilities.reactions => {
+                event.reply_to_message_id = Some(message_id.clone());
+                event.content = emoji.clone();
+                event.kind = ResponseKind::Message;
+            }
+            ResponseKind::Edit { .. } | ResponseKind::Delete { .. }
+                if !conn.capabilities.supports_edits =>
+            {
+                debug!(
+                    platform = %event.platform,
+                    channel = %event.channel_id,
+                    "Platform relay: connection cannot edit or delete, dropping action"
+                );
+                skip_delivery(queue, entry.id, now);
+                continue;
+            }
+            _ => {}
+        }
+        event.content = format::render(&event.content, conn.capabilities.markup);
+        let msg = AgentMessage::Response {
+            event,
+            delivery_id: conn.delivery_acks.then_some(entry.id),
+        };
+        if conn.outbox.try_send(msg).is_err() {
+            queue.release(entry.id);
+            continue;
+        }
+
+        // Legacy clients never report back; the frame being handed off is all we know.
+        if !conn.delivery_acks {
+            if let Err(e) = queue.complete(entry.id, DeliveryOutcome::Sent, now) {
+                error!(error = %e, delivery_id = entry.id, "Platform relay: failed to record delivery");
+            }
+        }
+    }
+}
+
+/// Settle an entry that is deliberately not sent.
+fn skip_delivery(queue: &mut OutboundQueue, delivery_id: u64, now: i64) {
+    if let Err(e) = queue.complete(delivery_id, DeliveryOutcome::Sent, now) {
+        error!(error = %e, delivery_id, "Platform relay: failed to record delivery");
+    }
+}
+
+/// Typing indicators are best-effort and never queued: a late one would
+/// outlive the reply it announced.
+async fn send_typing(registry: &ConnectionRegistry, typing: TypingEvent) {
+    let conn = registry
+        .read()
+        .await
+        .get(&typing.platform)
+        .and_then(|handles| handles.last().cloned());
+    if let Some(conn) = conn.filter(|c| c.capabilities.typing) {
+        let _ = conn.outbox.try_send(AgentMessage::Typing {
+            channel_id: typing.channel_id,
+            thread_id: typing.thread_id,
+        });
+    }
+}
+
+/// Capabilities of the connection serving `platform`, or its defaults.
+async fn capabilities_for(
+    registry: &ConnectionRegistry,
+    platform: Platform,
+) -> PlatformCapabilities {
+    registry
+        .read()
+        .await
+        .get(&platform)
+        .and_then(|handles| handles.last())
+        .map(|h| h.capabilities)
+        .unwrap_or_else(|| PlatformCapabilities::defaults_for(platform))
+}
+
+async fn register(registry: &ConnectionRegistry, platform: Platform, handle: ConnectionHandle) {
+    let mut registry = registry.write().await;
+    let handles = registry.entry(platform).or_default();
+    handles.retain(|h| h.id != handle.id);
+    handles.push(handle);
+}
+
+async fn unregister(registry: &ConnectionRegistry, id: u64) {
+    let mut registry = registry.write().await;
+    for handles in registry.values_mut() {
+        handles.retain(|h| h.id != id);
+    }
+}
+
+struct ConnectionContext {
+    id: u64,
+    event_tx: mpsc::Sender<Event>,
+    registry: ConnectionRegistry,
+    report_tx: mpsc::Sender<(u64, DeliveryOutcome)>,
+    protocol: RelayProtocolConfig,
+}
+
+async fn handle_connection(stream: UnixStream, conn: ConnectionContext) -> Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = tokio::io::BufReader::new(read_half);
+    let protocol = conn.protocol;
+
+    // Platform this connection serves, from its hello or its first ingest.
+    let mut registered_platform: Option<Platform> = None;
+    let mut delivery_acks = false;
+    let mut declared_capabilities: Option<PlatformCapabilities> = None;
+    // Last health reported for each name, so a dropped runner is not left looking healthy.
+    let mut reported_status: HashMap<String, WorkerStatus> = HashMap::new();
+
+    // Outbound compression stays off until the client says it can decode it.
+    let peer_accepts_compression = Arc::new(AtomicBool::new(false));
+    let mut budget = ErrorBudget::new(protocol.error_budget);
+
+    // Single writer so acks, pongs and responses never interleave mid-frame.
+    let (outbox_tx, mut outbox_rx) = mpsc::channel::<AgentMessage>(CONNECTION_OUTBOX_CAPACITY);
+    let compression_clone = Arc::clone(&peer_accepts_compression);
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = outbox_rx.recv().await {
+            let compress = protocol
+                .compress_min_bytes
+                .filter(|_| compression_clone.load(Ordering::Relaxed));
+            if let Err(e) = send_message(&mut write_half, &msg, compress).await {
+                debug!(error = %e, "Platform relay: failed to write frame");
+                break;
             }
         }
     });
 
     // Ingest loop — read frames from the platform process.
-    loop {
-        match recv_message::<_, PlatformMessage>(&mut reader).await? {
-            None => {
+    let result = loop {
+        let frame = match read_frame(&mut reader, protocol.max_frame_bytes).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
                 debug!("Platform relay connection EOF");
-                break;
+                break Ok(());
             }
-            Some(PlatformMessage::Ping) => {
-                let mut w = write_half.write().await;
-                send_message(&mut *w, &AgentMessage::Pong).await?;
+            Err(e) => break Err(e),
+        };
+
+        let message = match decode_frame::<PlatformMessage>(&frame, protocol.max_frame_bytes) {
+            Ok(message) => message,
+            Err(e) => {
+                if !budget.spend() {
+                    break Err(anyhow::anyhow!("platform relay error budget exhausted: {e}"));
+                }
+                warn!(
+                    error = %e,
+                    frame_len = frame.payload.len(),
+                    remaining_budget = budget.remaining(),
+                    "Platform relay: skipping undecodable frame"
+                );
+                continue;
             }
-            Some(PlatformMessage::Ingest { event }) => {
+        };
+
+        match message {
+            PlatformMessage::Hello {
+                platform,
+                compression,
+                delivery_acks: acks,
+                capabilities,
+            } => {
+                peer_accepts_compression.store(compression, Ordering::Relaxed);
+                delivery_acks = acks;
+                declared_capabilities = capabilities;
+                debug!(
+                    compression,
+                    delivery_acks,
+                    ?capabilities,
+                    "Platform relay: client hello"
+                );
+                if let Some(platform) = platform {
+                    registered_platform = Some(platform);
+                    register(
+                        &conn.registry,
+                        platform,
+                        ConnectionHandle {
+                            id: conn.id,
+                            outbox: outbox_tx.clone(),
+                            delivery_acks,
+                            capabilities: declared_capabilities
+                                .unwrap_or_else(|| PlatformCapabilities::defaults_for(platform)),
+                        },
+                    )
+                    .await;
+                    info!(platform = %platform, "Platform relay: new connection registered");
+                }
+            }
+            PlatformMessage::Status { name, status } => {
+                debug!(name = %name, status = %status, "Platform relay: status report");
+                reported_status.insert(name.clone(), status.clone());
+                let _ = conn
+                    .event_tx
+                    .send(Event::System(SystemEvent::WorkerHealth { name, status }))
+                    .await;
+            }
+            PlatformMessage::Ping => {
+                if outbox_tx.send(AgentMessage::Pong).await.is_err() {
+                    break Ok(());
+                }
+            }
+            PlatformMessage::Delivery {
+                delivery_id,
+                outcome,
+            } => {
+                let _ = conn.report_tx.send((delivery_id, outcome)).await;
+            }
+            PlatformMessage::MessageEdited { event } => {
+                let _ = conn.event_tx.send(Event::MessageEdited(event)).await;
+            }
+            PlatformMessage::MessageDeleted { event } => {
+                let _ = conn.event_tx.send(Event::MessageDeleted(event)).await;
+            }
+            PlatformMessage::Reaction { event } => {
+                let _ = conn.event_tx.send(Event::Reaction(event)).await;
+            }
+            PlatformMessage::Command { event } => {
+                let _ = conn.event_tx.send(Event::Command(event)).await;
+            }
+            PlatformMessage::Ingest { event } => {
                 let platform = event.platform;
 
-                // Detect platform from first ingest and notify the response forwarder.
-                if detected_platform.is_none() {
-                    detected_platform = Some(platform);
-                    let _ = platform_tx.send(platform).await;
+                // Clients that skip the hello are registered by their first ingest.
+                if registered_platform.is_none() {
+                    registered_platform = Some(platform);
+                    register(
+                        &conn.registry,
+                        platform,
+                        ConnectionHandle {
+                            id: conn.id,
+                            outbox: outbox_tx.clone(),
+                            delivery_acks,
+                            capabilities: declared_capabilities
+                                .unwrap_or_else(|| PlatformCapabilities::defaults_for(platform)),
+                        },
+                    )
+                    .await;
                     info!(
                         platform = %platform,
                         "Platform relay: new connection registered"
@@ -169,14 +593,96 @@ async fn handle_connection(
                     "Platform relay: ingest event"
                 );
 
-                let _ = event_tx.send(Event::Raw(event)).await;
+                let _ = conn.event_tx.send(Event::Raw(event)).await;
 
                 // Acknowledge receipt.
-                let mut w = write_half.write().await;
-                send_message(&mut *w, &AgentMessage::Ack).await?;
+                if outbox_tx.send(AgentMessage::Ack).await.is_err() {
+                    break Ok(());
+                }
             }
         }
+    };
+
+    for (name, status) in reported_status {
+        if status != WorkerStatus::Stopped {
+            let status = WorkerStatus::Degraded {
+                reason: "relay connection lost".to_string(),
+            };
+            let _ = conn
+                .event_tx
+                .send(Event::System(SystemEvent::WorkerHealth { name, status }))
+                .await;
+        }
     }
 
-    Ok(())
+    // Dropping the last outbox sender lets the writer flush and exit.
+    unregister(&conn.registry, conn.id).await;
+    drop(outbox_tx);
+    let _ = writer.await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::event::ResponseSource;
+
+    fn response(private_reply: bool) -> ResponseEvent {
+        ResponseEvent {
+            platform: Platform::Discord,
+            channel_id: "c1".to_string(),
+            thread_id: None,
+            reply_to_message_id: Some("m1".to_string()),
+            reply_to_user: None,
+            is_dm: false,
+            content: String::new(),
+            source: ResponseSource::CloudLLM,
+            kind: ResponseKind::Message,
+            private_reply,
+        }
+    }
+
+    #[test]
+    fn private_replies_keep_their_reference_on_every_chunk() {
+        let chunks = || vec!["one".to_string(), "two".to_string()];
+        let reply = response_chunks(&response(false), chunks());
+        let replies: Vec<_> = reply.iter().map(|c| c.reply_to_message_id.as_deref()).collect();
+        assert_eq!(replies, vec![Some("m1"), None]);
+
+        let answer = response_chunks(&response(true), chunks());
+        let replies: Vec<_> = answer.iter().map(|c| c.reply_to_message_id.as_deref()).collect();
+        assert_eq!(replies, vec![Some("m1"), Some("m1")]);
+        assert_eq!(answer[1].content, "two");
+    }
+
+    #[tokio::test]
+    async fn events_the_relay_falls_behind_on_are_counted() {
+        let (bcast_tx, bcast_rx) = broadcast::channel(2);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let (outbound_tx, mut outbound_rx) = mpsc::unbounded_channel();
+        let queue = Arc::new(Mutex::new(
+            OutboundQueue::open(
+                OutboundStore::open_in_memory().unwrap(),
+                OutboundConfig::default(),
+                0,
+            )
+            .unwrap(),
+        ));
+
+        for _ in 0..5 {
+            bcast_tx
+                .send(Event::Response(response(false)))
+                .unwrap();
+        }
+        drop(bcast_tx);
+        forward_outbound(bcast_rx, outbound_tx, Arc::clone(&queue), shutdown_rx).await;
+        drop(shutdown_tx);
+
+        let mut forwarded = 0;
+        while outbound_rx.try_recv().is_ok() {
+            forwarded += 1;
+        }
+        assert_eq!(forwarded, 2);
+        assert_eq!(queue.lock().await.stats(0).missed_total, 3);
+    }
 }
@@ -0,0 +1,105 @@
+//! Platform-side bookkeeping for streamed replies.
+//!
+//! The first `ResponseKind::StreamUpdate` for a `(stream_id, part)` creates a
+//! platform message; later updates edit it. Platform workers keep the message
+//! handle here between updates.
+
+use std::collections::{HashMap, VecDeque};
+
+use kernel::event::ResponseKind;
+
+/// Streams that never receive a final update (agent restart, dropped entry)
+/// are forgotten oldest-first past this many open messages.
+const MAX_OPEN_MESSAGES: usize = 256;
+
+type StreamKey = (String, u32);
+
+pub struct StreamedMessages<M> {
+    messages: HashMap<StreamKey, M>,
+    order: VecDeque<StreamKey>,
+}
+
+impl<M: Clone> Default for StreamedMessages<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Clone> StreamedMessages<M> {
+    pub fn new() -> Self {
+        Self {
+            messages: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The message an update should edit, if its stream already created one.
+    pub fn target(&self, kind: &ResponseKind) -> Option<M> {
+        stream_key(kind).and_then(|key| self.messages.get(&key).cloned())
+    }
+
+    /// Record a delivered update. Finished streams are forgotten.
+    pub fn delivered(&mut self, kind: &ResponseKind, message: M) {
+        let Some(key) = stream_key(kind) else {
+            return;
+        };
+        if matches!(kind, ResponseKind::StreamUpdate { is_final: true, .. }) {
+            self.forget(kind);
+            return;
+        }
+
+        if self.messages.insert(key.clone(), message).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_OPEN_MESSAGES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.messages.remove(&oldest);
+            }
+        }
+    }
+
+    /// Drop the message for an update, e.g. when it was deleted on the
+    /// platform and the next update should create a new one.
+    pub fn forget(&mut self, kind: &ResponseKind) {
+        if let Some(key) = stream_key(kind) {
+            self.messages.remove(&key);
+            self.order.retain(|k| *k != key);
+        }
+    }
+}
+
+fn stream_key(kind: &ResponseKind) -> Option<StreamKey> {
+    match kind {
+        ResponseKind::StreamUpdate {
+            stream_id, part, ..
+        } => Some((stream_id.clone(), *part)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(part: u32, is_final: bool) -> ResponseKind {
+        ResponseKind::StreamUpdate {
+            stream_id: "s".to_string(),
+            part,
+            is_final,
+        }
+    }
+
+    #[test]
+    fn updates_target_the_created_message_until_final() {
+        let mut streams = StreamedMessages::new();
+        assert_eq!(streams.target(&update(0, false)), None);
+
+        streams.delivered(&update(0, false), 42u64);
+        assert_eq!(streams.target(&update(0, false)), Some(42));
+        assert_eq!(streams.target(&update(1, false)), None);
+        assert_eq!(streams.target(&ResponseKind::Message), None);
+
+        streams.delivered(&update(0, true), 42);
+        assert_eq!(streams.target(&update(0, false)), None);
+    }
+}
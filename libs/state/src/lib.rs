@@ -6,7 +6,7 @@ use std::time::Duration;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use kernel::event::{Event, Intent, Platform, RawEvent, Sentiment};
+use kernel::event::{Command, Event, Intent, Platform, RawEvent, Sentiment};
 use kernel::worker::{Worker, WorkerContext, WorkerStatus};
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
@@ -126,6 +126,9 @@ pub struct EventDeltaRequest {
     pub source: String,
 }
 
+/// Actor recorded on history entries of a forgotten user.
+pub const FORGOTTEN_ACTOR: &str = "forgotten";
+
 #[derive(Debug, Clone)]
 pub struct StateStore {
     schema: Arc<StateSchema>,
@@ -249,6 +252,20 @@ impl StateStore {
         history.iter().rev().take(count).cloned().collect()
     }
 
+    /// Attribute the history entries caused by any of `actors` to nobody,
+    /// for a user who asked to be forgotten. Returns how many changed.
+    pub async fn forget_actors(&self, actors: &[String]) -> usize {
+        let mut history = self.history.write().await;
+        let mut changed = 0;
+        for log in history.iter_mut() {
+            if actors.iter().any(|actor| actor.eq_ignore_ascii_case(&log.actor)) {
+                log.actor = FORGOTTEN_ACTOR.to_string();
+                changed += 1;
+            }
+        }
+        changed
+    }
+
     pub async fn patch_manual(&self, req: ManualPatchRequest) -> Result<ManualPatchResult> {
         let dim = self
             .schema
@@ -1055,31 +1072,24 @@ impl Worker for StateCommandWorker {
                             if !self.store.mark_event_if_new("state_command", &raw.message_id).await {
                                 continue;
                             }
-
-                            let actor = raw.username.clone();
-                            for (dimension_id, value) in command.sets {
-                                let _ = self.store.patch_manual(ManualPatchRequest {
-                                    dimension_id,
-                                    value,
-                                    reason: "state_command".to_string(),
-                                    actor: Some(actor.clone()),
-                                }).await;
+                            self.apply_command(command, &raw.username).await;
+                        }
+                        Ok(Event::Command(command)) => {
+                            if !matches!(
+                                command.command,
+                                Command::State { .. } | Command::StateGet | Command::StateSet { .. }
+                            ) {
+                                continue;
                             }
-
-                            if !command.deltas.is_empty() {
-                                let updates: Vec<EventDeltaRequest> = command
-                                    .deltas
-                                    .into_iter()
-                                    .map(|(dimension_id, delta)| EventDeltaRequest {
-                                        dimension_id,
-                                        delta,
-                                        reason: "state_command".to_string(),
-                                        actor: actor.clone(),
-                                        source: "state_command".to_string(),
-                                    })
-                                    .collect();
-                                let _ = self.store.apply_event_deltas(&updates).await;
+                            if !self.store.mark_event_if_new("state_command", &command.message_id).await {
+                                continue;
                             }
+                            let reply = if !command.is_operator {
+                                STATE_COMMAND_OPERATOR_ONLY.to_string()
+                            } else {
+                                self.answer_command(&command.command, &command.username).await
+                            };
+                            let _ = ctx.event_tx.send(Event::Response(command.reply(reply))).await;
                         }
                         Ok(_) => {}
                         Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
@@ -1162,6 +1172,34 @@ impl Worker for StateUserWorker {
                                 let _ = self.store.apply_event_deltas(&updates).await;
                             }
                         }
+                        Ok(Event::Reaction(reaction)) => {
+                            // Only reactions to the agent's own messages are feedback on it.
+                            if !reaction.added || !reaction.on_agent_message {
+                                continue;
+                            }
+                            let reaction_id = format!(
+                                "{}:{}:{}",
+                                reaction.message_id, reaction.user_id, reaction.emoji
+                            );
+                            if !self.store.mark_event_if_new("state_user_reaction", &reaction_id).await {
+                                continue;
+                            }
+                            let mut updates = Vec::new();
+                            let actor = reaction.username.clone();
+                            push_user_delta(&mut updates, "user.engagement", 0.01, &actor, "reaction");
+                            match reaction.sentiment() {
+                                Sentiment::Positive => {
+                                    push_user_delta(&mut updates, "user.sentiment", 0.02, &actor, "reaction");
+                                    push_user_delta(&mut updates, "user.trust", 0.005, &actor, "reaction");
+                                }
+                                Sentiment::Negative => {
+                                    push_user_delta(&mut updates, "user.sentiment", -0.02, &actor, "reaction");
+                                    push_user_delta(&mut updates, "user.trust", -0.005, &actor, "reaction");
+                                }
+                                Sentiment::Neutral => {}
+                            }
+                            let _ = self.store.apply_event_deltas(&updates).await;
+                        }
                         Ok(Event::Response(response)) => {
                             if response.source != kernel::event::ResponseSource::CloudLLM {
                                 continue;
@@ -1532,8 +1570,98 @@ struct ParsedCommand {
     deltas: Vec<(String, f64)>,
 }
 
+/// Text `/state` commands. Discord sends `/state` as a slash command instead.
 fn should_handle_command(raw: &RawEvent) -> bool {
-    raw.is_dm || raw.is_mention || raw.platform == Platform::Cli
+    raw.platform != Platform::Discord
+        && (raw.is_dm || raw.is_mention || raw.platform == Platform::Cli)
+}
+
+const STATE_COMMAND_OPERATOR_ONLY: &str = "Only operators can change my state.";
+const STATE_COMMAND_USAGE: &str = "Usage: /state warmth=0.7 curiosity+=0.1";
+
+impl StateCommandWorker {
+    async fn answer_command(&self, command: &Command, actor: &str) -> String {
+        match command {
+            Command::State { args } => match parse_state_tokens(args) {
+                Some(parsed) => {
+                    let (updated, failures) = self.apply_command(parsed, actor).await;
+                    let mut text = format!("Updated {updated} state dimension(s).");
+                    if !failures.is_empty() {
+                        text.push_str(&format!(" Could not update {}.", failures.join("; ")));
+                    }
+                    text
+                }
+                None => STATE_COMMAND_USAGE.to_string(),
+            },
+            Command::StateGet => {
+                let mut text = String::from("Current state:");
+                for row in self.store.rows().await {
+                    text.push_str(&format!("\n{} = {:.2}", row.id, row.value));
+                }
+                text
+            }
+            Command::StateSet { dimension, value } => {
+                let Some(dimension_id) = normalize_dimension_key(dimension) else {
+                    return format!("Unknown state dimension: {dimension}");
+                };
+                let patched = self
+                    .store
+                    .patch_manual(ManualPatchRequest {
+                        dimension_id,
+                        value: *value,
+                        reason: "state_command".to_string(),
+                        actor: Some(actor.to_string()),
+                    })
+                    .await;
+                match patched {
+                    Ok(result) => format!("Set {} to {:.2}.", result.row.id, result.row.value),
+                    Err(e) => format!("Could not set {dimension}: {e}"),
+                }
+            }
+            other => format!("The state worker does not handle {other:?}."),
+        }
+    }
+
+    /// Applies `command` and returns how many dimensions were updated, along
+    /// with a `dimension (error)` note for each one that failed.
+    async fn apply_command(&self, command: ParsedCommand, actor: &str) -> (usize, Vec<String>) {
+        let mut updated = 0;
+        let mut failures = Vec::new();
+        for (dimension_id, value) in command.sets {
+            let patched = self.store.patch_manual(ManualPatchRequest {
+                dimension_id: dimension_id.clone(),
+                value,
+                reason: "state_command".to_string(),
+                actor: Some(actor.to_string()),
+            }).await;
+            match patched {
+                Ok(_) => updated += 1,
+                Err(e) => failures.push(format!("{dimension_id} ({e})")),
+            }
+        }
+
+        if !command.deltas.is_empty() {
+            let updates: Vec<EventDeltaRequest> = command
+                .deltas
+                .into_iter()
+                .map(|(dimension_id, delta)| EventDeltaRequest {
+                    dimension_id,
+                    delta,
+                    reason: "state_command".to_string(),
+                    actor: actor.to_string(),
+                    source: "state_command".to_string(),
+                })
+                .collect();
+            match self.store.apply_event_deltas(&updates).await {
+                Ok(_) => updated += updates.len(),
+                Err(e) => {
+                    let ids: Vec<&str> = updates.iter().map(|u| u.dimension_id.as_str()).collect();
+                    failures.push(format!("{} ({e})", ids.join(", ")));
+                }
+            }
+        }
+        (updated, failures)
+    }
 }
 
 fn parse_state_command(input: &str) -> Option<ParsedCommand> {
@@ -1552,6 +1680,14 @@ fn parse_state_command(input: &str) -> Option<ParsedCommand> {
     if body.starts_with(':') {
         body = body.trim_start_matches(':').trim_start();
     }
+    let _ = prefix;
+    parse_state_tokens(body)
+}
+
+/// `key=value`, `key+=delta` and `key-=delta` tokens, separated by spaces
+/// or commas. Unknown keys and malformed tokens are skipped.
+fn parse_state_tokens(body: &str) -> Option<ParsedCommand> {
+    let body = body.trim();
     if body.is_empty() {
         return None;
     }
@@ -1581,7 +1717,6 @@ fn parse_state_command(input: &str) -> Option<ParsedCommand> {
         return None;
     }
 
-    let _ = prefix;
     Some(ParsedCommand { sets, deltas })
 }
 
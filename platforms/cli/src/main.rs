@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use kernel::event::Platform;
+use sensory::capabilities::PlatformCapabilities;
+use sensory::commands::{help_text, parse_text_command, TextCommand};
+use sensory::relay::{DeliveryOutcome, RelayClient, RelayInbound};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+mod render;
+mod session;
+use render::{Activity, Renderer};
+use session::{Directive, Session};
+
+const USAGE: &str = "\
+Usage: cli-service [--user NAME] [--channel ID] [--dm] [--operator]
+                   [--script FILE] [--timeout SECS]
+
+  --script FILE    read input lines from FILE instead of the terminal, then
+                   wait for the last answer and exit
+  --timeout SECS   longest :wait for an answer (default 60)";
+
+/// After an answer, how long the agent must stay quiet before `:wait` returns.
+const ANSWER_QUIET: Duration = Duration::from_millis(1_500);
+
+struct Options {
+    username: String,
+    channel_id: String,
+    is_dm: bool,
+    is_operator: bool,
+    script: Option<String>,
+    timeout: Duration,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self {
+            username: std::env::var("USER").unwrap_or_else(|_| "cli-user".to_string()),
+            channel_id: "cli".to_string(),
+            is_dm: false,
+            is_operator: false,
+            script: None,
+            timeout: Duration::from_secs(60),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--user" => options.username = value()?,
+                "--channel" => options.channel_id = value()?,
+                "--dm" => options.is_dm = true,
+                "--operator" => options.is_operator = true,
+                "--script" => options.script = Some(value()?),
+                "--timeout" => {
+                    let secs: u64 = value()?.parse().context("--timeout takes whole seconds")?;
+                    options.timeout = Duration::from_secs(secs);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                other => bail!("unknown argument {other}\n\n{USAGE}"),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Where input lines come from.
+enum Input {
+    Terminal(Lines<BufReader<tokio::io::Stdin>>),
+    Script(Lines<BufReader<tokio::fs::File>>),
+}
+
+impl Input {
+    async fn next_line(&mut self) -> Result<Option<String>> {
+        let line = match self {
+            Input::Terminal(lines) => lines.next_line().await?,
+            Input::Script(lines) => lines.next_line().await?,
+        };
+        Ok(line)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match dotenvy::dotenv() {
+        Ok(path) => info!(path = %path.display(), "Loaded .env file"),
+        Err(dotenvy::Error::Io(_)) => info!("No .env file found"),
+        Err(e) => warn!(error = %e, "Failed to parse .env file"),
+    }
+
+    // Logs go to stderr and default to warnings, so they stay out of the conversation.
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(true)
+        .init();
+
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    // Edits are rendered by growing the line, so streamed replies arrive as they are written.
+    let capabilities = PlatformCapabilities {
+        supports_edits: true,
+        ..PlatformCapabilities::defaults_for(Platform::Cli)
+    };
+    let mut relay = RelayClient::connect_platform_with(Platform::Cli, capabilities)
+        .await
+        .context("Is the agent running?")?;
+    let sender = relay.sender();
+
+    let activity = Activity::default();
+    let mut renderer = Renderer::new(activity.clone());
+    let reporter = relay.sender();
+    tokio::spawn(async move {
+        while let Some(inbound) = relay.recv().await {
+            let RelayInbound::Response(delivery) = inbound else {
+                continue;
+            };
+            renderer.response(&delivery.event);
+            if let Err(e) = reporter
+                .report_delivery(delivery.delivery_id, DeliveryOutcome::Sent)
+                .await
+            {
+                error!(error = %e, "Failed to report CLI delivery to relay");
+            }
+        }
+        warn!("Relay connection closed");
+        std::process::exit(1);
+    });
+
+    let mut session = Session::new(options.username, options.channel_id);
+    session.is_dm = options.is_dm;
+    session.is_operator = options.is_operator;
+
+    let scripted = options.script.is_some();
+    let mut input = match &options.script {
+        Some(path) => {
+            let file = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("failed to open script {path}"))?;
+            Input::Script(BufReader::new(file).lines())
+        }
+        None => {
+            println!(
+                "{}. :help for session commands, :quit to exit.",
+                session.describe()
+            );
+            Input::Terminal(BufReader::new(tokio::io::stdin()).lines())
+        }
+    };
+
+    // Answers finished before the last message was sent.
+    let mut answered_before_send = activity.answers();
+    while let Some(line) = input.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() || (scripted && line.starts_with('#')) {
+            continue;
+        }
+        if scripted {
+            println!("{}> {line}", session.username);
+        }
+
+        if let Some(directive) = Directive::parse(line) {
+            let directive = match directive {
+                Ok(directive) => directive,
+                Err(e) => {
+                    println!("{e}");
+                    continue;
+                }
+            };
+            match directive {
+                Directive::Quit => break,
+                Directive::Wait(Some(secs)) => {
+                    tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                }
+                Directive::Wait(None) => {
+                    if !activity
+                        .wait_for_answer(answered_before_send, ANSWER_QUIET, options.timeout)
+                        .await
+                    {
+                        println!("(no answer within {}s)", options.timeout.as_secs());
+                    }
+                }
+                directive => match session.apply(directive) {
+                    Ok(note) => println!("{note}"),
+                    Err(e) => println!("{e:#}"),
+                },
+            }
+            continue;
+        }
+
+        answered_before_send = activity.answers();
+        match parse_text_command(line, None) {
+            Some(TextCommand::Agent(command)) => {
+                sender.command(session.command(command)).await?;
+            }
+            Some(TextCommand::Help) => println!("{}", help_text()),
+            Some(TextCommand::Unknown(name)) => println!("Unknown command /{name}. Try /help."),
+            None => sender.ingest(session.message(line)).await?,
+        }
+    }
+
+    if scripted {
+        activity
+            .wait_for_answer(answered_before_send, ANSWER_QUIET, options.timeout)
+            .await;
+    }
+    Ok(())
+}
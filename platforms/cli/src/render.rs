@@ -0,0 +1,264 @@
+//! Prints the agent's responses, growing streamed replies in place.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use kernel::event::{ResponseEvent, ResponseKind};
+
+/// When the agent last printed something, and how many answers it has
+/// finished. `:wait` watches this.
+#[derive(Clone)]
+pub struct Activity(Arc<Mutex<ActivityState>>);
+
+struct ActivityState {
+    last_output: Instant,
+    answers: u64,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(ActivityState {
+            last_output: Instant::now(),
+            answers: 0,
+        })))
+    }
+}
+
+impl Activity {
+    pub fn answers(&self) -> u64 {
+        self.0.lock().map(|state| state.answers).unwrap_or_default()
+    }
+
+    fn record(&self, answered: bool) {
+        if let Ok(mut state) = self.0.lock() {
+            state.last_output = Instant::now();
+            if answered {
+                state.answers += 1;
+            }
+        }
+    }
+
+    /// Wait until the agent finished an answer after `since` answers and
+    /// then stayed quiet for `quiet`, or until `timeout`.
+    pub async fn wait_for_answer(&self, since: u64, quiet: Duration, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let (answers, idle) = match self.0.lock() {
+                Ok(state) => (state.answers, state.last_output.elapsed()),
+                Err(_) => return false,
+            };
+            if answers > since && idle >= quiet {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+pub struct Renderer {
+    activity: Activity,
+    /// Text already printed for each `(stream_id, part)`.
+    streams: HashMap<(String, u32), String>,
+}
+
+impl Renderer {
+    pub fn new(activity: Activity) -> Self {
+        Self {
+            activity,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn response(&mut self, response: &ResponseEvent) {
+        let answered = self.write_response(&mut std::io::stdout().lock(), response);
+        self.activity.record(answered);
+    }
+
+    /// Print `response` to `out`. Returns whether it finished an answer.
+    fn write_response(&mut self, out: &mut impl Write, response: &ResponseEvent) -> bool {
+        let answered = match &response.kind {
+            ResponseKind::Message => {
+                let _ = writeln!(out, "{}{}", prefix(response), response.content);
+                true
+            }
+            ResponseKind::StreamUpdate {
+                stream_id,
+                part,
+                is_final,
+            } => {
+                let printed = self.streams.entry((stream_id.clone(), *part)).or_default();
+                if printed.is_empty() {
+                    let _ = write!(out, "{}", prefix(response));
+                }
+                match response.content.strip_prefix(printed.as_str()) {
+                    Some(grown) => {
+                        let _ = write!(out, "{grown}");
+                    }
+                    // The text was rewritten rather than extended.
+                    None => {
+                        let _ = write!(out, "\n{}{}", prefix(response), response.content);
+                    }
+                }
+                *printed = response.content.clone();
+                if *is_final {
+                    let _ = writeln!(out);
+                    self.streams.remove(&(stream_id.clone(), *part));
+                }
+                *is_final
+            }
+            ResponseKind::React { message_id, emoji } => {
+                let _ = writeln!(out, "{}reacted {emoji} to {message_id}", prefix(response));
+                true
+            }
+            ResponseKind::Edit { message_id } => {
+                let _ = writeln!(
+                    out,
+                    "{}edited {message_id}: {}",
+                    prefix(response),
+                    response.content
+                );
+                false
+            }
+            ResponseKind::Delete { message_id } => {
+                let _ = writeln!(out, "{}deleted {message_id}", prefix(response));
+                false
+            }
+        };
+        let _ = out.flush();
+        answered
+    }
+}
+
+fn prefix(response: &ResponseEvent) -> String {
+    let target = match &response.thread_id {
+        Some(thread) => format!("{}#{}", response.channel_id, thread),
+        None => response.channel_id.clone(),
+    };
+    match &response.reply_to_user {
+        Some(user) => format!("agent@{target} → {user}: "),
+        None => format!("agent@{target}: "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::event::{Platform, ResponseSource};
+
+    fn response(kind: ResponseKind, content: &str) -> ResponseEvent {
+        ResponseEvent {
+            platform: Platform::Cli,
+            channel_id: "cli".to_string(),
+            thread_id: None,
+            reply_to_message_id: None,
+            reply_to_user: Some("alice".to_string()),
+            is_dm: false,
+            content: content.to_string(),
+            source: ResponseSource::CloudLLM,
+            kind,
+            private_reply: false,
+        }
+    }
+
+    fn update(part: u32, content: &str, is_final: bool) -> ResponseEvent {
+        response(
+            ResponseKind::StreamUpdate {
+                stream_id: "s1".to_string(),
+                part,
+                is_final,
+            },
+            content,
+        )
+    }
+
+    fn render(renderer: &mut Renderer, events: &[ResponseEvent]) -> (String, Vec<bool>) {
+        let mut out = Vec::new();
+        let answered = events
+            .iter()
+            .map(|event| renderer.write_response(&mut out, event))
+            .collect();
+        (String::from_utf8(out).unwrap(), answered)
+    }
+
+    #[test]
+    fn streamed_replies_grow_in_place_until_final() {
+        let mut renderer = Renderer::new(Activity::default());
+        let (out, answered) = render(
+            &mut renderer,
+            &[
+                update(0, "Hel", false),
+                update(0, "Hello", false),
+                update(0, "Hello there", true),
+            ],
+        );
+        assert_eq!(out, "agent@cli → alice: Hello there\n");
+        assert_eq!(answered, vec![false, false, true]);
+        assert!(renderer.streams.is_empty());
+
+        // A finished stream id starts a fresh line if it comes back.
+        let (out, _) = render(&mut renderer, &[update(0, "again", true)]);
+        assert_eq!(out, "agent@cli → alice: again\n");
+    }
+
+    #[test]
+    fn rewritten_stream_text_is_printed_again() {
+        let mut renderer = Renderer::new(Activity::default());
+        let (out, _) = render(
+            &mut renderer,
+            &[update(0, "Hello wrld", false), update(0, "Hello world", true)],
+        );
+        assert_eq!(
+            out,
+            "agent@cli → alice: Hello wrld\nagent@cli → alice: Hello world\n"
+        );
+    }
+
+    #[test]
+    fn parts_of_one_stream_are_tracked_separately() {
+        let mut renderer = Renderer::new(Activity::default());
+        let (_, answered) = render(
+            &mut renderer,
+            &[update(0, "first", true), update(1, "second", false)],
+        );
+        assert_eq!(answered, vec![true, false]);
+        assert_eq!(renderer.streams.len(), 1);
+        assert!(renderer.streams.contains_key(&("s1".to_string(), 1)));
+    }
+
+    #[test]
+    fn messages_and_actions_print_one_line_each() {
+        let mut renderer = Renderer::new(Activity::default());
+        let mut threaded = response(ResponseKind::Message, "hi");
+        threaded.thread_id = Some("t1".to_string());
+        threaded.reply_to_user = None;
+        let (out, answered) = render(
+            &mut renderer,
+            &[
+                threaded,
+                response(
+                    ResponseKind::React {
+                        message_id: "m1".to_string(),
+                        emoji: "👍".to_string(),
+                    },
+                    "",
+                ),
+                response(
+                    ResponseKind::Delete {
+                        message_id: "m2".to_string(),
+                    },
+                    "",
+                ),
+            ],
+        );
+        assert_eq!(
+            out,
+            "agent@cli#t1: hi\nagent@cli → alice: reacted 👍 to m1\nagent@cli → alice: deleted m2\n"
+        );
+        assert_eq!(answered, vec![true, true, false]);
+    }
+}
@@ -0,0 +1,382 @@
+//! The simulated user and conversation typed messages are sent as.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use base64::Engine as _;
+use kernel::event::{
+    Command, CommandEvent, ImageAttachment, Platform, RawEvent, MAX_IMAGE_ATTACHMENTS_PER_MESSAGE,
+    MAX_IMAGE_ATTACHMENT_BYTES,
+};
+
+pub const DIRECTIVE_HELP: &str = "\
+Session commands:
+  :user NAME          send as NAME
+  :channel ID         send to channel ID (leaves any thread)
+  :thread ID|off      send inside thread ID of the channel
+  :dm on|off          mark messages as direct messages
+  :mention on|off     mark messages as mentioning the agent
+  :operator on|off    run /commands as an operator
+  :image PATH         attach an image to the next message
+  :wait [SECS]        wait SECS, or until the agent has answered
+  :who                show the current identity and flags
+  :help               this text
+  :quit               exit
+Lines starting with / are agent commands (/help lists them). Anything else
+is sent as a message.";
+
+/// A `:` line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    User(String),
+    Channel(String),
+    Thread(Option<String>),
+    Dm(bool),
+    Mention(bool),
+    Operator(bool),
+    Image(String),
+    /// Sleep this many seconds, or wait for the agent's answer when `None`.
+    Wait(Option<f64>),
+    Who,
+    Help,
+    Quit,
+}
+
+impl Directive {
+    /// Parse a line starting with `:`. `None` for anything else.
+    pub fn parse(line: &str) -> Option<Result<Self>> {
+        let rest = line.trim().strip_prefix(':')?;
+        let (name, arg) = match rest.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (rest, ""),
+        };
+        Some(Self::parse_parts(name, arg))
+    }
+
+    fn parse_parts(name: &str, arg: &str) -> Result<Self> {
+        let required = |what: &str| -> Result<String> {
+            if arg.is_empty() {
+                bail!(":{name} needs {what}");
+            }
+            Ok(arg.to_string())
+        };
+        let directive = match name {
+            "user" => Directive::User(required("a name")?),
+            "channel" => Directive::Channel(required("a channel id")?),
+            "thread" => match arg {
+                "" => bail!(":thread needs a thread id or off"),
+                "off" => Directive::Thread(None),
+                id => Directive::Thread(Some(id.to_string())),
+            },
+            "dm" => Directive::Dm(parse_switch(name, arg)?),
+            "mention" => Directive::Mention(parse_switch(name, arg)?),
+            "operator" => Directive::Operator(parse_switch(name, arg)?),
+            "image" => Directive::Image(required("a file path")?),
+            "wait" if arg.is_empty() => Directive::Wait(None),
+            "wait" => Directive::Wait(Some(
+                arg.parse()
+                    .ok()
+                    .filter(|secs: &f64| secs.is_finite() && *secs >= 0.0)
+                    .with_context(|| format!("not a number of seconds: {arg}"))?,
+            )),
+            "who" => Directive::Who,
+            "help" => Directive::Help,
+            "quit" | "exit" | "q" => Directive::Quit,
+            other => bail!("unknown session command :{other} (try :help)"),
+        };
+        Ok(directive)
+    }
+}
+
+fn parse_switch(name: &str, arg: &str) -> Result<bool> {
+    match arg {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => bail!(":{name} takes on or off"),
+    }
+}
+
+pub struct Session {
+    pub username: String,
+    pub channel_id: String,
+    pub thread_id: Option<String>,
+    pub is_dm: bool,
+    pub is_mention: bool,
+    pub is_operator: bool,
+    images: Vec<ImageAttachment>,
+    /// Message ids must stay unique across runs; the state worker
+    /// deduplicates commands by id.
+    id_prefix: String,
+    next_id: u64,
+}
+
+impl Session {
+    pub fn new(username: String, channel_id: String) -> Self {
+        Self {
+            username,
+            channel_id,
+            thread_id: None,
+            is_dm: false,
+            is_mention: true,
+            is_operator: false,
+            images: Vec::new(),
+            id_prefix: format!("cli-{}", chrono::Utc::now().timestamp_millis()),
+            next_id: 0,
+        }
+    }
+
+    /// Apply a directive that changes the session. Returns a line to show.
+    pub fn apply(&mut self, directive: Directive) -> Result<String> {
+        let note = match directive {
+            Directive::User(name) => {
+                self.username = name;
+                format!("now sending as {}", self.username)
+            }
+            Directive::Channel(id) => {
+                self.channel_id = id;
+                self.thread_id = None;
+                format!("now in {}", self.target())
+            }
+            Directive::Thread(id) => {
+                self.thread_id = id;
+                format!("now in {}", self.target())
+            }
+            Directive::Dm(on) => {
+                self.is_dm = on;
+                format!("dm {}", on_off(on))
+            }
+            Directive::Mention(on) => {
+                self.is_mention = on;
+                format!("mention {}", on_off(on))
+            }
+            Directive::Operator(on) => {
+                self.is_operator = on;
+                format!("operator {}", on_off(on))
+            }
+            Directive::Image(path) => {
+                self.attach(Path::new(&path))?;
+                format!(
+                    "{} image(s) attached to the next message",
+                    self.images.len()
+                )
+            }
+            Directive::Who => self.describe(),
+            Directive::Help => DIRECTIVE_HELP.to_string(),
+            Directive::Wait(_) | Directive::Quit => String::new(),
+        };
+        Ok(note)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} in {} (dm {}, mention {}, operator {})",
+            self.username,
+            self.target(),
+            on_off(self.is_dm),
+            on_off(self.is_mention),
+            on_off(self.is_operator)
+        )
+    }
+
+    fn target(&self) -> String {
+        match &self.thread_id {
+            Some(thread) => format!("{}#{}", self.channel_id, thread),
+            None => self.channel_id.clone(),
+        }
+    }
+
+    fn attach(&mut self, path: &Path) -> Result<()> {
+        if self.images.len() >= MAX_IMAGE_ATTACHMENTS_PER_MESSAGE {
+            bail!("at most {MAX_IMAGE_ATTACHMENTS_PER_MESSAGE} images per message");
+        }
+        let mime_type = match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => bail!("unsupported image type: {}", path.display()),
+        };
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        if bytes.len() > MAX_IMAGE_ATTACHMENT_BYTES {
+            bail!(
+                "{} is larger than {MAX_IMAGE_ATTACHMENT_BYTES} bytes",
+                path.display()
+            );
+        }
+        self.images.push(ImageAttachment {
+            mime_type: mime_type.to_string(),
+            filename: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            source_url: None,
+            data_base64: base64::prelude::BASE64_STANDARD.encode(bytes),
+        });
+        Ok(())
+    }
+
+    fn message_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("{}-{}", self.id_prefix, self.next_id)
+    }
+
+    /// A message from the simulated user, carrying any attached images.
+    pub fn message(&mut self, content: &str) -> RawEvent {
+        RawEvent {
+            platform: Platform::Cli,
+            channel_id: self.channel_id.clone(),
+            thread_id: self.thread_id.clone(),
+            message_id: self.message_id(),
+            user_id: self.username.clone(),
+            username: self.username.clone(),
+            content: content.to_string(),
+            attachments: std::mem::take(&mut self.images),
+            is_mention: self.is_mention || self.is_dm,
+            is_dm: self.is_dm,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            is_backfill: false,
+        }
+    }
+
+    pub fn command(&mut self, command: Command) -> CommandEvent {
+        CommandEvent {
+            platform: Platform::Cli,
+            channel_id: self.channel_id.clone(),
+            thread_id: self.thread_id.clone(),
+            message_id: self.message_id(),
+            user_id: self.username.clone(),
+            username: self.username.clone(),
+            is_dm: self.is_dm,
+            is_operator: self.is_operator,
+            command,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Directive {
+        Directive::parse(line).unwrap().unwrap()
+    }
+
+    fn parse_err(line: &str) -> String {
+        Directive::parse(line).unwrap().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn directives_parse_with_their_arguments() {
+        assert_eq!(parse(":user bob"), Directive::User("bob".to_string()));
+        assert_eq!(parse("  :channel   general  "), Directive::Channel("general".to_string()));
+        assert_eq!(parse(":thread t1"), Directive::Thread(Some("t1".to_string())));
+        assert_eq!(parse(":thread off"), Directive::Thread(None));
+        assert_eq!(parse(":dm on"), Directive::Dm(true));
+        assert_eq!(parse(":mention no"), Directive::Mention(false));
+        assert_eq!(parse(":operator true"), Directive::Operator(true));
+        assert_eq!(
+            parse(":image pics/a cat.png"),
+            Directive::Image("pics/a cat.png".to_string())
+        );
+        assert_eq!(parse(":wait"), Directive::Wait(None));
+        assert_eq!(parse(":wait 1.5"), Directive::Wait(Some(1.5)));
+        assert_eq!(parse(":who"), Directive::Who);
+        assert_eq!(parse(":help"), Directive::Help);
+        for quit in [":quit", ":exit", ":q"] {
+            assert_eq!(parse(quit), Directive::Quit);
+        }
+    }
+
+    #[test]
+    fn other_lines_are_not_directives() {
+        assert!(Directive::parse("hello :user bob").is_none());
+        assert!(Directive::parse("/help").is_none());
+        assert!(Directive::parse("").is_none());
+    }
+
+    #[test]
+    fn bad_directives_say_what_is_wrong() {
+        assert_eq!(parse_err(":user"), ":user needs a name");
+        assert_eq!(parse_err(":channel "), ":channel needs a channel id");
+        assert_eq!(parse_err(":thread"), ":thread needs a thread id or off");
+        assert_eq!(parse_err(":image"), ":image needs a file path");
+        assert_eq!(parse_err(":dm maybe"), ":dm takes on or off");
+        assert_eq!(parse_err(":mention"), ":mention takes on or off");
+        assert_eq!(parse_err(":wait soon"), "not a number of seconds: soon");
+        assert_eq!(parse_err(":wait -1"), "not a number of seconds: -1");
+        assert_eq!(parse_err(":wait inf"), "not a number of seconds: inf");
+        assert_eq!(parse_err(":nope"), "unknown session command :nope (try :help)");
+    }
+
+    #[test]
+    fn directives_change_what_the_next_message_carries() {
+        let mut session = Session::new("alice".to_string(), "general".to_string());
+        session.apply(parse(":thread t1")).unwrap();
+        assert_eq!(session.apply(parse(":user bob")).unwrap(), "now sending as bob");
+        session.apply(parse(":mention off")).unwrap();
+
+        let first = session.message("hi");
+        assert_eq!(first.username, "bob");
+        assert_eq!(first.channel_id, "general");
+        assert_eq!(first.thread_id.as_deref(), Some("t1"));
+        assert!(!first.is_mention);
+
+        // A new channel leaves the thread; a DM always reaches the agent.
+        assert_eq!(session.apply(parse(":channel random")).unwrap(), "now in random");
+        session.apply(parse(":dm on")).unwrap();
+        let second = session.message("hello");
+        assert_eq!(second.thread_id, None);
+        assert!(second.is_dm && second.is_mention);
+        assert_ne!(first.message_id, second.message_id);
+        assert_eq!(
+            session.describe(),
+            "bob in random (dm on, mention off, operator off)"
+        );
+    }
+
+    #[test]
+    fn images_attach_to_the_next_message_only() {
+        let dir = std::env::temp_dir().join(format!("cli-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = dir.join("dot.PNG");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\n").unwrap();
+        let text = dir.join("notes.txt");
+        std::fs::write(&text, b"notes").unwrap();
+
+        let mut session = Session::new("alice".to_string(), "general".to_string());
+        let note = session
+            .apply(Directive::Image(png.to_string_lossy().into_owned()))
+            .unwrap();
+        assert_eq!(note, "1 image(s) attached to the next message");
+        let err = session
+            .apply(Directive::Image(text.to_string_lossy().into_owned()))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("unsupported image type"), "{err}");
+        assert!(session
+            .apply(Directive::Image(dir.join("gone.png").to_string_lossy().into_owned()))
+            .is_err());
+
+        let message = session.message("look");
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].mime_type, "image/png");
+        assert_eq!(message.attachments[0].filename.as_deref(), Some("dot.PNG"));
+        assert!(session.message("again").attachments.is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -17,6 +17,7 @@ const MAX_FAILURES = 3;
 let outboundSendChain = Promise.resolve();
 const recentOutbound = new Map();
 const OUTBOUND_ECHO_TTL_MS = 15000;
+const MAX_FRAME_BYTES = Number(process.env.PLATFORM_RELAY_MAX_FRAME_BYTES) || 32 * 1024 * 1024;
 
 function rememberOutbound(channelId, content) {
     const normalized = `${channelId}::${String(content || '').trim()}`;
@@ -53,7 +54,13 @@ function connectUDS() {
         connected = true;
         consecutiveConnectionFailures = 0;
 
-        // Initial ping
+        // Register for responses and promise a delivery report for each one.
+        sendToRelay({
+            type: "hello",
+            platform: "DiscordSelfbot",
+            delivery_acks: true,
+            capabilities: { max_message_chars: 2000, markup: "discord_markdown" },
+        });
         sendToRelay({ type: "ping" });
     });
 
@@ -61,6 +68,11 @@ function connectUDS() {
         buffer = Buffer.concat([buffer, data]);
         while (buffer.length >= 4) {
             const msgLen = buffer.readUInt32LE(0);
+            if (msgLen > MAX_FRAME_BYTES) {
+                console.error(`[Selfbot] UDS frame of ${msgLen} bytes exceeds limit. Dropping connection.`);
+                clientSocket.destroy();
+                return;
+            }
             if (buffer.length < 4 + msgLen) {
                 break; // Need more data
             }
@@ -71,7 +83,7 @@ function connectUDS() {
             try {
                 const payload = JSON.parse(msgBuf.toString('utf8'));
                 if (payload.type === 'response') {
-                    enqueueOutgoingResponse(payload.event);
+                    enqueueOutgoingResponse(payload.event, payload.delivery_id);
                 } else if (payload.type === 'pong') {
                     // console.log('[Selfbot] Pong received');
                 } else if (payload.type === 'ack') {
@@ -102,15 +114,40 @@ function connectUDS() {
     });
 }
 
-function enqueueOutgoingResponse(event) {
+function reportDelivery(deliveryId, outcome) {
+    if (deliveryId === undefined || deliveryId === null) return;
+    sendToRelay({ type: 'delivery', delivery_id: deliveryId, outcome });
+}
+
+function deliveryOutcomeFor(error) {
+    const status = error?.httpStatus ?? error?.status;
+    if (status === 429) {
+        const retryAfterMs = Math.ceil((error?.retryAfter ?? 5) * 1000);
+        return { status: 'retry_after', retry_after_ms: retryAfterMs };
+    }
+    return {
+        status: 'failed',
+        error: String(error?.message ?? error),
+        retryable: !status || status >= 500
+    };
+}
+
+function enqueueOutgoingResponse(event, deliveryId) {
     outboundSendChain = outboundSendChain
         .catch(() => {})
         .then(async () => {
-            const { channel_id, content, reply_to_message_id } = event;
+            // A Discord thread is a channel, so a thread id is posted to directly.
+            const channel_id = event.thread_id || event.channel_id;
+            const { content, reply_to_message_id } = event;
             const channel = await client.channels.fetch(channel_id).catch(() => null);
 
             if (!channel) {
                 console.error(`[Selfbot] Unknown channel ID: ${channel_id}`);
+                reportDelivery(deliveryId, {
+                    status: 'failed',
+                    error: `unknown channel ${channel_id}`,
+                    retryable: false
+                });
                 return;
             }
 
@@ -120,6 +157,7 @@ function enqueueOutgoingResponse(event) {
                     const sent = await message.reply(content);
                     rememberOutbound(channel_id, sent?.content ?? content);
                     console.log(`[Selfbot] Replied to ${reply_to_message_id} in ${channel_id}`);
+                    reportDelivery(deliveryId, { status: 'sent' });
                     return;
                 } catch (error) {
                     console.error(`[Selfbot] Could not reply to ${reply_to_message_id}:`, error.message);
@@ -129,9 +167,11 @@ function enqueueOutgoingResponse(event) {
             const sent = await channel.send(content);
             rememberOutbound(channel_id, sent?.content ?? content);
             console.log(`[Selfbot] Sent message to ${channel_id}`);
+            reportDelivery(deliveryId, { status: 'sent' });
         })
         .catch((error) => {
             console.error('[Selfbot] Failed to send queued response:', error.message);
+            reportDelivery(deliveryId, deliveryOutcomeFor(error));
         });
 }
 
@@ -179,6 +219,16 @@ async function extractImageAttachments(msg) {
     return attachments;
 }
 
+const MAX_REPLY_SNIPPET_CHARS = 300;
+
+function replySnippet(content) {
+    const chars = Array.from((content || '').trim());
+    if (chars.length <= MAX_REPLY_SNIPPET_CHARS) {
+        return chars.join('');
+    }
+    return chars.slice(0, MAX_REPLY_SNIPPET_CHARS).join('') + '…';
+}
+
 client.on('messageCreate', async (msg) => {
     const isAllowedChannel = msg.channelId === '1410283966992351363';
     const isAllowedDm = !msg.guildId && msg.author.id === '1320303839701897230';
@@ -199,14 +249,24 @@ client.on('messageCreate', async (msg) => {
     let isMention = false;
     let isDm = !msg.guildId;
     let isReplyToSelf = false;
+    let replyTo = null;
 
     const hasExplicitMention = msg.content.includes(`<@${client.user.id}>`) ||
         msg.content.includes(`<@!${client.user.id}>`);
 
-    if (!isDm && msg.reference?.messageId) {
+    if (msg.reference?.messageId) {
         try {
             const referenced = await msg.fetchReference();
             isReplyToSelf = referenced?.author?.id === client.user.id;
+            if (referenced) {
+                replyTo = {
+                    message_id: referenced.id,
+                    author_id: referenced.author?.id ?? '',
+                    author_name: referenced.author?.username ?? 'Unknown',
+                    snippet: replySnippet(referenced.content),
+                    is_agent: isReplyToSelf
+                };
+            }
         } catch (error) {
             console.warn(`[Selfbot] Failed to resolve reply reference for ${msg.id}: ${error.message}`);
         }
@@ -228,7 +288,8 @@ client.on('messageCreate', async (msg) => {
             attachments: await extractImageAttachments(msg),
             is_mention: isMention,
             is_dm: isDm,
-            timestamp: new Date().toISOString()
+            timestamp: new Date().toISOString(),
+            reply_to: replyTo
         }
     };
 
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct SelfbotConfig {
+    /// Program and arguments for the child, normally `node <script>`.
+    pub program: String,
+    pub args: Vec<String>,
+    pub restart_min: Duration,
+    pub restart_max: Duration,
+    /// A child that stays up this long starts the next backoff from
+    /// `restart_min` again.
+    pub stable_after: Duration,
+    pub heartbeat_interval: Duration,
+    pub heartbeat_timeout: Duration,
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+}
+
+fn env_ms(name: &str, default: u64) -> Duration {
+    Duration::from_millis(env_parse(name).unwrap_or(default))
+}
+
+impl SelfbotConfig {
+    pub fn from_env() -> Self {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/nodejs-selfbot/index.js");
+        let restart_min =
+            env_ms("DISCORD_SELFBOT_RESTART_MIN_MS", 1_000).max(Duration::from_millis(100));
+
+        Self {
+            program: std::env::var("DISCORD_SELFBOT_NODE")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| "node".to_string()),
+            args: vec![script.to_string()],
+            restart_min,
+            restart_max: env_ms("DISCORD_SELFBOT_RESTART_MAX_MS", 60_000).max(restart_min),
+            stable_after: Duration::from_secs(60),
+            heartbeat_interval: env_ms("DISCORD_SELFBOT_HEARTBEAT_MS", 15_000)
+                .max(Duration::from_secs(1)),
+            heartbeat_timeout: Duration::from_secs(5),
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use anyhow::Result;
-use tracing::{info, warn, error};
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
+mod config;
+mod supervisor;
+
+use config::SelfbotConfig;
+use supervisor::SelfbotSupervisor;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     match dotenvy::dotenv() {
@@ -25,22 +31,10 @@ async fn main() -> Result<()> {
 
     info!("=== Discord Selfbot Runner Starting ===");
 
-    let node_script_path = concat!(env!("CARGO_MANIFEST_DIR"), "/nodejs-selfbot/index.js");
-
-    let mut child = match tokio::process::Command::new("node")
-        .arg(node_script_path)
-        .kill_on_drop(true)
-        .spawn()
-    {
-        Ok(child) => {
-            info!("Spawned Node.js selfbot process (PID: {:?})", child.id());
-            child
-        }
-        Err(e) => {
-            error!(error = %e, "Failed to spawn Node.js selfbot process");
-            return Err(e.into());
-        }
-    };
+    let mut supervisor = SelfbotSupervisor::new(
+        SelfbotConfig::from_env(),
+        sensory::relay::resolve_socket_path(),
+    );
 
     let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(1);
     tokio::spawn(async move {
@@ -49,19 +43,7 @@ async fn main() -> Result<()> {
         let _ = tx.send(()).await;
     });
 
-    tokio::select! {
-        status = child.wait() => {
-            match status {
-                Ok(s) => info!("Node.js process exited with status: {}", s),
-                Err(e) => error!("Node.js process wait error: {}", e),
-            }
-        }
-        _ = rx.recv() => {
-            info!("Killing Node.js process...");
-            let _ = child.kill().await;
-            let _ = child.wait().await;
-        }
-    }
+    supervisor.run(&mut rx).await?;
 
     info!("=== Discord Selfbot Runner Stopped ===");
     Ok(())
@@ -0,0 +1,298 @@
+//! Keeps the Node.js selfbot running.
+//!
+//! The child is restarted with exponential backoff whenever it exits, and
+//! its stdout/stderr are forwarded into tracing. The runner holds its own
+//! relay connection: a periodic ping checks that the agent is answering,
+//! and the child's health is reported as `PlatformMessage::Status` so the
+//! cockpit lists it next to the agent's workers. The Node client gives up
+//! after a few failed connects, so a new child is only spawned once the
+//! relay answers.
+
+use std::process::{ExitStatus, Stdio};
+use std::time::Instant;
+
+use anyhow::Result;
+use kernel::worker::WorkerStatus;
+use sensory::relay::{RelayClient, RelaySender};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::SelfbotConfig;
+
+/// Name the selfbot is listed under in the cockpit.
+pub const STATUS_NAME: &str = "discord_selfbot";
+
+pub struct SelfbotSupervisor {
+    config: SelfbotConfig,
+    socket_path: String,
+    /// Kept alive alongside `relay`; dropping it closes the connection.
+    relay_client: Option<RelayClient>,
+    relay: Option<RelaySender>,
+    status: WorkerStatus,
+}
+
+impl SelfbotSupervisor {
+    pub fn new(config: SelfbotConfig, socket_path: impl Into<String>) -> Self {
+        Self {
+            config,
+            socket_path: socket_path.into(),
+            relay_client: None,
+            relay: None,
+            status: WorkerStatus::NotStarted,
+        }
+    }
+
+    /// Supervise the child until `shutdown` fires.
+    pub async fn run(&mut self, shutdown: &mut mpsc::Receiver<()>) -> Result<()> {
+        let mut backoff = self.config.restart_min;
+        let mut heartbeat = tokio::time::interval(self.config.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            let mut waiting_logged = false;
+            while !self.check_relay().await {
+                if !waiting_logged {
+                    info!(socket = %self.socket_path, "Waiting for the agent relay before starting the selfbot");
+                    waiting_logged = true;
+                }
+                tokio::select! {
+                    _ = heartbeat.tick() => {}
+                    _ = shutdown.recv() => return self.stopped().await,
+                }
+            }
+
+            let started = Instant::now();
+            let exit = match self.spawn() {
+                Ok(mut child) => {
+                    self.report(WorkerStatus::Healthy).await;
+                    loop {
+                        tokio::select! {
+                            status = child.wait() => break describe_exit(status),
+                            _ = heartbeat.tick() => {
+                                self.check_relay().await;
+                            }
+                            _ = shutdown.recv() => {
+                                info!("Stopping Node.js selfbot process");
+                                let _ = child.kill().await;
+                                return self.stopped().await;
+                            }
+                        }
+                    }
+                }
+                Err(e) => format!("failed to spawn: {e}"),
+            };
+
+            if started.elapsed() >= self.config.stable_after {
+                backoff = self.config.restart_min;
+            }
+            warn!(
+                reason = %exit,
+                uptime_secs = started.elapsed().as_secs(),
+                restart_in_ms = backoff.as_millis() as u64,
+                "Node.js selfbot process exited"
+            );
+            self.report(WorkerStatus::Degraded {
+                reason: format!("{exit}; restarting in {}s", backoff.as_secs_f32().ceil()),
+            })
+            .await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.recv() => return self.stopped().await,
+            }
+            backoff = (backoff * 2).min(self.config.restart_max);
+        }
+    }
+
+    fn spawn(&self) -> Result<Child> {
+        let mut child = Command::new(&self.config.program)
+            .args(&self.config.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let pid = child.id();
+        info!(pid = ?pid, program = %self.config.program, "Spawned Node.js selfbot process");
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(forward_output(stdout, "stdout", pid));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(forward_output(stderr, "stderr", pid));
+        }
+        Ok(child)
+    }
+
+    /// Connect if needed and ping the agent. Returns whether it answered.
+    async fn check_relay(&mut self) -> bool {
+        if self.relay.is_none() {
+            match RelayClient::connect(&self.socket_path).await {
+                Ok(client) => {
+                    let sender = client.sender();
+                    // A fresh connection has not heard our current status yet.
+                    if self.status != WorkerStatus::NotStarted {
+                        let _ = sender.status(STATUS_NAME, self.status.clone()).await;
+                    }
+                    self.relay_client = Some(client);
+                    self.relay = Some(sender);
+                }
+                Err(_) => return false,
+            }
+        }
+
+        let Some(relay) = &self.relay else {
+            return false;
+        };
+        match relay.heartbeat(self.config.heartbeat_timeout).await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!(error = %e, "Agent relay heartbeat failed");
+                self.relay = None;
+                self.relay_client = None;
+                false
+            }
+        }
+    }
+
+    async fn report(&mut self, status: WorkerStatus) {
+        self.status = status.clone();
+        if let Some(relay) = &self.relay {
+            if relay.status(STATUS_NAME, status).await.is_err() {
+                self.relay = None;
+                self.relay_client = None;
+            }
+        }
+    }
+
+    async fn stopped(&mut self) -> Result<()> {
+        self.report(WorkerStatus::Stopped).await;
+        Ok(())
+    }
+}
+
+fn describe_exit(status: std::io::Result<ExitStatus>) -> String {
+    match status {
+        Ok(status) => status.to_string(),
+        Err(e) => format!("wait failed: {e}"),
+    }
+}
+
+/// The message part of a line printed by the Node script.
+fn node_message(line: &str) -> &str {
+    let line = line.trim();
+    line.strip_prefix("[Selfbot]")
+        .map(str::trim_start)
+        .unwrap_or(line)
+}
+
+/// Forward the child's output as log lines. Node writes `console.warn` and
+/// `console.error` to stderr, so those come through as warnings.
+async fn forward_output<R>(output: R, stream: &'static str, pid: Option<u32>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let message = node_message(&line);
+        if message.is_empty() {
+            continue;
+        }
+        if stream == "stderr" {
+            warn!(target: "discord_selfbot::node", pid = ?pid, stream, "{message}");
+        } else {
+            info!(target: "discord_selfbot::node", pid = ?pid, stream, "{message}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use sensory::relay::protocol::{recv_message, send_message, AgentMessage, PlatformMessage};
+    use tokio::net::UnixListener;
+
+    const STEP: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn strips_the_node_log_prefix() {
+        assert_eq!(
+            node_message("[Selfbot] Connected to Discord as x"),
+            "Connected to Discord as x"
+        );
+        assert_eq!(node_message("  plain line \n"), "plain line");
+    }
+
+    /// Answers pings and passes status reports to the test.
+    async fn stand_in_relay(listener: UnixListener, statuses: mpsc::Sender<WorkerStatus>) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Ok(Some(msg)) = recv_message::<_, PlatformMessage>(&mut stream, 1 << 20).await
+            {
+                match msg {
+                    PlatformMessage::Ping => {
+                        send_message(&mut stream, &AgentMessage::Pong, None)
+                            .await
+                            .unwrap();
+                    }
+                    PlatformMessage::Status { name, status } => {
+                        assert_eq!(name, STATUS_NAME);
+                        let _ = statuses.send(status).await;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    async fn next_status(statuses: &mut mpsc::Receiver<WorkerStatus>) -> WorkerStatus {
+        tokio::time::timeout(STEP, statuses.recv())
+            .await
+            .expect("status report")
+            .expect("relay running")
+    }
+
+    #[tokio::test]
+    async fn restarts_the_child_and_reports_status() {
+        let socket = std::env::temp_dir().join(format!("selfbot-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let (status_tx, mut statuses) = mpsc::channel(16);
+        tokio::spawn(stand_in_relay(listener, status_tx));
+
+        let config = SelfbotConfig {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), "echo '[Selfbot] up'; exit 3".to_string()],
+            restart_min: Duration::from_millis(20),
+            restart_max: Duration::from_millis(40),
+            stable_after: Duration::from_secs(60),
+            heartbeat_interval: Duration::from_millis(50),
+            heartbeat_timeout: Duration::from_secs(1),
+        };
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let mut supervisor = SelfbotSupervisor::new(config, socket.to_string_lossy());
+        let runner = tokio::spawn(async move { supervisor.run(&mut shutdown_rx).await });
+
+        for _ in 0..2 {
+            assert_eq!(next_status(&mut statuses).await, WorkerStatus::Healthy);
+            match next_status(&mut statuses).await {
+                WorkerStatus::Degraded { reason } => {
+                    assert!(reason.contains("exit status: 3"), "{reason}");
+                }
+                other => panic!("unexpected status: {other:?}"),
+            }
+        }
+
+        shutdown_tx.send(()).await.unwrap();
+        loop {
+            if next_status(&mut statuses).await == WorkerStatus::Stopped {
+                break;
+            }
+        }
+        runner.await.unwrap().unwrap();
+        let _ = std::fs::remove_file(&socket);
+    }
+}
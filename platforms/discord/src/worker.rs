@@ -1,19 +1,29 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use base64::Engine as _;
 use kernel::event::{
-    ImageAttachment, Platform, RawEvent, MAX_IMAGE_ATTACHMENTS_PER_MESSAGE,
-    MAX_IMAGE_ATTACHMENT_BYTES,
+    Command, CommandEvent, ImageAttachment, MessageDeleteEvent, MessageEditEvent, Platform,
+    RawEvent, ReactionEvent, ReplyReference, ResponseEvent, ResponseKind,
+    MAX_IMAGE_ATTACHMENTS_PER_MESSAGE, MAX_IMAGE_ATTACHMENT_BYTES,
 };
 use serenity::all::{
-    Context, CreateMessage, EventHandler, GatewayIntents, Message, Ready,
+    ChannelId, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateInteractionResponseFollowup, CreateMessage,
+    EditInteractionResponse, EditMessage, EventHandler, GatewayIntents, GetMessages, GuildId,
+    Interaction, Message, MessageId, MessageUpdateEvent, Permissions, Reaction, ReactionType,
+    Ready, ResolvedOption, ResolvedValue, UserId,
 };
 use serenity::Client;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{debug, error, info, warn};
-use sensory::relay::RelayClient;
+use sensory::relay::{
+    DeliveryOutcome, RelayClient, RelayInbound, RelaySender, StreamedMessages,
+};
+use sensory::{BackfillConfig, HighWaterMarks, RecentMessages};
 
 async fn extract_image_attachments(msg: &Message) -> Vec<ImageAttachment> {
     let mut images = Vec::new();
@@ -54,11 +64,41 @@ async fn extract_image_attachments(msg: &Message) -> Vec<ImageAttachment> {
     images
 }
 
-async fn build_raw_event(msg: &Message, is_mention: bool, is_dm: bool) -> RawEvent {
+/// The message `msg` replies to, when Discord resolved it.
+fn reply_reference(msg: &Message, bot_id: Option<UserId>) -> Option<ReplyReference> {
+    let referenced = msg.referenced_message.as_deref()?;
+    Some(ReplyReference {
+        message_id: referenced.id.to_string(),
+        author_id: referenced.author.id.to_string(),
+        author_name: referenced.author.name.clone(),
+        snippet: ReplyReference::snippet_of(&referenced.content),
+        is_agent: bot_id == Some(referenced.author.id),
+    })
+}
+
+fn sent_at(msg: &Message) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(msg.timestamp.unix_timestamp(), 0)
+        .unwrap_or_else(chrono::Utc::now)
+}
+
+async fn build_raw_event(
+    msg: &Message,
+    is_mention: bool,
+    is_dm: bool,
+    reply_to: Option<ReplyReference>,
+    is_backfill: bool,
+) -> RawEvent {
     let attachments = extract_image_attachments(msg).await;
+    // A caught-up message keeps its send time so the agent can tell how late it is.
+    let timestamp = if is_backfill {
+        sent_at(msg)
+    } else {
+        chrono::Utc::now()
+    };
     RawEvent {
         platform: Platform::Discord,
         channel_id: msg.channel_id.to_string(),
+        thread_id: None,
         message_id: msg.id.to_string(),
         user_id: msg.author.id.to_string(),
         username: msg.author.name.clone(),
@@ -66,13 +106,182 @@ async fn build_raw_event(msg: &Message, is_mention: bool, is_dm: bool) -> RawEve
         attachments,
         is_mention,
         is_dm,
-        timestamp: chrono::Utc::now(),
+        timestamp,
+        reply_to,
+        is_backfill,
+    }
+}
+
+/// State dimensions offered by `/state set`, as `(choice, dimension)`.
+const STATE_DIMENSION_CHOICES: &[(&str, &str)] = &[
+    ("warmth", "style.warmth"),
+    ("playfulness", "style.playfulness"),
+    ("formality", "style.formality"),
+    ("brevity", "style.brevity"),
+    ("curiosity", "preference.curiosity"),
+    ("depth", "preference.depth"),
+    ("directness", "preference.directness"),
+    ("empathy", "preference.empathy_bias"),
+    ("risk tolerance", "preference.risk_tolerance"),
+];
+
+/// The bot's slash commands. Operator commands are hidden from members
+/// without Manage Server; the agent still checks the operator list.
+fn slash_commands() -> Vec<CreateCommand> {
+    let operator = |name: &str, description: &str| {
+        CreateCommand::new(name)
+            .description(description)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+    };
+    let dimension = STATE_DIMENSION_CHOICES.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "dimension", "Dimension to set")
+            .required(true),
+        |option, (name, id)| option.add_string_choice(*name, *id),
+    );
+
+    vec![
+        CreateCommand::new("forget-me").description("Erase everything I remember about you"),
+        CreateCommand::new("memory").description("See what I remember about you"),
+        CreateCommand::new("link")
+            .description("Link your accounts on other platforms")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "code",
+                "Code from /link on your other account; leave out to get one",
+            )),
+        CreateCommand::new("mute").description("Stop answering in this channel"),
+        CreateCommand::new("unmute").description("Answer in this channel again"),
+        CreateCommand::new("status").description("Show whether I'm online and answering here"),
+        operator("state", "Inspect or adjust my state")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "get",
+                "Show every state dimension",
+            ))
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Set one dimension")
+                    .add_sub_option(dimension)
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::Number, "value", "New value")
+                            .required(true),
+                    ),
+            ),
+        operator("pause", "Stop answering everywhere"),
+        operator("resume", "Answer again after /pause"),
+        operator("say", "Post a message as me in this channel").add_option(
+            CreateCommandOption::new(CommandOptionType::String, "text", "What to say")
+                .required(true),
+        ),
+    ]
+}
+
+/// The agent command behind a slash command interaction.
+fn slash_command(interaction: &CommandInteraction) -> Option<Command> {
+    let options = interaction.data.options();
+    let command = match interaction.data.name.as_str() {
+        "forget-me" => Command::ForgetMe,
+        "memory" => Command::Memory,
+        "link" => Command::Link {
+            code: string_option(&options, "code").map(str::to_string),
+        },
+        "mute" => Command::Mute,
+        "unmute" => Command::Unmute,
+        "status" => Command::Status,
+        "pause" => Command::Pause,
+        "resume" => Command::Resume,
+        "say" => Command::Say {
+            text: string_option(&options, "text")?.to_string(),
+        },
+        "state" => match options.first() {
+            Some(ResolvedOption {
+                name: "get",
+                value: ResolvedValue::SubCommand(_),
+                ..
+            }) => Command::StateGet,
+            Some(ResolvedOption {
+                name: "set",
+                value: ResolvedValue::SubCommand(sub),
+                ..
+            }) => Command::StateSet {
+                dimension: string_option(sub, "dimension")?.to_string(),
+                value: sub.iter().find_map(|option| match option.value {
+                    ResolvedValue::Number(value) if option.name == "value" => Some(value),
+                    _ => None,
+                })?,
+            },
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+/// Interaction tokens stop working after 15 minutes.
+const INTERACTION_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// A deferred slash command and how its answer is shown.
+#[derive(Clone)]
+struct PendingCommand {
+    at: Instant,
+    interaction: CommandInteraction,
+    /// Whether the answer is visible only to the invoking user.
+    ephemeral: bool,
+    /// Set once the deferred reply is filled in; later chunks of the same
+    /// answer go out as follow-ups with the same visibility.
+    answered: bool,
+}
+
+/// Slash commands waiting for the agent's answer, by interaction id. The
+/// answer fills in the deferred interaction reply instead of posting a
+/// message. Entries stay until the interaction token expires so every chunk
+/// of a long answer can reach it.
+#[derive(Clone, Default)]
+struct PendingCommands(Arc<Mutex<HashMap<String, PendingCommand>>>);
+
+impl PendingCommands {
+    async fn insert(&self, interaction: CommandInteraction, ephemeral: bool) {
+        let mut pending = self.0.lock().await;
+        pending.retain(|_, command| command.at.elapsed() < INTERACTION_TTL);
+        pending.insert(
+            interaction.id.to_string(),
+            PendingCommand {
+                at: Instant::now(),
+                interaction,
+                ephemeral,
+                answered: false,
+            },
+        );
+    }
+
+    async fn get(&self, id: &str) -> Option<PendingCommand> {
+        self.0
+            .lock()
+            .await
+            .get(id)
+            .filter(|command| command.at.elapsed() < INTERACTION_TTL)
+            .cloned()
+    }
+
+    async fn mark_answered(&self, id: &str) {
+        if let Some(command) = self.0.lock().await.get_mut(id) {
+            command.answered = true;
+        }
     }
 }
 
 pub struct DiscordWorker {
     token: String,
     http: Arc<RwLock<Option<Arc<serenity::http::Http>>>>,
+    operators: HashSet<u64>,
+    backfill: BackfillConfig,
+    marks: Option<HighWaterMarks>,
 }
 
 impl DiscordWorker {
@@ -80,14 +289,171 @@ impl DiscordWorker {
         Self {
             token,
             http: Arc::new(RwLock::new(None)),
+            operators: HashSet::new(),
+            backfill: BackfillConfig::default(),
+            marks: None,
         }
     }
+
+    pub fn with_operators(mut self, operators: impl IntoIterator<Item = u64>) -> Self {
+        self.operators = operators.into_iter().collect();
+        self
+    }
+
+    /// Catch up on channels listed in `marks` after every (re)connect.
+    pub fn with_backfill(mut self, config: BackfillConfig, marks: HighWaterMarks) -> Self {
+        self.backfill = config;
+        self.marks = Some(marks);
+        self
+    }
 }
 
 struct DiscordHandler {
-    relay: Arc<RelayClient>,
+    relay: RelaySender,
     http_store: Arc<RwLock<Option<Arc<serenity::http::Http>>>>,
     bot_user_id: Arc<RwLock<Option<serenity::model::id::UserId>>>,
+    operators: Arc<HashSet<u64>>,
+    pending: PendingCommands,
+    backfill: BackfillConfig,
+    marks: Option<HighWaterMarks>,
+    ingested: RecentMessages,
+}
+
+impl DiscordHandler {
+    /// Ingest a user message, live or caught up on after a reconnect.
+    async fn forward_message(&self, msg: &Message, is_backfill: bool) {
+        if msg.author.bot {
+            return;
+        }
+        let channel_id = msg.channel_id.to_string();
+        if !self.ingested.first_sighting(&channel_id, msg.id.get()) {
+            return;
+        }
+
+        let is_dm = msg.guild_id.is_none();
+        let bot_id = *self.bot_user_id.read().await;
+        let reply_to = reply_reference(msg, bot_id);
+        // Replying to one of the bot's messages counts as tagging it.
+        let is_mention = is_dm
+            || reply_to.as_ref().is_some_and(|reference| reference.is_agent)
+            || bot_id.is_some_and(|bot_id| msg.mentions.iter().any(|u| u.id == bot_id));
+
+        if is_mention {
+            info!(
+                user = %msg.author.name,
+                channel = %msg.channel_id,
+                content = %msg.content,
+                dm = is_dm,
+                backfill = is_backfill,
+                "[MENTION] Bot was tagged on Discord"
+            );
+        } else {
+            debug!(
+                user = %msg.author.name,
+                channel = %msg.channel_id,
+                content = %msg.content,
+                backfill = is_backfill,
+                "Discord message received"
+            );
+        }
+
+        let raw = build_raw_event(msg, is_mention, is_dm, reply_to, is_backfill).await;
+
+        if let Err(e) = self.relay.ingest(raw).await {
+            error!(error = %e, "Failed to ingest message to relay");
+            return;
+        }
+        if let Some(marks) = &self.marks {
+            if let Err(e) = marks.record(&channel_id, msg.id.get()) {
+                warn!(error = %e, channel = %channel_id, "Failed to record Discord high-water mark");
+            }
+        }
+    }
+
+    /// Ingest what was sent in known channels since their high-water marks.
+    async fn catch_up(&self, http: &serenity::http::Http) {
+        let Some(marks) = &self.marks else {
+            return;
+        };
+        if !self.backfill.enabled || self.backfill.max_messages == 0 {
+            return;
+        }
+        let channels = match marks.all() {
+            Ok(channels) => channels,
+            Err(e) => {
+                warn!(error = %e, "Failed to read Discord high-water marks");
+                return;
+            }
+        };
+
+        // Discord pages at 100; the newest page is the one worth keeping.
+        let limit = self.backfill.max_messages.min(100) as u8;
+        let mut total = 0;
+        for (channel_id, mark) in channels {
+            let Ok(channel) = channel_id.parse::<u64>().map(ChannelId::new) else {
+                continue;
+            };
+            let messages = match channel
+                .messages(http, GetMessages::new().limit(limit))
+                .await
+            {
+                Ok(messages) => messages,
+                Err(e) => {
+                    debug!(error = %e, channel = %channel_id, "Failed to fetch Discord history for backfill");
+                    continue;
+                }
+            };
+            let missed = self.backfill.select(messages, mark, chrono::Utc::now(), |msg| {
+                (msg.id.get(), sent_at(msg))
+            });
+            total += missed.len();
+            for msg in &missed {
+                self.forward_message(msg, true).await;
+            }
+        }
+        if total > 0 {
+            info!(messages = total, "Caught up on missed Discord messages");
+        }
+    }
+
+    async fn forward_reaction(&self, reaction: Reaction, added: bool) {
+        let Some(user_id) = reaction.user_id else {
+            return;
+        };
+        let bot_id = *self.bot_user_id.read().await;
+        if Some(user_id) == bot_id {
+            return;
+        }
+
+        // Discord only names the message author on additions.
+        let on_agent_message = bot_id.is_some() && reaction.message_author_id == bot_id;
+        let username = reaction
+            .member
+            .as_ref()
+            .map(|member| member.user.name.clone())
+            .unwrap_or_else(|| user_id.to_string());
+        let event = ReactionEvent {
+            platform: Platform::Discord,
+            channel_id: reaction.channel_id.to_string(),
+            message_id: reaction.message_id.to_string(),
+            user_id: user_id.to_string(),
+            username,
+            emoji: reaction.emoji.to_string(),
+            added,
+            on_agent_message,
+            timestamp: chrono::Utc::now(),
+        };
+        debug!(
+            user = %event.username,
+            emoji = %event.emoji,
+            added,
+            on_agent_message,
+            "Discord reaction received"
+        );
+        if let Err(e) = self.relay.reaction(event).await {
+            error!(error = %e, "Failed to forward Discord reaction to relay");
+        }
+    }
 }
 
 #[async_trait]
@@ -108,47 +474,122 @@ impl EventHandler for DiscordHandler {
             let mut bot_id = self.bot_user_id.write().await;
             *bot_id = Some(ready.user.id);
         }
+
+        match serenity::all::Command::set_global_commands(&ctx.http, slash_commands()).await {
+            Ok(commands) => info!(count = commands.len(), "Discord slash commands registered"),
+            Err(e) => warn!(error = %e, "Failed to register Discord slash commands"),
+        }
+
+        self.catch_up(&ctx.http).await;
     }
 
-    async fn message(&self, _ctx: Context, msg: Message) {
-        if msg.author.bot {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Command(interaction) = interaction else {
+            return;
+        };
+        let Some(command) = slash_command(&interaction) else {
+            warn!(command = %interaction.data.name, "Unknown Discord slash command");
+            return;
+        };
+
+        // Mutes concern the whole channel; everything else is answered privately.
+        let ephemeral = !matches!(command, Command::Mute | Command::Unmute);
+        let deferred = if ephemeral {
+            interaction.defer_ephemeral(&ctx.http).await
+        } else {
+            interaction.defer(&ctx.http).await
+        };
+        if let Err(e) = deferred {
+            error!(error = %e, command = %interaction.data.name, "Failed to acknowledge Discord slash command");
             return;
         }
 
-        let is_dm = msg.guild_id.is_none();
-        let is_mention = is_dm || {
-            let bot_id = self.bot_user_id.read().await;
-            if let Some(bot_id) = *bot_id {
-                msg.mentions.iter().any(|u| u.id == bot_id)
-            } else {
-                false
-            }
+        info!(
+            user = %interaction.user.name,
+            channel = %interaction.channel_id,
+            command = ?command,
+            "Discord slash command received"
+        );
+        let event = CommandEvent {
+            platform: Platform::Discord,
+            channel_id: interaction.channel_id.to_string(),
+            thread_id: None,
+            message_id: interaction.id.to_string(),
+            user_id: interaction.user.id.to_string(),
+            username: interaction.user.name.clone(),
+            is_dm: interaction.guild_id.is_none(),
+            is_operator: self.operators.contains(&interaction.user.id.get()),
+            command,
+            timestamp: chrono::Utc::now(),
         };
+        self.pending.insert(interaction, ephemeral).await;
+        if let Err(e) = self.relay.command(event).await {
+            error!(error = %e, "Failed to forward Discord slash command to relay");
+        }
+    }
 
-        if is_mention {
-            info!(
-                user = %msg.author.name,
-                channel = %msg.channel_id,
-                content = %msg.content,
-                dm = is_dm,
-                "[MENTION] Bot was tagged on Discord"
-            );
-        } else {
-            debug!(
-                user = %msg.author.name,
-                channel = %msg.channel_id,
-                content = %msg.content,
-                "Discord message received"
-            );
+    async fn message(&self, _ctx: Context, msg: Message) {
+        self.forward_message(&msg, false).await;
+    }
+
+    async fn message_update(
+        &self,
+        _ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // Embed unfurls also arrive as updates, without content or author.
+        let (Some(author), Some(content)) = (event.author, event.content) else {
+            return;
+        };
+        if author.bot {
+            return;
         }
 
-        let raw = build_raw_event(&msg, is_mention, is_dm).await;
+        debug!(user = %author.name, channel = %event.channel_id, "Discord message edited");
+        let edit = MessageEditEvent {
+            platform: Platform::Discord,
+            channel_id: event.channel_id.to_string(),
+            thread_id: None,
+            message_id: event.id.to_string(),
+            user_id: author.id.to_string(),
+            username: author.name,
+            content,
+            timestamp: chrono::Utc::now(),
+        };
+        if let Err(e) = self.relay.message_edited(edit).await {
+            error!(error = %e, "Failed to forward Discord message edit to relay");
+        }
+    }
 
-        if let Err(e) = self.relay.ingest(raw).await {
-            error!(error = %e, "Failed to ingest message to relay");
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        debug!(channel = %channel_id, message = %deleted_message_id, "Discord message deleted");
+        let deleted = MessageDeleteEvent {
+            platform: Platform::Discord,
+            channel_id: channel_id.to_string(),
+            message_id: deleted_message_id.to_string(),
+            timestamp: chrono::Utc::now(),
+        };
+        if let Err(e) = self.relay.message_deleted(deleted).await {
+            error!(error = %e, "Failed to forward Discord message deletion to relay");
         }
     }
 
+    async fn reaction_add(&self, _ctx: Context, add_reaction: Reaction) {
+        self.forward_reaction(add_reaction, true).await;
+    }
+
+    async fn reaction_remove(&self, _ctx: Context, removed_reaction: Reaction) {
+        self.forward_reaction(removed_reaction, false).await;
+    }
+
     async fn typing_start(&self, _ctx: Context, _event: serenity::all::TypingStartEvent) {
         // typing detection is a no-op
     }
@@ -169,26 +610,29 @@ impl DiscordWorker {
         let http_store = Arc::clone(&self.http);
         let bot_user_id = Arc::new(RwLock::new(None));
 
-        // To handle response_rx which is in RelayClient, we need to extract it
-        // Or we can just spawn the listener here, but RelayClient borrows response_rx.
-        // Let's modify RelayClient to return the receiver so we can spawn it separately,
-        // or just loop it here.
-        // Actually, we can't share RelayClient if it has mutable recv_response.
-        // So we will split it manually.
-
-        let relay_client_shared = Arc::new(RelayClient::connect_default().await?);
+        // One connection both ingests and receives this platform's responses.
+        let mut relay = RelayClient::connect_platform(Platform::Discord).await?;
+        let relay_sender = relay.sender();
 
+        let pending = PendingCommands::default();
         let handler = DiscordHandler {
-            relay: Arc::clone(&relay_client_shared),
+            relay: relay_sender.clone(),
             http_store: Arc::clone(&http_store),
             bot_user_id: Arc::clone(&bot_user_id),
+            operators: Arc::new(self.operators.clone()),
+            pending: pending.clone(),
+            backfill: self.backfill.clone(),
+            marks: self.marks.clone(),
+            ingested: RecentMessages::default(),
         };
 
         let intents = GatewayIntents::GUILD_MESSAGES
             | GatewayIntents::DIRECT_MESSAGES
             | GatewayIntents::MESSAGE_CONTENT
             | GatewayIntents::GUILD_MESSAGE_TYPING
-            | GatewayIntents::DIRECT_MESSAGE_TYPING;
+            | GatewayIntents::DIRECT_MESSAGE_TYPING
+            | GatewayIntents::GUILD_MESSAGE_REACTIONS
+            | GatewayIntents::DIRECT_MESSAGE_REACTIONS;
 
         let mut client = match Client::builder(&self.token, intents)
             .event_handler(handler)
@@ -204,56 +648,50 @@ impl DiscordWorker {
         let http_clone = Arc::clone(&self.http);
 
         // Spawn response loop
-        let mut relay_reader = RelayClient::connect_default().await?;
         tokio::spawn(async move {
-            while let Some(response) = relay_reader.recv_response().await {
-                if response.platform == Platform::Discord {
-                    debug!(
-                        channel = %response.channel_id,
-                        content_len = response.content.len(),
-                        "Discord received ResponseEvent from relay"
-                    );
-                    let http = http_clone.read().await;
-                    if let Some(http) = http.as_ref() {
-                        let channel_id: u64 =
-                            response.channel_id.parse().unwrap_or_default();
-                        let channel =
-                            serenity::model::id::ChannelId::new(channel_id);
-
-                        let mut builder =
-                            CreateMessage::new().content(&response.content);
-
-                        if !response.is_dm {
-                            if let Some(ref reply_id) = response.reply_to_message_id {
-                                if let Ok(msg_id) = reply_id.parse::<u64>() {
-                                    let msg_ref =
-                                        serenity::model::id::MessageId::new(msg_id);
-                                    builder = builder.reference_message(
-                                        serenity::model::channel::MessageReference::from(
-                                            (channel, msg_ref),
-                                        ),
-                                    );
-                                }
-                            }
+            let mut streams = StreamedMessages::new();
+            while let Some(inbound) = relay.recv().await {
+                let delivery = match inbound {
+                    RelayInbound::Response(delivery) => delivery,
+                    RelayInbound::Typing { channel_id, thread_id } => {
+                        if let Some(http) = http_clone.read().await.clone() {
+                            tokio::spawn(show_typing(http, thread_id.unwrap_or(channel_id)));
                         }
+                        continue;
+                    }
+                };
+                let response = delivery.event;
+                if response.platform != Platform::Discord {
+                    continue;
+                }
+                debug!(
+                    channel = %response.channel_id,
+                    content_len = response.content.len(),
+                    "Discord received ResponseEvent from relay"
+                );
 
-                        match channel.send_message(http, builder).await {
-                            Ok(_) => {
-                                info!(
-                                    channel = %channel_id,
-                                    "Discord response sent successfully"
-                                );
-                            }
-                            Err(e) => {
-                                error!(error = %e, "Failed to send Discord message");
-                            }
-                        }
-                    } else {
+                let http = http_clone.read().await.clone();
+                let outcome = match http {
+                    Some(http) => match answer_interaction(&http, &response, &pending).await {
+                        Some(outcome) => outcome,
+                        None => send_response(&http, &response, &mut streams).await,
+                    },
+                    None => {
                         warn!(
                             channel = %response.channel_id,
-                            "Discord HTTP client not ready, dropping response"
+                            "Discord HTTP client not ready, deferring response"
                         );
+                        DeliveryOutcome::RetryAfter {
+                            retry_after_ms: 2_000,
+                        }
                     }
+                };
+
+                if let Err(e) = relay_sender
+                    .report_delivery(delivery.delivery_id, outcome)
+                    .await
+                {
+                    error!(error = %e, "Failed to report Discord delivery to relay");
                 }
             }
             warn!("Relay response stream closed");
@@ -279,3 +717,207 @@ impl DiscordWorker {
         Ok(())
     }
 }
+
+async fn show_typing(http: Arc<serenity::http::Http>, channel_id: String) {
+    let Ok(channel_id) = channel_id.parse::<u64>() else {
+        return;
+    };
+    let channel = serenity::model::id::ChannelId::new(channel_id);
+    if let Err(e) = channel.broadcast_typing(&http).await {
+        debug!(error = %e, channel = %channel_id, "Failed to show Discord typing indicator");
+    }
+}
+
+/// Answer a deferred slash command. `None` when `response` does not reply
+/// to a pending interaction. The first chunk fills in the deferred reply;
+/// overflow chunks are follow-ups with the same visibility, so a private
+/// answer never spills into the channel.
+async fn answer_interaction(
+    http: &serenity::http::Http,
+    response: &ResponseEvent,
+    pending: &PendingCommands,
+) -> Option<DeliveryOutcome> {
+    if !matches!(response.kind, ResponseKind::Message) {
+        return None;
+    }
+    let id = response.reply_to_message_id.as_deref()?;
+    let command = pending.get(id).await?;
+
+    let sent = if command.answered {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(&response.content)
+            .ephemeral(command.ephemeral);
+        command.interaction.create_followup(http, followup).await.map(|_| ())
+    } else {
+        let edit = EditInteractionResponse::new().content(&response.content);
+        command.interaction.edit_response(http, edit).await.map(|_| ())
+    };
+    Some(match sent {
+        Ok(()) => {
+            debug!(interaction = %id, "Discord slash command answered");
+            pending.mark_answered(id).await;
+            DeliveryOutcome::Sent
+        }
+        Err(e) => {
+            error!(error = %e, interaction = %id, "Failed to answer Discord slash command");
+            delivery_outcome_for(&e)
+        }
+    })
+}
+
+async fn send_response(
+    http: &serenity::http::Http,
+    response: &ResponseEvent,
+    streams: &mut StreamedMessages<MessageId>,
+) -> DeliveryOutcome {
+    // A Discord thread is a channel, so its id is posted to directly.
+    let target = response.thread_id.as_deref().unwrap_or(&response.channel_id);
+    let channel_id: u64 = target.parse().unwrap_or_default();
+    if channel_id == 0 {
+        return DeliveryOutcome::Failed {
+            error: format!("invalid Discord channel id: {}", target),
+            retryable: false,
+        };
+    }
+    let channel = serenity::model::id::ChannelId::new(channel_id);
+
+    if let Some(outcome) = perform_action(http, channel, &response.kind, &response.content).await {
+        return outcome;
+    }
+
+    // Later updates of a streamed reply edit the message the first one created.
+    if let Some(message_id) = streams.target(&response.kind) {
+        let edit = EditMessage::new().content(&response.content);
+        match channel.edit_message(http, message_id, edit).await {
+            Ok(_) => {
+                debug!(
+                    channel = %channel_id,
+                    message = %message_id,
+                    "Discord streamed response edited"
+                );
+                streams.delivered(&response.kind, message_id);
+                return DeliveryOutcome::Sent;
+            }
+            Err(e) if http_status(&e) == Some(404) => {
+                warn!(
+                    channel = %channel_id,
+                    message = %message_id,
+                    "Streamed Discord message is gone, sending a new one"
+                );
+                streams.forget(&response.kind);
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to edit Discord message");
+                return delivery_outcome_for(&e);
+            }
+        }
+    }
+
+    let mut builder = CreateMessage::new().content(&response.content);
+
+    if !response.is_dm {
+        if let Some(ref reply_id) = response.reply_to_message_id {
+            if let Ok(msg_id) = reply_id.parse::<u64>() {
+                let msg_ref = serenity::model::id::MessageId::new(msg_id);
+                builder = builder.reference_message(
+                    serenity::model::channel::MessageReference::from((channel, msg_ref)),
+                );
+            }
+        }
+    }
+
+    match channel.send_message(http, builder).await {
+        Ok(message) => {
+            info!(channel = %channel_id, "Discord response sent successfully");
+            streams.delivered(&response.kind, message.id);
+            DeliveryOutcome::Sent
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to send Discord message");
+            delivery_outcome_for(&e)
+        }
+    }
+}
+
+/// Reactions, edits and deletions of an existing message. `None` for
+/// responses that send a message.
+async fn perform_action(
+    http: &serenity::http::Http,
+    channel: ChannelId,
+    kind: &ResponseKind,
+    content: &str,
+) -> Option<DeliveryOutcome> {
+    let (message_id, action) = match kind {
+        ResponseKind::React { message_id, .. } => (message_id, "react"),
+        ResponseKind::Edit { message_id } => (message_id, "edit"),
+        ResponseKind::Delete { message_id } => (message_id, "delete"),
+        ResponseKind::Message | ResponseKind::StreamUpdate { .. } => return None,
+    };
+    let Ok(message) = message_id.parse::<u64>().map(MessageId::new) else {
+        return Some(DeliveryOutcome::Failed {
+            error: format!("invalid Discord message id: {message_id}"),
+            retryable: false,
+        });
+    };
+
+    let result = match kind {
+        ResponseKind::React { emoji, .. } => match ReactionType::try_from(emoji.as_str()) {
+            Ok(reaction) => channel.create_reaction(http, message, reaction).await,
+            Err(_) => {
+                return Some(DeliveryOutcome::Failed {
+                    error: format!("invalid Discord reaction: {emoji}"),
+                    retryable: false,
+                })
+            }
+        },
+        ResponseKind::Edit { .. } => channel
+            .edit_message(http, message, EditMessage::new().content(content))
+            .await
+            .map(|_| ()),
+        _ => channel.delete_message(http, message).await,
+    };
+
+    Some(match result {
+        Ok(()) => {
+            debug!(channel = %channel, message = %message, action, "Discord message action done");
+            DeliveryOutcome::Sent
+        }
+        // The message is already gone; nothing left to do.
+        Err(e) if http_status(&e) == Some(404) && action == "delete" => DeliveryOutcome::Sent,
+        Err(e) => {
+            error!(
+                error = %e,
+                channel = %channel,
+                message = %message,
+                action,
+                "Failed to perform Discord message action"
+            );
+            delivery_outcome_for(&e)
+        }
+    })
+}
+
+fn http_status(error: &serenity::Error) -> Option<u16> {
+    match error {
+        serenity::Error::Http(http) => http.status_code().map(|status| status.as_u16()),
+        _ => None,
+    }
+}
+
+/// Classify a send error for the agent's outbound queue.
+fn delivery_outcome_for(error: &serenity::Error) -> DeliveryOutcome {
+    match http_status(error) {
+        // Serenity already waits out bucket limits; a 429 here is a global or shared limit.
+        Some(429) => DeliveryOutcome::RetryAfter {
+            retry_after_ms: 5_000,
+        },
+        Some(status) => DeliveryOutcome::Failed {
+            error: error.to_string(),
+            retryable: status >= 500,
+        },
+        None => DeliveryOutcome::Failed {
+            error: error.to_string(),
+            retryable: true,
+        },
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct SaslCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelJoin {
+    pub name: String,
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IrcConfig {
+    pub server: String,
+    pub port: u16,
+    pub tls: bool,
+    pub nick: String,
+    pub username: String,
+    pub realname: String,
+    /// Sent as `PASS` before registration (bouncers, private servers).
+    pub server_password: Option<String>,
+    /// Authenticate with SASL PLAIN during registration.
+    pub sasl: Option<SaslCredentials>,
+    pub channels: Vec<ChannelJoin>,
+    /// Longest message text per line. Lines are also kept inside the
+    /// 512-byte protocol limit after the server adds our prefix.
+    pub max_line_bytes: usize,
+    pub flood_burst: u32,
+    pub flood_interval: Duration,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env(name).and_then(|v| v.parse().ok())
+}
+
+/// `#one,#two key` joins `#one` and the keyed channel `#two`.
+fn parse_channels(value: &str) -> Vec<ChannelJoin> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split_whitespace();
+            let name = parts.next()?.to_string();
+            Some(ChannelJoin {
+                name,
+                key: parts.next().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+impl IrcConfig {
+    pub fn from_env() -> Self {
+        let tls = env("IRC_TLS")
+            .map(|v| {
+                !matches!(
+                    v.to_ascii_lowercase().as_str(),
+                    "0" | "false" | "no" | "off"
+                )
+            })
+            .unwrap_or(true);
+        let nick = env("IRC_NICK").unwrap_or_else(|| "polyverse".to_string());
+        let sasl = env("IRC_SASL_PASSWORD").map(|password| SaslCredentials {
+            username: env("IRC_SASL_USERNAME").unwrap_or_else(|| nick.clone()),
+            password,
+        });
+
+        Self {
+            server: env("IRC_SERVER").unwrap_or_default(),
+            port: env_parse("IRC_PORT").unwrap_or(if tls { 6697 } else { 6667 }),
+            tls,
+            username: env("IRC_USERNAME").unwrap_or_else(|| nick.clone()),
+            realname: env("IRC_REALNAME").unwrap_or_else(|| "Polyverse Agent".to_string()),
+            nick,
+            server_password: env("IRC_PASSWORD"),
+            sasl,
+            channels: env("IRC_CHANNELS")
+                .map(|v| parse_channels(&v))
+                .unwrap_or_default(),
+            max_line_bytes: env_parse("IRC_MAX_LINE_BYTES").unwrap_or(400),
+            flood_burst: env_parse("IRC_FLOOD_BURST").unwrap_or(4),
+            flood_interval: Duration::from_millis(
+                env_parse("IRC_FLOOD_INTERVAL_MS").unwrap_or(2_000),
+            ),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Client-side flood control.
+//!
+//! Servers disconnect clients that send faster than they allow, usually a
+//! short burst followed by about one line every two seconds. Each line adds
+//! `interval` to a penalty clock; a line may go out once the clock is at
+//! most `burst` intervals ahead of now.
+
+use std::time::{Duration, Instant};
+
+pub struct FloodControl {
+    burst: u32,
+    interval: Duration,
+    clock: Instant,
+}
+
+impl FloodControl {
+    pub fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst: burst.max(1),
+            interval,
+            clock: Instant::now(),
+        }
+    }
+
+    /// Account for one line sent at `now`; returns how long to wait first.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let allowance = self.interval * self.burst;
+        self.clock = self.clock.max(now) + self.interval;
+        self.clock.saturating_duration_since(now + allowance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_a_burst_then_paces() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut flood = FloodControl::new(2, second);
+        flood.clock = start;
+
+        assert_eq!(flood.reserve(start), Duration::ZERO);
+        assert_eq!(flood.reserve(start), Duration::ZERO);
+        assert_eq!(flood.reserve(start), second);
+        assert_eq!(flood.reserve(start), second * 2);
+        // After a quiet spell the burst is available again.
+        assert_eq!(flood.reserve(start + second * 10), Duration::ZERO);
+    }
+}
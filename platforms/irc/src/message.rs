@@ -0,0 +1,153 @@
+//! IRC protocol lines (RFC 1459 framing with IRCv3 message tags).
+
+/// One line received from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub tags: Vec<(String, String)>,
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl Message {
+    /// Parse a line without its trailing CRLF. `None` for blank or
+    /// malformed lines.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+
+        let mut tags = Vec::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw, after) = tagged.split_once(' ')?;
+            tags = raw
+                .split(';')
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| match tag.split_once('=') {
+                    Some((key, value)) => (key.to_string(), unescape_tag_value(value)),
+                    None => (tag.to_string(), String::new()),
+                })
+                .collect();
+            rest = after.trim_start_matches(' ');
+        }
+
+        let mut prefix = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (raw, after) = prefixed.split_once(' ')?;
+            prefix = Some(raw.to_string());
+            rest = after.trim_start_matches(' ');
+        }
+
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = head.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?.to_ascii_uppercase();
+        let mut params: Vec<String> = words.map(str::to_string).collect();
+        if let Some(trailing) = trailing {
+            params.push(trailing.to_string());
+        }
+
+        Some(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn param(&self, index: usize) -> Option<&str> {
+        self.params.get(index).map(String::as_str)
+    }
+
+    /// Nick of the sender, from a `nick!user@host` prefix.
+    pub fn nick(&self) -> Option<&str> {
+        let prefix = self.prefix.as_deref()?;
+        let nick = prefix.split(['!', '@']).next().unwrap_or(prefix);
+        (!nick.is_empty()).then_some(nick)
+    }
+}
+
+fn unescape_tag_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Format an outgoing line, without CRLF. Line breaks and NULs inside
+/// parameters become spaces so a parameter can never start a new command.
+pub fn line(command: &str, params: &[&str]) -> String {
+    let mut out = command.to_string();
+    for (i, param) in params.iter().enumerate() {
+        let param = param.replace(['\r', '\n', '\0'], " ");
+        let is_last = i + 1 == params.len();
+        out.push(' ');
+        if is_last && (param.is_empty() || param.contains(' ') || param.starts_with(':')) {
+            out.push(':');
+        }
+        out.push_str(&param);
+    }
+    out
+}
+
+/// Channel names start with one of the RFC 2811 channel prefixes.
+pub fn is_channel(target: &str) -> bool {
+    target.starts_with(['#', '&', '+', '!'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tags_prefix_and_trailing() {
+        let msg = Message::parse(
+            "@account=alice;time=2026-01-01T00:00:00.000Z;+draft/x=a\\sb :alice!a@host PRIVMSG #chan :hi there\r\n",
+        )
+        .unwrap();
+        assert_eq!(msg.tag("account"), Some("alice"));
+        assert_eq!(msg.tag("+draft/x"), Some("a b"));
+        assert_eq!(msg.nick(), Some("alice"));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec!["#chan", "hi there"]);
+    }
+
+    #[test]
+    fn parses_numerics_without_trailing() {
+        let msg = Message::parse(":irc.example.net 433 * poly").unwrap();
+        assert_eq!(msg.nick(), Some("irc.example.net"));
+        assert_eq!(msg.command, "433");
+        assert_eq!(msg.params, vec!["*", "poly"]);
+        assert!(Message::parse("").is_none());
+    }
+
+    #[test]
+    fn line_adds_trailing_marker_and_strips_line_breaks() {
+        assert_eq!(line("NICK", &["poly"]), "NICK poly");
+        assert_eq!(
+            line("PRIVMSG", &["#chan", "hi\r\nQUIT :bye"]),
+            "PRIVMSG #chan :hi  QUIT :bye"
+        );
+        assert_eq!(line("PRIVMSG", &["#chan", ":)"]), "PRIVMSG #chan ::)");
+    }
+}
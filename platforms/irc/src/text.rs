@@ -0,0 +1,123 @@
+//! Message text: formatting codes, CTCP, mentions and line splitting.
+
+/// Remove mIRC bold, colour, italic, underline, strikethrough, monospace
+/// and reverse codes.
+pub fn strip_formatting(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x02' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f' => {}
+            // ^C[fg[,bg]] with one or two digits each.
+            '\x03' => {
+                for _ in 0..2 {
+                    if chars.peek().is_some_and(char::is_ascii_digit) {
+                        chars.next();
+                    }
+                }
+                let mut lookahead = chars.clone();
+                if lookahead.next() == Some(',')
+                    && lookahead.peek().is_some_and(char::is_ascii_digit)
+                {
+                    chars.next();
+                    for _ in 0..2 {
+                        if chars.peek().is_some_and(char::is_ascii_digit) {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Split a CTCP message (`\x01VERB body\x01`) into its verb and body.
+pub fn ctcp(text: &str) -> Option<(&str, &str)> {
+    let inner = text.strip_prefix('\x01')?;
+    let inner = inner.strip_suffix('\x01').unwrap_or(inner);
+    Some(inner.split_once(' ').unwrap_or((inner, "")))
+}
+
+fn is_nick_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "[]\\`_^{|}-".contains(c)
+}
+
+/// `nick` appears in `text` as a whole word, ignoring case.
+pub fn mentions(text: &str, nick: &str) -> bool {
+    if nick.is_empty() {
+        return false;
+    }
+    let haystack = text.to_ascii_lowercase();
+    let needle = nick.to_ascii_lowercase();
+    haystack.match_indices(&needle).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + needle.len()..].chars().next();
+        !before.is_some_and(is_nick_char) && !after.is_some_and(is_nick_char)
+    })
+}
+
+/// Break `text` into lines of at most `max_bytes` bytes, on word boundaries
+/// where possible. Blank lines are dropped: IRC cannot send them.
+pub fn split_lines(text: &str, max_bytes: usize) -> Vec<String> {
+    let max_bytes = max_bytes.max(16);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut rest = line.trim_end();
+        while rest.len() > max_bytes {
+            let mut cut = max_bytes;
+            while !rest.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            if let Some(space) = rest[..cut].rfind(' ').filter(|space| *space > 0) {
+                cut = space;
+            }
+            lines.push(rest[..cut].trim_end().to_string());
+            rest = rest[cut..].trim_start();
+        }
+        if !rest.trim().is_empty() {
+            lines.push(rest.to_string());
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_colour_and_style_codes() {
+        assert_eq!(
+            strip_formatting("\x02bold\x02 \x0304,12red\x03 \x0312,x \x1funder\x0f"),
+            "bold red ,x under"
+        );
+    }
+
+    #[test]
+    fn mentions_need_a_whole_word() {
+        assert!(mentions("poly: hi", "Poly"));
+        assert!(mentions("hey @poly!", "poly"));
+        assert!(!mentions("polyverse is great", "poly"));
+        assert!(!mentions("poly_ are you there", "poly"));
+    }
+
+    #[test]
+    fn reads_ctcp_actions() {
+        assert_eq!(ctcp("\x01ACTION waves\x01"), Some(("ACTION", "waves")));
+        assert_eq!(ctcp("\x01VERSION\x01"), Some(("VERSION", "")));
+        assert_eq!(ctcp("plain"), None);
+    }
+
+    #[test]
+    fn splits_on_words_and_char_boundaries() {
+        let lines = split_lines("one two three four five\n\nsix", 16);
+        assert_eq!(lines, vec!["one two three", "four five", "six"]);
+
+        let wide = "é".repeat(20);
+        for line in split_lines(&wide, 17) {
+            assert!(line.len() <= 17);
+        }
+    }
+}
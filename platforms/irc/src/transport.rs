@@ -0,0 +1,38 @@
+//! Plain TCP or TLS connection to the IRC server.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{crypto, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+pub async fn connect(server: &str, port: u16, tls: bool) -> Result<Box<dyn Transport>> {
+    let tcp = TcpStream::connect((server, port))
+        .await
+        .with_context(|| format!("failed to connect to {server}:{port}"))?;
+    tcp.set_nodelay(true)?;
+    if !tls {
+        return Ok(Box::new(tcp));
+    }
+
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(server.to_string())
+        .with_context(|| format!("invalid TLS server name {server}"))?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(name, tcp)
+        .await
+        .with_context(|| format!("TLS handshake with {server} failed"))?;
+    Ok(Box::new(stream))
+}
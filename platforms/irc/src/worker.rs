@@ -0,0 +1,823 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use base64::Engine as _;
+use kernel::event::{Platform, RawEvent, ResponseEvent, ResponseKind};
+use sensory::relay::{DeliveryOutcome, RelayClient, RelayInbound, RelaySender};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, WriteHalf};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, warn};
+
+use crate::config::IrcConfig;
+use crate::flood::FloodControl;
+use crate::message::{is_channel, line, Message};
+use crate::text::{ctcp, mentions, split_lines, strip_formatting};
+use crate::transport::{self, Transport};
+
+const RECONNECT_MIN: Duration = Duration::from_secs(2);
+const RECONNECT_MAX: Duration = Duration::from_secs(300);
+/// Ping the server after this long without hearing from it...
+const PING_IDLE: Duration = Duration::from_secs(90);
+/// ...and give up on the connection after this long.
+const PING_TIMEOUT: Duration = Duration::from_secs(180);
+const LIVENESS_CHECK: Duration = Duration::from_secs(15);
+/// Longest hostname a server may put in our prefix.
+const MAX_HOST_BYTES: usize = 63;
+/// SASL payloads are sent in chunks of this many base64 bytes.
+const SASL_CHUNK: usize = 400;
+
+/// Optional IRCv3 capabilities we use when the server offers them.
+const WANTED_CAPS: &[&str] = &["account-tag", "server-time", "message-tags"];
+
+enum SessionEnd {
+    RelayClosed,
+    Disconnected(String),
+}
+
+/// A chat line, with a signal for the writer to fire once it is flushed.
+struct PacedLine {
+    line: String,
+    flushed: Option<oneshot::Sender<()>>,
+}
+
+/// Lines waiting to be written. Protocol replies jump the queue; chat lines
+/// go through flood control.
+struct Outgoing {
+    urgent: mpsc::UnboundedSender<String>,
+    paced: mpsc::UnboundedSender<PacedLine>,
+}
+
+impl Outgoing {
+    fn now(&self, line: String) {
+        let _ = self.urgent.send(line);
+    }
+
+    fn paced(&self, line: String) {
+        let _ = self.paced.send(PacedLine {
+            line,
+            flushed: None,
+        });
+    }
+
+    /// Queue `lines` and return a receiver that completes once the last one
+    /// is on the wire. It errors instead if the writer stops first.
+    fn paced_batch(&self, lines: Vec<String>) -> oneshot::Receiver<()> {
+        let (flushed_tx, flushed_rx) = oneshot::channel();
+        let mut flushed_tx = Some(flushed_tx);
+        let last = lines.len().saturating_sub(1);
+        for (i, line) in lines.into_iter().enumerate() {
+            let flushed = if i == last { flushed_tx.take() } else { None };
+            let _ = self.paced.send(PacedLine { line, flushed });
+        }
+        if let Some(flushed_tx) = flushed_tx {
+            // Nothing to write; the batch is trivially done.
+            let _ = flushed_tx.send(());
+        }
+        flushed_rx
+    }
+}
+
+async fn write_lines(
+    mut writer: WriteHalf<Box<dyn Transport>>,
+    mut urgent_rx: mpsc::UnboundedReceiver<String>,
+    mut paced_rx: mpsc::UnboundedReceiver<PacedLine>,
+    mut flood: FloodControl,
+) -> Result<()> {
+    loop {
+        let next = tokio::select! {
+            biased;
+            line = urgent_rx.recv() => line.map(|line| PacedLine { line, flushed: None }),
+            line = paced_rx.recv() => {
+                if line.is_some() {
+                    let wait = flood.reserve(Instant::now());
+                    if !wait.is_zero() {
+                        tokio::time::sleep(wait).await;
+                    }
+                }
+                line
+            }
+        };
+        let Some(PacedLine { line, flushed }) = next else {
+            return Ok(());
+        };
+        writer.write_all(line.as_bytes()).await?;
+        writer.write_all(b"\r\n").await?;
+        writer.flush().await?;
+        if let Some(flushed) = flushed {
+            let _ = flushed.send(());
+        }
+    }
+}
+
+/// How a response handed to [`Session::deliver`] ends up.
+enum Queued {
+    Done(DeliveryOutcome),
+    /// Written by the writer task; completes once its last line is flushed.
+    Flushing(oneshot::Receiver<()>),
+}
+
+pub struct IrcWorker {
+    config: IrcConfig,
+}
+
+impl IrcWorker {
+    pub fn new(config: IrcConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        info!("IRC worker starting...");
+
+        if self.config.server.is_empty() || self.config.nick.is_empty() {
+            warn!("IRC server or nick is not configured, exiting");
+            return Ok(());
+        }
+
+        let mut relay = RelayClient::connect_platform(Platform::Irc).await?;
+        self.run_with(&mut relay).await
+    }
+
+    async fn run_with(&self, relay: &mut RelayClient) -> Result<()> {
+        let sender = relay.sender();
+        let mut backoff = RECONNECT_MIN;
+        loop {
+            let mut session = Session::new(&self.config);
+            match session.run(relay, &sender).await {
+                Ok(SessionEnd::RelayClosed) => {
+                    warn!("Relay connection closed");
+                    return Ok(());
+                }
+                Ok(SessionEnd::Disconnected(reason)) => {
+                    warn!(reason = %reason, "Disconnected from IRC");
+                }
+                Err(e) => warn!(error = %e, "IRC session failed"),
+            }
+            if session.registered {
+                backoff = RECONNECT_MIN;
+            }
+            info!(delay_secs = backoff.as_secs(), "Reconnecting to IRC");
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    }
+}
+
+/// One connection to the server, from registration until it drops.
+struct Session<'a> {
+    config: &'a IrcConfig,
+    nick: String,
+    registered: bool,
+    /// Server capability list, gathered across multi-line `CAP LS` replies.
+    offered_caps: Vec<String>,
+    id_prefix: String,
+    next_id: u64,
+    last_heard: Instant,
+    ping_sent: bool,
+}
+
+impl<'a> Session<'a> {
+    fn new(config: &'a IrcConfig) -> Self {
+        Self {
+            config,
+            nick: config.nick.clone(),
+            registered: false,
+            offered_caps: Vec::new(),
+            id_prefix: format!("irc-{}", chrono::Utc::now().timestamp_millis()),
+            next_id: 0,
+            last_heard: Instant::now(),
+            ping_sent: false,
+        }
+    }
+
+    async fn run(&mut self, relay: &mut RelayClient, sender: &RelaySender) -> Result<SessionEnd> {
+        info!(
+            server = %self.config.server,
+            port = self.config.port,
+            tls = self.config.tls,
+            "Connecting to IRC"
+        );
+        let stream =
+            transport::connect(&self.config.server, self.config.port, self.config.tls).await?;
+        let (reader, writer) = tokio::io::split(stream);
+        let (urgent_tx, urgent_rx) = mpsc::unbounded_channel();
+        let (paced_tx, paced_rx) = mpsc::unbounded_channel();
+        let out = Outgoing {
+            urgent: urgent_tx,
+            paced: paced_tx,
+        };
+        let flood = FloodControl::new(self.config.flood_burst, self.config.flood_interval);
+        let writer_task = tokio::spawn(write_lines(writer, urgent_rx, paced_rx, flood));
+
+        let result = self
+            .drive(BufReader::new(reader), &out, relay, sender)
+            .await;
+        writer_task.abort();
+        result
+    }
+
+    async fn drive<R>(
+        &mut self,
+        mut reader: BufReader<R>,
+        out: &Outgoing,
+        relay: &mut RelayClient,
+        sender: &RelaySender,
+    ) -> Result<SessionEnd>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        if let Some(password) = &self.config.server_password {
+            out.now(line("PASS", &[password]));
+        }
+        out.now(line("CAP", &["LS", "302"]));
+        out.now(line("NICK", &[&self.nick]));
+        out.now(line(
+            "USER",
+            &[&self.config.username, "0", "*", &self.config.realname],
+        ));
+
+        let mut liveness = tokio::time::interval(LIVENESS_CHECK);
+        let mut buf = Vec::new();
+        loop {
+            tokio::select! {
+                read = reader.read_until(b'\n', &mut buf) => {
+                    match read {
+                        Ok(0) => {
+                            return Ok(SessionEnd::Disconnected(
+                                "server closed the connection".to_string(),
+                            ))
+                        }
+                        Ok(_) => {}
+                        Err(e) => return Ok(SessionEnd::Disconnected(e.to_string())),
+                    }
+                    let received = String::from_utf8_lossy(&buf).into_owned();
+                    buf.clear();
+                    self.last_heard = Instant::now();
+                    self.ping_sent = false;
+                    let Some(msg) = Message::parse(&received) else {
+                        continue;
+                    };
+                    if let Some(end) = self.handle(msg, out, sender).await? {
+                        return Ok(end);
+                    }
+                }
+                inbound = relay.recv() => match inbound {
+                    Some(RelayInbound::Response(delivery)) => {
+                        match self.deliver(&delivery.event, out) {
+                            Queued::Done(outcome) => {
+                                report(sender, delivery.delivery_id, outcome).await;
+                            }
+                            // Flood control may hold the lines for a while; the
+                            // report waits for them without blocking the session.
+                            Queued::Flushing(flushed) => {
+                                let sender = sender.clone();
+                                tokio::spawn(async move {
+                                    let outcome = match flushed.await {
+                                        Ok(()) => DeliveryOutcome::Sent,
+                                        Err(_) => DeliveryOutcome::Failed {
+                                            error: "IRC connection closed before the response was sent"
+                                                .to_string(),
+                                            retryable: true,
+                                        },
+                                    };
+                                    report(&sender, delivery.delivery_id, outcome).await;
+                                });
+                            }
+                        }
+                    }
+                    Some(RelayInbound::Typing { .. }) => {}
+                    None => return Ok(SessionEnd::RelayClosed),
+                },
+                _ = liveness.tick() => {
+                    let quiet = self.last_heard.elapsed();
+                    if quiet >= PING_TIMEOUT {
+                        return Ok(SessionEnd::Disconnected("ping timeout".to_string()));
+                    }
+                    if quiet >= PING_IDLE && !self.ping_sent {
+                        out.now(line("PING", &[&self.config.server]));
+                        self.ping_sent = true;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle(
+        &mut self,
+        msg: Message,
+        out: &Outgoing,
+        sender: &RelaySender,
+    ) -> Result<Option<SessionEnd>> {
+        match msg.command.as_str() {
+            "PING" => out.now(line("PONG", &[msg.param(0).unwrap_or_default()])),
+            "CAP" => self.negotiate_caps(&msg, out)?,
+            "AUTHENTICATE" if msg.param(0) == Some("+") => self.send_sasl_credentials(out),
+            // RPL_SASLSUCCESS
+            "903" => out.now(line("CAP", &["END"])),
+            // ERR_NICKLOCKED, ERR_SASLFAIL, ERR_SASLTOOLONG, ERR_SASLABORTED
+            "902" | "904" | "905" | "906" => {
+                bail!(
+                    "SASL authentication failed: {}",
+                    msg.params.last().cloned().unwrap_or_default()
+                );
+            }
+            // RPL_WELCOME
+            "001" => {
+                if let Some(nick) = msg.param(0) {
+                    self.nick = nick.to_string();
+                }
+                self.registered = true;
+                info!(nick = %self.nick, server = %self.config.server, "Registered on IRC");
+                for channel in &self.config.channels {
+                    match &channel.key {
+                        Some(key) => out.paced(line("JOIN", &[&channel.name, key])),
+                        None => out.paced(line("JOIN", &[&channel.name])),
+                    }
+                }
+            }
+            // ERR_NICKNAMEINUSE, ERR_NICKCOLLISION
+            "433" | "436" if !self.registered => {
+                self.nick.push('_');
+                info!(nick = %self.nick, "Nick in use, trying another");
+                out.now(line("NICK", &[&self.nick]));
+            }
+            // ERR_ERRONEUSNICKNAME
+            "432" if !self.registered => bail!("server rejected nick {}", self.nick),
+            // ERR_PASSWDMISMATCH, ERR_YOUREBANNEDCREEP
+            "464" | "465" => bail!(
+                "server refused the connection: {}",
+                msg.params.last().cloned().unwrap_or_default()
+            ),
+            "NICK"
+                if msg
+                    .nick()
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(&self.nick)) =>
+            {
+                if let Some(nick) = msg.param(0) {
+                    self.nick = nick.to_string();
+                }
+            }
+            "JOIN"
+                if msg
+                    .nick()
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(&self.nick)) =>
+            {
+                info!(channel = %msg.param(0).unwrap_or_default(), "Joined IRC channel");
+            }
+            "KICK"
+                if msg
+                    .param(1)
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(&self.nick)) =>
+            {
+                warn!(
+                    channel = %msg.param(0).unwrap_or_default(),
+                    by = %msg.nick().unwrap_or_default(),
+                    reason = %msg.param(2).unwrap_or_default(),
+                    "Kicked from IRC channel"
+                );
+            }
+            "PRIVMSG" => self.ingest(&msg, sender).await,
+            "ERROR" => {
+                return Ok(Some(SessionEnd::Disconnected(
+                    msg.param(0).unwrap_or("server error").to_string(),
+                )));
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn negotiate_caps(&mut self, msg: &Message, out: &Outgoing) -> Result<()> {
+        let subcommand = msg.param(1).unwrap_or_default();
+        let list = msg.params.last().map(String::as_str).unwrap_or_default();
+        match subcommand {
+            "LS" => {
+                // Values follow `=` in 302 replies (`sasl=PLAIN,EXTERNAL`).
+                self.offered_caps.extend(
+                    list.split_whitespace()
+                        .map(|cap| cap.split('=').next().unwrap_or(cap).to_string()),
+                );
+                // `CAP * LS * :...` means more lines follow.
+                if msg.param(2) == Some("*") {
+                    return Ok(());
+                }
+                let offered = |cap: &str| self.offered_caps.iter().any(|c| c == cap);
+                let mut wanted: Vec<&str> = Vec::new();
+                if self.config.sasl.is_some() {
+                    if !offered("sasl") {
+                        bail!("SASL is configured but the server does not offer it");
+                    }
+                    wanted.push("sasl");
+                }
+                wanted.extend(WANTED_CAPS.iter().copied().filter(|cap| offered(cap)));
+                if wanted.is_empty() {
+                    out.now(line("CAP", &["END"]));
+                } else {
+                    out.now(line("CAP", &["REQ", &wanted.join(" ")]));
+                }
+            }
+            "ACK" => {
+                if list.split_whitespace().any(|cap| cap == "sasl") {
+                    out.now(line("AUTHENTICATE", &["PLAIN"]));
+                } else {
+                    out.now(line("CAP", &["END"]));
+                }
+            }
+            "NAK" => {
+                if self.config.sasl.is_some() {
+                    bail!("server refused capabilities: {list}");
+                }
+                out.now(line("CAP", &["END"]));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn send_sasl_credentials(&self, out: &Outgoing) {
+        let Some(sasl) = &self.config.sasl else {
+            out.now(line("AUTHENTICATE", &["*"]));
+            return;
+        };
+        let payload = format!("{0}\0{0}\0{1}", sasl.username, sasl.password);
+        let encoded = base64::prelude::BASE64_STANDARD.encode(payload);
+        for chunk in encoded.as_bytes().chunks(SASL_CHUNK) {
+            out.now(line(
+                "AUTHENTICATE",
+                &[std::str::from_utf8(chunk).unwrap_or_default()],
+            ));
+        }
+        // A payload that fills its last chunk exactly is terminated by `+`.
+        if encoded.len() % SASL_CHUNK == 0 {
+            out.now(line("AUTHENTICATE", &["+"]));
+        }
+    }
+
+    fn next_message_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("{}-{}", self.id_prefix, self.next_id)
+    }
+
+    async fn ingest(&mut self, msg: &Message, sender: &RelaySender) {
+        let (Some(nick), Some(target), Some(text)) = (msg.nick(), msg.param(0), msg.param(1))
+        else {
+            return;
+        };
+        if nick.eq_ignore_ascii_case(&self.nick) {
+            return;
+        }
+        let text = match ctcp(text) {
+            Some(("ACTION", body)) => format!("* {nick} {body}"),
+            // VERSION, PING and friends are not conversation.
+            Some(_) => return,
+            None => text.to_string(),
+        };
+        let content = strip_formatting(&text);
+        if content.trim().is_empty() {
+            return;
+        }
+
+        let is_dm = !is_channel(target);
+        // Replies to a DM go back to the sender's nick.
+        let channel_id = if is_dm { nick } else { target }.to_ascii_lowercase();
+        // Nicks change hands; a services account is the stable identity when
+        // the server shares it.
+        let user_id = msg
+            .tag("account")
+            .filter(|account| !account.is_empty() && *account != "*")
+            .map(str::to_string)
+            .unwrap_or_else(|| nick.to_ascii_lowercase());
+        let is_mention = is_dm || mentions(&content, &self.nick);
+        let timestamp = msg
+            .tag("time")
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&chrono::Utc))
+            .unwrap_or_else(chrono::Utc::now);
+        let message_id = match msg.tag("msgid") {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => self.next_message_id(),
+        };
+
+        if is_mention {
+            info!(user = %nick, channel = %channel_id, content = %content, dm = is_dm, "[MENTION] Bot was tagged on IRC");
+        } else {
+            debug!(user = %nick, channel = %channel_id, content = %content, "IRC message received");
+        }
+
+        let raw = RawEvent {
+            platform: Platform::Irc,
+            channel_id,
+            thread_id: None,
+            message_id,
+            user_id,
+            username: nick.to_string(),
+            content,
+            attachments: Vec::new(),
+            is_mention,
+            is_dm,
+            timestamp,
+            reply_to: None,
+            is_backfill: false,
+        };
+        if let Err(e) = sender.ingest(raw).await {
+            error!(error = %e, "Failed to ingest IRC message to relay");
+        }
+    }
+
+    /// Text bytes that fit in one PRIVMSG to `target` once the server has
+    /// prepended `:nick!user@host`.
+    fn line_budget(&self, target: &str) -> usize {
+        let overhead = format!(
+            ":{}!{}@ PRIVMSG {} :\r\n",
+            self.nick, self.config.username, target
+        )
+        .len()
+            + MAX_HOST_BYTES;
+        self.config
+            .max_line_bytes
+            .min(512usize.saturating_sub(overhead))
+    }
+
+    fn deliver(&self, response: &ResponseEvent, out: &Outgoing) -> Queued {
+        match &response.kind {
+            ResponseKind::Message | ResponseKind::StreamUpdate { is_final: true, .. } => {}
+            // Without edits the relay only sends final text; nothing else applies.
+            _ => return Queued::Done(DeliveryOutcome::Sent),
+        }
+        if !self.registered {
+            return Queued::Done(DeliveryOutcome::Failed {
+                error: "not connected to IRC".to_string(),
+                retryable: true,
+            });
+        }
+
+        let target = response.channel_id.as_str();
+        let text = match &response.reply_to_user {
+            // IRC has no replies; addressing the user by nick is the convention.
+            Some(user) if !response.is_dm && !response.content.starts_with(user.as_str()) => {
+                format!("{user}: {}", response.content)
+            }
+            _ => response.content.clone(),
+        };
+        let lines = split_lines(&text, self.line_budget(target));
+        debug!(target = %target, lines = lines.len(), "Sending IRC response");
+        let lines = lines
+            .iter()
+            .map(|text| line("PRIVMSG", &[target, text]))
+            .collect();
+        Queued::Flushing(out.paced_batch(lines))
+    }
+}
+
+async fn report(sender: &RelaySender, delivery_id: Option<u64>, outcome: DeliveryOutcome) {
+    if let Err(e) = sender.report_delivery(delivery_id, outcome).await {
+        error!(error = %e, "Failed to report IRC delivery to relay");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChannelJoin, SaslCredentials};
+    use kernel::event::ResponseSource;
+    use sensory::relay::protocol::{recv_message, send_message};
+    use sensory::relay::{AgentMessage, PlatformMessage, RelayProtocolConfig};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, UnixListener, UnixStream};
+
+    const STEP: Duration = Duration::from_secs(5);
+
+    fn test_config(port: u16) -> IrcConfig {
+        IrcConfig {
+            server: "127.0.0.1".to_string(),
+            port,
+            tls: false,
+            nick: "poly".to_string(),
+            username: "poly".to_string(),
+            realname: "Polyverse Agent".to_string(),
+            server_password: None,
+            sasl: Some(SaslCredentials {
+                username: "poly".to_string(),
+                password: "secret".to_string(),
+            }),
+            channels: vec![ChannelJoin {
+                name: "#test".to_string(),
+                key: None,
+            }],
+            max_line_bytes: 400,
+            flood_burst: 10,
+            flood_interval: Duration::from_millis(10),
+        }
+    }
+
+    /// The IRC side of the stand-in server.
+    struct Client {
+        reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+        writer: tokio::net::tcp::OwnedWriteHalf,
+    }
+
+    impl Client {
+        async fn accept(listener: &TcpListener) -> Self {
+            let (stream, _) = tokio::time::timeout(STEP, listener.accept())
+                .await
+                .expect("client connects")
+                .unwrap();
+            let (reader, writer) = stream.into_split();
+            Self {
+                reader: BufReader::new(reader),
+                writer,
+            }
+        }
+
+        async fn send(&mut self, line: &str) {
+            self.writer.write_all(line.as_bytes()).await.unwrap();
+            self.writer.write_all(b"\r\n").await.unwrap();
+        }
+
+        /// Skip lines until one starts with `prefix`.
+        async fn expect(&mut self, prefix: &str) -> String {
+            loop {
+                let mut received = String::new();
+                tokio::time::timeout(STEP, self.reader.read_line(&mut received))
+                    .await
+                    .unwrap_or_else(|_| panic!("timed out waiting for {prefix:?}"))
+                    .unwrap();
+                let received = received.trim_end().to_string();
+                if received.starts_with(prefix) {
+                    return received;
+                }
+            }
+        }
+    }
+
+    async fn expect_frame<F>(relay: &mut UnixStream, mut matches: F) -> PlatformMessage
+    where
+        F: FnMut(&PlatformMessage) -> bool,
+    {
+        loop {
+            let msg: PlatformMessage = tokio::time::timeout(STEP, recv_message(relay, 1 << 20))
+                .await
+                .expect("relay frame")
+                .unwrap()
+                .expect("relay open");
+            if matches(&msg) {
+                return msg;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn registers_with_sasl_relays_messages_and_reconnects() {
+        let socket = std::env::temp_dir().join(format!("irc-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let relay_listener = UnixListener::bind(&socket).unwrap();
+        let irc_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = irc_listener.local_addr().unwrap().port();
+
+        let socket_path = socket.to_string_lossy().into_owned();
+        let worker = tokio::spawn(async move {
+            let mut relay = RelayClient::connect_with_config(
+                &socket_path,
+                Some(Platform::Irc),
+                None,
+                RelayProtocolConfig::default(),
+            )
+            .await
+            .unwrap();
+            IrcWorker::new(test_config(port)).run_with(&mut relay).await
+        });
+        let (mut relay, _) = relay_listener.accept().await.unwrap();
+
+        let mut irc = Client::accept(&irc_listener).await;
+        irc.expect("CAP LS 302").await;
+        irc.expect("NICK poly").await;
+        irc.expect("USER poly 0 * :Polyverse Agent").await;
+        irc.send(":srv CAP * LS * :multi-prefix sasl=PLAIN").await;
+        irc.send(":srv CAP * LS :account-tag").await;
+        assert_eq!(irc.expect("CAP REQ").await, "CAP REQ :sasl account-tag");
+        irc.send(":srv CAP poly ACK :sasl account-tag").await;
+        irc.expect("AUTHENTICATE PLAIN").await;
+        irc.send("AUTHENTICATE +").await;
+        let credentials = base64::prelude::BASE64_STANDARD.encode("poly\0poly\0secret");
+        assert_eq!(
+            irc.expect("AUTHENTICATE").await,
+            format!("AUTHENTICATE {credentials}")
+        );
+        irc.send(":srv 903 poly :SASL authentication successful")
+            .await;
+        irc.expect("CAP END").await;
+        irc.send(":srv 001 poly :Welcome").await;
+        irc.expect("JOIN #test").await;
+
+        irc.send("@account=alice_acct :Alice!a@host PRIVMSG #Test :\x02poly\x02: hello there")
+            .await;
+        let ingest = expect_frame(&mut relay, |msg| {
+            matches!(msg, PlatformMessage::Ingest { .. })
+        })
+        .await;
+        let PlatformMessage::Ingest { event } = ingest else {
+            unreachable!()
+        };
+        assert_eq!(event.platform, Platform::Irc);
+        assert_eq!(event.channel_id, "#test");
+        assert_eq!(event.user_id, "alice_acct");
+        assert_eq!(event.username, "Alice");
+        assert_eq!(event.content, "poly: hello there");
+        assert!(event.is_mention);
+        assert!(!event.is_dm);
+
+        let response = ResponseEvent {
+            platform: Platform::Irc,
+            channel_id: "#test".to_string(),
+            thread_id: None,
+            reply_to_message_id: Some(event.message_id.clone()),
+            reply_to_user: Some("Alice".to_string()),
+            is_dm: false,
+            content: "hi\nsecond line".to_string(),
+            source: ResponseSource::CloudLLM,
+            kind: ResponseKind::Message,
+            private_reply: false,
+        };
+        send_message(
+            &mut relay,
+            &AgentMessage::Response {
+                event: response,
+                delivery_id: Some(7),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(irc.expect("PRIVMSG").await, "PRIVMSG #test :Alice: hi");
+        assert_eq!(irc.expect("PRIVMSG").await, "PRIVMSG #test :second line");
+        let delivery = expect_frame(&mut relay, |msg| {
+            matches!(msg, PlatformMessage::Delivery { .. })
+        })
+        .await;
+        assert!(matches!(
+            delivery,
+            PlatformMessage::Delivery {
+                delivery_id: 7,
+                outcome: DeliveryOutcome::Sent
+            }
+        ));
+
+        irc.send("PING :abc").await;
+        irc.expect("PONG abc").await;
+
+        // A netsplit drops the connection; the worker registers again and
+        // steps around its ghost still holding the nick.
+        drop(irc);
+        let mut irc = Client::accept(&irc_listener).await;
+        irc.expect("NICK poly").await;
+        irc.send(":srv 433 * poly :Nickname is already in use")
+            .await;
+        irc.expect("NICK poly_").await;
+        irc.send(":srv 001 poly_ :Welcome").await;
+        irc.expect("JOIN #test").await;
+
+        // The connection drops while flood control still holds most of a
+        // long answer; the relay hears it can retry rather than that it went out.
+        let long: Vec<String> = (0..100).map(|i| format!("line {i}")).collect();
+        let response = ResponseEvent {
+            platform: Platform::Irc,
+            channel_id: "#test".to_string(),
+            thread_id: None,
+            reply_to_message_id: None,
+            reply_to_user: None,
+            is_dm: false,
+            content: long.join("\n"),
+            source: ResponseSource::CloudLLM,
+            kind: ResponseKind::Message,
+            private_reply: false,
+        };
+        send_message(
+            &mut relay,
+            &AgentMessage::Response {
+                event: response,
+                delivery_id: Some(8),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(irc.expect("PRIVMSG").await, "PRIVMSG #test :line 0");
+        drop(irc);
+        let delivery = expect_frame(&mut relay, |msg| {
+            matches!(msg, PlatformMessage::Delivery { .. })
+        })
+        .await;
+        assert!(matches!(
+            delivery,
+            PlatformMessage::Delivery {
+                delivery_id: 8,
+                outcome: DeliveryOutcome::Failed {
+                    retryable: true,
+                    ..
+                }
+            }
+        ));
+
+        worker.abort();
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
 use tracing::info;
+use kernel::event::Platform;
+use sensory::{BackfillConfig, HighWaterMarks};
 use tracing_subscriber::EnvFilter;
 
 mod worker;
@@ -24,7 +26,19 @@ async fn main() -> Result<()> {
 
     info!("=== Telegram Service Starting ===");
 
-    let mut worker = TelegramWorker::new(token);
+    // Comma-separated Telegram user ids allowed to run operator commands.
+    let operators: Vec<u64> = std::env::var("TELEGRAM_OPERATOR_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect();
+
+    let mut worker = TelegramWorker::new(token).with_operators(operators);
+    let profile = kernel::get_agent_profile();
+    match HighWaterMarks::open(&profile.backfill_db_path, Platform::Telegram) {
+        Ok(marks) => worker = worker.with_backfill(BackfillConfig::from_env(), marks),
+        Err(e) => tracing::warn!(error = %e, "Backfill disabled: high-water marks unavailable"),
+    }
 
     // Catch shutdown
     let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(1);
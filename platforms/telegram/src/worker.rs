@@ -1,17 +1,75 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
 use anyhow::Result;
 use base64::Engine as _;
 use bytes::BytesMut;
 use futures_util::StreamExt;
 use kernel::event::{
-    ImageAttachment, Platform, RawEvent, MAX_IMAGE_ATTACHMENTS_PER_MESSAGE,
-    MAX_IMAGE_ATTACHMENT_BYTES,
+    CommandEvent, ImageAttachment, MessageEditEvent, Platform, RawEvent, ReactionEvent,
+    ReplyReference, ResponseEvent, ResponseKind, MAX_IMAGE_ATTACHMENTS_PER_MESSAGE, MAX_IMAGE_ATTACHMENT_BYTES,
+};
+use sensory::commands::{help_text, parse_text_command, TextCommand, COMMANDS};
+use sensory::format::html_to_plain;
+use sensory::relay::{
+    DeliveryOutcome, RelayClient, RelayInbound, RelaySender, StreamedMessages,
 };
-use sensory::relay::RelayClient;
+use sensory::{BackfillConfig, HighWaterMarks};
+use teloxide::{ApiError, RequestError};
 use teloxide::net::Download;
 use teloxide::prelude::*;
+use teloxide::error_handlers::LoggingErrorHandler;
+use teloxide::types::{
+    AllowedUpdate, BotCommand, ChatAction, MessageId, MessageReactionUpdated, ParseMode,
+    ReactionType, ReplyParameters, ThreadId, User, UserId,
+};
+use teloxide::update_listeners::Polling;
 use tracing::{debug, error, info, warn};
 
+/// Stable name for a user: their @handle, or their id when they have none.
+/// Display names are neither unique nor fixed.
+fn handle_of(user: &User) -> String {
+    user.username
+        .clone()
+        .unwrap_or_else(|| format!("tg-{}", user.id.0))
+}
+
+/// Forum topic of `msg`. Reply threads in ordinary groups also carry a
+/// thread id but stay part of the chat's conversation.
+fn topic_of(msg: &Message) -> Option<String> {
+    if !msg.is_topic_message {
+        return None;
+    }
+    msg.thread_id.map(|thread| thread.0 .0.to_string())
+}
+
+fn parse_topic(thread_id: Option<&str>) -> Option<ThreadId> {
+    thread_id?.parse::<i32>().ok().map(|id| ThreadId(MessageId(id)))
+}
+
+/// The message `msg` replies to. A quoted excerpt is preferred over the
+/// start of the original.
+fn reply_reference(msg: &Message, bot_id: UserId) -> Option<ReplyReference> {
+    let replied = msg.reply_to_message()?;
+    // Inside a forum topic, every message "replies" to the topic's first message.
+    if msg.is_topic_message && msg.thread_id.is_some_and(|thread| thread.0 == replied.id) {
+        return None;
+    }
+    let author = replied.from.as_ref();
+    let text = msg
+        .quote()
+        .map(|quote| quote.text.as_str())
+        .or_else(|| replied.text().or_else(|| replied.caption()))
+        .unwrap_or_default();
+    Some(ReplyReference {
+        message_id: replied.id.0.to_string(),
+        author_id: author.map(|u| u.id.0.to_string()).unwrap_or_default(),
+        author_name: author.map(handle_of).unwrap_or_else(|| "Unknown".to_string()),
+        snippet: ReplyReference::snippet_of(text),
+        is_agent: author.is_some_and(|u| u.id == bot_id),
+    })
+}
+
 async fn extract_photo_attachments(bot: &Bot, msg: &Message) -> Vec<ImageAttachment> {
     let mut attachments = Vec::new();
 
@@ -92,13 +150,119 @@ async fn extract_photo_attachments(bot: &Bot, msg: &Message) -> Vec<ImageAttachm
     attachments
 }
 
+/// Telegram doesn't say who wrote a reacted-to message, so the worker
+/// remembers the ids of messages it sent recently.
+#[derive(Clone, Default)]
+struct SentMessages(Arc<Mutex<RecentIds>>);
+
+#[derive(Default)]
+struct RecentIds {
+    ids: HashSet<(i64, i32)>,
+    order: VecDeque<(i64, i32)>,
+}
+
+impl SentMessages {
+    const CAPACITY: usize = 1024;
+
+    fn remember(&self, chat_id: ChatId, message_id: MessageId) {
+        let key = (chat_id.0, message_id.0);
+        let mut recent = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.ids.insert(key) {
+            recent.order.push_back(key);
+        }
+        while recent.order.len() > Self::CAPACITY {
+            if let Some(oldest) = recent.order.pop_front() {
+                recent.ids.remove(&oldest);
+            }
+        }
+    }
+
+    fn contains(&self, chat_id: ChatId, message_id: MessageId) -> bool {
+        let recent = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        recent.ids.contains(&(chat_id.0, message_id.0))
+    }
+}
+
+/// Telegram user ids allowed to run operator commands such as `/state`.
+#[derive(Clone, Default)]
+struct Operators(Arc<HashSet<u64>>);
+
+/// The Bot API has no history to fetch, but Telegram holds undelivered
+/// updates for a day and hands them over first after a restart. Messages
+/// sent before the worker started are those held ones.
+#[derive(Clone)]
+struct CatchUp {
+    config: BackfillConfig,
+    marks: Option<HighWaterMarks>,
+    started_at: chrono::DateTime<chrono::Utc>,
+    /// Held messages ingested so far, per chat.
+    counts: Arc<Mutex<HashMap<i64, usize>>>,
+}
+
+impl CatchUp {
+    /// Whether `msg` was sent while the worker was down, or `None` when it
+    /// should be dropped: already ingested before a crash, or past the limits.
+    fn classify(&self, msg: &Message) -> Option<bool> {
+        let chat_id = msg.chat.id.0.to_string();
+        if let Some(marks) = &self.marks {
+            match marks.get(&chat_id) {
+                Ok(Some(mark)) if msg.id.0 as u64 <= mark => return None,
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, chat = %chat_id, "Failed to read Telegram high-water mark"),
+            }
+        }
+        if msg.date >= self.started_at {
+            return Some(false);
+        }
+        if !self.config.admits(msg.date, chrono::Utc::now()) {
+            return None;
+        }
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(msg.chat.id.0).or_default();
+        if *count >= self.config.max_messages {
+            return None;
+        }
+        *count += 1;
+        Some(true)
+    }
+
+    fn record(&self, msg: &Message) {
+        if let Some(marks) = &self.marks {
+            let chat_id = msg.chat.id.0.to_string();
+            if let Err(e) = marks.record(&chat_id, msg.id.0 as u64) {
+                warn!(error = %e, chat = %chat_id, "Failed to record Telegram high-water mark");
+            }
+        }
+    }
+}
+
 pub struct TelegramWorker {
     token: String,
+    operators: HashSet<u64>,
+    backfill: BackfillConfig,
+    marks: Option<HighWaterMarks>,
 }
 
 impl TelegramWorker {
     pub fn new(token: String) -> Self {
-        Self { token }
+        Self {
+            token,
+            operators: HashSet::new(),
+            backfill: BackfillConfig::default(),
+            marks: None,
+        }
+    }
+
+    pub fn with_operators(mut self, operators: impl IntoIterator<Item = u64>) -> Self {
+        self.operators = operators.into_iter().collect();
+        self
+    }
+
+    /// Limit and flag the messages Telegram held while the worker was down.
+    pub fn with_backfill(mut self, config: BackfillConfig, marks: HighWaterMarks) -> Self {
+        self.backfill = config;
+        self.marks = Some(marks);
+        self
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -124,43 +288,84 @@ impl TelegramWorker {
         let bot_username = me.username().to_string();
         info!(bot_name = %bot_username, "Telegram bot connected");
 
-        let mut relay_client = RelayClient::connect_default().await?;
-        let relay_client_shared = Arc::new(RelayClient::connect_default().await?);
+        let menu = COMMANDS
+            .iter()
+            .map(|(name, description)| BotCommand::new(*name, *description));
+        if let Err(e) = bot.set_my_commands(menu).await {
+            warn!(error = %e, "Failed to register Telegram command menu");
+        }
+        let operators = Operators(Arc::new(self.operators.clone()));
+        let catch_up = CatchUp {
+            config: self.backfill.clone(),
+            marks: self.marks.clone(),
+            started_at: chrono::Utc::now(),
+            counts: Arc::default(),
+        };
+
+        // One connection both ingests and receives this platform's responses.
+        let mut relay_client = RelayClient::connect_platform(Platform::Telegram).await?;
+        let relay_sender = relay_client.sender();
 
+        let sent_messages = SentMessages::default();
         let bot_clone = bot.clone();
+        let report_sender = relay_sender.clone();
+        let sent = sent_messages.clone();
         tokio::spawn(async move {
-            while let Some(response) = relay_client.recv_response().await {
-                if response.platform == Platform::Telegram {
-                    let chat_id: i64 = response.channel_id.parse().unwrap_or_default();
-                    let req = bot_clone.send_message(ChatId(chat_id), &response.content);
-                    match req.await {
-                        Ok(_) => info!(chat = %chat_id, "Telegram response sent successfully"),
-                        Err(e) => error!(error = %e, "Failed to send Telegram message"),
+            let mut streams = StreamedMessages::new();
+            while let Some(inbound) = relay_client.recv().await {
+                let delivery = match inbound {
+                    RelayInbound::Response(delivery) => delivery,
+                    RelayInbound::Typing { channel_id, thread_id } => {
+                        tokio::spawn(show_typing(bot_clone.clone(), channel_id, thread_id));
+                        continue;
                     }
+                };
+                let response = delivery.event;
+                if response.platform != Platform::Telegram {
+                    continue;
+                }
+                let outcome = send_response(&bot_clone, &response, &mut streams, &sent).await;
+                if let Err(e) = report_sender
+                    .report_delivery(delivery.delivery_id, outcome)
+                    .await
+                {
+                    error!(error = %e, "Failed to report Telegram delivery to relay");
                 }
             }
             warn!("Relay response stream closed");
         });
 
-        let handler = Update::filter_message().endpoint(
+        let messages = Update::filter_message().endpoint(
             move |bot: Bot,
                   msg: Message,
-                  relay: Arc<RelayClient>,
-                  bot_un: String| async move {
+                  relay: RelaySender,
+                  bot_un: String,
+                  bot_id: UserId,
+                  operators: Operators,
+                  catch_up: CatchUp| async move {
+                let Some(is_backfill) = catch_up.classify(&msg) else {
+                    debug!(chat = %msg.chat.id, message = %msg.id, "Skipping held Telegram message");
+                    return Ok::<(), anyhow::Error>(());
+                };
                 let text = msg.caption().or_else(|| msg.text()).unwrap_or_default().to_string();
                 let has_images = msg.photo().is_some() || msg.document().is_some();
-                if text.starts_with('/') && !has_images {
+                if let Some(command) = parse_text_command(&text, Some(&bot_un)) {
+                    route_command(&bot, &msg, command, &relay, &operators).await;
+                    catch_up.record(&msg);
                     return Ok::<(), anyhow::Error>(());
                 }
                 if text.is_empty() && !has_images {
                     return Ok::<(), anyhow::Error>(());
                 }
 
-                let user = msg.from.as_ref().map(|u| u.first_name.clone()).unwrap_or_else(|| "Unknown".to_string());
+                let user = msg.from.as_ref().map(handle_of).unwrap_or_else(|| "Unknown".to_string());
                 let user_id = msg.from.as_ref().map(|u| u.id.0.to_string()).unwrap_or_default();
                 let is_dm = msg.chat.is_private();
                 let mention_tag = format!("@{}", bot_un);
-                let is_mention = is_dm || text.contains(&mention_tag) || msg.entities().map(|entities| {
+                let reply_to = reply_reference(&msg, bot_id);
+                // Replying to one of the bot's messages counts as tagging it.
+                let replied_to_bot = reply_to.as_ref().is_some_and(|reference| reference.is_agent);
+                let is_mention = is_dm || replied_to_bot || text.contains(&mention_tag) || msg.entities().map(|entities| {
                     entities.iter().any(|e| {
                         e.kind == teloxide::types::MessageEntityKind::Mention
                             && text.get(e.offset..e.offset + e.length).map(|s| s.eq_ignore_ascii_case(&mention_tag)).unwrap_or(false)
@@ -168,14 +373,15 @@ impl TelegramWorker {
                 }).unwrap_or(false);
 
                 if is_mention {
-                    info!(user = %user, chat = %msg.chat.id, content = %text, dm = is_dm, "[MENTION] Bot was tagged on Telegram");
+                    info!(user = %user, chat = %msg.chat.id, content = %text, dm = is_dm, backfill = is_backfill, "[MENTION] Bot was tagged on Telegram");
                 } else {
-                    debug!(user = %user, chat = %msg.chat.id, content = %text, "Telegram message received");
+                    debug!(user = %user, chat = %msg.chat.id, content = %text, backfill = is_backfill, "Telegram message received");
                 }
 
                 let raw = RawEvent {
                     platform: Platform::Telegram,
                     channel_id: msg.chat.id.0.to_string(),
+                    thread_id: topic_of(&msg),
                     message_id: msg.id.0.to_string(),
                     user_id,
                     username: user,
@@ -183,24 +389,350 @@ impl TelegramWorker {
                     attachments: extract_photo_attachments(&bot, &msg).await,
                     is_mention,
                     is_dm: msg.chat.is_private(),
+                    // A held message keeps its send time so the agent can tell how late it is.
+                    timestamp: if is_backfill { msg.date } else { chrono::Utc::now() },
+                    reply_to,
+                    is_backfill,
+                };
+
+                match relay.ingest(raw).await {
+                    Ok(()) => catch_up.record(&msg),
+                    Err(e) => error!(error = %e, "Failed to ingest message to relay"),
+                }
+                Ok::<(), anyhow::Error>(())
+            },
+        );
+
+        let edits = Update::filter_edited_message().endpoint(
+            |msg: Message, relay: RelaySender| async move {
+                let Some(from) = msg.from.as_ref().filter(|u| !u.is_bot) else {
+                    return Ok::<(), anyhow::Error>(());
+                };
+                let Some(text) = msg.text().or_else(|| msg.caption()) else {
+                    return Ok::<(), anyhow::Error>(());
+                };
+
+                debug!(user = %handle_of(from), chat = %msg.chat.id, "Telegram message edited");
+                let edit = MessageEditEvent {
+                    platform: Platform::Telegram,
+                    channel_id: msg.chat.id.0.to_string(),
+                    thread_id: topic_of(&msg),
+                    message_id: msg.id.0.to_string(),
+                    user_id: from.id.0.to_string(),
+                    username: handle_of(from),
+                    content: text.to_string(),
                     timestamp: chrono::Utc::now(),
                 };
+                if let Err(e) = relay.message_edited(edit).await {
+                    error!(error = %e, "Failed to forward Telegram message edit to relay");
+                }
+                Ok::<(), anyhow::Error>(())
+            },
+        );
 
-                if let Err(e) = relay.ingest(raw).await {
-                    error!(error = %e, "Failed to ingest message to relay");
+        let reactions = Update::filter_message_reaction_updated().endpoint(
+            |update: MessageReactionUpdated, relay: RelaySender, sent: SentMessages| async move {
+                // Anonymous admins react as the chat; there is no user to attribute it to.
+                let Some(user) = update.user.as_ref() else {
+                    return Ok::<(), anyhow::Error>(());
+                };
+                let on_agent_message = sent.contains(update.chat.id, update.message_id);
+                let changes = reaction_changes(&update.old_reaction, &update.new_reaction);
+                for (emoji, added) in changes {
+                    debug!(user = %handle_of(user), emoji = %emoji, added, "Telegram reaction received");
+                    let event = ReactionEvent {
+                        platform: Platform::Telegram,
+                        channel_id: update.chat.id.0.to_string(),
+                        message_id: update.message_id.0.to_string(),
+                        user_id: user.id.0.to_string(),
+                        username: handle_of(user),
+                        emoji,
+                        added,
+                        on_agent_message,
+                        timestamp: chrono::Utc::now(),
+                    };
+                    if let Err(e) = relay.reaction(event).await {
+                        error!(error = %e, "Failed to forward Telegram reaction to relay");
+                    }
                 }
                 Ok::<(), anyhow::Error>(())
             },
         );
 
+        let handler = dptree::entry()
+            .branch(messages)
+            .branch(edits)
+            .branch(reactions);
+
+        // Reaction updates are only delivered when asked for explicitly.
+        let listener = Polling::builder(bot.clone())
+            .allowed_updates(vec![
+                AllowedUpdate::Message,
+                AllowedUpdate::EditedMessage,
+                AllowedUpdate::MessageReaction,
+            ])
+            .build();
+
         let mut dispatcher = Dispatcher::builder(bot, handler)
-            .dependencies(dptree::deps![relay_client_shared, bot_username])
+            .dependencies(dptree::deps![relay_sender, bot_username, me.id, sent_messages, operators, catch_up])
             .default_handler(|_| async {})
             .build();
 
-        dispatcher.dispatch().await;
+        dispatcher
+            .dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("Telegram update listener error"),
+            )
+            .await;
 
         info!("Telegram worker stopped");
         Ok(())
     }
 }
+
+/// Emoji added and removed between two reaction lists, as `(emoji, added)`.
+fn reaction_changes(old: &[ReactionType], new: &[ReactionType]) -> Vec<(String, bool)> {
+    let old: Vec<String> = old.iter().map(reaction_label).collect();
+    let new: Vec<String> = new.iter().map(reaction_label).collect();
+    let added = new.iter().filter(|r| !old.contains(r)).map(|r| (r.clone(), true));
+    let removed = old.iter().filter(|r| !new.contains(r)).map(|r| (r.clone(), false));
+    removed.chain(added).collect()
+}
+
+fn reaction_label(reaction: &ReactionType) -> String {
+    match reaction {
+        ReactionType::Emoji { emoji } => emoji.clone(),
+        ReactionType::CustomEmoji { custom_emoji_id } => format!("custom:{custom_emoji_id}"),
+    }
+}
+
+/// Answer `/help` and unknown commands here; pass the rest to the agent.
+async fn route_command(
+    bot: &Bot,
+    msg: &Message,
+    command: TextCommand,
+    relay: &RelaySender,
+    operators: &Operators,
+) {
+    let Some(from) = msg.from.as_ref().filter(|u| !u.is_bot) else {
+        return;
+    };
+    let answer = match command {
+        TextCommand::Agent(command) => {
+            info!(user = %handle_of(from), chat = %msg.chat.id, command = ?command, "Telegram command received");
+            let event = CommandEvent {
+                platform: Platform::Telegram,
+                channel_id: msg.chat.id.0.to_string(),
+                thread_id: topic_of(msg),
+                message_id: msg.id.0.to_string(),
+                user_id: from.id.0.to_string(),
+                username: handle_of(from),
+                is_dm: msg.chat.is_private(),
+                is_operator: operators.0.contains(&from.id.0),
+                command,
+                timestamp: chrono::Utc::now(),
+            };
+            if let Err(e) = relay.command(event).await {
+                error!(error = %e, "Failed to forward Telegram command to relay");
+            }
+            return;
+        }
+        TextCommand::Help => help_text(),
+        TextCommand::Unknown(name) => format!("Unknown command /{name}. Try /help."),
+    };
+
+    let mut req = bot
+        .send_message(msg.chat.id, answer)
+        .reply_parameters(ReplyParameters::new(msg.id).allow_sending_without_reply());
+    if let Some(topic) = msg.thread_id.filter(|_| msg.is_topic_message) {
+        req = req.message_thread_id(topic);
+    }
+    if let Err(e) = req.await {
+        warn!(error = %e, chat = %msg.chat.id, "Failed to answer Telegram command");
+    }
+}
+
+async fn show_typing(bot: Bot, channel_id: String, thread_id: Option<String>) {
+    let Ok(chat_id) = channel_id.parse::<i64>() else {
+        return;
+    };
+    let mut req = bot.send_chat_action(ChatId(chat_id), ChatAction::Typing);
+    if let Some(topic) = parse_topic(thread_id.as_deref()) {
+        req = req.message_thread_id(topic);
+    }
+    if let Err(e) = req.await {
+        debug!(error = %e, chat = %chat_id, "Failed to show Telegram typing indicator");
+    }
+}
+
+async fn send_response(
+    bot: &Bot,
+    response: &ResponseEvent,
+    streams: &mut StreamedMessages<MessageId>,
+    sent: &SentMessages,
+) -> DeliveryOutcome {
+    let chat_id = ChatId(response.channel_id.parse().unwrap_or_default());
+
+    if let Some(outcome) = perform_action(bot, chat_id, &response.kind, &response.content).await {
+        return outcome;
+    }
+
+    // The relay renders responses as Telegram HTML for this connection.
+    if let Some(message_id) = streams.target(&response.kind) {
+        let mut result = bot
+            .edit_message_text(chat_id, message_id, &response.content)
+            .parse_mode(ParseMode::Html)
+            .await;
+        if markup_refused(&result, chat_id) {
+            result = bot
+                .edit_message_text(chat_id, message_id, html_to_plain(&response.content))
+                .await;
+        }
+        match result {
+            // The final update often repeats the last edit verbatim.
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {
+                debug!(chat = %chat_id, "Telegram streamed response edited");
+                streams.delivered(&response.kind, message_id);
+                return DeliveryOutcome::Sent;
+            }
+            Err(RequestError::Api(ApiError::MessageToEditNotFound)) => {
+                warn!(chat = %chat_id, "Streamed Telegram message is gone, sending a new one");
+                streams.forget(&response.kind);
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to edit Telegram message");
+                return delivery_outcome_for(&e);
+            }
+        }
+    }
+
+    // Like Discord, only group replies quote the triggering message.
+    let reply_to = response
+        .reply_to_message_id
+        .as_deref()
+        .and_then(|id| id.parse::<i32>().ok())
+        .filter(|_| !response.is_dm);
+    let send = |text: String, parse_mode: Option<ParseMode>| {
+        let mut req = bot.send_message(chat_id, text);
+        if let Some(parse_mode) = parse_mode {
+            req = req.parse_mode(parse_mode);
+        }
+        if let Some(topic) = parse_topic(response.thread_id.as_deref()) {
+            req = req.message_thread_id(topic);
+        }
+        if let Some(reply_to) = reply_to {
+            req = req.reply_parameters(
+                ReplyParameters::new(MessageId(reply_to)).allow_sending_without_reply(),
+            );
+        }
+        req
+    };
+    let mut result = send(response.content.clone(), Some(ParseMode::Html)).await;
+    if markup_refused(&result, chat_id) {
+        result = send(html_to_plain(&response.content), None).await;
+    }
+    match result {
+        Ok(message) => {
+            info!(chat = %chat_id, "Telegram response sent successfully");
+            sent.remember(chat_id, message.id);
+            streams.delivered(&response.kind, message.id);
+            DeliveryOutcome::Sent
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to send Telegram message");
+            delivery_outcome_for(&e)
+        }
+    }
+}
+
+/// Reactions, edits and deletions of an existing message. `None` for
+/// responses that send a message.
+async fn perform_action(
+    bot: &Bot,
+    chat_id: ChatId,
+    kind: &ResponseKind,
+    content: &str,
+) -> Option<DeliveryOutcome> {
+    let (message_id, action) = match kind {
+        ResponseKind::React { message_id, .. } => (message_id, "react"),
+        ResponseKind::Edit { message_id } => (message_id, "edit"),
+        ResponseKind::Delete { message_id } => (message_id, "delete"),
+        ResponseKind::Message | ResponseKind::StreamUpdate { .. } => return None,
+    };
+    let Ok(message) = message_id.parse::<i32>().map(MessageId) else {
+        return Some(DeliveryOutcome::Failed {
+            error: format!("invalid Telegram message id: {message_id}"),
+            retryable: false,
+        });
+    };
+
+    let result = match kind {
+        ResponseKind::React { emoji, .. } => bot
+            .set_message_reaction(chat_id, message)
+            .reaction(vec![ReactionType::Emoji {
+                emoji: emoji.clone(),
+            }])
+            .await
+            .map(|_| ()),
+        ResponseKind::Edit { .. } => {
+            let result = bot
+                .edit_message_text(chat_id, message, content)
+                .parse_mode(ParseMode::Html)
+                .await;
+            if markup_refused(&result, chat_id) {
+                bot.edit_message_text(chat_id, message, html_to_plain(content))
+                    .await
+                    .map(|_| ())
+            } else {
+                result.map(|_| ())
+            }
+        }
+        _ => bot.delete_message(chat_id, message).await.map(|_| ()),
+    };
+
+    Some(match result {
+        Ok(()) | Err(RequestError::Api(ApiError::MessageNotModified)) => {
+            debug!(chat = %chat_id, message = %message_id, action, "Telegram message action done");
+            DeliveryOutcome::Sent
+        }
+        // The message is already gone; nothing left to do.
+        Err(RequestError::Api(ApiError::MessageToDeleteNotFound)) => DeliveryOutcome::Sent,
+        Err(e) => {
+            error!(
+                error = %e,
+                chat = %chat_id,
+                message = %message_id,
+                action,
+                "Failed to perform Telegram message action"
+            );
+            delivery_outcome_for(&e)
+        }
+    })
+}
+
+/// Telegram refuses a whole message whose HTML does not parse. The text is
+/// still worth delivering, so such a message is sent again without markup.
+fn markup_refused<T>(result: &Result<T, RequestError>, chat_id: ChatId) -> bool {
+    let Err(RequestError::Api(ApiError::CantParseEntities(reason))) = result else {
+        return false;
+    };
+    warn!(chat = %chat_id, reason = %reason, "Telegram refused the response markup, resending as plain text");
+    true
+}
+
+/// Classify a send error for the agent's outbound queue.
+fn delivery_outcome_for(error: &RequestError) -> DeliveryOutcome {
+    match error {
+        RequestError::RetryAfter(wait) => DeliveryOutcome::RetryAfter {
+            retry_after_ms: wait.duration().as_millis() as u64,
+        },
+        RequestError::Network(_) | RequestError::Io(_) => DeliveryOutcome::Failed {
+            error: error.to_string(),
+            retryable: true,
+        },
+        _ => DeliveryOutcome::Failed {
+            error: error.to_string(),
+            retryable: false,
+        },
+    }
+}
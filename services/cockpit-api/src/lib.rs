@@ -7,24 +7,32 @@ use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use axum::body::Body;
 use axum::extract::{Query, State};
 use axum::http::Method;
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt};
 use kernel::agent_profile::get_agent_profile;
-use kernel::event::{Event, SystemEvent};
+use kernel::event::{Event, Platform, SystemEvent};
 use kernel::worker::{Worker, WorkerContext, WorkerStatus};
+use memory::archive::{ArchiveCounts, ArchiveScope, Archiver};
 use memory::episodic::EpisodicStore;
+use memory::forget::{ForgetReceipt, ForgetSubject, Forgetter};
 use memory::graph::{CognitiveGraph, RelationshipGraphSnapshot};
+use memory::identity::{IdentityStore, Person};
 use memory::short_term::{ActiveSessionSnapshot, ShortTermMemory};
+use memory::store::{MessageHit, MessageSearch};
 use memory::{MemoryMessage, MemoryStore};
+use sensory::outbound::{OutboundQueue, OutboundStats};
 use state::{ManualPatchRequest, ManualPatchResult, StateMetricsSnapshot, StateStore};
 use serde::{Deserialize, Serialize};
 use sysinfo::{Components, Disks, System};
 use tokio::net::TcpListener;
 use tokio::sync::{oneshot, Mutex, RwLock};
+use tokio_util::io::{ReaderStream, StreamReader};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{info, warn};
 
@@ -192,6 +200,10 @@ struct AppState {
     short_term: Option<Arc<Mutex<ShortTermMemory>>>,
     episodic: Option<Arc<EpisodicStore>>,
     graph: Option<CognitiveGraph>,
+    identity_store: Option<IdentityStore>,
+    forgetter: Option<Forgetter>,
+    archiver: Option<Archiver>,
+    outbound: Option<Arc<Mutex<OutboundQueue>>>,
     system_cache: Arc<RwLock<Option<CachedSystemSnapshot>>>,
     relationship_cache: Arc<RwLock<Option<CachedRelationshipSnapshot>>>,
 }
@@ -210,6 +222,10 @@ pub struct CockpitWorker {
     short_term: Option<Arc<Mutex<ShortTermMemory>>>,
     episodic: Option<Arc<EpisodicStore>>,
     graph: Option<CognitiveGraph>,
+    identity_store: Option<IdentityStore>,
+    forgetter: Option<Forgetter>,
+    archiver: Option<Archiver>,
+    outbound: Option<Arc<Mutex<OutboundQueue>>>,
     system_cache: Arc<RwLock<Option<CachedSystemSnapshot>>>,
     relationship_cache: Arc<RwLock<Option<CachedRelationshipSnapshot>>>,
     status: WorkerStatus,
@@ -299,6 +315,8 @@ struct CachedRelationshipSnapshot {
 
 const SYSTEM_CACHE_TTL: Duration = Duration::from_millis(1500);
 const RELATIONSHIP_CACHE_TTL: Duration = Duration::from_millis(1200);
+/// Archive bytes buffered between the exporter and the response body.
+const ARCHIVE_STREAM_BUFFER: usize = 64 * 1024;
 
 impl CockpitWorker {
     pub fn new(config: CockpitApiConfig, state_store: StateStore) -> Self {
@@ -311,6 +329,10 @@ impl CockpitWorker {
             short_term: None,
             episodic: None,
             graph: None,
+            identity_store: None,
+            forgetter: None,
+            archiver: None,
+            outbound: None,
             system_cache: Arc::new(RwLock::new(None)),
             relationship_cache: Arc::new(RwLock::new(None)),
             status: WorkerStatus::NotStarted,
@@ -338,6 +360,26 @@ impl CockpitWorker {
         self
     }
 
+    pub fn with_identity_store(mut self, identity_store: IdentityStore) -> Self {
+        self.identity_store = Some(identity_store);
+        self
+    }
+
+    pub fn with_forgetter(mut self, forgetter: Forgetter) -> Self {
+        self.forgetter = Some(forgetter);
+        self
+    }
+
+    pub fn with_archiver(mut self, archiver: Archiver) -> Self {
+        self.archiver = Some(archiver);
+        self
+    }
+
+    pub fn with_outbound(mut self, outbound: Arc<Mutex<OutboundQueue>>) -> Self {
+        self.outbound = Some(outbound);
+        self
+    }
+
     async fn track_event(metrics: &Arc<RwLock<CockpitMetrics>>, event: Event) {
         let mut metrics = metrics.write().await;
         match event {
@@ -378,6 +420,37 @@ impl CockpitWorker {
                     ),
                 );
             }
+            // Re-sent every few seconds per reply; too noisy for the event log.
+            Event::Typing(_) => {}
+            Event::MessageEdited(edit) => {
+                metrics.push_event(
+                    "message_edited",
+                    format!("{}: {}", edit.username, truncate(&edit.content, 120)),
+                );
+            }
+            Event::MessageDeleted(deleted) => {
+                metrics.push_event(
+                    "message_deleted",
+                    format!("{}:{}", deleted.channel_id, deleted.message_id),
+                );
+            }
+            Event::Reaction(reaction) => {
+                metrics.push_event(
+                    "reaction",
+                    format!(
+                        "{} {} {}",
+                        reaction.username,
+                        if reaction.added { "added" } else { "removed" },
+                        reaction.emoji
+                    ),
+                );
+            }
+            Event::Command(command) => {
+                metrics.push_event(
+                    "command",
+                    format!("{}: {:?}", command.username, command.command),
+                );
+            }
             Event::Biology(_) => {
                 metrics.counters.biology_events += 1;
                 metrics.push_event("biology", "biology update".to_string());
@@ -402,6 +475,17 @@ impl CockpitWorker {
                             format!("worker_error: {} ({})", name, truncate(&error, 80)),
                         );
                     }
+                    SystemEvent::WorkerHealth { name, status } => {
+                        let status = status.to_string();
+                        // Only changes are worth a line in the event log.
+                        if metrics.worker_status.get(&name) != Some(&status) {
+                            metrics.push_event(
+                                "system",
+                                format!("worker_health: {} ({})", name, truncate(&status, 80)),
+                            );
+                        }
+                        metrics.worker_status.insert(name, status);
+                    }
                     SystemEvent::ShutdownRequested => {
                         metrics.push_event("system", "shutdown_requested".to_string());
                     }
@@ -448,6 +532,10 @@ impl Worker for CockpitWorker {
             short_term: self.short_term.clone(),
             episodic: self.episodic.clone(),
             graph: self.graph.clone(),
+            identity_store: self.identity_store.clone(),
+            forgetter: self.forgetter.clone(),
+            archiver: self.archiver.clone(),
+            outbound: self.outbound.clone(),
             system_cache: Arc::clone(&self.system_cache),
             relationship_cache: Arc::clone(&self.relationship_cache),
         };
@@ -465,8 +553,17 @@ impl Worker for CockpitWorker {
             .route("/api/cockpit/state/metrics", get(get_state_metrics))
             .route("/api/cockpit/state/patch", post(post_state_patch))
             .route("/api/cockpit/memory", get(get_memory))
+            .route("/api/cockpit/memory/search", get(get_memory_search))
             .route("/api/cockpit/episodic", get(get_episodic))
             .route("/api/cockpit/relationships", get(get_relationships))
+            .route("/api/cockpit/persons", get(get_person))
+            .route("/api/cockpit/persons/merge", post(post_person_merge))
+            .route("/api/cockpit/persons/split", post(post_person_split))
+            .route("/api/cockpit/persons/forget", post(post_person_forget))
+            .route("/api/cockpit/forget/receipts", get(get_forget_receipts))
+            .route("/api/cockpit/archive", get(get_archive))
+            .route("/api/cockpit/archive/import", post(post_archive_import))
+            .route("/api/cockpit/outbound", get(get_outbound))
             .route("/api/cockpit/system", get(get_system))
             .route("/api/cockpit/prompts", get(get_prompts))
             .route("/api/cockpit/prompts/document", get(get_prompt_document))
@@ -704,6 +801,47 @@ async fn get_memory(
     Json(overview)
 }
 
+async fn get_memory_search(
+    State(state): State<AppState>,
+    Query(search): Query<MessageSearch>,
+) -> Result<Json<Vec<MessageHit>>, (axum::http::StatusCode, String)> {
+    let (Some(reader), Some(path)) = (&state.memory_reader, &state.memory_db_path) else {
+        return Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "memory store is not configured".to_string(),
+        ));
+    };
+    let reader = Arc::clone(reader);
+    let path = path.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut guard = reader.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            let store = MemoryStore::open_read_only(&path).map_err(|err| {
+                (
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                    err.to_string(),
+                )
+            })?;
+            *guard = Some(store);
+        }
+        let store = guard.as_ref().expect("memory reader was just opened");
+        store.search(&search).map(Json).map_err(|err| {
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                err.to_string(),
+            )
+        })
+    })
+    .await
+    .map_err(|err| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )
+    })?
+}
+
 async fn get_episodic(
     State(state): State<AppState>,
     Query(query): Query<EventQuery>,
@@ -733,6 +871,14 @@ async fn get_episodic(
     Json(overview)
 }
 
+async fn get_outbound(State(state): State<AppState>) -> impl IntoResponse {
+    let stats = match &state.outbound {
+        Some(outbound) => outbound.lock().await.stats(sensory::outbound::now_ms()),
+        None => OutboundStats::default(),
+    };
+    Json(stats)
+}
+
 async fn get_relationships(State(state): State<AppState>) -> impl IntoResponse {
     if let Some(cached) = state
         .relationship_cache
@@ -767,6 +913,212 @@ async fn get_relationships(State(state): State<AppState>) -> impl IntoResponse {
     })
 }
 
+#[derive(Debug, Deserialize)]
+struct PersonQuery {
+    /// A person id or any username the person went by.
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchiveQuery {
+    /// Archive one person, by person id or any username they went by.
+    /// Without it the whole agent is archived.
+    person: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonMergeRequest {
+    from: String,
+    into: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonSplitRequest {
+    platform: Platform,
+    user_id: String,
+}
+
+type PersonResult = Result<Json<Person>, (axum::http::StatusCode, String)>;
+
+fn identity_store(state: &AppState) -> Result<&IdentityStore, (axum::http::StatusCode, String)> {
+    state.identity_store.as_ref().ok_or((
+        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        "identity store is not configured".to_string(),
+    ))
+}
+
+fn found_person(store: &IdentityStore, id: &str) -> PersonResult {
+    match store.person(id) {
+        Ok(Some(person)) => Ok(Json(person)),
+        Ok(None) => Err((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("unknown person {}", id),
+        )),
+        Err(err) => Err((
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )),
+    }
+}
+
+async fn get_person(State(state): State<AppState>, Query(query): Query<PersonQuery>) -> PersonResult {
+    let store = identity_store(&state)?;
+    let id = store.canonical(&query.id).map_err(|err| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )
+    })?;
+    found_person(store, &id)
+}
+
+async fn post_person_merge(
+    State(state): State<AppState>,
+    Json(req): Json<PersonMergeRequest>,
+) -> PersonResult {
+    let store = identity_store(&state)?;
+    let into = store.merge(&req.from, &req.into).map_err(|err| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            err.to_string(),
+        )
+    })?;
+    if let Some(graph) = &state.graph {
+        if let Err(err) = graph.merge_person(&req.from, &into).await {
+            warn!(error = %err, from = %req.from, into = %into, "failed to carry relationship over");
+        }
+    }
+    *state.relationship_cache.write().await = None;
+    found_person(store, &into)
+}
+
+async fn post_person_split(
+    State(state): State<AppState>,
+    Json(req): Json<PersonSplitRequest>,
+) -> PersonResult {
+    let store = identity_store(&state)?;
+    let person = store.split(req.platform, &req.user_id).map_err(|err| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            err.to_string(),
+        )
+    })?;
+    *state.relationship_cache.write().await = None;
+    found_person(store, &person)
+}
+
+fn forgetter(state: &AppState) -> Result<&Forgetter, (axum::http::StatusCode, String)> {
+    state.forgetter.as_ref().ok_or((
+        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        "forgetting is not configured".to_string(),
+    ))
+}
+
+async fn post_person_forget(
+    State(state): State<AppState>,
+    Json(subject): Json<ForgetSubject>,
+) -> Result<Json<ForgetReceipt>, (axum::http::StatusCode, String)> {
+    let receipt = forgetter(&state)?
+        .forget(&subject, "cockpit")
+        .await
+        .map_err(|err| {
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                err.to_string(),
+            )
+        })?;
+    *state.relationship_cache.write().await = None;
+    Ok(Json(receipt))
+}
+
+async fn get_forget_receipts(
+    State(state): State<AppState>,
+    Query(query): Query<EventQuery>,
+) -> Result<Json<Vec<ForgetReceipt>>, (axum::http::StatusCode, String)> {
+    let limit = query.limit.unwrap_or(50).min(500);
+    let receipts = forgetter(&state)?.receipts(limit).await.map_err(|err| {
+        (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            err.to_string(),
+        )
+    })?;
+    Ok(Json(receipts))
+}
+
+fn archiver(state: &AppState) -> Result<&Archiver, (axum::http::StatusCode, String)> {
+    state.archiver.as_ref().ok_or((
+        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        "archives are not configured".to_string(),
+    ))
+}
+
+async fn get_archive(
+    State(state): State<AppState>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<impl IntoResponse, (axum::http::StatusCode, String)> {
+    let scope = match query.person {
+        Some(person) => ArchiveScope::Person { person },
+        None => ArchiveScope::Agent,
+    };
+    let archiver = archiver(&state)?;
+    let (mut writer, reader) = tokio::io::duplex(ARCHIVE_STREAM_BUFFER);
+    let (done_tx, done_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let result = archiver.export(&scope, &mut writer).await;
+        let _ = done_tx.send(result);
+    });
+
+    // Resolving the scope fails before the manifest is written, while an
+    // error status can still be sent.
+    let mut chunks = ReaderStream::new(reader);
+    let first = match chunks.next().await {
+        Some(Ok(chunk)) => chunk,
+        Some(Err(err)) => {
+            return Err((
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                err.to_string(),
+            ))
+        }
+        None => {
+            let err = match done_rx.await {
+                Ok(Err(err)) => err.to_string(),
+                _ => "archive export stopped".to_string(),
+            };
+            return Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, err));
+        }
+    };
+    // A later failure ends the body with an error, so the client sees a
+    // broken transfer rather than a short archive.
+    let outcome = futures_util::stream::once(async move {
+        match done_rx.await {
+            Ok(Ok(_)) => None,
+            Ok(Err(err)) => Some(Err(std::io::Error::other(err.to_string()))),
+            Err(_) => Some(Err(std::io::Error::other("archive export stopped"))),
+        }
+    })
+    .filter_map(std::future::ready);
+    let body = futures_util::stream::once(std::future::ready(Ok(first)))
+        .chain(chunks)
+        .chain(outcome);
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body),
+    ))
+}
+
+async fn post_archive_import(
+    State(state): State<AppState>,
+    body: Body,
+) -> Result<Json<ArchiveCounts>, (axum::http::StatusCode, String)> {
+    let input = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let counts = archiver(&state)?
+        .import(input)
+        .await
+        .map_err(|err| (axum::http::StatusCode::BAD_REQUEST, err.to_string()))?;
+    *state.relationship_cache.write().await = None;
+    Ok(Json(counts))
+}
+
 async fn get_system(State(state): State<AppState>) -> impl IntoResponse {
     if let Some(cached) = state
         .system_cache
@@ -0,0 +1,130 @@
+#[derive(Debug, Clone)]
+pub struct HttpPlatformConfig {
+    pub enabled: bool,
+    pub bind_addr: String,
+    /// Bearer token every request must carry. Unset leaves the API open,
+    /// which is only sensible on a loopback bind.
+    pub token: Option<String>,
+    /// Responses are POSTed here when set, in addition to being buffered
+    /// for long-poll and SSE clients.
+    pub webhook_url: Option<String>,
+    /// Key for the `X-Polyverse-Signature` HMAC on webhook calls.
+    pub webhook_secret: Option<String>,
+    pub webhook_timeout_ms: u64,
+    /// How many recent responses are kept for clients to catch up on.
+    pub buffer_size: usize,
+}
+
+impl Default for HttpPlatformConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_addr: "127.0.0.1:4791".to_string(),
+            token: None,
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_timeout_ms: 5_000,
+            buffer_size: 256,
+        }
+    }
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+impl HttpPlatformConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let enabled = std::env::var("HTTP_PLATFORM_ENABLED")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "on"
+                )
+            })
+            .unwrap_or(defaults.enabled);
+
+        let webhook_timeout_ms = std::env::var("HTTP_PLATFORM_WEBHOOK_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|v| v.max(100))
+            .unwrap_or(defaults.webhook_timeout_ms);
+
+        let buffer_size = std::env::var("HTTP_PLATFORM_BUFFER_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .map(|v| v.max(1))
+            .unwrap_or(defaults.buffer_size);
+
+        Self {
+            enabled,
+            bind_addr: non_empty_env("HTTP_PLATFORM_BIND").unwrap_or(defaults.bind_addr),
+            token: non_empty_env("HTTP_PLATFORM_TOKEN"),
+            webhook_url: non_empty_env("HTTP_PLATFORM_WEBHOOK_URL"),
+            webhook_secret: non_empty_env("HTTP_PLATFORM_WEBHOOK_SECRET"),
+            webhook_timeout_ms,
+            buffer_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpPlatformConfig;
+    use std::sync::{Mutex, OnceLock};
+
+    fn env_lock() -> &'static Mutex<()> {
+        static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+        LOCK.get_or_init(|| Mutex::new(()))
+    }
+
+    fn clear_env() {
+        unsafe {
+            std::env::remove_var("HTTP_PLATFORM_ENABLED");
+            std::env::remove_var("HTTP_PLATFORM_BIND");
+            std::env::remove_var("HTTP_PLATFORM_TOKEN");
+            std::env::remove_var("HTTP_PLATFORM_WEBHOOK_URL");
+            std::env::remove_var("HTTP_PLATFORM_WEBHOOK_SECRET");
+            std::env::remove_var("HTTP_PLATFORM_WEBHOOK_TIMEOUT_MS");
+            std::env::remove_var("HTTP_PLATFORM_BUFFER_SIZE");
+        }
+    }
+
+    #[test]
+    fn from_env_is_disabled_on_loopback_by_default() {
+        let _guard = env_lock().lock().unwrap_or_else(|p| p.into_inner());
+        clear_env();
+        let config = HttpPlatformConfig::from_env();
+        assert!(!config.enabled);
+        assert_eq!(config.bind_addr, "127.0.0.1:4791");
+        assert!(config.token.is_none());
+        assert!(config.webhook_url.is_none());
+    }
+
+    #[test]
+    fn from_env_treats_blank_values_as_unset() {
+        let _guard = env_lock().lock().unwrap_or_else(|p| p.into_inner());
+        clear_env();
+        unsafe {
+            std::env::set_var("HTTP_PLATFORM_ENABLED", "on");
+            std::env::set_var("HTTP_PLATFORM_TOKEN", "  ");
+            std::env::set_var("HTTP_PLATFORM_WEBHOOK_URL", "https://example.test/hook");
+            std::env::set_var("HTTP_PLATFORM_WEBHOOK_TIMEOUT_MS", "5");
+        }
+        let config = HttpPlatformConfig::from_env();
+        assert!(config.enabled);
+        assert!(config.token.is_none());
+        assert_eq!(
+            config.webhook_url.as_deref(),
+            Some("https://example.test/hook")
+        );
+        assert_eq!(config.webhook_timeout_ms, 100);
+        clear_env();
+    }
+}
@@ -0,0 +1,161 @@
+//! The JSON body of `POST /v1/messages`.
+
+use chrono::{DateTime, Utc};
+use kernel::event::{
+    ImageAttachment, Platform, RawEvent, ReplyReference, MAX_IMAGE_ATTACHMENTS_PER_MESSAGE,
+    MAX_IMAGE_ATTACHMENT_BYTES,
+};
+use serde::Deserialize;
+
+/// A [`RawEvent`] without the fields the worker fills in. Only
+/// `channel_id`, `user_id` and `content` (or an attachment) are required.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InboundMessage {
+    pub channel_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// Generated when omitted. Reuse ids from your own system to let the
+    /// agent deduplicate retried posts.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    pub user_id: String,
+    /// Defaults to `user_id`.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub attachments: Vec<ImageAttachment>,
+    /// Defaults to true: most HTTP surfaces are a chat with the agent.
+    #[serde(default = "default_is_mention")]
+    pub is_mention: bool,
+    #[serde(default)]
+    pub is_dm: bool,
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub reply_to: Option<ReplyReference>,
+}
+
+fn default_is_mention() -> bool {
+    true
+}
+
+fn required(name: &str, value: String) -> Result<String, String> {
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        return Err(format!("{name} must not be empty"));
+    }
+    Ok(value)
+}
+
+impl InboundMessage {
+    /// Validate the message and turn it into an HTTP-platform event,
+    /// using `fallback_id` when no `message_id` was given.
+    pub fn into_raw_event(self, fallback_id: String) -> Result<RawEvent, String> {
+        let channel_id = required("channel_id", self.channel_id)?;
+        let user_id = required("user_id", self.user_id)?;
+        if self.content.trim().is_empty() && self.attachments.is_empty() {
+            return Err("content must not be empty without attachments".to_string());
+        }
+        if self.attachments.len() > MAX_IMAGE_ATTACHMENTS_PER_MESSAGE {
+            return Err(format!(
+                "at most {MAX_IMAGE_ATTACHMENTS_PER_MESSAGE} attachments per message"
+            ));
+        }
+        for attachment in &self.attachments {
+            if !ImageAttachment::is_supported_image_mime(&attachment.mime_type) {
+                return Err(format!(
+                    "unsupported attachment type {}",
+                    attachment.mime_type
+                ));
+            }
+            if attachment.data_base64.len() * 3 / 4 > MAX_IMAGE_ATTACHMENT_BYTES {
+                return Err(format!(
+                    "attachments must be at most {MAX_IMAGE_ATTACHMENT_BYTES} bytes"
+                ));
+            }
+        }
+
+        let username = self
+            .username
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| user_id.clone());
+        let message_id = self
+            .message_id
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .unwrap_or(fallback_id);
+
+        Ok(RawEvent {
+            platform: Platform::Http,
+            channel_id,
+            thread_id: self.thread_id.filter(|id| !id.trim().is_empty()),
+            message_id,
+            user_id,
+            username,
+            content: self.content,
+            attachments: self.attachments,
+            is_mention: self.is_mention || self.is_dm,
+            is_dm: self.is_dm,
+            timestamp: self.timestamp.unwrap_or_else(Utc::now),
+            reply_to: self.reply_to,
+            is_backfill: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: serde_json::Value) -> InboundMessage {
+        serde_json::from_value(json).expect("valid inbound message")
+    }
+
+    #[test]
+    fn minimal_message_gets_defaults() {
+        let event = parse(serde_json::json!({
+            "channel_id": "widget",
+            "user_id": "u1",
+            "content": "hello"
+        }))
+        .into_raw_event("http-1".to_string())
+        .unwrap();
+
+        assert_eq!(event.platform, Platform::Http);
+        assert_eq!(event.message_id, "http-1");
+        assert_eq!(event.username, "u1");
+        assert!(event.is_mention);
+        assert!(!event.is_dm);
+    }
+
+    #[test]
+    fn rejects_blank_ids_and_empty_messages() {
+        let blank_channel = parse(serde_json::json!({
+            "channel_id": " ", "user_id": "u1", "content": "hi"
+        }));
+        assert!(blank_channel.into_raw_event("x".into()).is_err());
+
+        let empty = parse(serde_json::json!({
+            "channel_id": "c", "user_id": "u1", "content": "  "
+        }));
+        assert!(empty.into_raw_event("x".into()).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_attachments() {
+        let message = parse(serde_json::json!({
+            "channel_id": "c",
+            "user_id": "u1",
+            "attachments": [{
+                "mime_type": "application/pdf",
+                "filename": null,
+                "source_url": null,
+                "data_base64": "AAAA"
+            }]
+        }));
+        assert!(message.into_raw_event("x".into()).is_err());
+    }
+}
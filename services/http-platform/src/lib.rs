@@ -0,0 +1,443 @@
+//! Built-in HTTP platform. Chat surfaces POST messages as JSON and receive
+//! the agent's responses by long-poll, server-sent events or a signed
+//! webhook, so a web widget or internal tool needs no relay client of its own.
+//!
+//! - `POST /v1/messages` takes an [`InboundMessage`] and answers `202`.
+//! - `GET /v1/responses?channel_id=&after=&timeout_ms=` long-polls for
+//!   [`OutboundItem`]s newer than the `after` cursor.
+//! - `GET /v1/events?channel_id=&after=` streams the same items as SSE;
+//!   `Last-Event-ID` resumes a dropped stream.
+//!
+//! With `HTTP_PLATFORM_WEBHOOK_URL` set, each response is also POSTed there
+//! and its delivery report follows the webhook's answer.
+
+mod config;
+mod inbound;
+mod outbox;
+mod webhook;
+
+pub use config::HttpPlatformConfig;
+pub use inbound::InboundMessage;
+pub use outbox::{OutboundItem, OutboundKind};
+pub use webhook::{sign, verify, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::extract::{Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::Stream;
+use kernel::event::Platform;
+use kernel::worker::{Worker, WorkerContext, WorkerStatus};
+use sensory::capabilities::PlatformCapabilities;
+use sensory::relay::{DeliveryOutcome, RelayClient, RelayInbound, RelaySender};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, oneshot, RwLock};
+use tracing::{debug, info, warn};
+
+use outbox::Outbox;
+use webhook::Webhook;
+
+/// How often to retry reaching the relay while the agent starts up.
+const RELAY_RETRY: Duration = Duration::from_secs(1);
+const DEFAULT_POLL_TIMEOUT_MS: u64 = 25_000;
+const MAX_POLL_TIMEOUT_MS: u64 = 60_000;
+
+#[derive(Clone)]
+struct AppState {
+    token: Option<Arc<str>>,
+    relay: Arc<RwLock<Option<RelaySender>>>,
+    outbox: Arc<Outbox>,
+    id_prefix: Arc<str>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl AppState {
+    fn next_message_id(&self) -> String {
+        let n = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{}-{}", self.id_prefix, n)
+    }
+}
+
+pub struct HttpPlatformWorker {
+    config: HttpPlatformConfig,
+    status: WorkerStatus,
+}
+
+impl HttpPlatformWorker {
+    pub fn new(config: HttpPlatformConfig) -> Self {
+        Self {
+            config,
+            status: WorkerStatus::NotStarted,
+        }
+    }
+}
+
+#[async_trait]
+impl Worker for HttpPlatformWorker {
+    fn name(&self) -> &str {
+        "http_platform"
+    }
+
+    async fn start(&mut self, ctx: WorkerContext) -> Result<()> {
+        if !self.config.enabled {
+            self.status = WorkerStatus::Stopped;
+            return Ok(());
+        }
+
+        let bind_addr: SocketAddr = self.config.bind_addr.parse().with_context(|| {
+            format!(
+                "invalid HTTP_PLATFORM_BIND address: {}",
+                self.config.bind_addr
+            )
+        })?;
+        if self.config.token.is_none() && !bind_addr.ip().is_loopback() {
+            warn!(bind = %bind_addr, "HTTP platform listens beyond loopback without HTTP_PLATFORM_TOKEN");
+        }
+
+        let webhook = match &self.config.webhook_url {
+            Some(url) => {
+                let webhook = Webhook::new(
+                    url.clone(),
+                    self.config.webhook_secret.clone(),
+                    Duration::from_millis(self.config.webhook_timeout_ms),
+                )?;
+                if !webhook.is_signed() {
+                    warn!("HTTP_PLATFORM_WEBHOOK_SECRET is unset; webhook calls are not signed");
+                }
+                Some(webhook)
+            }
+            None => None,
+        };
+
+        let state = AppState {
+            token: self.config.token.as_deref().map(Arc::from),
+            relay: Arc::new(RwLock::new(None)),
+            outbox: Arc::new(Outbox::new(self.config.buffer_size)),
+            id_prefix: Arc::from(format!("http-{}", chrono::Utc::now().timestamp_millis())),
+            next_id: Arc::new(AtomicU64::new(0)),
+        };
+
+        let app = Router::new()
+            .route("/v1/messages", post(post_message))
+            .route("/v1/responses", get(get_responses))
+            .route("/v1/events", get(get_events))
+            .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind(bind_addr)
+            .await
+            .with_context(|| format!("failed to bind HTTP platform on {}", bind_addr))?;
+
+        let (server_shutdown_tx, server_shutdown_rx) = oneshot::channel::<()>();
+        let server_handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = server_shutdown_rx.await;
+                })
+                .await
+            {
+                warn!(error = %e, "HTTP platform server exited with error");
+            }
+        });
+        info!(
+            bind = %bind_addr,
+            webhook = webhook.is_some(),
+            "HTTP platform started"
+        );
+        self.status = WorkerStatus::Degraded {
+            reason: "waiting for relay".to_string(),
+        };
+
+        let mut shutdown_rx = ctx.subscribe_shutdown();
+        'connection: loop {
+            let mut client = tokio::select! {
+                client = connect_relay() => client,
+                _ = shutdown_rx.recv() => break 'connection,
+            };
+            let reporter = client.sender();
+            *state.relay.write().await = Some(client.sender());
+            self.status = WorkerStatus::Healthy;
+            info!("HTTP platform connected to relay");
+
+            loop {
+                tokio::select! {
+                    inbound = client.recv() => match inbound {
+                        Some(inbound) => {
+                            forward(&state.outbox, webhook.as_ref(), &reporter, inbound).await;
+                        }
+                        None => break,
+                    },
+                    _ = shutdown_rx.recv() => break 'connection,
+                }
+            }
+
+            *state.relay.write().await = None;
+            warn!("HTTP platform lost the relay connection; reconnecting");
+            self.status = WorkerStatus::Degraded {
+                reason: "relay disconnected".to_string(),
+            };
+        }
+
+        let _ = server_shutdown_tx.send(());
+        let _ = tokio::time::timeout(Duration::from_secs(2), server_handle).await;
+        self.status = WorkerStatus::Stopped;
+        info!("HTTP platform stopped");
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.status = WorkerStatus::Stopped;
+        Ok(())
+    }
+
+    fn health_check(&self) -> WorkerStatus {
+        self.status.clone()
+    }
+}
+
+/// The relay server is another worker and may not be listening yet.
+async fn connect_relay() -> RelayClient {
+    let capabilities = PlatformCapabilities::defaults_for(Platform::Http);
+    loop {
+        match RelayClient::connect_platform_with(Platform::Http, capabilities).await {
+            Ok(client) => return client,
+            Err(e) => {
+                debug!(error = %e, "Relay not reachable yet");
+                tokio::time::sleep(RELAY_RETRY).await;
+            }
+        }
+    }
+}
+
+async fn forward(
+    outbox: &Outbox,
+    webhook: Option<&Webhook>,
+    reporter: &RelaySender,
+    inbound: RelayInbound,
+) {
+    let (delivery_id, kind) = match inbound {
+        // Typing indicators only go to polling and streaming clients.
+        RelayInbound::Typing {
+            channel_id,
+            thread_id,
+        } => {
+            outbox.notify(OutboundKind::Typing {
+                channel_id,
+                thread_id,
+            });
+            return;
+        }
+        RelayInbound::Response(response) => (
+            response.delivery_id,
+            OutboundKind::Response {
+                event: response.event,
+            },
+        ),
+    };
+
+    let outcome = match webhook {
+        Some(webhook) => match serde_json::to_vec(&kind) {
+            Ok(body) => webhook.deliver(body).await,
+            Err(e) => DeliveryOutcome::Failed {
+                error: format!("failed to encode response: {e}"),
+                retryable: false,
+            },
+        },
+        None => DeliveryOutcome::Sent,
+    };
+
+    // A response the relay will send again is buffered on that attempt,
+    // so pollers do not see it twice.
+    let retrying = matches!(
+        outcome,
+        DeliveryOutcome::RetryAfter { .. }
+            | DeliveryOutcome::Failed {
+                retryable: true,
+                ..
+            }
+    );
+    if !retrying {
+        outbox.push(kind);
+    }
+    if let DeliveryOutcome::Failed { error, retryable } = &outcome {
+        warn!(error = %error, retryable, "HTTP platform webhook delivery failed");
+    }
+    if let Err(e) = reporter.report_delivery(delivery_id, outcome).await {
+        warn!(error = %e, "Failed to report HTTP delivery to relay");
+    }
+}
+
+async fn require_token(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !bool::from(presented.as_bytes().ct_eq(token.as_bytes())) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid bearer token".to_string(),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[derive(Debug, Serialize)]
+struct Accepted {
+    message_id: String,
+}
+
+async fn post_message(
+    State(state): State<AppState>,
+    Json(message): Json<InboundMessage>,
+) -> Result<(StatusCode, Json<Accepted>), (StatusCode, String)> {
+    let event = message
+        .into_raw_event(state.next_message_id())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let relay = state.relay.read().await.clone().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "not connected to the agent yet".to_string(),
+    ))?;
+    let message_id = event.message_id.clone();
+    relay
+        .ingest(event)
+        .await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("{e:#}")))?;
+    Ok((StatusCode::ACCEPTED, Json(Accepted { message_id })))
+}
+
+#[derive(Debug, Deserialize)]
+struct PollQuery {
+    channel_id: Option<String>,
+    after: Option<u64>,
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PollResponse {
+    items: Vec<OutboundItem>,
+    /// Pass back as `after` on the next poll.
+    cursor: u64,
+}
+
+async fn get_responses(
+    State(state): State<AppState>,
+    Query(query): Query<PollQuery>,
+) -> Json<PollResponse> {
+    let channel_id = query.channel_id.as_deref();
+    let after = query.after.unwrap_or(0);
+    let timeout = Duration::from_millis(
+        query
+            .timeout_ms
+            .unwrap_or(DEFAULT_POLL_TIMEOUT_MS)
+            .min(MAX_POLL_TIMEOUT_MS),
+    );
+
+    let mut live_rx = state.outbox.subscribe();
+    let mut items = state.outbox.since(after, channel_id);
+    if !timeout.is_zero() {
+        let _ = tokio::time::timeout(timeout, async {
+            while items.is_empty() {
+                match live_rx.recv().await {
+                    Ok(item) if !item.is_for(channel_id) => {}
+                    Ok(item) if item.is_live_only() => items.push(item),
+                    Err(RecvError::Closed) => break,
+                    _ => items = state.outbox.since(after, channel_id),
+                }
+            }
+        })
+        .await;
+    }
+
+    let cursor = items
+        .last()
+        .map(|item| item.seq)
+        .unwrap_or_else(|| after.min(state.outbox.last_seq()));
+    Json(PollResponse { items, cursor })
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    channel_id: Option<String>,
+    after: Option<u64>,
+}
+
+/// Items still to send on one SSE stream.
+struct Feed {
+    outbox: Arc<Outbox>,
+    live_rx: broadcast::Receiver<OutboundItem>,
+    pending: VecDeque<OutboundItem>,
+    last_seq: u64,
+    channel_id: Option<String>,
+}
+
+async fn get_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<SseEvent, axum::Error>>> {
+    let resume_from = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let after = resume_from.or(query.after).unwrap_or(0);
+
+    let live_rx = state.outbox.subscribe();
+    let feed = Feed {
+        pending: state
+            .outbox
+            .since(after, query.channel_id.as_deref())
+            .into(),
+        last_seq: after.min(state.outbox.last_seq()),
+        outbox: state.outbox,
+        live_rx,
+        channel_id: query.channel_id,
+    };
+
+    let stream = futures_util::stream::unfold(feed, |mut feed| async move {
+        loop {
+            if let Some(item) = feed.pending.pop_front() {
+                // Without an id, a live-only item leaves `Last-Event-ID` alone.
+                let event = if item.is_live_only() {
+                    SseEvent::default()
+                } else {
+                    feed.last_seq = item.seq;
+                    SseEvent::default().id(item.seq.to_string())
+                };
+                return Some((event.json_data(&item), feed));
+            }
+            match feed.live_rx.recv().await {
+                Ok(item) => {
+                    let fresh = item.is_live_only() || item.seq > feed.last_seq;
+                    if fresh && item.is_for(feed.channel_id.as_deref()) {
+                        feed.pending.push_back(item);
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {
+                    let missed = feed.outbox.since(feed.last_seq, feed.channel_id.as_deref());
+                    feed.pending.extend(missed);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,219 @@
+//! Recent responses, numbered so long-poll and SSE clients can resume
+//! where they left off.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use kernel::event::ResponseEvent;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutboundKind {
+    Response {
+        event: ResponseEvent,
+    },
+    Typing {
+        channel_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        thread_id: Option<String>,
+    },
+}
+
+/// What clients receive: `{"seq": 7, "type": "response", "event": {...}}`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboundItem {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub kind: OutboundKind,
+}
+
+impl OutboundItem {
+    pub fn channel_id(&self) -> &str {
+        match &self.kind {
+            OutboundKind::Response { event } => &event.channel_id,
+            OutboundKind::Typing { channel_id, .. } => channel_id,
+        }
+    }
+
+    /// `None` matches every channel.
+    pub fn is_for(&self, channel_id: Option<&str>) -> bool {
+        channel_id.is_none_or(|channel_id| self.channel_id() == channel_id)
+    }
+
+    /// Typing indicators only reach clients listening at the time; they are
+    /// never buffered and do not move a client's cursor.
+    pub fn is_live_only(&self) -> bool {
+        matches!(self.kind, OutboundKind::Typing { .. })
+    }
+}
+
+pub struct Outbox {
+    inner: Mutex<OutboxInner>,
+    live_tx: broadcast::Sender<OutboundItem>,
+}
+
+struct OutboxInner {
+    next_seq: u64,
+    capacity: usize,
+    items: VecDeque<OutboundItem>,
+}
+
+impl Outbox {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (live_tx, _) = broadcast::channel(capacity);
+        Self {
+            inner: Mutex::new(OutboxInner {
+                next_seq: 1,
+                capacity,
+                items: VecDeque::with_capacity(capacity),
+            }),
+            live_tx,
+        }
+    }
+
+    /// Number the item, keep it for catch-up and hand it to live listeners.
+    pub fn push(&self, kind: OutboundKind) -> OutboundItem {
+        let item = {
+            let mut inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+            let item = OutboundItem {
+                seq: inner.next_seq,
+                kind,
+            };
+            inner.next_seq += 1;
+            if inner.items.len() == inner.capacity {
+                inner.items.pop_front();
+            }
+            inner.items.push_back(item.clone());
+            item
+        };
+        let _ = self.live_tx.send(item.clone());
+        item
+    }
+
+    /// Hand an item to live listeners without buffering it, so a burst of
+    /// them cannot push responses out of the buffer. It carries the newest
+    /// sequence number rather than taking one of its own.
+    pub fn notify(&self, kind: OutboundKind) -> OutboundItem {
+        let item = OutboundItem {
+            seq: self.last_seq(),
+            kind,
+        };
+        let _ = self.live_tx.send(item.clone());
+        item
+    }
+
+    /// Buffered items after `after`. A cursor from before a restart is ahead
+    /// of the current numbering and replays the whole buffer.
+    pub fn since(&self, after: u64, channel_id: Option<&str>) -> Vec<OutboundItem> {
+        let inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let after = if after >= inner.next_seq { 0 } else { after };
+        inner
+            .items
+            .iter()
+            .filter(|item| item.seq > after && item.is_for(channel_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Sequence number of the newest item, 0 before the first.
+    pub fn last_seq(&self) -> u64 {
+        let inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        inner.next_seq - 1
+    }
+
+    /// Subscribe before calling [`Outbox::since`] so nothing pushed in
+    /// between is missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<OutboundItem> {
+        self.live_tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(channel_id: &str) -> OutboundKind {
+        OutboundKind::Response {
+            event: ResponseEvent {
+                platform: kernel::event::Platform::Http,
+                channel_id: channel_id.to_string(),
+                thread_id: None,
+                reply_to_message_id: None,
+                reply_to_user: None,
+                is_dm: false,
+                content: "hi".to_string(),
+                source: kernel::event::ResponseSource::CloudLLM,
+                kind: kernel::event::ResponseKind::Message,
+                private_reply: false,
+            },
+        }
+    }
+
+    fn typing(channel_id: &str) -> OutboundKind {
+        OutboundKind::Typing {
+            channel_id: channel_id.to_string(),
+            thread_id: None,
+        }
+    }
+
+    #[test]
+    fn since_filters_by_cursor_and_channel() {
+        let outbox = Outbox::new(8);
+        outbox.push(typing("a"));
+        outbox.push(typing("b"));
+        outbox.push(typing("a"));
+
+        let seqs = |items: Vec<OutboundItem>| items.iter().map(|i| i.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(outbox.since(0, None)), vec![1, 2, 3]);
+        assert_eq!(seqs(outbox.since(1, Some("a"))), vec![3]);
+        assert_eq!(seqs(outbox.since(3, None)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn oldest_items_are_dropped_and_stale_cursors_replay() {
+        let outbox = Outbox::new(2);
+        for _ in 0..3 {
+            outbox.push(typing("a"));
+        }
+        let seqs: Vec<u64> = outbox.since(0, None).iter().map(|i| i.seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+        assert_eq!(outbox.since(99, None).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn responses_survive_a_burst_of_typing() {
+        let outbox = Outbox::new(2);
+        outbox.push(response("a"));
+        outbox.push(response("b"));
+        let mut live_rx = outbox.subscribe();
+        for _ in 0..10 {
+            outbox.notify(typing("a"));
+        }
+
+        let seqs: Vec<u64> = outbox.since(0, None).iter().map(|i| i.seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(outbox.last_seq(), 2);
+        // A lagging listener recovers from the buffer, which still has both.
+        assert!(matches!(
+            live_rx.recv().await,
+            Err(broadcast::error::RecvError::Lagged(_))
+        ));
+        let item = live_rx.recv().await.unwrap();
+        assert!(item.is_live_only());
+        assert_eq!(item.seq, 2);
+    }
+
+    #[test]
+    fn items_serialize_flat_with_a_type_tag() {
+        let outbox = Outbox::new(1);
+        let item = outbox.push(typing("a"));
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"seq": 1, "type": "typing", "channel_id": "a"})
+        );
+    }
+}
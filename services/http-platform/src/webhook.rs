@@ -0,0 +1,165 @@
+//! Pushes responses to a configured URL, signed so the receiver can check
+//! they came from the agent.
+//!
+//! Each call carries `X-Polyverse-Timestamp` (unix seconds) and
+//! `X-Polyverse-Signature: sha256=<hex>`, the HMAC-SHA256 of
+//! `"{timestamp}.{body}"` keyed with the webhook secret.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use sensory::relay::DeliveryOutcome;
+use sha2::Sha256;
+
+pub const TIMESTAMP_HEADER: &str = "X-Polyverse-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Polyverse-Signature";
+
+/// Used when a 429 does not say how long to wait.
+const DEFAULT_RETRY_AFTER_MS: u64 = 5_000;
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// The `X-Polyverse-Signature` value for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let digest = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("sha256={}", hex::encode(digest))
+}
+
+/// Check a signature the way a receiver would, in constant time.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> bool {
+    let Some(Ok(expected)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&expected).is_ok()
+}
+
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: Option<String>, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("failed to build webhook HTTP client")?;
+        Ok(Self {
+            client,
+            url,
+            secret,
+        })
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// POST `body` and translate the reply into a delivery report.
+    pub async fn deliver(&self, body: Vec<u8>) -> DeliveryOutcome {
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+        }
+
+        match request.body(body).send().await {
+            Ok(response) => {
+                let retry_after_ms = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(|secs| secs * 1000);
+                outcome_for_status(response.status(), retry_after_ms)
+            }
+            Err(e) => DeliveryOutcome::Failed {
+                error: format!("webhook request failed: {e}"),
+                retryable: true,
+            },
+        }
+    }
+}
+
+/// 429 and 5xx are worth retrying; other 4xx mean the receiver rejected
+/// the response and will keep doing so.
+fn outcome_for_status(status: StatusCode, retry_after_ms: Option<u64>) -> DeliveryOutcome {
+    if status.is_success() {
+        DeliveryOutcome::Sent
+    } else if status == StatusCode::TOO_MANY_REQUESTS {
+        DeliveryOutcome::RetryAfter {
+            retry_after_ms: retry_after_ms.unwrap_or(DEFAULT_RETRY_AFTER_MS),
+        }
+    } else {
+        DeliveryOutcome::Failed {
+            error: format!("webhook answered {status}"),
+            retryable: status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1_700_000_000, br#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn verify_rejects_other_bodies_timestamps_and_secrets() {
+        let signature = sign("secret", 10, b"body");
+        assert!(verify("secret", 10, b"body", &signature));
+        assert!(!verify("secret", 10, b"other", &signature));
+        assert!(!verify("secret", 11, b"body", &signature));
+        assert!(!verify("other", 10, b"body", &signature));
+        assert!(!verify("secret", 10, b"body", "sha256=zz"));
+    }
+
+    #[test]
+    fn status_codes_map_to_delivery_outcomes() {
+        assert_eq!(
+            outcome_for_status(StatusCode::NO_CONTENT, None),
+            DeliveryOutcome::Sent
+        );
+        assert_eq!(
+            outcome_for_status(StatusCode::TOO_MANY_REQUESTS, Some(2_000)),
+            DeliveryOutcome::RetryAfter {
+                retry_after_ms: 2_000
+            }
+        );
+        assert!(matches!(
+            outcome_for_status(StatusCode::BAD_GATEWAY, None),
+            DeliveryOutcome::Failed {
+                retryable: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            outcome_for_status(StatusCode::UNAUTHORIZED, None),
+            DeliveryOutcome::Failed {
+                retryable: false,
+                ..
+            }
+        ));
+    }
+}
@@ -9,8 +9,8 @@ pub mod stdio;
 pub use config::{McpConfig, McpTransport};
 pub use dispatch::{McpDispatcher, ToolCallExecutor, ToolCallFailure, ToolCallFailureKind, ToolCallRequest};
 pub use provider::{
-    default_providers, ExecutionToolProvider, RegisteredTool, SearchProviderConfig,
-    SearchToolProvider, SocialToolProvider, ToolProvider, WebFetchProviderConfig,
-    WebFetchToolProvider,
+    default_providers, ExecutionToolProvider, ForgetToolProvider, MemorySearchProviderConfig,
+    MemorySearchToolProvider, RegisteredTool, SearchProviderConfig, SearchToolProvider,
+    SocialToolProvider, ToolProvider, WebFetchProviderConfig, WebFetchToolProvider,
 };
 pub use server::{build_mcp_router, build_mcp_router_for_tests, McpWorker};
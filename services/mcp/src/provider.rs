@@ -6,7 +6,11 @@ use std::time::{Duration, Instant};
 use anyhow::{anyhow, bail, Context};
 use async_trait::async_trait;
 use cognitive::DialogueToolRegistry;
+use kernel::event::Platform;
+use memory::forget::{ForgetSubject, Forgetter};
 use memory::graph::CognitiveGraph;
+use memory::store::MessageSearch;
+use memory::MemoryStore;
 use reqwest::{redirect::Policy, Client, StatusCode};
 use serde::Deserialize;
 use serde_json::{json, Value};
@@ -15,6 +19,8 @@ use url::Url;
 use crate::registry::{ToolDescriptor, ToolNamespace};
 
 const SEARCH_WEB_TOOL: &str = "search.web";
+const MEMORY_SEARCH_TOOL: &str = "memory.search_messages";
+const FORGET_USER_TOOL: &str = "memory.forget_user";
 const WEB_FETCH_TOOL: &str = "web.fetch";
 const BRAVE_SEARCH_API_BASE_DEFAULT: &str = "https://api.search.brave.com/res/v1/web/search";
 
@@ -51,6 +57,10 @@ const WEB_FETCH_BLOCKED_HOSTS: [&str; 7] = [
 ];
 const WEB_FETCH_USER_AGENT: &str = "polyverse-agent-mcp/0.1";
 
+const MEMORY_SEARCH_MAX_RESULTS_DEFAULT: usize = 10;
+const MEMORY_SEARCH_MAX_RESULTS_CEILING: usize = 50;
+const MEMORY_SEARCH_MAX_CONTENT_CHARS: usize = 600;
+
 #[derive(Debug, Clone)]
 pub struct RegisteredTool {
     pub descriptor: ToolDescriptor,
@@ -115,6 +125,31 @@ pub struct WebFetchToolProvider {
     tools: Vec<RegisteredTool>,
 }
 
+#[derive(Debug, Clone)]
+pub struct MemorySearchProviderConfig {
+    pub enabled: bool,
+    pub db_path: String,
+    pub max_results: usize,
+}
+
+/// Full-text search over the persisted message history, through a
+/// read-only connection opened on first use.
+#[derive(Clone)]
+pub struct MemorySearchToolProvider {
+    config: MemorySearchProviderConfig,
+    store: Arc<std::sync::Mutex<Option<MemoryStore>>>,
+    tools: Vec<RegisteredTool>,
+}
+
+/// Erases a person from every memory store. It needs the running stores,
+/// so it is registered by the agent rather than by [`default_providers`].
+#[derive(Clone)]
+pub struct ForgetToolProvider {
+    forgetter: Forgetter,
+    enabled: bool,
+    tools: Vec<RegisteredTool>,
+}
+
 impl Default for SocialToolProvider {
     fn default() -> Self {
         Self {
@@ -575,6 +610,223 @@ impl ToolProvider for WebFetchToolProvider {
     }
 }
 
+impl Default for MemorySearchProviderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: parse_env_bool("MCP_MEMORY_SEARCH_ENABLED", false),
+            db_path: kernel::agent_profile::get_agent_profile().memory_db_path.clone(),
+            max_results: parse_env_usize(
+                "MCP_MEMORY_SEARCH_MAX_RESULTS",
+                MEMORY_SEARCH_MAX_RESULTS_DEFAULT,
+            )
+            .clamp(1, MEMORY_SEARCH_MAX_RESULTS_CEILING),
+        }
+    }
+}
+
+impl MemorySearchProviderConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Default for MemorySearchToolProvider {
+    fn default() -> Self {
+        Self::new(MemorySearchProviderConfig::default())
+    }
+}
+
+impl MemorySearchToolProvider {
+    pub fn new(config: MemorySearchProviderConfig) -> Self {
+        let tools = if config.is_enabled() {
+            vec![RegisteredTool {
+                descriptor: ToolDescriptor {
+                    namespace: ToolNamespace::Read,
+                    name: MEMORY_SEARCH_TOOL,
+                    read_only: true,
+                },
+                description: "Search past conversation messages by keyword, optionally by user, channel or time range.",
+                input_schema: memory_search_input_schema(),
+            }]
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            config,
+            store: Arc::new(std::sync::Mutex::new(None)),
+            tools,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    async fn execute_memory_search(&self, input: Value) -> anyhow::Result<Value> {
+        if !self.is_enabled() {
+            bail!("memory search tools are disabled");
+        }
+
+        let input: MemorySearchInput = serde_json::from_value(input)?;
+        let query = input.query.trim().to_string();
+        if query.is_empty() {
+            bail!("query is required");
+        }
+        let search = MessageSearch {
+            query,
+            user: input.user,
+            platform: input.platform,
+            channel_id: input.channel_id,
+            since: parse_optional_time("since", input.since)?,
+            until: parse_optional_time("until", input.until)?,
+            from_bot: input.from_bot,
+            limit: Some(
+                input
+                    .limit
+                    .unwrap_or(self.config.max_results)
+                    .clamp(1, self.config.max_results),
+            ),
+        };
+
+        let store = Arc::clone(&self.store);
+        let db_path = self.config.db_path.clone();
+        let hits = tokio::task::spawn_blocking(move || {
+            let mut guard = store.lock().unwrap_or_else(|e| e.into_inner());
+            if guard.is_none() {
+                *guard = Some(MemoryStore::open_read_only(&db_path)?);
+            }
+            guard
+                .as_ref()
+                .expect("memory store was just opened")
+                .search(&search)
+        })
+        .await
+        .context("memory search task failed")??;
+
+        let results: Vec<Value> = hits
+            .into_iter()
+            .map(|hit| {
+                json!({
+                    "id": hit.message.id,
+                    "platform": hit.message.platform.to_string(),
+                    "channel_id": hit.message.channel_id,
+                    "thread_id": hit.message.thread_id,
+                    "user_id": hit.message.user_id,
+                    "username": hit.message.username,
+                    "from_bot": hit.message.is_bot_response,
+                    "timestamp": hit.message.timestamp.to_rfc3339(),
+                    "snippet": hit.snippet,
+                    "content": truncate_chars(&hit.message.content, MEMORY_SEARCH_MAX_CONTENT_CHARS),
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "query": input.query,
+            "results": results,
+            "meta": { "source": "memory_fts" },
+        }))
+    }
+}
+
+#[async_trait]
+impl ToolProvider for MemorySearchToolProvider {
+    fn tools(&self) -> &[RegisteredTool] {
+        &self.tools
+    }
+
+    async fn execute(
+        &self,
+        name: &str,
+        input: Value,
+        _graph: &CognitiveGraph,
+    ) -> Option<anyhow::Result<Value>> {
+        if name != MEMORY_SEARCH_TOOL {
+            return None;
+        }
+
+        Some(self.execute_memory_search(input).await)
+    }
+}
+
+impl ForgetToolProvider {
+    /// Enabled by `MCP_FORGET_USER_ENABLED`.
+    pub fn new(forgetter: Forgetter) -> Self {
+        Self::with_enabled(forgetter, parse_env_bool("MCP_FORGET_USER_ENABLED", false))
+    }
+
+    pub fn with_enabled(forgetter: Forgetter, enabled: bool) -> Self {
+        let tools = if enabled {
+            vec![RegisteredTool {
+                descriptor: ToolDescriptor {
+                    namespace: ToolNamespace::Action,
+                    name: FORGET_USER_TOOL,
+                    read_only: false,
+                },
+                description: "Erase a person from every memory store and return the audit receipt. Cannot be undone.",
+                input_schema: forget_user_input_schema(),
+            }]
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            forgetter,
+            enabled,
+            tools,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    async fn execute_forget_user(&self, input: Value) -> anyhow::Result<Value> {
+        if !self.enabled {
+            bail!("forget tools are disabled");
+        }
+
+        let input: ForgetUserInput = serde_json::from_value(input)?;
+        let subject = match (input.person, input.platform, input.user_id) {
+            (Some(person), None, None) if !person.trim().is_empty() => ForgetSubject::Person {
+                person: person.trim().to_string(),
+            },
+            (None, Some(platform), Some(user_id)) if !user_id.trim().is_empty() => {
+                ForgetSubject::Account {
+                    platform: platform.parse::<Platform>().map_err(|e| anyhow!(e))?,
+                    user_id: user_id.trim().to_string(),
+                    username: None,
+                }
+            }
+            _ => bail!("give either person, or platform and user_id"),
+        };
+
+        let receipt = self.forgetter.forget(&subject, "mcp").await?;
+        Ok(serde_json::to_value(receipt)?)
+    }
+}
+
+#[async_trait]
+impl ToolProvider for ForgetToolProvider {
+    fn tools(&self) -> &[RegisteredTool] {
+        &self.tools
+    }
+
+    async fn execute(
+        &self,
+        name: &str,
+        input: Value,
+        _graph: &CognitiveGraph,
+    ) -> Option<anyhow::Result<Value>> {
+        if name != FORGET_USER_TOOL {
+            return None;
+        }
+
+        Some(self.execute_forget_user(input).await)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct SearchWebInput {
@@ -597,6 +849,37 @@ struct WebFetchInput {
     max_chars: Option<usize>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemorySearchInput {
+    query: String,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    platform: Option<String>,
+    #[serde(default)]
+    channel_id: Option<String>,
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    until: Option<String>,
+    #[serde(default)]
+    from_bot: Option<bool>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ForgetUserInput {
+    #[serde(default)]
+    person: Option<String>,
+    #[serde(default)]
+    platform: Option<String>,
+    #[serde(default)]
+    user_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct BraveSearchResponse {
     #[serde(default)]
@@ -661,6 +944,18 @@ fn normalize_safesearch(value: Option<String>) -> anyhow::Result<String> {
     }
 }
 
+fn parse_optional_time(
+    field: &str,
+    value: Option<String>,
+) -> anyhow::Result<Option<chrono::DateTime<chrono::Utc>>> {
+    let Some(value) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let parsed = chrono::DateTime::parse_from_rfc3339(&value)
+        .map_err(|_| anyhow!("{field} must be an RFC3339 timestamp"))?;
+    Ok(Some(parsed.with_timezone(&chrono::Utc)))
+}
+
 fn social_tool_input_schema() -> Value {
     json!({
         "type": "object",
@@ -707,6 +1002,36 @@ fn search_web_input_schema() -> Value {
     })
 }
 
+fn memory_search_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "query": { "type": "string", "description": "Words that must all appear; end a word with * to match a prefix." },
+            "user": { "type": "string", "description": "Only messages by this user id or username." },
+            "platform": { "type": "string", "description": "Only messages from this platform, e.g. Discord." },
+            "channel_id": { "type": "string" },
+            "since": { "type": "string", "description": "RFC3339 timestamp, inclusive." },
+            "until": { "type": "string", "description": "RFC3339 timestamp, exclusive." },
+            "from_bot": { "type": "boolean", "description": "true for the agent's own replies, false for everyone else's." },
+            "limit": { "type": "integer", "minimum": 1, "maximum": MEMORY_SEARCH_MAX_RESULTS_CEILING }
+        },
+        "required": ["query"],
+        "additionalProperties": false
+    })
+}
+
+fn forget_user_input_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "person": { "type": "string", "description": "Person id or any username they used." },
+            "platform": { "type": "string", "description": "Platform of the account, e.g. Telegram. Use with user_id." },
+            "user_id": { "type": "string", "description": "Platform user id of the account." }
+        },
+        "additionalProperties": false
+    })
+}
+
 fn web_fetch_input_schema() -> Value {
     json!({
         "type": "object",
@@ -1100,6 +1425,7 @@ pub fn default_providers() -> Vec<Arc<dyn ToolProvider>> {
         Arc::new(ExecutionToolProvider::default()),
         Arc::new(SearchToolProvider::default()),
         Arc::new(WebFetchToolProvider::default()),
+        Arc::new(MemorySearchToolProvider::default()),
     ]
 }
 
@@ -1311,4 +1637,116 @@ mod tests {
                 .contains("url host is not allowed")
         );
     }
+
+    #[test]
+    fn memory_search_provider_registers_read_tool_only_when_enabled() {
+        let config = |enabled| MemorySearchProviderConfig {
+            enabled,
+            db_path: "unused.db".to_string(),
+            max_results: 10,
+        };
+        assert!(MemorySearchToolProvider::new(config(false)).tools().is_empty());
+
+        let provider = MemorySearchToolProvider::new(config(true));
+        assert_eq!(provider.tools().len(), 1);
+        let tool = &provider.tools()[0];
+        assert_eq!(tool.descriptor.namespace, ToolNamespace::Read);
+        assert_eq!(tool.descriptor.name, MEMORY_SEARCH_TOOL);
+        assert!(tool.descriptor.read_only);
+    }
+
+    #[tokio::test]
+    async fn memory_search_finds_messages_by_user() {
+        let dir = std::env::temp_dir().join(format!(
+            "mcp-memory-search-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default()
+        ));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let db_path = dir.join("memory.db").to_string_lossy().to_string();
+
+        let store = MemoryStore::open(&db_path).expect("memory store");
+        for (id, username, content) in [
+            ("m1", "alice", "the deploy broke again"),
+            ("m2", "bob", "deploy went fine for me"),
+        ] {
+            store
+                .insert(&memory::MemoryMessage {
+                    id: id.to_string(),
+                    platform: kernel::event::Platform::Discord,
+                    channel_id: "c1".to_string(),
+                    thread_id: None,
+                    user_id: format!("{username}-id"),
+                    username: username.to_string(),
+                    content: content.to_string(),
+                    is_mention: false,
+                    is_bot_response: false,
+                    reply_to_user: None,
+                    importance: 0.5,
+                    timestamp: chrono::Utc::now(),
+                })
+                .expect("insert");
+        }
+
+        let provider = MemorySearchToolProvider::new(MemorySearchProviderConfig {
+            enabled: true,
+            db_path,
+            max_results: 10,
+        });
+        let graph = CognitiveGraph::new("memory")
+            .await
+            .expect("in-memory graph should initialize");
+
+        let output = provider
+            .execute(MEMORY_SEARCH_TOOL, json!({ "query": "deploy", "user": "alice" }), &graph)
+            .await
+            .expect("provider should handle memory.search_messages")
+            .expect("search should succeed");
+        let results = output["results"].as_array().expect("results array");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["id"], "m1");
+        assert_eq!(results[0]["snippet"], "the **deploy** broke again");
+
+        let bad_time = provider
+            .execute(MEMORY_SEARCH_TOOL, json!({ "query": "deploy", "since": "last week" }), &graph)
+            .await
+            .expect("provider should handle memory.search_messages");
+        assert!(bad_time.is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn forget_provider_is_an_action_tool_that_needs_one_subject() {
+        let forgetter = Forgetter::new("unused-memory.db");
+        assert!(ForgetToolProvider::with_enabled(forgetter.clone(), false)
+            .tools()
+            .is_empty());
+
+        let provider = ForgetToolProvider::with_enabled(forgetter, true);
+        let tools = provider.tools();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].descriptor.name, FORGET_USER_TOOL);
+        assert!(matches!(tools[0].descriptor.namespace, ToolNamespace::Action));
+        assert!(!tools[0].descriptor.read_only);
+
+        let graph = CognitiveGraph::new("memory")
+            .await
+            .expect("in-memory graph should initialize");
+        for input in [
+            json!({}),
+            json!({ "person": "alice", "user_id": "9" }),
+            json!({ "platform": "Telegram" }),
+            json!({ "platform": "Myspace", "user_id": "9" }),
+        ] {
+            let result = provider
+                .execute(FORGET_USER_TOOL, input.clone(), &graph)
+                .await
+                .expect("provider should handle memory.forget_user");
+            assert!(result.is_err(), "{input} should be refused");
+        }
+    }
 }
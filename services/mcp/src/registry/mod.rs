@@ -38,6 +38,16 @@ impl ToolRegistry {
         }
     }
 
+    /// Add a provider built outside [`default_providers`], e.g. one that
+    /// needs the agent's running stores.
+    pub fn with_provider(mut self, provider: Arc<dyn ToolProvider>) -> Self {
+        let registered: Vec<RegisteredTool> = provider.tools().to_vec();
+        self.tools.extend(registered.iter().map(|tool| tool.descriptor.clone()));
+        self.registered_tools.extend(registered);
+        self.providers.push(provider);
+        self
+    }
+
     pub fn list(&self) -> &[ToolDescriptor] {
         &self.tools
     }
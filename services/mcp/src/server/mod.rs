@@ -17,6 +17,7 @@ use tracing::{info, warn};
 
 use crate::config::{McpConfig, McpTransport};
 use crate::dispatch::{McpDispatcher, ToolCallExecutor, ToolCallFailureKind, ToolCallRequest};
+use crate::provider::ToolProvider;
 use crate::registry::ToolRegistry;
 use crate::stdio::serve_process_stdio;
 
@@ -116,6 +117,11 @@ impl McpWorker {
         }
     }
 
+    pub fn with_tool_provider(mut self, provider: Arc<dyn ToolProvider>) -> Self {
+        self.registry = self.registry.with_provider(provider);
+        self
+    }
+
     pub fn registry(&self) -> &ToolRegistry {
         &self.registry
     }
@@ -1,10 +1,13 @@
-use cognitive::{dialogue_engine::DialogueToolCallingConfig, DialogueEngineConfig, DialogueEngineWorker};
-use kernel::event::{Event, ResponseSource};
+use cognitive::{
+    dialogue_engine::{DialogueStreamingConfig, DialogueToolCallingConfig},
+    ContextBudgetConfig, DialogueEngineConfig, DialogueEngineWorker,
+};
+use kernel::event::{Command, Event, ResponseSource};
 use kernel::worker::{Worker, WorkerStatus};
 use memory::graph::SocialDelta;
 use state::EventDeltaRequest;
 use test_support::{
-    bot_history_message, expect_no_event_within, formatted_history_context, history_message,
+    bot_history_message, command_event, expect_no_event_within, formatted_history_context, history_message,
     in_memory_graph, mention_event_in_channel, mention_event_with_image, plain_streaming_responses,
     planning_then_streaming_responses, recv_event_within, seeded_short_term_memory,
     seeded_social_graph, seeded_state_store, shutdown_dialogue_worker, spawn_mock_chat_server,
@@ -27,6 +30,8 @@ fn disabled_tool_config(api_base: String) -> DialogueEngineConfig {
             max_candidate_users: 3,
         },
         api_timeout_secs: None,
+        streaming: DialogueStreamingConfig::default(),
+        context_budget: ContextBudgetConfig::default(),
     }
 }
 
@@ -44,6 +49,8 @@ fn enabled_tool_config(api_base: String) -> DialogueEngineConfig {
             max_candidate_users: 3,
         },
         api_timeout_secs: None,
+        streaming: DialogueStreamingConfig::default(),
+        context_budget: ContextBudgetConfig::default(),
     }
 }
 
@@ -77,6 +84,71 @@ async fn dialogue_worker_ignores_non_mention_events() {
     assert_eq!(worker.health_check(), WorkerStatus::Stopped);
 }
 
+#[tokio::test]
+async fn dialogue_worker_stays_quiet_in_muted_conversations() {
+    let (addr, requests) = spawn_mock_chat_server(Vec::new()).await;
+    let worker = DialogueEngineWorker::new(disabled_tool_config(format!("http://{}", addr)))
+        .with_system_prompt("system".to_string());
+
+    let (handle, mut event_rx, broadcast_tx, shutdown_tx) = start_dialogue_worker(worker, 16).await;
+
+    broadcast_tx
+        .send(Event::Command(command_event("alice", "muted-channel", Command::Mute)))
+        .expect("broadcast should send");
+    match recv_event_within(&mut event_rx, Duration::from_secs(1)).await {
+        Event::Response(response) => {
+            assert_eq!(response.source, ResponseSource::Template);
+            assert_eq!(response.channel_id, "muted-channel");
+        }
+        other => panic!("expected command reply, got {other:?}"),
+    }
+
+    broadcast_tx
+        .send(Event::Raw(mention_event_in_channel("alice", "muted-channel", "hello?")))
+        .expect("broadcast should send");
+    expect_no_event_within(&mut event_rx, Duration::from_millis(300)).await;
+    assert!(requests.lock().expect("requests lock").is_empty());
+
+    let worker = shutdown_dialogue_worker(handle, &shutdown_tx).await;
+    assert_eq!(worker.health_check(), WorkerStatus::Stopped);
+}
+
+#[tokio::test]
+async fn dialogue_worker_pauses_only_for_operators() {
+    let (addr, requests) = spawn_mock_chat_server(Vec::new()).await;
+    let worker = DialogueEngineWorker::new(disabled_tool_config(format!("http://{}", addr)))
+        .with_system_prompt("system".to_string());
+
+    let (handle, mut event_rx, broadcast_tx, shutdown_tx) = start_dialogue_worker(worker, 16).await;
+
+    let mut pause = command_event("mallory", "ops", Command::Pause);
+    broadcast_tx
+        .send(Event::Command(pause.clone()))
+        .expect("broadcast should send");
+    match recv_event_within(&mut event_rx, Duration::from_secs(1)).await {
+        Event::Response(response) => assert_eq!(response.content, "Only operators can do that."),
+        other => panic!("expected command reply, got {other:?}"),
+    }
+
+    pause.is_operator = true;
+    broadcast_tx
+        .send(Event::Command(pause))
+        .expect("broadcast should send");
+    match recv_event_within(&mut event_rx, Duration::from_secs(1)).await {
+        Event::Response(response) => assert_eq!(response.source, ResponseSource::Template),
+        other => panic!("expected command reply, got {other:?}"),
+    }
+
+    broadcast_tx
+        .send(Event::Raw(mention_event_in_channel("alice", "elsewhere", "hello?")))
+        .expect("broadcast should send");
+    expect_no_event_within(&mut event_rx, Duration::from_millis(300)).await;
+    assert!(requests.lock().expect("requests lock").is_empty());
+
+    let worker = shutdown_dialogue_worker(handle, &shutdown_tx).await;
+    assert_eq!(worker.health_check(), WorkerStatus::Stopped);
+}
+
 #[tokio::test]
 async fn dialogue_worker_stops_when_api_config_is_invalid() {
     let mut worker = DialogueEngineWorker::new(DialogueEngineConfig {
@@ -92,6 +164,8 @@ async fn dialogue_worker_stops_when_api_config_is_invalid() {
             max_candidate_users: 3,
         },
         api_timeout_secs: None,
+        streaming: DialogueStreamingConfig::default(),
+        context_budget: ContextBudgetConfig::default(),
     });
 
     let (ctx, _event_rx, _broadcast_tx, _shutdown_tx) = test_support::worker_context_channels(16);
@@ -113,6 +187,12 @@ async fn dialogue_worker_streams_final_response_and_completion_for_mentions() {
     let message_id = raw.message_id.clone();
     broadcast_tx.send(Event::Raw(raw)).expect("broadcast should send");
 
+    let typing = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
+    match typing {
+        Event::Typing(typing) => assert_eq!(typing.channel_id, "reply-channel"),
+        other => panic!("expected typing event, got {other:?}"),
+    }
+
     let response = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
     match response {
         Event::Response(response) => {
@@ -165,6 +245,7 @@ async fn dialogue_worker_sends_multimodal_user_content_for_image_turns() {
     let raw = mention_event_with_image("alice", "vision-channel", "nhìn ảnh này nhé");
     broadcast_tx.send(Event::Raw(raw)).expect("broadcast should send");
 
+    let _typing = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
     let _ = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
     let _ = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
 
@@ -212,6 +293,7 @@ async fn dialogue_worker_executes_tool_loop_before_streaming_final_answer() {
     let message_id = raw.message_id.clone();
     broadcast_tx.send(Event::Raw(raw)).expect("broadcast should send");
 
+    let _typing = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
     let response = recv_event_within(&mut event_rx, Duration::from_secs(1)).await;
     match response {
         Event::Response(response) => {
@@ -5,7 +5,7 @@ use chrono::Utc;
 use kernel::biology::BiologyState;
 use kernel::event::{
     BiologyEvent, BiologyEventKind, BotTurnCompletion, Event, Platform, RawEvent, ResponseEvent,
-    ResponseSource, SystemEvent,
+    ResponseKind, ResponseSource, SystemEvent,
 };
 use kernel::worker::{Worker, WorkerStatus};
 use runtime::{Coordinator, EventBus};
@@ -17,6 +17,7 @@ fn raw_event(content: &str) -> Event {
     Event::Raw(RawEvent {
         platform: Platform::Cli,
         channel_id: "cli".to_string(),
+        thread_id: None,
         message_id: "m1".to_string(),
         user_id: "u1".to_string(),
         username: "tester".to_string(),
@@ -25,6 +26,8 @@ fn raw_event(content: &str) -> Event {
         is_mention: false,
         is_dm: true,
         timestamp: Utc::now(),
+        reply_to: None,
+        is_backfill: false,
     })
 }
 
@@ -32,11 +35,14 @@ fn response_event(content: &str) -> Event {
     Event::Response(ResponseEvent {
         platform: Platform::Cli,
         channel_id: "cli".to_string(),
+        thread_id: None,
         reply_to_message_id: Some("m1".to_string()),
         reply_to_user: Some("tester".to_string()),
         is_dm: true,
         content: content.to_string(),
         source: ResponseSource::CloudLLM,
+        kind: ResponseKind::Message,
+        private_reply: false,
     })
 }
 
@@ -44,6 +50,7 @@ fn completion_event(content: &str) -> Event {
     Event::BotTurnCompletion(BotTurnCompletion {
         platform: Platform::Cli,
         channel_id: "cli".to_string(),
+        thread_id: None,
         reply_to_message_id: Some("m1".to_string()),
         reply_to_user: Some("tester".to_string()),
         content: content.to_string(),
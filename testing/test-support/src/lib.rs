@@ -12,7 +12,7 @@ use axum::{
     Json, Router,
 };
 use chrono::{Duration as ChronoDuration, Utc};
-use kernel::event::{Event, Platform, RawEvent};
+use kernel::event::{Command, CommandEvent, Event, Platform, RawEvent};
 use kernel::worker::{Worker, WorkerContext};
 use memory::graph::{CognitiveGraph, SocialDelta};
 use memory::{ConversationKey, MemoryMessage, ShortTermMemory};
@@ -105,6 +105,7 @@ pub fn mention_event_in_channel(username: &str, channel_id: &str, content: &str)
     RawEvent {
         platform: Platform::Cli,
         channel_id: channel_id.to_string(),
+        thread_id: None,
         message_id: format!("msg-{}-{}", channel_id, username),
         user_id: username.to_string(),
         username: username.to_string(),
@@ -113,6 +114,23 @@ pub fn mention_event_in_channel(username: &str, channel_id: &str, content: &str)
         is_mention: true,
         is_dm: true,
         timestamp: Utc::now(),
+        reply_to: None,
+        is_backfill: false,
+    }
+}
+
+pub fn command_event(username: &str, channel_id: &str, command: Command) -> CommandEvent {
+    CommandEvent {
+        platform: Platform::Cli,
+        channel_id: channel_id.to_string(),
+        thread_id: None,
+        message_id: format!("cmd-{}-{}", channel_id, username),
+        user_id: username.to_string(),
+        username: username.to_string(),
+        is_dm: true,
+        is_operator: false,
+        command,
+        timestamp: Utc::now(),
     }
 }
 
@@ -131,6 +149,7 @@ pub fn history_message(username: &str, channel_id: &str, content: &str) -> Memor
     MemoryMessage::from_raw(&RawEvent {
         platform: Platform::Cli,
         channel_id: channel_id.to_string(),
+        thread_id: None,
         message_id: format!(
             "history-{}-{}-{}",
             channel_id,
@@ -144,6 +163,8 @@ pub fn history_message(username: &str, channel_id: &str, content: &str) -> Memor
         is_mention: false,
         is_dm: true,
         timestamp: Utc::now(),
+        reply_to: None,
+        is_backfill: false,
     })
 }
 